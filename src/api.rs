@@ -0,0 +1,86 @@
+//! JSON API calls over HTTPS through curl (no HTTP client dependency)
+//!
+//! The request - including auth headers and a body that may hold secret values -
+//! is written to curl as a config file on stdin, so none of it shows up in the
+//! process list.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Send a request and return the response body. `headers` are full header lines
+/// (e.g. `Authorization: Bearer ...`); a JSON `body` gets a Content-Type header.
+pub fn request(method: &str, url: &str, headers: &[String], body: Option<&str>) -> Result<Vec<u8>> {
+    let mut child = Command::new("curl")
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl (is it installed?)")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(curl_config(method, url, headers, body).as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        // Heroku and GitHub both explain failures in a JSON `message`
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = serde_json::from_slice::<serde_json::Value>(&output.stdout)
+            .ok()
+            .and_then(|v| v.get("message")?.as_str().map(String::from))
+            .unwrap_or_else(|| stderr.trim().to_string());
+        anyhow::bail!("{} {} failed: {}", method, url, message);
+    }
+    Ok(output.stdout)
+}
+
+/// curl config (read from stdin) for one request
+fn curl_config(method: &str, url: &str, headers: &[String], body: Option<&str>) -> String {
+    let mut config = String::new();
+    let mut option = |name: &str, value: &str| {
+        config.push_str(&format!("{} = \"{}\"\n", name, curl_quote(value)));
+    };
+    option("url", url);
+    option("request", method);
+    option("proto", "=https");
+    for header in headers {
+        option("header", header);
+    }
+    if let Some(body) = body {
+        option("header", "Content-Type: application/json");
+        option("data-binary", body);
+    }
+    config.push_str("silent\nshow-error\nfail-with-body\n");
+    config
+}
+
+/// Escape a value for a double-quoted curl config string
+fn curl_quote(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curl_config() {
+        let config = curl_config(
+            "PATCH",
+            "https://api.heroku.com/apps/x/config-vars",
+            &["Authorization: Bearer tok".to_string()],
+            Some(r#"{"A":"say \"hi\"\n"}"#),
+        );
+        assert!(config.contains("request = \"PATCH\"\n"));
+        assert!(config.contains("header = \"Authorization: Bearer tok\"\n"));
+        assert!(config.contains(r#"data-binary = "{\"A\":\"say \\\"hi\\\"\\n\"}""#));
+        assert!(!curl_config("GET", "https://x", &[], None).contains("data-binary"));
+    }
+}
@@ -0,0 +1,239 @@
+//! Failed passphrase attempts (`[unlock]` in the global config)
+//!
+//! Each attempt is counted in the store's metadata before the passphrase is
+//! checked, and the count is cleared once one succeeds, so only wrong ones are
+//! left. After a few free tries the next attempt has to wait, twice as long
+//! after every failure, and an optional limit locks the store for a while.
+//! This slows brute-forcing through the CLI; it can't stop someone who reads
+//! the database directly, which is what the slow KDF is for.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use crate::store::{read_metadata, write_metadata};
+
+/// Failures allowed before attempts are delayed
+const FREE_ATTEMPTS: u32 = 3;
+
+/// Longest delay between attempts (before any lockout)
+const MAX_DELAY_SECS: i64 = 15 * 60;
+
+const FAILURES_KEY: &str = "failed_unlock_attempts";
+const LAST_FAILURE_KEY: &str = "last_failed_unlock";
+
+/// Rate limiting for wrong passphrases
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockPolicy {
+    /// Delay attempts after repeated failures
+    #[serde(default = "default_true")]
+    pub backoff: bool,
+    /// Lock the store after this many failures in a row (off if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lockout_after: Option<u32>,
+    /// How long a lockout lasts
+    #[serde(default = "default_lockout_minutes")]
+    pub lockout_minutes: i64,
+    /// Times to ask again after a wrong passphrase at the prompt (0 to fail at once)
+    #[serde(default = "default_prompt_retries")]
+    pub prompt_retries: u32,
+}
+
+impl Default for UnlockPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: true,
+            lockout_after: None,
+            lockout_minutes: default_lockout_minutes(),
+            prompt_retries: default_prompt_retries(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_lockout_minutes() -> i64 {
+    60
+}
+
+/// Three tries in all, the ones that don't count towards the backoff
+fn default_prompt_retries() -> u32 {
+    FREE_ATTEMPTS - 1
+}
+
+/// Failures since the last successful unlock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Attempts {
+    pub failures: u32,
+    pub last_failure: Option<DateTime<Utc>>,
+}
+
+impl UnlockPolicy {
+    /// How much longer the next attempt has to wait, if at all
+    pub fn wait(&self, attempts: &Attempts, now: DateTime<Utc>) -> Option<Duration> {
+        let last_failure = attempts.last_failure?;
+        let delay = match self.lockout_after {
+            Some(limit) if attempts.failures >= limit => Duration::minutes(self.lockout_minutes),
+            _ if self.backoff && attempts.failures >= FREE_ATTEMPTS => {
+                let doublings = (attempts.failures - FREE_ATTEMPTS).min(20);
+                Duration::seconds((1i64 << doublings).min(MAX_DELAY_SECS))
+            }
+            _ => return None,
+        };
+        let remaining = last_failure + delay - now;
+        (remaining > Duration::zero()).then_some(remaining)
+    }
+}
+
+/// Read the failure count from the store's metadata
+pub fn load(conn: &Connection) -> Result<Attempts> {
+    let failures = read_metadata(conn, FAILURES_KEY)?
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+    let last_failure = read_metadata(conn, LAST_FAILURE_KEY)?
+        .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+        .map(|t| t.with_timezone(&Utc));
+    Ok(Attempts {
+        failures,
+        last_failure,
+    })
+}
+
+/// Claim an attempt before the (slow) passphrase check. In one write
+/// transaction, either the wait still due is returned, or the attempt is
+/// counted as a failure up front. Guesses run in parallel therefore see each
+/// other and can't all get in before the first failure is recorded. Returns
+/// the failures before this attempt; `clear` the count if it succeeds.
+pub fn reserve(
+    conn: &Connection,
+    policy: &UnlockPolicy,
+    now: DateTime<Utc>,
+) -> Result<Result<Attempts, Duration>> {
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let attempts = load(&tx)?;
+    if let Some(wait) = policy.wait(&attempts, now) {
+        return Ok(Err(wait));
+    }
+    tx.execute(
+        "INSERT INTO metadata (key, value) VALUES (?1, '1')
+         ON CONFLICT(key) DO UPDATE SET value = CAST(value AS INTEGER) + 1",
+        params![FAILURES_KEY],
+    )?;
+    write_metadata(&tx, LAST_FAILURE_KEY, &now.to_rfc3339())?;
+    tx.commit()?;
+    Ok(Ok(attempts))
+}
+
+/// Reset the count after a successful unlock
+pub fn clear(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "DELETE FROM metadata WHERE key IN (?1, ?2)",
+        params![FAILURES_KEY, LAST_FAILURE_KEY],
+    )?;
+    Ok(())
+}
+
+/// `42s`, `3m`, `1h 5m`
+pub fn describe(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(1);
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", (s + 59) / 60),
+        s => format!("{}h {}m", s / 3600, (s % 3600 + 59) / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempts(failures: u32, seconds_ago: i64, now: DateTime<Utc>) -> Attempts {
+        Attempts {
+            failures,
+            last_failure: Some(now - Duration::seconds(seconds_ago)),
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = UnlockPolicy::default();
+        let now = Utc::now();
+
+        assert_eq!(policy.wait(&Attempts::default(), now), None);
+        assert_eq!(policy.wait(&attempts(2, 0, now), now), None);
+        assert_eq!(
+            policy.wait(&attempts(3, 0, now), now),
+            Some(Duration::seconds(1))
+        );
+        assert_eq!(
+            policy.wait(&attempts(6, 3, now), now),
+            Some(Duration::seconds(5))
+        );
+        assert_eq!(policy.wait(&attempts(6, 8, now), now), None);
+        assert_eq!(
+            policy.wait(&attempts(40, 0, now), now),
+            Some(Duration::seconds(MAX_DELAY_SECS))
+        );
+
+        let off = UnlockPolicy {
+            backoff: false,
+            ..UnlockPolicy::default()
+        };
+        assert_eq!(off.wait(&attempts(40, 0, now), now), None);
+    }
+
+    #[test]
+    fn test_lockout() {
+        let policy = UnlockPolicy {
+            lockout_after: Some(5),
+            lockout_minutes: 30,
+            ..UnlockPolicy::default()
+        };
+        let now = Utc::now();
+
+        assert_eq!(
+            policy.wait(&attempts(5, 60, now), now),
+            Some(Duration::minutes(29))
+        );
+        assert_eq!(policy.wait(&attempts(5, 30 * 60, now), now), None);
+        assert_eq!(
+            UnlockPolicy::default().wait(&attempts(5, 60, now), now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reserve() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL)")
+            .unwrap();
+        let policy = UnlockPolicy {
+            lockout_after: Some(3),
+            ..UnlockPolicy::default()
+        };
+        let now = Utc::now();
+
+        // Attempts that never finish still count, so the fourth is refused
+        for failures in 0..3 {
+            let attempts = reserve(&conn, &policy, now).unwrap().unwrap();
+            assert_eq!(attempts.failures, failures);
+        }
+        assert!(reserve(&conn, &policy, now).unwrap().is_err());
+        assert_eq!(load(&conn).unwrap().failures, 3);
+
+        clear(&conn).unwrap();
+        assert_eq!(load(&conn).unwrap(), Attempts::default());
+        assert!(reserve(&conn, &policy, now).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(Duration::milliseconds(200)), "1s");
+        assert_eq!(describe(Duration::seconds(42)), "42s");
+        assert_eq!(describe(Duration::seconds(61)), "2m");
+        assert_eq!(describe(Duration::minutes(65)), "1h 5m");
+    }
+}
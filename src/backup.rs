@@ -0,0 +1,76 @@
+//! Full-store backups: the whole database in one passphrase-encrypted file
+//!
+//! `tinysecrets backup export` writes one and `backup import` restores it, so
+//! moving to a new machine keeps every project, all history and the store's
+//! KDF parameters rather than only current values. The archive is age
+//! (scrypt) encrypted with the store passphrase, which the restored store
+//! needs anyway. Inside is a short header and the SQLite file as it was.
+
+use anyhow::{Context, Result};
+use secrecy::SecretString;
+use std::io::{Read, Write};
+
+use crate::crypto::SecureBytes;
+
+/// Starts every decrypted archive, so a stray age file isn't restored as a store
+const MAGIC: &[u8] = b"tinysecrets-backup-v1\n";
+
+/// Encrypt a database snapshot into an archive
+pub fn seal(database: &[u8], passphrase: &SecretString) -> Result<Vec<u8>> {
+    let encryptor = age::Encryptor::with_user_passphrase(passphrase.clone());
+
+    let mut archive = vec![];
+    let mut writer = encryptor
+        .wrap_output(&mut archive)
+        .context("Failed to create encryption writer")?;
+    writer.write_all(MAGIC)?;
+    writer
+        .write_all(database)
+        .context("Failed to write encrypted data")?;
+    writer.finish().context("Failed to finish encryption")?;
+
+    Ok(archive)
+}
+
+/// Decrypt an archive back into the database it holds
+pub fn open(archive: &[u8], passphrase: &SecretString) -> Result<SecureBytes> {
+    let decryptor = match age::Decryptor::new(archive) {
+        Ok(age::Decryptor::Passphrase(d)) => d,
+        _ => anyhow::bail!("Not a tinysecrets backup"),
+    };
+    let mut reader = decryptor
+        .decrypt(passphrase, None)
+        .map_err(|_| anyhow::anyhow!("Wrong passphrase for this backup"))?;
+
+    let mut contents = vec![];
+    reader
+        .read_to_end(&mut contents)
+        .context("Backup is corrupted")?;
+    let contents = SecureBytes::take(contents);
+
+    match contents.expose_secret().strip_prefix(MAGIC) {
+        Some(database) => Ok(SecureBytes::take(database.to_vec())),
+        None => anyhow::bail!("Not a tinysecrets backup"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open() {
+        let passphrase = SecretString::new("backup passphrase".to_string());
+        let database = b"SQLite format 3\0...";
+
+        let archive = seal(database, &passphrase).unwrap();
+        assert!(!archive.windows(6).any(|w| w == b"SQLite"));
+        let opened = open(&archive, &passphrase).unwrap();
+        assert_eq!(opened.expose_secret(), database);
+
+        let wrong = SecretString::new("not it".to_string());
+        let err = open(&archive, &wrong).unwrap_err();
+        assert!(err.to_string().contains("Wrong passphrase"));
+        assert!(open(b"plain bytes", &passphrase).is_err());
+    }
+}
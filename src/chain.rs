@@ -0,0 +1,423 @@
+//! Tamper-evident history
+//!
+//! Every `secret_history` row and `audit_log` entry carries `prev_hash` and
+//! `entry_hash`, where `entry_hash` is an HMAC (keyed from the master key) over
+//! `prev_hash` and the row itself, id included. Editing, reordering or removing
+//! a row breaks its chain at that point. The `chain_head` metadata tags the
+//! last link of both chains, so rows cut off the end show up too.
+//!
+//! A few maintenance commands rewrite history on purpose (`security
+//! hide-names`, `migrate`, `trash empty`). They rebuild the history chain and
+//! append an audit log entry saying what they did, so the rewrite itself is
+//! on the record.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::json;
+
+use crate::crypto::{self, MasterKey};
+use crate::store::{read_metadata, write_metadata};
+
+/// Metadata key holding the tag over both chains' last links
+const HEAD_KEY: &str = "chain_head";
+
+/// The two chained tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Table {
+    History,
+    Audit,
+}
+
+impl Table {
+    fn name(self) -> &'static str {
+        match self {
+            Table::History => "secret_history",
+            Table::Audit => "audit_log",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Table::History => "history",
+            Table::Audit => "audit log",
+        }
+    }
+}
+
+/// A row as the chain sees it
+struct Link {
+    id: i64,
+    prev_hash: Option<String>,
+    entry_hash: Option<String>,
+    contents: Vec<u8>,
+}
+
+/// What `verify` found
+#[derive(Debug, Default)]
+pub struct ChainReport {
+    pub history_rows: usize,
+    pub audit_entries: usize,
+    /// History rows written before chaining existed; the next write chains them
+    pub unchained: usize,
+    /// Every break found, in table order
+    pub problems: Vec<String>,
+    /// `ID:HASH` of the last history row. Recorded outside the store, it pins
+    /// everything up to that row: rewriting any of it changes the hash.
+    pub anchor: Option<String>,
+}
+
+impl ChainReport {
+    pub fn intact(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Rows after `after`, in order
+fn links(conn: &Connection, table: Table, after: i64) -> Result<Vec<Link>> {
+    let sql = match table {
+        Table::History => {
+            "SELECT id, project, environment, key, encrypted_value, version, created_at,
+                    deleted_at, changed_by, reason, git, description, source, prev_hash, entry_hash
+             FROM secret_history WHERE id > ?1 ORDER BY id"
+        }
+        Table::Audit => {
+            "SELECT id, at, action, detail, changed_by, history_head, prev_hash, entry_hash
+             FROM audit_log WHERE id > ?1 ORDER BY id"
+        }
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([after], |row| {
+        let id: i64 = row.get(0)?;
+        let (contents, hashes) = match table {
+            Table::History => (
+                json!([
+                    id,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                    row.get::<_, Option<String>>(9)?,
+                    row.get::<_, Option<String>>(10)?,
+                    row.get::<_, Option<String>>(11)?,
+                    row.get::<_, Option<String>>(12)?,
+                ]),
+                13,
+            ),
+            Table::Audit => (
+                json!([
+                    id,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ]),
+                6,
+            ),
+        };
+        Ok(Link {
+            id,
+            prev_hash: row.get(hashes)?,
+            entry_hash: row.get(hashes + 1)?,
+            contents: contents.to_string().into_bytes(),
+        })
+    })?;
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// The id and hash of the last chained row, or (0, "") before the first
+fn last_link(conn: &Connection, table: Table) -> Result<(i64, String)> {
+    let last = conn
+        .query_row(
+            &format!(
+                "SELECT id, entry_hash FROM {} WHERE entry_hash IS NOT NULL ORDER BY id DESC LIMIT 1",
+                table.name()
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(last.unwrap_or((0, String::new())))
+}
+
+/// Hash `links` onto the chain after `prev`; returns the new last hash
+fn extend(
+    conn: &Connection,
+    key: &MasterKey,
+    table: Table,
+    mut prev: String,
+    links: &[Link],
+) -> Result<String> {
+    let sql = format!(
+        "UPDATE {} SET prev_hash = ?1, entry_hash = ?2 WHERE id = ?3",
+        table.name()
+    );
+    for link in links {
+        let hash = crypto::chain_hash(key, &prev, &link.contents);
+        conn.execute(&sql, params![prev, hash, link.id])?;
+        prev = hash;
+    }
+    Ok(prev)
+}
+
+/// Tag over both chains' last links (None while both are empty)
+fn head_tag(conn: &Connection, key: &MasterKey) -> Result<Option<String>> {
+    let history = last_link(conn, Table::History)?;
+    let audit = last_link(conn, Table::Audit)?;
+    if history.0 == 0 && audit.0 == 0 {
+        return Ok(None);
+    }
+    let heads = json!([history.0, history.1, audit.0, audit.1]).to_string();
+    Ok(Some(crypto::chain_hash(key, "", heads.as_bytes())))
+}
+
+fn update_head(conn: &Connection, key: &MasterKey) -> Result<()> {
+    if let Some(tag) = head_tag(conn, key)? {
+        write_metadata(conn, HEAD_KEY, &tag)?;
+    }
+    Ok(())
+}
+
+/// Chain history rows written since the last chained one. Called in the same
+/// transaction that wrote them. If the chain's head doesn't check out, the new
+/// rows are left unchained rather than vouching for whatever came before;
+/// `verify` reports them.
+pub fn seal(conn: &Connection, key: &MasterKey, changed_by: &str) -> Result<()> {
+    let (last_id, prev) = last_link(conn, Table::History)?;
+    let pending = links(conn, Table::History, last_id)?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+    if read_metadata(conn, HEAD_KEY)? != head_tag(conn, key)? {
+        return Ok(());
+    }
+    // A store from before chaining: its existing rows start the chain
+    let starting = last_id == 0 && pending.len() > 1;
+    extend(conn, key, Table::History, prev, &pending)?;
+    if starting {
+        append_audit(
+            conn,
+            key,
+            "chain-start",
+            &format!("started the chain with {} history rows", pending.len()),
+            changed_by,
+        )?;
+    }
+    update_head(conn, key)
+}
+
+/// Rebuild the history chain after a deliberate rewrite, and record `action`
+/// in the audit log
+pub fn rechain(
+    conn: &Connection,
+    key: &MasterKey,
+    action: &str,
+    detail: &str,
+    changed_by: &str,
+) -> Result<()> {
+    let all = links(conn, Table::History, 0)?;
+    extend(conn, key, Table::History, String::new(), &all)?;
+    append_audit(conn, key, action, detail, changed_by)?;
+    update_head(conn, key)
+}
+
+fn append_audit(
+    conn: &Connection,
+    key: &MasterKey,
+    action: &str,
+    detail: &str,
+    changed_by: &str,
+) -> Result<()> {
+    let (_, history_head) = last_link(conn, Table::History)?;
+    let (last_id, prev) = last_link(conn, Table::Audit)?;
+    conn.execute(
+        "INSERT INTO audit_log (at, action, detail, changed_by, history_head)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            Utc::now().to_rfc3339(),
+            action,
+            detail,
+            changed_by,
+            Some(history_head).filter(|h| !h.is_empty())
+        ],
+    )?;
+    let pending = links(conn, Table::Audit, last_id)?;
+    extend(conn, key, Table::Audit, prev, &pending)?;
+    Ok(())
+}
+
+/// Walk both chains and the head tag, checking `anchor` (an `ID:HASH` from an
+/// earlier report) on the way
+pub fn verify(conn: &Connection, key: &MasterKey, anchor: Option<&str>) -> Result<ChainReport> {
+    let anchor = anchor
+        .map(|anchor| {
+            anchor
+                .split_once(':')
+                .and_then(|(id, hash)| Some((id.parse::<i64>().ok()?, hash)))
+                .with_context(|| format!("Expected ID:HASH, got '{}'", anchor))
+        })
+        .transpose()?;
+    let mut report = ChainReport::default();
+    for table in [Table::History, Table::Audit] {
+        let all = links(conn, table, 0)?;
+        match table {
+            Table::History => report.history_rows = all.len(),
+            Table::Audit => report.audit_entries = all.len(),
+        }
+        // Chaining covers every row once it has started
+        let started = all.iter().any(|link| link.entry_hash.is_some());
+        let mut prev = String::new();
+        // The chain rebuilt from scratch, which is what an anchor pins
+        let mut rebuilt = String::new();
+        for link in &all {
+            rebuilt = crypto::chain_hash(key, &rebuilt, &link.contents);
+            if let Some((id, hash)) =
+                anchor.filter(|(id, _)| table == Table::History && *id == link.id)
+            {
+                if rebuilt != hash {
+                    report.problems.push(format!(
+                        "history up to row {} no longer matches the anchor",
+                        id
+                    ));
+                }
+            }
+            let Some(entry_hash) = &link.entry_hash else {
+                if started {
+                    report.problems.push(format!(
+                        "{} row {} has no hash (written while the chain was broken, or its hash was removed)",
+                        table.label(),
+                        link.id
+                    ));
+                } else {
+                    report.unchained += 1;
+                }
+                continue;
+            };
+            if link.prev_hash.as_deref() != Some(prev.as_str()) {
+                report.problems.push(format!(
+                    "{} row {} doesn't follow the row before it (rows removed or reordered)",
+                    table.label(),
+                    link.id
+                ));
+            }
+            if crypto::chain_hash(key, link.prev_hash.as_deref().unwrap_or(""), &link.contents)
+                != *entry_hash
+            {
+                report
+                    .problems
+                    .push(format!("{} row {} was modified", table.label(), link.id));
+            }
+            // Carry on from the stored hash, so one edit is reported once
+            prev = entry_hash.clone();
+        }
+        if table == Table::History {
+            if let Some((id, _)) = anchor.filter(|(id, _)| !all.iter().any(|l| l.id == *id)) {
+                report
+                    .problems
+                    .push(format!("history row {} from the anchor is gone", id));
+            }
+            report.anchor = all
+                .iter()
+                .rev()
+                .find_map(|l| Some(format!("{}:{}", l.id, l.entry_hash.as_ref()?)));
+        }
+    }
+
+    if read_metadata(conn, HEAD_KEY)? != head_tag(conn, key)? {
+        report.problems.push(
+            "the chain head doesn't match: the latest rows were removed, or the head was edited"
+                .to_string(),
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::SecretString;
+
+    fn setup() -> (Connection, MasterKey) {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(include_str!("schema.sql")).unwrap();
+        let key = MasterKey::derive(&SecretString::new("chain".into()), &[7u8; 16]).unwrap();
+        (conn, key)
+    }
+
+    fn add_row(conn: &Connection, key: &MasterKey, version: i64) {
+        conn.execute(
+            "INSERT INTO secret_history (project, environment, key, encrypted_value, version, created_at)
+             VALUES ('api', 'prod', 'TOKEN', 'v2:x', ?1, '2026-01-01T00:00:00Z')",
+            [version],
+        )
+        .unwrap();
+        seal(conn, key, "me@host").unwrap();
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let (conn, key) = setup();
+        for version in 1..=4 {
+            add_row(&conn, &key, version);
+        }
+        let report = verify(&conn, &key, None).unwrap();
+        assert!(report.intact(), "{:?}", report.problems);
+        assert_eq!(report.history_rows, 4);
+        let anchor = report.anchor.clone().unwrap();
+        assert!(anchor.starts_with("4:"));
+
+        // Edited row
+        conn.execute("UPDATE secret_history SET reason = 'x' WHERE id = 2", [])
+            .unwrap();
+        let report = verify(&conn, &key, None).unwrap();
+        assert_eq!(report.problems, ["history row 2 was modified"]);
+        let report = verify(&conn, &key, Some(&anchor)).unwrap();
+        assert!(report.problems.len() == 2 && report.problems[1].contains("anchor"));
+        conn.execute("UPDATE secret_history SET reason = NULL WHERE id = 2", [])
+            .unwrap();
+        assert!(verify(&conn, &key, Some(&anchor)).unwrap().intact());
+
+        // Removed row in the middle, then at the end
+        conn.execute("DELETE FROM secret_history WHERE id = 3", [])
+            .unwrap();
+        assert!(verify(&conn, &key, None).unwrap().problems[0].contains("row 4 doesn't follow"));
+        conn.execute("DELETE FROM secret_history WHERE id = 4", [])
+            .unwrap();
+        let report = verify(&conn, &key, None).unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].contains("chain head"));
+
+        // A deliberate rewrite is rechained and logged
+        rechain(&conn, &key, "purge", "removed 2 rows", "me@host").unwrap();
+        let report = verify(&conn, &key, None).unwrap();
+        assert!(report.intact(), "{:?}", report.problems);
+        assert_eq!(report.audit_entries, 1);
+    }
+
+    #[test]
+    fn test_existing_rows_start_the_chain() {
+        let (conn, key) = setup();
+        for version in 1..=2 {
+            conn.execute(
+                "INSERT INTO secret_history (project, environment, key, encrypted_value, version, created_at)
+                 VALUES ('api', 'prod', 'TOKEN', 'v2:x', ?1, '2026-01-01T00:00:00Z')",
+                [version],
+            )
+            .unwrap();
+        }
+        let report = verify(&conn, &key, None).unwrap();
+        assert!(report.intact());
+        assert_eq!(report.unchained, 2);
+
+        add_row(&conn, &key, 3);
+        let report = verify(&conn, &key, None).unwrap();
+        assert!(report.intact(), "{:?}", report.problems);
+        assert_eq!(report.unchained, 0);
+        assert_eq!(report.audit_entries, 1);
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::collections::BTreeMap;
+
+use crate::cli::open_store;
+use crate::refs::SecretPath;
+use crate::ui;
+
+/// Report values that more than one environment or project holds, compared by
+/// keyed fingerprint. Exits non-zero if any are found.
+pub fn run(project: Option<&str>, min_length: usize) -> Result<()> {
+    let store = open_store()?;
+    let groups = shared_values(store.value_fingerprints(project, min_length)?);
+    let scope = project.map_or("the store".to_string(), |p| p.cyan().to_string());
+
+    if groups.is_empty() {
+        eprintln!(
+            "{} No values are shared between environments in {}",
+            ui::ok(),
+            scope
+        );
+        return Ok(());
+    }
+
+    std::eprintln!(
+        "{} {} value(s) in {} are shared between environments:",
+        ui::fail(),
+        groups.len(),
+        scope
+    );
+    for group in &groups {
+        let paths: Vec<String> = group
+            .iter()
+            .map(|path| {
+                format!(
+                    "{}/{}/{}",
+                    path.project.cyan(),
+                    path.environment.yellow(),
+                    path.key.bold()
+                )
+            })
+            .collect();
+        std::eprintln!("  {} {}", ui::bullet(), paths.join(", "));
+    }
+    std::eprintln!(
+        "  Give each environment its own credentials (rotate with {})",
+        "tinysecrets set -p <project> -e <env> <KEY>".cyan()
+    );
+    std::process::exit(1);
+}
+
+/// Keys grouped by value, keeping only values found in more than one
+/// environment or project. A value repeated within one environment is left
+/// alone. Groups are ordered by their first key.
+fn shared_values(fingerprints: Vec<(SecretPath, String)>) -> Vec<Vec<SecretPath>> {
+    let mut by_value: BTreeMap<String, Vec<SecretPath>> = BTreeMap::new();
+    for (path, fingerprint) in fingerprints {
+        by_value.entry(fingerprint).or_default().push(path);
+    }
+
+    let mut groups: Vec<Vec<SecretPath>> = by_value
+        .into_values()
+        .filter(|group| {
+            group.iter().any(|path| {
+                (&path.project, &path.environment) != (&group[0].project, &group[0].environment)
+            })
+        })
+        .collect();
+    groups.sort_by_key(|group| group[0].to_string());
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_values() {
+        let fingerprints = [
+            ("api", "prod", "STRIPE_KEY", "aa"),
+            ("api", "staging", "STRIPE_KEY", "aa"),
+            ("api", "dev", "DB_URL", "bb"),
+            ("api", "dev", "READ_DB_URL", "bb"),
+            ("api", "prod", "DB_URL", "cc"),
+            ("web", "prod", "SESSION_KEY", "dd"),
+            ("api", "prod", "SESSION_KEY", "dd"),
+        ]
+        .into_iter()
+        .map(|(project, env, key, fingerprint)| {
+            (SecretPath::new(project, env, key), fingerprint.to_string())
+        })
+        .collect();
+
+        let groups: Vec<Vec<String>> = shared_values(fingerprints)
+            .iter()
+            .map(|group| group.iter().map(ToString::to_string).collect())
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                vec!["api/prod/STRIPE_KEY", "api/staging/STRIPE_KEY"],
+                vec!["web/prod/SESSION_KEY", "api/prod/SESSION_KEY"],
+            ]
+        );
+    }
+}
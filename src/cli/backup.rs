@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::io::Write;
+use std::path::Path;
+
+use crate::cli::{open_store, resolve_passphrase, BackupAction};
+use crate::permissions;
+use crate::store::Store;
+use crate::ui;
+
+pub fn run(action: BackupAction) -> Result<()> {
+    match action {
+        BackupAction::Export { file, force } => export(&file, force),
+        BackupAction::Import { file, force } => import(&file, force),
+    }
+}
+
+fn export(file: &Path, force: bool) -> Result<()> {
+    if file.exists() && !force {
+        anyhow::bail!(
+            "{} already exists. Use --force to overwrite it.",
+            file.display()
+        );
+    }
+
+    let store = open_store()?;
+    let archive = store.backup()?;
+    let projects = store.project_summaries()?;
+
+    permissions::create_private_file(file)
+        .and_then(|mut out| out.write_all(&archive))
+        .with_context(|| format!("Failed to write {}", file.display()))?;
+
+    eprintln!(
+        "{} Backed up {} project(s), {} secret(s) and their history to {}",
+        ui::ok(),
+        projects.len(),
+        projects.iter().map(|p| p.secrets).sum::<usize>(),
+        file.display().to_string().cyan()
+    );
+    eprintln!(
+        "  Restore with: {} (needs the store passphrase)",
+        format!("tinysecrets backup import --file {}", file.display()).cyan()
+    );
+    Ok(())
+}
+
+fn import(file: &Path, force: bool) -> Result<()> {
+    let archive =
+        std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let path = Store::path()?;
+    if path.exists() && !force {
+        anyhow::bail!(
+            "A store already exists at {}. Use --force to replace it (it's kept as a .bak file).",
+            path.display()
+        );
+    }
+
+    let (passphrase, _) = resolve_passphrase()?;
+    let previous = Store::restore(&archive, &passphrase, force)?;
+    // Opening checks the passphrase and brings an older schema up to date
+    let store = Store::open(passphrase)?;
+    let projects = store.project_summaries()?;
+
+    eprintln!(
+        "{} Restored {} project(s) and {} secret(s) with history to {}",
+        ui::ok(),
+        projects.len(),
+        projects.iter().map(|p| p.secrets).sum::<usize>(),
+        path.display().to_string().cyan()
+    );
+    if let Some(previous) = previous {
+        eprintln!(
+            "  The previous store was kept at {}",
+            previous.display().to_string().dimmed()
+        );
+    }
+    Ok(())
+}
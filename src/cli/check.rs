@@ -0,0 +1,79 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::{check_value_rules, open_store};
+use crate::config::Config;
+use crate::ui;
+
+pub fn run(project: &str, environment: &str, config: Option<&Config>) -> Result<()> {
+    let Some(config) = config.filter(|c| !c.required.is_empty() || !c.validate.is_empty()) else {
+        eprintln!(
+            "{} No required keys or [validate] rules declared. Add `required = [\"KEY\", ...]` to .tinysecrets.toml",
+            ui::note()
+        );
+        return Ok(());
+    };
+
+    let store = open_store()?;
+    let secrets = store.get_all(project, environment)?;
+
+    require_keys(project, environment, &secrets, config);
+    if !config.required.is_empty() {
+        eprintln!(
+            "{} All {} required keys present for {}/{}",
+            ui::ok(),
+            config.required.len().to_string().bold(),
+            project.cyan(),
+            environment.yellow()
+        );
+    }
+
+    if !config.validate.is_empty() {
+        let mut allowed = true;
+        for (key, value) in secrets.iter() {
+            allowed &= check_value_rules(Some(config), key, value.as_bytes())?;
+        }
+        if !allowed {
+            std::eprintln!(
+                "{} {}/{} has values that break the [validate] rules",
+                ui::fail(),
+                project.cyan(),
+                environment.yellow()
+            );
+            std::process::exit(1);
+        }
+        eprintln!(
+            "{} Values in {}/{} follow the [validate] rules",
+            ui::ok(),
+            project.cyan(),
+            environment.yellow()
+        );
+    }
+
+    Ok(())
+}
+
+/// Exit with the list of missing keys if any required key is missing
+pub fn require_keys(
+    project: &str,
+    environment: &str,
+    secrets: &[(String, String)],
+    config: &Config,
+) {
+    let missing = config.missing_keys(secrets.iter().map(|(k, _)| k.as_str()));
+    if missing.is_empty() {
+        return;
+    }
+
+    std::eprintln!(
+        "{} {}/{} is missing {} required keys:",
+        ui::fail(),
+        project.cyan(),
+        environment.yellow(),
+        missing.len()
+    );
+    for key in &missing {
+        std::eprintln!("  {} {}", ui::bullet(), key.bold());
+    }
+    std::process::exit(1);
+}
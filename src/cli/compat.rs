@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::cli::open_store;
+use crate::compat::{self, BUNDLE_FORMAT_VERSION, CLI_VERSION};
+use crate::store::ExportBundle;
+use crate::ui;
+
+pub fn run_check(bundle: Option<&str>) -> Result<()> {
+    match bundle {
+        Some(path) => check_bundle(path),
+        None => check_store(),
+    }
+}
+
+fn check_bundle(path: &str) -> Result<()> {
+    let json = std::fs::read_to_string(path).context(format!("Failed to read bundle: {}", path))?;
+    let bundle: ExportBundle =
+        serde_json::from_str(&json).context("Failed to parse export bundle (invalid format)")?;
+
+    println!("{}{}", ui::icon("📦 ", "Bundle: "), path.cyan());
+    for (i, section) in bundle.sections().iter().enumerate() {
+        println!(
+            "  {}{}/{} ({} secrets)",
+            if i == 0 {
+                "contents:       "
+            } else {
+                "                "
+            },
+            section.project,
+            section.environment,
+            section.secrets.len()
+        );
+    }
+    println!(
+        "  bundle format:  v{} (this binary reads up to v{})",
+        bundle.version, BUNDLE_FORMAT_VERSION
+    );
+    println!(
+        "  exported by:    {}",
+        bundle
+            .tinysecrets_version
+            .as_deref()
+            .unwrap_or("unknown (older tinysecrets)")
+    );
+    println!(
+        "  integrity tag:  {}",
+        match &bundle.integrity {
+            Some(_) => "yes (verified on import)",
+            None if bundle.version >= compat::TAGGED_BUNDLE_FORMAT_VERSION => {
+                "missing (the bundle was modified; import refuses it)"
+            }
+            None => "none (older bundle; import needs --allow-untagged)",
+        }
+    );
+    println!(
+        "  signed by:      {}",
+        match &bundle.signature {
+            Some(signature) => signature.signer.as_str(),
+            None => "not signed",
+        }
+    );
+    println!("  this binary:    {}", CLI_VERSION);
+    println!();
+
+    if let Err(e) = compat::check_bundle_version(bundle.version) {
+        std::eprintln!("{} {}", ui::fail(), e);
+        std::process::exit(1);
+    }
+
+    match bundle.tinysecrets_version.as_deref() {
+        Some(version) if compat::is_newer_than_cli(version) => eprintln!(
+            "{} Bundle was exported by a newer tinysecrets; import should work but consider upgrading",
+            ui::warn()
+        ),
+        _ => eprintln!("{} Bundle is compatible", ui::ok()),
+    }
+
+    Ok(())
+}
+
+fn check_store() -> Result<()> {
+    let store = open_store()?;
+    let written_by = store.written_by_version();
+
+    println!(
+        "{}{}",
+        ui::icon("🔐 ", "Store: "),
+        crate::store::Store::path()?.display().to_string().cyan()
+    );
+    println!(
+        "  last written by: {}",
+        written_by
+            .as_deref()
+            .unwrap_or("unknown (older tinysecrets)")
+    );
+    println!("  this binary:     {}", CLI_VERSION);
+    println!();
+
+    match written_by.as_deref() {
+        Some(version) if compat::is_newer_than_cli(version) => eprintln!(
+            "{} Store was written by a newer tinysecrets; upgrade before writing to it",
+            ui::warn()
+        ),
+        _ => eprintln!("{} Store is compatible", ui::ok()),
+    }
+
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use anyhow::Result;
 use colored::Colorize;
 
-use crate::config::Config;
+use crate::config::{self, Config, ConfigResolver};
+use crate::keychain;
+use crate::store::{PathSource, Store, STORE_ENV_VAR};
+use crate::ui;
 
 pub fn run_init(project: &str, environment: Option<&str>) -> Result<()> {
     let path = Config::config_path()?;
@@ -9,7 +12,7 @@ pub fn run_init(project: &str, environment: Option<&str>) -> Result<()> {
     if path.exists() {
         eprintln!(
             "{} Config file already exists at {}",
-            "⚠".yellow(),
+            ui::warn(),
             path.display().to_string().cyan()
         );
         eprintln!("  Use `tinysecrets config show` to view or `tinysecrets config set` to modify");
@@ -20,7 +23,7 @@ pub fn run_init(project: &str, environment: Option<&str>) -> Result<()> {
 
     eprintln!(
         "{} Created {} with:",
-        "✓".green(),
+        ui::ok(),
         saved_path.display().to_string().cyan()
     );
     eprintln!("  project: {}", project.yellow());
@@ -37,45 +40,155 @@ pub fn run_init(project: &str, environment: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-pub fn run_show() -> Result<()> {
-    match Config::found_path()? {
-        Some(path) => {
-            eprintln!(
-                "{} {}",
-                "Config file:".dimmed(),
-                path.display().to_string().cyan()
-            );
-            eprintln!();
-
-            let config = Config::load()?.unwrap();
+/// Show what every setting resolves to and why: project, environment, the
+/// config file, the store, keychain and crypto format
+pub fn run_show(project: Option<&str>, environment: Option<&str>) -> Result<()> {
+    let resolver = ConfigResolver::new()?;
 
-            if let Some(project) = &config.project {
-                eprintln!("  project: {}", project.yellow());
-            }
-            if let Some(environment) = &config.environment {
-                eprintln!("  environment: {}", environment.yellow());
-            }
+    match resolver.path() {
+        Some(path) => eprintln!(
+            "{} {}",
+            "Config file:".dimmed(),
+            path.display().to_string().cyan()
+        ),
+        None => eprintln!(
+            "{} none found in the current directory or ancestors (create one with {})",
+            "Config file:".dimmed(),
+            "tinysecrets config init <project> [environment]".cyan()
+        ),
+    }
+    match config::active_profile() {
+        Some((name, _)) => eprintln!("{} {}", "Profile:".dimmed(), name.cyan()),
+        None => eprintln!("{} {}", "Profile:".dimmed(), "(none)".dimmed()),
+    }
+    eprintln!();
 
-            if config.project.is_none() && config.environment.is_none() {
-                eprintln!("  {}", "(empty config)".dimmed());
-            }
-        }
-        None => {
+    match resolver.project_source(project) {
+        Ok((name, source)) => {
+            let resolved = resolver.alias(&name);
+            let via = if resolved != name {
+                format!("; alias {}", name)
+            } else {
+                String::new()
+            };
             eprintln!(
-                "{} No .tinysecrets.toml found in current directory or ancestors",
-                "⚠".yellow()
+                "  project:     {} {}",
+                resolved.yellow(),
+                format!("(from {}{})", source.label(), via).dimmed()
             );
-            eprintln!();
+        }
+        Err(_) => eprintln!("  project:     {}", "(not set)".dimmed()),
+    }
+    match resolver.environment_source(environment) {
+        Ok((name, source)) => {
+            let resolved = config::expand_environment(name.clone())?;
+            let via = if resolved != name {
+                format!("; {}", name)
+            } else {
+                String::new()
+            };
             eprintln!(
-                "Create one with: {}",
-                "tinysecrets config init <project> [environment]".cyan()
+                "  environment: {} {}",
+                resolved.yellow(),
+                format!("(from {}{})", source.label(), via).dimmed()
             );
         }
+        Err(_) => eprintln!("  environment: {}", "(not set)".dimmed()),
     }
+    eprintln!(
+        "  {}",
+        "resolution order: -p/-e flags > TINYSECRETS_PROJECT/TINYSECRETS_ENV > config file > profile"
+            .dimmed()
+    );
 
+    if let Some(config) = resolver.config() {
+        if !config.required.is_empty() {
+            eprintln!("  required: {}", config.required.join(", ").yellow());
+        }
+        for (key, value) in &config.defaults {
+            eprintln!("  default: {}={}", key.yellow(), value);
+        }
+        for (alias, project) in &config.aliases {
+            eprintln!("  alias: {} {} {}", alias.yellow(), ui::arrow(), project);
+        }
+        for (path, member) in &config.workspace {
+            let target = [member.project.as_deref(), member.environment.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("/");
+            eprintln!("  workspace: {} {} {}", path.yellow(), ui::arrow(), target);
+        }
+    }
+
+    show_store_path()?;
+    show_store_state();
     Ok(())
 }
 
+/// Print the active store path and how it was resolved
+fn show_store_path() -> Result<()> {
+    let (path, source) = Store::resolve_path()?;
+
+    eprintln!();
+    eprintln!(
+        "{} {} {}",
+        "Store:".dimmed(),
+        path.display().to_string().cyan(),
+        format!("(from {})", source.label()).dimmed()
+    );
+    eprintln!(
+        "  {}",
+        format!(
+            "resolution order: --store flag > {} > profile > global config (store_path) > default",
+            STORE_ENV_VAR
+        )
+        .dimmed()
+    );
+    if source == PathSource::Default {
+        eprintln!(
+            "  {}",
+            "default: ~/.tinysecrets/store.db, or $XDG_DATA_HOME/tinysecrets/store.db on Linux"
+                .dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// Keychain and crypto format, read without unlocking the store
+fn show_store_state() {
+    let keychain = match keychain::get_passphrase() {
+        Ok(Some(_)) => "passphrase saved".to_string(),
+        Ok(None) => "available, no passphrase saved".to_string(),
+        Err(e) => format!("unavailable ({:#})", e),
+    };
+    eprintln!("  keychain: {}", keychain);
+
+    match Store::status() {
+        Ok(status) => {
+            let format = if status.legacy_values > 0 || !status.fast_check {
+                format!(
+                    "{} legacy (age) value(s), run `tinysecrets migrate`",
+                    status.legacy_values
+                )
+            } else {
+                "ChaCha20-Poly1305 with a scrypt key".to_string()
+            };
+            eprintln!(
+                "  format:   schema v{}, {} ({} value(s))",
+                status.schema_version,
+                format,
+                status.current_values + status.legacy_values
+            );
+            if status.read_only_passphrase {
+                eprintln!("  read-only passphrase: set");
+            }
+        }
+        Err(e) => eprintln!("  format:   {}", format!("{:#}", e).dimmed()),
+    }
+}
+
 pub fn run_set(project: Option<&str>, environment: Option<&str>) -> Result<()> {
     let mut config = Config::load()?.unwrap_or_default();
     let mut changed = false;
@@ -91,18 +204,14 @@ pub fn run_set(project: Option<&str>, environment: Option<&str>) -> Result<()> {
     }
 
     if !changed {
-        eprintln!("{} No changes specified", "⚠".yellow());
+        eprintln!("{} No changes specified", ui::warn());
         eprintln!("Usage: tinysecrets config set [--project <name>] [--environment <name>]");
         return Ok(());
     }
 
     let path = config.save()?;
 
-    eprintln!(
-        "{} Updated {}",
-        "✓".green(),
-        path.display().to_string().cyan()
-    );
+    eprintln!("{} Updated {}", ui::ok(), path.display().to_string().cyan());
 
     if let Some(p) = &config.project {
         eprintln!("  project: {}", p.yellow());
@@ -0,0 +1,150 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::{open_store, provide_database_key, DbAction};
+use crate::compat::CLI_VERSION;
+use crate::keychain;
+use crate::migrations::{self, SCHEMA_VERSION};
+use crate::store::Store;
+use crate::ui;
+
+pub fn run(action: DbAction) -> Result<()> {
+    match action {
+        DbAction::Status => status(),
+        DbAction::Encrypt => set_full_encryption(true),
+        DbAction::Decrypt => set_full_encryption(false),
+    }
+}
+
+fn status() -> Result<()> {
+    provide_database_key()?;
+    let status = Store::status()?;
+
+    println!(
+        "{}{}",
+        ui::icon("🔐 ", "Store: "),
+        Store::path()?.display().to_string().cyan()
+    );
+    println!(
+        "  schema:           v{} (this binary writes v{})",
+        status.schema_version, SCHEMA_VERSION
+    );
+    println!(
+        "  passphrase check: {}",
+        if status.fast_check {
+            "v2 (HMAC)"
+        } else {
+            "legacy (age)"
+        }
+    );
+    println!(
+        "  key salt:         {}",
+        if status.has_salt {
+            "present"
+        } else {
+            "missing"
+        }
+    );
+    println!(
+        "  values:           {} current, {} legacy",
+        status.current_values, status.legacy_values
+    );
+    println!(
+        "  last written by:  {}",
+        status
+            .written_by_version
+            .as_deref()
+            .unwrap_or("unknown (older tinysecrets)")
+    );
+    println!(
+        "  read-only pass:   {}",
+        if status.read_only_passphrase {
+            "set"
+        } else {
+            "none"
+        }
+    );
+    println!(
+        "  file:             {}",
+        if status.full_encryption {
+            "fully encrypted (SQLCipher)"
+        } else {
+            "plain SQLite, values encrypted"
+        }
+    );
+    println!("  this binary:      {}", CLI_VERSION);
+    println!();
+
+    if status.schema_version > SCHEMA_VERSION {
+        std::eprintln!(
+            "{} Store schema is newer than this tinysecrets supports. Upgrade to open it.",
+            ui::fail()
+        );
+        std::process::exit(1);
+    }
+
+    let pending: Vec<_> = migrations::pending(status.schema_version).collect();
+    if pending.is_empty() {
+        eprintln!("{} Schema is up to date", ui::ok());
+    } else {
+        eprintln!(
+            "{} {} pending migration(s), applied automatically on next open:",
+            ui::info(),
+            pending.len()
+        );
+        for migration in pending {
+            eprintln!(
+                "  {} v{}: {}",
+                ui::arrow(),
+                migration.version,
+                migration.description
+            );
+        }
+    }
+
+    if status.legacy_values > 0 {
+        eprintln!(
+            "{} {} values use the legacy format. Run `tinysecrets migrate` to upgrade them.",
+            ui::note(),
+            status.legacy_values
+        );
+    }
+
+    Ok(())
+}
+
+/// Convert the store to or from full-file encryption. Its keychain entry
+/// follows, since a fully encrypted store is known by a different ID.
+fn set_full_encryption(on: bool) -> Result<()> {
+    let account = keychain::account()?;
+    let store = open_store()?;
+    if !store.set_full_encryption(on)? {
+        eprintln!(
+            "{} Store is already {}",
+            ui::note(),
+            if on {
+                "fully encrypted"
+            } else {
+                "a plain SQLite file"
+            }
+        );
+        return Ok(());
+    }
+
+    if let Err(e) = keychain::adopt_entry(&account) {
+        eprintln!("{} Keychain error: {}", ui::warn(), e);
+    }
+    if on {
+        eprintln!(
+            "{} Store is now fully encrypted: key names, projects and timestamps are hidden too",
+            ui::ok()
+        );
+        eprintln!("  SSH keys and the read-only passphrase can no longer unlock it");
+    } else {
+        eprintln!(
+            "{} Store is now a plain SQLite file (values are still encrypted)",
+            ui::ok()
+        );
+    }
+    Ok(())
+}
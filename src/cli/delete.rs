@@ -1,31 +1,187 @@
 use anyhow::Result;
 use colored::Colorize;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::{confirm_destructive, open_store};
+use crate::errors;
+use crate::pattern::{glob_match, is_glob};
+use crate::ui;
 
-pub fn run(project: &str, environment: &str, key: &str) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+pub fn run(project: &str, environment: &str, key: &str, force: bool) -> Result<()> {
+    if is_glob(key) {
+        return run_glob(project, environment, key, force);
+    }
+
+    let store = open_store()?;
+
+    if store.entry(project, environment, key)?.is_some() {
+        confirm_destructive(
+            &format!(
+                "Delete {}/{}/{}? (restorable with `undelete`)",
+                project.cyan(),
+                environment.yellow(),
+                key.bold()
+            ),
+            force,
+        )?;
+    }
 
     if store.delete(project, environment, key)? {
         eprintln!(
             "{} Deleted {}/{}/{}",
-            "✓".green(),
+            ui::ok(),
             project.cyan(),
             environment.yellow(),
             key.bold()
         );
     } else {
-        eprintln!(
-            "{} Secret not found: {}/{}/{}",
-            "✗".red(),
+        return Err(errors::not_found(format!(
+            "Secret not found: {}/{}/{}",
+            project, environment, key
+        )));
+    }
+
+    Ok(())
+}
+
+/// Delete every key in the environment matching a glob (e.g. `STRIPE_*`)
+fn run_glob(project: &str, environment: &str, pattern: &str, force: bool) -> Result<()> {
+    let store = open_store()?;
+
+    let keys: Vec<String> = store
+        .list(Some(project), Some(environment))?
+        .into_iter()
+        .map(|entry| entry.key)
+        .filter(|key| glob_match(pattern, key))
+        .collect();
+    if keys.is_empty() {
+        return Err(errors::not_found(format!(
+            "No secrets in {}/{} match {}",
+            project, environment, pattern
+        )));
+    }
+
+    if !force {
+        list_keys(&keys);
+    }
+    confirm_destructive(
+        &format!(
+            "Delete these {} secrets from {}/{}? (restorable with `undelete`)",
+            keys.len(),
+            project.cyan(),
+            environment.yellow()
+        ),
+        force,
+    )?;
+
+    let deleted = store.delete_keys(project, environment, &keys)?;
+    eprintln!(
+        "{} Deleted {} secrets from {}/{}",
+        ui::ok(),
+        deleted.to_string().bold(),
+        project.cyan(),
+        environment.yellow()
+    );
+    Ok(())
+}
+
+/// Delete an environment: all its secrets and its inheritance settings
+pub fn run_env(project: &str, environment: &str, force: bool) -> Result<()> {
+    let store = open_store()?;
+
+    let keys: Vec<String> = store
+        .list(Some(project), Some(environment))?
+        .into_iter()
+        .map(|entry| entry.key)
+        .collect();
+    let known = store
+        .list_environments(project)?
+        .iter()
+        .any(|e| e == environment);
+    if !known {
+        return Err(errors::not_found(format!(
+            "Environment not found: {}/{}",
+            project, environment
+        )));
+    }
+    let children = store.children(project, environment)?;
+
+    if !force {
+        list_keys(&keys);
+        if !children.is_empty() {
+            eprintln!(
+                "{} {} inherit from {} and will stop inheriting",
+                ui::warn(),
+                children.join(", ").yellow(),
+                environment.yellow()
+            );
+        }
+    }
+    confirm_destructive(
+        &format!(
+            "Delete environment {}/{} and its {} secrets? (restorable with `undelete`)",
             project.cyan(),
             environment.yellow(),
-            key.bold()
-        );
-        std::process::exit(1);
+            keys.len()
+        ),
+        force,
+    )?;
+
+    let deleted = store.delete_environment(project, environment)?;
+    eprintln!(
+        "{} Deleted {}/{} ({} secrets)",
+        ui::ok(),
+        project.cyan(),
+        environment.yellow(),
+        deleted
+    );
+    Ok(())
+}
+
+/// Delete a project: every secret in every environment
+pub fn run_project(project: &str, force: bool) -> Result<()> {
+    let store = open_store()?;
+
+    let environments = store.list_environments(project)?;
+    if environments.is_empty() {
+        return Err(errors::not_found(format!("Project not found: {}", project)));
     }
 
+    let mut total = 0;
+    for environment in &environments {
+        let count = store.list(Some(project), Some(environment))?.len();
+        if !force {
+            eprintln!(
+                "  {} {} ({} secrets)",
+                ui::bullet(),
+                environment.yellow(),
+                count
+            );
+        }
+        total += count;
+    }
+    confirm_destructive(
+        &format!(
+            "Delete project {} with {} environments and {} secrets? (restorable with `undelete`)",
+            project.cyan(),
+            environments.len(),
+            total
+        ),
+        force,
+    )?;
+
+    let deleted = store.delete_project(project)?;
+    eprintln!(
+        "{} Deleted {} ({} secrets in {} environments)",
+        ui::ok(),
+        project.cyan(),
+        deleted,
+        environments.len()
+    );
     Ok(())
 }
+
+fn list_keys(keys: &[String]) {
+    for key in keys {
+        eprintln!("  {} {}", ui::bullet(), key.bold());
+    }
+}
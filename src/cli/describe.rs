@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::cli::open_store;
+use crate::errors;
+use crate::permissions;
+use crate::ui;
+
+pub fn run(project: &str, environment: &str, key: &str, description: Option<&str>) -> Result<()> {
+    let store = open_store()?;
+
+    let Some(entry) = store.entry(project, environment, key)? else {
+        return Err(errors::not_found(format!(
+            "Secret not found: {}/{}/{}",
+            project, environment, key
+        )));
+    };
+
+    let new_description = match description {
+        Some(d) => d.trim().to_string(),
+        None => {
+            let template = format!(
+                "{}\n# Describe {}/{}/{}\n# Lines starting with # will be ignored. Leave empty to clear.\n",
+                entry.description.as_deref().unwrap_or_default(),
+                project,
+                environment,
+                key
+            );
+
+            let edited = permissions::edit(&template)
+                .context("Failed to open editor. Set $EDITOR or pass the description directly.")?;
+
+            edited
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string()
+        }
+    };
+
+    let new_description = (!new_description.is_empty()).then_some(new_description);
+    store.set_description(project, environment, key, new_description.as_deref())?;
+
+    match new_description {
+        Some(_) => eprintln!(
+            "{} Updated description for {}/{}/{}",
+            ui::ok(),
+            project.cyan(),
+            environment.yellow(),
+            key.bold()
+        ),
+        None => eprintln!(
+            "{} Cleared description for {}/{}/{}",
+            ui::ok(),
+            project.cyan(),
+            environment.yellow(),
+            key.bold()
+        ),
+    }
+
+    Ok(())
+}
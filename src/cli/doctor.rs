@@ -0,0 +1,288 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use crate::cli::warn_if_synced;
+use crate::keychain;
+use crate::migrations::{self, SCHEMA_VERSION};
+use crate::permissions::{self, Exposure};
+use crate::store::Store;
+use crate::ui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One line of the report, with what to do about it
+struct Check {
+    status: Status,
+    message: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(message: impl Into<String>) -> Self {
+        Self {
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Check the store and the environment around it, printing a fix for each problem
+pub fn run() -> Result<()> {
+    let mut checks = Vec::new();
+    let (path, source) = Store::resolve_path()?;
+
+    if path.exists() {
+        checks.push(Check::ok(format!(
+            "Store at {} ({})",
+            path.display(),
+            source.label()
+        )));
+        checks.extend(check_permissions(&path));
+        checks.extend(check_database());
+    } else {
+        checks.push(Check::fail(
+            format!("No store at {} ({})", path.display(), source.label()),
+            "Create one with `tinysecrets init`, or point --store / TINYSECRETS_STORE at yours",
+        ));
+    }
+    checks.push(check_keychain());
+    checks.push(check_editor());
+    checks.push(check_locale(
+        std::env::var("LC_ALL").ok().as_deref(),
+        std::env::var("LC_CTYPE").ok().as_deref(),
+        std::env::var("LANG").ok().as_deref(),
+    ));
+
+    for check in &checks {
+        let marker = match check.status {
+            Status::Ok => ui::ok(),
+            Status::Warn => ui::warn(),
+            Status::Fail => ui::fail(),
+        };
+        println!("{} {}", marker, check.message);
+        if let Some(fix) = &check.fix {
+            println!("  {} {}", ui::arrow(), fix.cyan());
+        }
+    }
+    if path.exists() {
+        warn_if_synced(&path)?;
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    let warned = checks.iter().filter(|c| c.status == Status::Warn).count();
+    println!();
+    if failed > 0 {
+        anyhow::bail!("{} problem(s) found, {} warning(s)", failed, warned);
+    }
+    if warned > 0 {
+        eprintln!("{} No problems, {} warning(s)", ui::ok(), warned);
+    } else {
+        eprintln!("{} Everything looks good", ui::ok());
+    }
+    Ok(())
+}
+
+/// The store and its directory should only be readable by their owner
+fn check_permissions(path: &Path) -> Vec<Check> {
+    let mut checks = Vec::new();
+    for (target, exposure, wanted) in permissions::store_exposure(path) {
+        let fix = format!("chmod {:o} {}", wanted, target.display());
+        match exposure {
+            Exposure::Private => {}
+            Exposure::Readable => checks.push(Check::warn(
+                format!("{} is readable by other users", target.display()),
+                fix,
+            )),
+            Exposure::Writable => checks.push(Check::fail(
+                format!(
+                    "{} is writable by other users (refused without --insecure-ok)",
+                    target.display()
+                ),
+                fix,
+            )),
+        }
+    }
+    if checks.is_empty() {
+        checks.push(Check::ok("Store permissions are owner-only"));
+    }
+    checks
+}
+
+/// SQLite integrity, schema version and leftover legacy encryption
+fn check_database() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    // Doctor doesn't ask for the passphrase, so there's nothing more to read
+    if Store::fully_encrypted().unwrap_or(false) {
+        checks.push(Check::ok(
+            "Store is fully encrypted (SQLCipher); `tinysecrets db status` checks its schema",
+        ));
+        return checks;
+    }
+
+    match Store::integrity_check() {
+        Ok(problems) if problems.is_empty() => {
+            checks.push(Check::ok("SQLite integrity check passed"))
+        }
+        Ok(problems) => checks.push(Check::fail(
+            format!(
+                "SQLite integrity check found {} problem(s): {}",
+                problems.len(),
+                problems.first().map(String::as_str).unwrap_or_default()
+            ),
+            "Restore store.db from a backup, or salvage it with `sqlite3 store.db .recover`",
+        )),
+        Err(e) => checks.push(Check::fail(
+            format!("Couldn't open the database: {:#}", e),
+            "Check the path and permissions, or restore from a backup",
+        )),
+    }
+
+    let status = match Store::status() {
+        Ok(status) => status,
+        Err(_) => return checks,
+    };
+    if status.schema_version > SCHEMA_VERSION {
+        checks.push(Check::fail(
+            format!(
+                "Store schema v{} is newer than this tinysecrets supports (v{})",
+                status.schema_version, SCHEMA_VERSION
+            ),
+            "Upgrade tinysecrets",
+        ));
+    } else if migrations::pending(status.schema_version).next().is_some() {
+        checks.push(Check::warn(
+            format!(
+                "Store schema v{} will be upgraded to v{} on next unlock",
+                status.schema_version, SCHEMA_VERSION
+            ),
+            "Run any command that unlocks the store, e.g. `tinysecrets list`",
+        ));
+    } else {
+        checks.push(Check::ok(format!("Schema v{} is current", SCHEMA_VERSION)));
+    }
+
+    if status.legacy_values > 0 || !status.fast_check {
+        checks.push(Check::warn(
+            format!(
+                "{} value(s) still use the legacy (age) format",
+                status.legacy_values
+            ),
+            "Re-encrypt them with `tinysecrets migrate`",
+        ));
+    } else {
+        checks.push(Check::ok(format!(
+            "All {} value(s) use the current format",
+            status.current_values
+        )));
+    }
+    checks
+}
+
+fn check_keychain() -> Check {
+    match keychain::get_passphrase() {
+        Ok(Some(_)) => Check::ok("System keychain available, passphrase saved"),
+        Ok(None) => Check::ok("System keychain available (no passphrase saved)"),
+        Err(e) => Check::warn(
+            format!("System keychain unavailable: {:#}", e),
+            "Enter the passphrase each time, or set TINYSECRETS_PASSPHRASE in CI",
+        ),
+    }
+}
+
+fn check_editor() -> Check {
+    let configured = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.trim().is_empty()));
+    match (edit::get_editor(), configured) {
+        (Ok(editor), Some(_)) => Check::ok(format!("Editor: {}", editor.display())),
+        (Ok(editor), None) => Check::warn(
+            format!(
+                "$EDITOR isn't set; `set` without a value falls back to {}",
+                editor.display()
+            ),
+            "export EDITOR=vim  # or your editor of choice",
+        ),
+        (Err(_), Some(editor)) => Check::warn(
+            format!("Editor `{}` wasn't found on PATH", editor),
+            "Fix $EDITOR / $VISUAL, or pass values with --stdin",
+        ),
+        (Err(_), None) => Check::warn(
+            "No editor found, so `set` without a value will fail",
+            "export EDITOR=vim  # or pass values with --stdin",
+        ),
+    }
+}
+
+/// The effective character locale (LC_ALL > LC_CTYPE > LANG) should be UTF-8
+fn check_locale(lc_all: Option<&str>, lc_ctype: Option<&str>, lang: Option<&str>) -> Check {
+    let locale = [lc_all, lc_ctype, lang]
+        .into_iter()
+        .flatten()
+        .find(|value| !value.is_empty());
+    match locale {
+        Some(locale) if is_utf8_locale(locale) => Check::ok(format!("Locale: {}", locale)),
+        Some(locale) => Check::warn(
+            format!(
+                "Locale {} isn't UTF-8; non-ASCII values and symbols may be garbled",
+                locale
+            ),
+            "export LANG=en_US.UTF-8, or set [ui] accessible = true for plain-text output",
+        ),
+        None => Check::warn(
+            "No locale set (LANG, LC_CTYPE, LC_ALL); non-ASCII values may be garbled",
+            "export LANG=en_US.UTF-8",
+        ),
+    }
+}
+
+fn is_utf8_locale(locale: &str) -> bool {
+    let locale = locale.to_ascii_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(
+            check_locale(None, None, Some("en_US.UTF-8")).status,
+            Status::Ok
+        );
+        assert_eq!(
+            check_locale(None, Some("C.utf8"), Some("C")).status,
+            Status::Ok
+        );
+        // LC_ALL wins over the others
+        assert_eq!(
+            check_locale(Some("C"), None, Some("en_US.UTF-8")).status,
+            Status::Warn
+        );
+        assert_eq!(check_locale(Some(""), None, None).status, Status::Warn);
+    }
+}
@@ -0,0 +1,89 @@
+//! `tinysecrets edit`: change a value in $EDITOR, starting from what's stored
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use zeroize::Zeroizing;
+
+use crate::cli::{check_value_rules, open_store};
+use crate::config::{Config, GlobalConfig};
+use crate::errors;
+use crate::git;
+use crate::permissions;
+use crate::store::Store;
+use crate::ui;
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    key: &str,
+    reason: Option<&str>,
+    config: Option<&Config>,
+) -> Result<()> {
+    let mut store = open_store()?;
+    store.set_reason(reason);
+    if GlobalConfig::load()?.history.record_git {
+        store.set_git_context(git::context());
+    }
+
+    let Some(value) = store.get(project, environment, key)? else {
+        return Err(errors::not_found(format!(
+            "Secret not found: {}/{}/{} (create it with `set`)",
+            project, environment, key
+        )));
+    };
+    edit_value(&store, project, environment, key, &value, config)
+}
+
+/// Open `value` in $EDITOR through an owner-only scratch file and save a new
+/// version of `key` only if it changed
+pub fn edit_value(
+    store: &Store,
+    project: &str,
+    environment: &str,
+    key: &str,
+    value: &[u8],
+    config: Option<&Config>,
+) -> Result<()> {
+    let text = std::str::from_utf8(value).with_context(|| {
+        format!(
+            "{} holds binary data, which can't be edited as text (use `set --from-file`)",
+            key
+        )
+    })?;
+    let edited =
+        Zeroizing::new(permissions::edit(text).context("Failed to open editor. Set $EDITOR.")?);
+    // Editors add a final newline; only keep one the value already had
+    let edited = match edited.strip_suffix('\n') {
+        Some(stripped) if !text.ends_with('\n') => stripped,
+        _ => edited.as_str(),
+    };
+
+    if edited == text {
+        eprintln!("{} {} unchanged (no new version)", ui::note(), key.bold());
+        return Ok(());
+    }
+    if edited.is_empty() {
+        anyhow::bail!("Secret value cannot be empty (use `delete` to remove it)");
+    }
+    if !check_value_rules(config, key, edited.as_bytes())? {
+        anyhow::bail!("{} breaks the [validate] rules in .tinysecrets.toml", key);
+    }
+
+    let inherited = store.entry(project, environment, key)?.is_none();
+    store.set_bytes(project, environment, key, edited.as_bytes(), None, None)?;
+    eprintln!(
+        "{} Updated {}/{}/{}",
+        ui::ok(),
+        project.cyan(),
+        environment.yellow(),
+        key.bold()
+    );
+    if inherited {
+        eprintln!(
+            "  {} It was inherited; {} now has its own value",
+            ui::note(),
+            environment.yellow()
+        );
+    }
+    Ok(())
+}
@@ -1,27 +1,62 @@
 use anyhow::Result;
+use chrono::Utc;
 use colored::Colorize;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::list::relative_time;
+use crate::cli::open_store;
+use crate::ui;
 
-pub fn run(project: &str) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+pub fn run(project: &str, json: bool) -> Result<()> {
+    let store = open_store()?;
 
-    let envs = store.list_environments(project)?;
+    let envs = store.environment_summaries(project)?;
+
+    if json {
+        let mut out = Vec::with_capacity(envs.len());
+        for env in &envs {
+            let mut value = serde_json::to_value(env)?;
+            value["inherits"] = serde_json::json!(store.parent(project, &env.name)?);
+            out.push(value);
+        }
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
+    }
 
     if envs.is_empty() {
         eprintln!(
             "{} No environments found for project '{}'",
-            "○".yellow(),
+            ui::note(),
             project.cyan()
         );
         return Ok(());
     }
 
-    println!("📦 {} environments:", project.cyan().bold());
+    println!(
+        "{}{} environments:",
+        ui::icon("📦 ", "Project "),
+        project.cyan().bold()
+    );
+    let now = Utc::now();
+    let width = envs
+        .iter()
+        .map(|e| e.name.chars().count())
+        .max()
+        .unwrap_or(0);
     for env in envs {
-        println!("  {} {}", "└".dimmed(), env.yellow());
+        let mut details = format!("{} secret(s)", env.secrets);
+        if let Some(updated) = env.last_updated {
+            details.push_str(&format!(", updated {}", relative_time(updated, now)));
+        }
+        if let Some(parent) = store.parent(project, &env.name)? {
+            details.push_str(&format!(", inherits {}", parent));
+        }
+        println!(
+            "  {} {:<width$}  {}",
+            ui::branch(),
+            env.name.yellow(),
+            details.dimmed(),
+            width = width
+        );
     }
 
     Ok(())
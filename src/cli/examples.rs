@@ -132,6 +132,11 @@ CI/CD
 
 "#;
 
+    if crate::ui::accessible() {
+        print_plain(examples);
+        return;
+    }
+
     // Print with some color highlighting
     for line in examples.lines() {
         if line.starts_with("  #") {
@@ -167,3 +172,20 @@ CI/CD
         }
     }
 }
+
+/// Print examples without box drawing or bullets (accessible mode)
+fn print_plain(examples: &str) {
+    for line in examples.lines() {
+        if line.contains('┌') || line.contains('└') || line.contains('─') {
+            continue;
+        }
+        if line.contains('│') {
+            println!(
+                "{}",
+                line.trim_matches(|c: char| c == '│' || c == '🔐' || c.is_whitespace())
+            );
+            continue;
+        }
+        println!("{}", line.replace('•', "-"));
+    }
+}
@@ -1,31 +1,100 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::fs::File;
 use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroizing;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::run::expand;
+use crate::cli::{check_env_policy, open_store, ExportFormat};
+use crate::pattern::KeyFilter;
+use crate::permissions;
+use crate::sops;
+use crate::store::SecretValues;
+use crate::ui;
 
-pub fn run(project: &str, environment: &str, output: Option<&str>) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+/// Export to a bundle. `project: None` exports the whole store, and no
+/// `environments` exports every environment in the project; a single
+/// environment is written as a plain (v2) bundle, anything else as sections.
+/// Only keys passing `filter` are included.
+pub fn run(
+    project: Option<&str>,
+    environments: &[String],
+    filter: &KeyFilter,
+    output: Option<&str>,
+    sign: bool,
+) -> Result<()> {
+    let store = open_store()?;
 
-    let bundle = store.export(project, environment)?;
+    let mut bundle = match (project, environments) {
+        (Some(project), [environment]) => store.export(project, environment, filter)?,
+        _ => {
+            let mut targets = Vec::new();
+            let projects = match project {
+                Some(project) => vec![project.to_string()],
+                None => store.list_projects()?,
+            };
+            for project in projects {
+                let environments = if environments.is_empty() {
+                    store.list_environments(&project)?
+                } else {
+                    environments.to_vec()
+                };
+                targets.extend(environments.into_iter().map(|e| (project.clone(), e)));
+            }
+            if targets.is_empty() {
+                anyhow::bail!("Nothing to export");
+            }
+            store.export_many(&targets, filter)?
+        }
+    };
+    let total: usize = bundle.sections().iter().map(|s| s.secrets.len()).sum();
+    if total == 0 && !filter.is_empty() {
+        anyhow::bail!("No keys match --only/--exclude");
+    }
+    for section in bundle.sections() {
+        check_env_policy(section.secrets.iter().map(|s| s.key.as_str()))?;
+    }
+    if sign {
+        store.sign_bundle(&mut bundle)?;
+    }
+    let sections = bundle.sections();
     let json = serde_json::to_string_pretty(&bundle)?;
 
     match output {
         Some(path) => {
-            let mut file =
-                File::create(path).context(format!("Failed to create output file: {}", path))?;
+            let mut file = permissions::create_private_file(Path::new(path))
+                .context(format!("Failed to create output file: {}", path))?;
             file.write_all(json.as_bytes())?;
 
-            eprintln!(
-                "{} Exported {} secrets to {}",
-                "✓".green(),
-                bundle.secrets.len().to_string().bold(),
-                path.cyan()
-            );
-            eprintln!("{} Bundle is encrypted with your passphrase", "ℹ".blue());
+            if sections.len() == 1 {
+                eprintln!(
+                    "{} Exported {} secrets to {}",
+                    ui::ok(),
+                    total.to_string().bold(),
+                    path.cyan()
+                );
+            } else {
+                eprintln!(
+                    "{} Exported {} secrets from {} environments to {}",
+                    ui::ok(),
+                    total.to_string().bold(),
+                    sections.len(),
+                    path.cyan()
+                );
+                for section in &sections {
+                    eprintln!(
+                        "  {} {}/{} ({})",
+                        ui::bullet(),
+                        section.project.cyan(),
+                        section.environment.yellow(),
+                        section.secrets.len()
+                    );
+                }
+            }
+            eprintln!("{} Bundle is encrypted with your passphrase", ui::info());
+            if let Some(signature) = &bundle.signature {
+                eprintln!("{} Signed by {}", ui::info(), signature.signer.cyan());
+            }
         }
         None => {
             // Output to stdout for piping
@@ -35,3 +104,293 @@ pub fn run(project: &str, environment: &str, output: Option<&str>) -> Result<()>
 
     Ok(())
 }
+
+/// Write the environment as a SOPS file, for teams migrating to or from sops
+pub fn run_sops(
+    project: &str,
+    environment: &str,
+    filter: &KeyFilter,
+    output: &Path,
+    sops_args: &[String],
+) -> Result<()> {
+    let store = open_store()?;
+
+    let secrets = filtered(store.get_all(project, environment)?, filter)?;
+    check_env_policy(secrets.iter().map(|(key, _)| key.as_str()))?;
+    let encrypted = sops::encrypt(&secrets, output, sops_args)?;
+    std::fs::write(output, encrypted)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    eprintln!(
+        "{} Exported {} secrets to {}",
+        ui::ok(),
+        secrets.len().to_string().bold(),
+        output.display().to_string().cyan()
+    );
+    eprintln!("{} File is encrypted with sops", ui::info());
+
+    Ok(())
+}
+
+/// Write the environment unencrypted: `KEY=value` lines, Terraform
+/// `key = "value"` assignments, or a flat JSON object of strings (what
+/// Terraform's `external` data source and `.tfvars.json` files expect)
+pub fn run_plaintext(
+    project: &str,
+    environment: &str,
+    filter: &KeyFilter,
+    format: ExportFormat,
+    output: Option<&str>,
+    interpolate: bool,
+) -> Result<()> {
+    let store = open_store()?;
+
+    let mut secrets = store.get_all(project, environment)?;
+    if interpolate {
+        secrets = expand(&secrets)?;
+    }
+    let secrets = filtered(secrets, filter)?;
+    check_env_policy(secrets.iter().map(|(key, _)| key.as_str()))?;
+    let contents = Zeroizing::new(match format {
+        ExportFormat::Tfvars => tfvars(&secrets)?,
+        ExportFormat::TfJson => tf_json(&secrets)?,
+        _ => secrets
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, dotenv_quote(value)))
+            .collect::<String>(),
+    });
+
+    match output {
+        Some(path) => {
+            // Plaintext secrets - keep the file owner-only
+            let mut file = permissions::create_private_file(Path::new(path))
+                .context(format!("Failed to create output file: {}", path))?;
+            file.write_all(contents.as_bytes())?;
+
+            eprintln!(
+                "{} Exported {} secrets to {} (mode 600)",
+                ui::ok(),
+                secrets.len().to_string().bold(),
+                path.cyan()
+            );
+            eprintln!("{} The file is not encrypted", ui::warn());
+        }
+        None => print!("{}", *contents),
+    }
+
+    Ok(())
+}
+
+/// Write each secret to its own 0600 file in `dir`, for systemd's
+/// `LoadCredential=`
+pub fn run_systemd_creds(
+    project: &str,
+    environment: &str,
+    filter: &KeyFilter,
+    dir: &Path,
+    interpolate: bool,
+) -> Result<()> {
+    let store = open_store()?;
+
+    let mut secrets = store.get_all(project, environment)?;
+    if interpolate {
+        secrets = expand(&secrets)?;
+    }
+    let secrets = filtered(secrets, filter)?;
+    check_env_policy(secrets.iter().map(|(key, _)| key.as_str()))?;
+    permissions::create_private_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    write_credential_files(dir, &secrets)?;
+
+    eprintln!(
+        "{} Exported {} secrets to {} (one mode 600 file each)",
+        ui::ok(),
+        secrets.len().to_string().bold(),
+        dir.display().to_string().cyan()
+    );
+    if let Some((key, _)) = secrets.first() {
+        eprintln!(
+            "{} In the unit: LoadCredential={}:{}",
+            ui::info(),
+            key,
+            dir.join(key).display()
+        );
+    }
+    eprintln!("{} The files are not encrypted", ui::warn());
+
+    Ok(())
+}
+
+/// The secrets passing `filter`. Filtering runs after `--interpolate`, so a
+/// kept value can still reference a key that's left out.
+fn filtered(mut secrets: SecretValues, filter: &KeyFilter) -> Result<SecretValues> {
+    let before = secrets.len();
+    secrets.retain(|(key, _)| filter.matches(key));
+    if secrets.is_empty() && before > 0 && !filter.is_empty() {
+        anyhow::bail!("No keys match --only/--exclude");
+    }
+    Ok(secrets)
+}
+
+/// One owner-only file per secret, named after its key (the layout of
+/// systemd's `$CREDENTIALS_DIRECTORY`)
+pub fn write_credential_files(dir: &Path, secrets: &[(String, String)]) -> Result<()> {
+    if let Some((key, _)) = secrets.iter().find(|(key, _)| !is_file_name(key)) {
+        anyhow::bail!("{} can't be used as a file name", key);
+    }
+    for (key, value) in secrets {
+        let path = dir.join(key);
+        permissions::create_private_file(&path)
+            .and_then(|mut file| file.write_all(value.as_bytes()))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// A single path component that isn't `.` or `..`
+fn is_file_name(key: &str) -> bool {
+    !key.is_empty() && key != "." && key != ".." && !key.contains(['/', '\0'])
+}
+
+/// `key = "value"` lines for a `.tfvars` file. Keys must be valid Terraform
+/// variable names, since HCL has no quoted attribute names.
+fn tfvars(secrets: &[(String, String)]) -> Result<String> {
+    let invalid: Vec<&str> = secrets
+        .iter()
+        .map(|(key, _)| key.as_str())
+        .filter(|key| !is_hcl_identifier(key))
+        .collect();
+    if !invalid.is_empty() {
+        anyhow::bail!(
+            "Not valid Terraform variable names: {} (use --format tf-json, which allows any key)",
+            invalid.join(", ")
+        );
+    }
+    Ok(secrets
+        .iter()
+        .map(|(key, value)| format!("{} = {}\n", key, hcl_quote(value)))
+        .collect())
+}
+
+/// `{"KEY": "value", ...}`, pretty-printed, with a trailing newline
+fn tf_json(secrets: &[(String, String)]) -> Result<String> {
+    let object: serde_json::Map<String, serde_json::Value> = secrets
+        .iter()
+        .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+        .collect();
+    Ok(serde_json::to_string_pretty(&object)? + "\n")
+}
+
+/// A letter or underscore, then letters, digits, underscores and dashes
+fn is_hcl_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// A double-quoted HCL string. `${` and `%{` are doubled so Terraform doesn't
+/// treat the value as a template.
+fn hcl_quote(value: &str) -> String {
+    let mut quoted = String::from('"');
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '$' | '%' if chars.peek() == Some(&'{') => {
+                quoted.push(c);
+                quoted.push(c);
+            }
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quote a value for a .env file: bare if that's unambiguous, single quotes
+/// (no escapes) if possible, otherwise double quotes with escapes
+fn dotenv_quote(value: &str) -> String {
+    let bare = |c: char| c.is_ascii_alphanumeric() || "_-.,:/@%+=".contains(c);
+    if !value.is_empty() && value.chars().all(bare) {
+        return value.to_string();
+    }
+    if !value.contains(['\'', '\n']) {
+        return format!("'{}'", value);
+    }
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '$' => quoted.push_str("\\$"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dotenv_quote() {
+        assert_eq!(
+            dotenv_quote("postgres://db:5432/app"),
+            "postgres://db:5432/app"
+        );
+        assert_eq!(dotenv_quote(""), "''");
+        assert_eq!(dotenv_quote("two words $HOME"), "'two words $HOME'");
+        assert_eq!(dotenv_quote("it's"), r#""it's""#);
+        assert_eq!(dotenv_quote("a\"b\\c\n$d'"), r#""a\"b\\c\n\$d'""#);
+    }
+
+    #[test]
+    fn test_hcl_quote() {
+        assert_eq!(hcl_quote("plain"), r#""plain""#);
+        assert_eq!(hcl_quote("a\"b\\c\nd"), r#""a\"b\\c\nd""#);
+        assert_eq!(hcl_quote("${var} %{if} $5"), r#""$${var} %%{if} $5""#);
+        assert_eq!(hcl_quote("\u{1b}"), r#""\u001b""#);
+    }
+
+    #[test]
+    fn test_write_credential_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = vec![("DB_PASSWORD".to_string(), "hunter2".to_string())];
+        write_credential_files(dir.path(), &secrets).unwrap();
+        let path = dir.path().join("DB_PASSWORD");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hunter2");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        for key in ["../escape", "..", "a/b"] {
+            let secrets = vec![(key.to_string(), "x".to_string())];
+            assert!(write_credential_files(dir.path(), &secrets).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tfvars() {
+        let secrets = vec![
+            ("db_password".to_string(), "hunter2".to_string()),
+            ("API_KEY".to_string(), "sk-1".to_string()),
+        ];
+        assert_eq!(
+            tfvars(&secrets).unwrap(),
+            "db_password = \"hunter2\"\nAPI_KEY = \"sk-1\"\n"
+        );
+        assert!(tfvars(&[("1BAD".to_string(), "x".to_string())]).is_err());
+        assert!(tfvars(&[("a.b".to_string(), "x".to_string())]).is_err());
+    }
+}
@@ -1,36 +1,298 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use zeroize::Zeroizing;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::open_store;
+use crate::cli::render::render_template;
+use crate::errors;
+use crate::json_path;
+use crate::refs::{self, SecretPath};
+use crate::ui;
 
-pub fn run(project: &str, environment: &str, key: &str, version: Option<i32>) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+/// How to encode a value on stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Raw,
+    Base64,
+    Hex,
+    JsonString,
+}
+
+/// Output transforms for `get`
+pub struct OutputOptions {
+    pub encoding: Encoding,
+    pub trim: bool,
+    pub newline: bool,
+    /// Replace `{{ref:...}}` references with the values they point at
+    pub resolve_refs: bool,
+    /// Extract a field from a JSON value with a jq-style path (`.credentials.password`)
+    pub path: Option<String>,
+}
+
+impl OutputOptions {
+    /// Apply trimming and encoding to a value
+    fn format(&self, value: &str) -> Result<String> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        let value = if self.trim { value.trim() } else { value };
+        Ok(match self.encoding {
+            Encoding::Raw => value.to_string(),
+            Encoding::Base64 => BASE64.encode(value),
+            Encoding::Hex => value.bytes().map(|b| format!("{:02x}", b)).collect(),
+            Encoding::JsonString => serde_json::to_string(value)?,
+        })
+    }
+
+    /// Like `format`, for values that may be binary
+    fn format_bytes(&self, value: &[u8]) -> Result<Vec<u8>> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        if let Ok(text) = std::str::from_utf8(value) {
+            return Ok(self.format(text)?.into_bytes());
+        }
+
+        let value = if self.trim { value.trim_ascii() } else { value };
+        Ok(match self.encoding {
+            Encoding::Raw => value.to_vec(),
+            Encoding::Base64 => BASE64.encode(value).into_bytes(),
+            Encoding::Hex => value
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+                .into_bytes(),
+            Encoding::JsonString => {
+                anyhow::bail!("Binary values can't be output as a JSON string; use --base64")
+            }
+        })
+    }
+
+    /// Whether the formatted output is text (safe to end with a newline)
+    fn is_text(&self, value: &[u8]) -> bool {
+        self.encoding != Encoding::Raw || std::str::from_utf8(value).is_ok()
+    }
+}
+
+/// Parse an octal file mode like `600` or `0640`
+pub fn parse_mode(input: &str) -> Result<u32, String> {
+    u32::from_str_radix(input, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("invalid file mode '{}' (expected octal, e.g. 600)", input))
+}
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    key: &str,
+    version: Option<i32>,
+    output: &OutputOptions,
+    to_file: Option<(&Path, u32)>,
+) -> Result<()> {
+    let store = open_store()?;
 
-    let value = match version {
+    let mut value = match version {
         Some(v) => store.get_version(project, environment, key, v)?,
         None => store.get(project, environment, key)?,
     };
+    if let Some(text) = value.as_deref().and_then(|v| std::str::from_utf8(v).ok()) {
+        if output.resolve_refs && refs::contains_refs(text) {
+            let resolved = store.resolve_refs(text, &SecretPath::new(project, environment, key))?;
+            value = Some(Zeroizing::new(resolved.into_bytes()));
+        }
+    }
+    if let (Some(path), Some(val)) = (output.path.as_deref(), value.as_deref()) {
+        value = Some(Zeroizing::new(extract_path(key, val, path)?.into_bytes()));
+    }
 
     match value {
         Some(val) => {
+            let formatted = Zeroizing::new(output.format_bytes(&val)?);
+
+            if let Some((path, mode)) = to_file {
+                write_file(path, &formatted, mode)?;
+                eprintln!(
+                    "{} Wrote {} bytes to {} (mode {:o})",
+                    ui::ok(),
+                    formatted.len(),
+                    path.display().to_string().cyan(),
+                    mode
+                );
+                return Ok(());
+            }
+
+            let is_text = output.is_text(&val);
+            if !is_text && std::io::stdout().is_terminal() {
+                anyhow::bail!(
+                    "{} holds binary data. Use --to-file, --base64 or --hex, or redirect stdout.",
+                    key
+                );
+            }
+
             // Print just the value so it can be used in scripts: $(ts get ...)
-            println!("{}", val);
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&formatted)?;
+            if output.newline && is_text {
+                stdout.write_all(b"\n")?;
+            }
+            stdout.flush()?;
         }
         None => {
             let version_str = version.map(|v| format!(" (v{})", v)).unwrap_or_default();
-            eprintln!(
-                "{} Secret not found: {}/{}/{}{}",
-                "✗".red(),
-                project.cyan(),
-                environment.yellow(),
-                key.bold(),
-                version_str.dimmed()
-            );
-            std::process::exit(1);
+            return Err(errors::not_found(format!(
+                "Secret not found: {}/{}/{}{}",
+                project, environment, key, version_str
+            )));
         }
     }
 
     Ok(())
 }
+
+/// Pull the field at `path` out of a JSON-valued secret
+fn extract_path(key: &str, value: &[u8], path: &str) -> Result<String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(value).with_context(|| format!("{} isn't valid JSON", key))?;
+    let field = json_path::query(&json, path)
+        .with_context(|| format!("Failed to read {} from {}", path, key))?;
+    json_path::render(field)
+}
+
+/// Write a value to a file created with `mode` (tightening an existing file too)
+fn write_file(path: &Path, data: &[u8], mode: u32) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+    file.write_all(data)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Print a secret's metadata without revealing its value
+pub fn run_info(project: &str, environment: &str, key: &str) -> Result<()> {
+    let store = open_store()?;
+
+    let Some(entry) = store.entry(project, environment, key)? else {
+        return Err(errors::not_found(format!(
+            "Secret not found: {}/{}/{}",
+            project, environment, key
+        )));
+    };
+
+    println!(
+        "{}/{}/{}",
+        entry.project.cyan(),
+        entry.environment.yellow(),
+        entry.key.bold()
+    );
+    println!("  version:     v{}", entry.version);
+    println!(
+        "  created:     {}",
+        entry.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    println!(
+        "  updated:     {}",
+        entry.updated_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    println!(
+        "  description: {}",
+        entry.description.as_deref().unwrap_or("(none)")
+    );
+    println!(
+        "  source:      {}",
+        entry.source.as_deref().unwrap_or("(set by hand)")
+    );
+    println!(
+        "  changed by:  {}",
+        entry.changed_by.as_deref().unwrap_or("(not recorded)")
+    );
+    if let Some(reason) = &entry.reason {
+        println!("  reason:      {}", reason);
+    }
+    if let Some(git) = &entry.git {
+        println!("  git:         {}", git);
+    }
+
+    Ok(())
+}
+
+/// Print a `{{KEY}}` template filled in with secrets from the environment
+pub fn run_format(project: &str, environment: &str, format: &str) -> Result<()> {
+    let store = open_store()?;
+
+    let secrets = store.get_all(project, environment)?;
+    let lookup: HashMap<&str, &str> = secrets
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    println!("{}", *Zeroizing::new(render_template(format, &lookup)?));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(encoding: Encoding, trim: bool) -> OutputOptions {
+        OutputOptions {
+            encoding,
+            trim,
+            newline: true,
+            resolve_refs: true,
+            path: None,
+        }
+    }
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(options(Encoding::Raw, false).format(" a ").unwrap(), " a ");
+        assert_eq!(options(Encoding::Raw, true).format(" a ").unwrap(), "a");
+        assert_eq!(
+            options(Encoding::Base64, false)
+                .format("user:pass")
+                .unwrap(),
+            "dXNlcjpwYXNz"
+        );
+        assert_eq!(options(Encoding::Hex, false).format("hi").unwrap(), "6869");
+        assert_eq!(
+            options(Encoding::JsonString, false)
+                .format("say \"hi\"\n")
+                .unwrap(),
+            "\"say \\\"hi\\\"\\n\""
+        );
+    }
+
+    #[test]
+    fn test_binary_encodings() {
+        let binary = [0xff, 0x00, 0x10];
+        assert_eq!(
+            options(Encoding::Raw, false).format_bytes(&binary).unwrap(),
+            binary
+        );
+        assert_eq!(
+            options(Encoding::Hex, false).format_bytes(&binary).unwrap(),
+            b"ff0010"
+        );
+        assert!(options(Encoding::JsonString, false)
+            .format_bytes(&binary)
+            .is_err());
+        assert!(!options(Encoding::Raw, false).is_text(&binary));
+        assert!(options(Encoding::Base64, false).is_text(&binary));
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("600"), Ok(0o600));
+        assert_eq!(parse_mode("0640"), Ok(0o640));
+        assert!(parse_mode("800").is_err());
+        assert!(parse_mode("1777").is_err());
+    }
+}
@@ -0,0 +1,35 @@
+use crate::cli::{open_store, provide_database_key};
+use crate::errors;
+use crate::store::{Store, StoreError};
+
+/// Exit 0 if the secret is set, 1 if not, or with the shared error status if the
+/// store can't be checked. Prints nothing on success or absence, so it's safe in
+/// scripts and Makefiles.
+pub fn run(project: &str, environment: &str, key: &str, json: bool) -> ! {
+    match check(project, environment, key) {
+        Ok(true) => std::process::exit(0),
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            let e = e.context(format!("Can't check {}/{}/{}", project, environment, key));
+            errors::report(&e, json);
+            std::process::exit(errors::classify(&e).status());
+        }
+    }
+}
+
+/// Look the key up without unlocking, unless the store hides key names
+fn check(project: &str, environment: &str, key: &str) -> anyhow::Result<bool> {
+    provide_database_key()?;
+    match Store::has(project, environment, key) {
+        Err(e) if matches!(e.downcast_ref(), Some(StoreError::NamesHidden)) => {
+            let store = open_store()?;
+            for env in store.lineage(project, environment)? {
+                if store.entry(project, &env, key)?.is_some() {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        has => has,
+    }
+}
@@ -1,8 +1,17 @@
 use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use colored::Colorize;
+use std::io::Write;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::list::parse_age;
+use crate::cli::open_store;
+use crate::diff::{self, Line};
+use crate::errors;
+use crate::store::{SecretHistoryEntry, Store};
+use crate::ui;
+
+/// Unchanged lines shown around each change in `--diff`
+const DIFF_CONTEXT: usize = 3;
 
 pub fn run(
     project: &str,
@@ -11,17 +20,16 @@ pub fn run(
     limit: usize,
     show_values: bool,
 ) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+    let store = open_store()?;
 
-    // Get current version info
-    let current = store.get(project, environment, key)?;
+    // Get current version info (this environment only, not inherited)
+    let current = store.entry(project, environment, key)?;
     let entries = store.history(project, environment, key, limit)?;
 
     if current.is_none() && entries.is_empty() {
         eprintln!(
             "{} No history found for {}/{}/{}",
-            "○".yellow(),
+            ui::note(),
             project.cyan(),
             environment.yellow(),
             key.bold()
@@ -30,7 +38,8 @@ pub fn run(
     }
 
     println!(
-        "📜 History for {}/{}/{}",
+        "{}History for {}/{}/{}",
+        ui::icon("📜 ", ""),
         project.cyan(),
         environment.yellow(),
         key.bold()
@@ -38,22 +47,23 @@ pub fn run(
     println!();
 
     // Show current version first
-    if let Some(value) = current {
-        let current_version: i32 = entries.first().map(|e| e.version + 1).unwrap_or(1);
+    if let Some(current) = current {
+        let current_version = current.version;
 
-        print!(
-            "  {} v{} - {} {}",
-            "•".dimmed(),
+        println!(
+            "  {} v{} - {} {}{}",
+            ui::bullet(),
             current_version.to_string().bold(),
             "current".green().bold(),
-            "(latest)".dimmed()
+            "(latest)".dimmed(),
+            changed_by(current.changed_by.as_deref())
         );
+        print_context(current.reason.as_deref(), current.git.as_deref());
 
         if show_values {
-            println!();
-            println!("    {}", value.dimmed());
-        } else {
-            println!();
+            if let Some(value) = store.get_version(project, environment, key, current_version)? {
+                println!("    {}", display_value(&value).dimmed());
+            }
         }
     }
 
@@ -67,30 +77,287 @@ pub fn run(
 
         let timestamp = entry.created_at.format("%Y-%m-%d %H:%M:%S UTC");
 
-        print!(
-            "  {} v{} - {} at {}",
-            "•".dimmed(),
+        println!(
+            "  {} v{} - {} at {}{}",
+            ui::bullet(),
             entry.version.to_string().bold(),
             status,
-            timestamp.to_string().dimmed()
+            timestamp.to_string().dimmed(),
+            changed_by(entry.changed_by.as_deref())
         );
+        print_context(entry.reason.as_deref(), entry.git.as_deref());
 
         if show_values && entry.deleted_at.is_none() {
             if let Ok(Some(value)) = store.get_version(project, environment, key, entry.version) {
-                println!();
-                println!("    {}", value.dimmed());
+                println!("    {}", display_value(&value).dimmed());
+            }
+        }
+    }
+
+    if !show_values {
+        println!();
+        println!("  {} Use {} to show values", ui::info(), "--show".cyan());
+    }
+
+    Ok(())
+}
+
+/// Print one version's value (with --show), or say it exists
+pub fn run_version(
+    project: &str,
+    environment: &str,
+    key: &str,
+    version: i32,
+    show_value: bool,
+) -> Result<()> {
+    let store = open_store()?;
+
+    let Some(value) = store.get_version(project, environment, key, version)? else {
+        return Err(errors::not_found(format!(
+            "No version v{} of {}/{}/{}",
+            version, project, environment, key
+        )));
+    };
+
+    if !show_value {
+        eprintln!(
+            "{} v{} of {}/{}/{} holds {} bytes. Use {} to print it",
+            ui::info(),
+            version,
+            project.cyan(),
+            environment.yellow(),
+            key.bold(),
+            value.len(),
+            "--show".cyan()
+        );
+        return Ok(());
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&value)?;
+    if std::str::from_utf8(&value).is_ok() {
+        stdout.write_all(b"\n")?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Show what changed between consecutive versions, newest first. Lines are
+/// masked unless `show_values`.
+pub fn run_diff(
+    project: &str,
+    environment: &str,
+    key: &str,
+    limit: usize,
+    show_values: bool,
+) -> Result<()> {
+    let store = open_store()?;
+
+    // Newest first, with the current version in the same shape as the archived ones
+    let mut versions: Vec<SecretHistoryEntry> = Vec::new();
+    if let Some(current) = store.entry(project, environment, key)? {
+        versions.push(SecretHistoryEntry {
+            project: current.project,
+            environment: current.environment,
+            key: current.key,
+            version: current.version,
+            created_at: current.updated_at,
+            deleted_at: None,
+            changed_by: current.changed_by,
+            reason: current.reason,
+            git: current.git,
+        });
+    }
+    versions.extend(store.history(project, environment, key, limit)?);
+
+    if versions.len() < 2 {
+        eprintln!(
+            "{} {}/{}/{} has fewer than two versions; nothing to compare",
+            ui::note(),
+            project.cyan(),
+            environment.yellow(),
+            key.bold()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{}Changes to {}/{}/{}",
+        ui::icon("📜 ", ""),
+        project.cyan(),
+        environment.yellow(),
+        key.bold()
+    );
+
+    for pair in versions.windows(2) {
+        let (newer, old_version) = (&pair[0], pair[1].version);
+        let new_version = newer.version;
+        let (Some(new), Some(old)) = (
+            store.get_version(project, environment, key, new_version)?,
+            store.get_version(project, environment, key, old_version)?,
+        ) else {
+            continue;
+        };
+        let (new, old) = (display_value(&new), display_value(&old));
+
+        println!();
+        println!(
+            "  v{} {} v{}{}  {}{}",
+            old_version,
+            ui::arrow(),
+            new_version.to_string().bold(),
+            if newer.deleted_at.is_some() {
+                " (deleted)".red()
             } else {
-                println!();
+                "".normal()
+            },
+            newer
+                .created_at
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string()
+                .dimmed(),
+            changed_by(newer.changed_by.as_deref())
+        );
+        print_context(newer.reason.as_deref(), newer.git.as_deref());
+
+        let lines = diff::diff_lines(&old, &new);
+        let hunks = diff::hunks(&lines, DIFF_CONTEXT);
+        if hunks.is_empty() {
+            println!("    {}", "(value unchanged)".dimmed());
+        }
+        for hunk in hunks {
+            println!("    {}", hunk.header().cyan());
+            for line in hunk.lines {
+                let text = |t: &str| {
+                    if show_values {
+                        t.to_string()
+                    } else {
+                        diff::mask_line(t)
+                    }
+                };
+                match line {
+                    Line::Same(t) => println!("     {}", text(t).dimmed()),
+                    Line::Removed(t) => println!("    {}{}", "-".red(), text(t).red()),
+                    Line::Added(t) => println!("    {}{}", "+".green(), text(t).green()),
+                }
             }
-        } else {
-            println!();
         }
     }
 
     if !show_values {
         println!();
-        println!("  {} Use {} to show values", "ℹ".blue(), "--show".cyan());
+        println!(
+            "  {} Values are masked. Use {} to show them",
+            ui::info(),
+            "--show".cyan()
+        );
     }
 
     Ok(())
 }
+
+/// ` by user@host` for a version's author, if it was recorded
+fn changed_by(who: Option<&str>) -> String {
+    who.map(|who| format!(" by {}", who.bold()))
+        .unwrap_or_default()
+}
+
+/// The reason given for a version and the git commit it came from, one per line
+fn print_context(reason: Option<&str>, git: Option<&str>) {
+    if let Some(reason) = reason {
+        println!("    {} {}", "reason:".dimmed(), reason.italic());
+    }
+    if let Some(git) = git {
+        println!("    {} {}", "git:".dimmed(), git);
+    }
+}
+
+/// Text values as-is, binary ones as a size placeholder
+fn display_value(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(text) => text.to_string(),
+        Err(_) => format!("<binary, {} bytes>", value.len()),
+    }
+}
+
+/// Read every value as it was at `at` (`--as-of`) from here on
+pub fn pin_as_of(at: DateTime<Utc>) {
+    Store::set_as_of(at);
+    eprintln!(
+        "{} Using values as of {}",
+        ui::info(),
+        at.with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S %Z")
+            .to_string()
+            .bold()
+    );
+}
+
+/// Parse `--as-of`: an RFC 3339 timestamp, a local `YYYY-MM-DD[ HH:MM[:SS]]`
+/// (a bare date means the start of that day), or an age like `30d`
+pub fn parse_as_of(input: &str) -> Result<DateTime<Utc>, String> {
+    let input = input.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(input) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    });
+    if let Some(naive) = naive {
+        return Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|at| at.with_timezone(&Utc))
+            .ok_or_else(|| format!("'{}' doesn't exist in the local timezone", input));
+    }
+    if input.starts_with(|c: char| c.is_ascii_digit()) && !input.contains('-') {
+        return parse_age(input).map(|age| Utc::now() - age);
+    }
+    Err(format!(
+        "invalid time '{}' (expected e.g. 2024-05-01, '2024-05-01 14:30', an RFC 3339 timestamp, or 30d)",
+        input
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_parse_as_of() {
+        assert_eq!(
+            parse_as_of("2024-05-01T12:00:00+02:00"),
+            Ok(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap())
+        );
+        let local = |h, m| {
+            Local
+                .with_ymd_and_hms(2024, 5, 1, h, m, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert_eq!(parse_as_of("2024-05-01"), Ok(local(0, 0)));
+        assert_eq!(parse_as_of("2024-05-01 14:30"), Ok(local(14, 30)));
+
+        let week_ago = parse_as_of("7d").unwrap();
+        assert!(
+            (Utc::now() - Duration::days(7) - week_ago)
+                .num_seconds()
+                .abs()
+                < 5
+        );
+
+        assert!(parse_as_of("last tuesday").is_err());
+        assert!(parse_as_of("2024-13-01").is_err());
+    }
+}
@@ -0,0 +1,405 @@
+//! Shell hook: load a project's secrets on `cd` (direnv-style)
+//!
+//! `hook <shell>` prints a snippet for the user's rc file that runs the hidden
+//! `hook-env` command before each prompt. `hook-env` looks for a `.tinysecrets.toml`
+//! above the current directory and prints shell code that exports its secrets, or
+//! puts back whatever they shadowed when the directory is left. A config only loads
+//! once it has been approved with `allow`; editing it requires approving it again.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::cli::{check_env_policy, open_store};
+use crate::config::{Config, ConfigResolver, GlobalConfig};
+use crate::permissions;
+use crate::store::SecretValues;
+use crate::ui;
+
+/// Identity of the loaded config (`<status>:<hash>`), so unchanged prompts are a no-op
+const HOOK_ID_ENV_VAR: &str = "TINYSECRETS_HOOK_ID";
+/// Space-separated keys exported by the hook, to unset or restore on leaving
+const HOOK_KEYS_ENV_VAR: &str = "TINYSECRETS_HOOK_KEYS";
+/// JSON object of the shell's own values for those keys, to restore on leaving
+const HOOK_SAVED_ENV_VAR: &str = "TINYSECRETS_HOOK_SAVED";
+/// Approved configs, one `<hash> <path>` per line, next to the global config
+const ALLOW_FILE: &str = "allowed";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl HookShell {
+    fn export(self, key: &str, value: &str) -> String {
+        match self {
+            HookShell::Bash | HookShell::Zsh => format!("export {}={};", key, posix_quote(value)),
+            HookShell::Fish => format!("set -gx {} {};", key, fish_quote(value)),
+        }
+    }
+
+    fn unset(self, key: &str) -> String {
+        match self {
+            HookShell::Bash | HookShell::Zsh => format!("unset {};", key),
+            HookShell::Fish => format!("set -e {};", key),
+        }
+    }
+}
+
+/// Print the snippet to add to the shell's rc file
+pub fn run_hook(shell: HookShell) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to locate the tinysecrets binary")?;
+    let exe = exe.to_string_lossy();
+
+    let script = match shell {
+        HookShell::Bash => format!(
+            r#"_tinysecrets_hook() {{
+  local previous_exit_status=$?
+  eval "$({exe} hook-env bash)"
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_tinysecrets_hook;"* ]]; then
+  PROMPT_COMMAND="_tinysecrets_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi
+"#,
+            exe = posix_quote(&exe)
+        ),
+        HookShell::Zsh => format!(
+            r#"_tinysecrets_hook() {{
+  eval "$({exe} hook-env zsh)"
+}}
+typeset -ag precmd_functions
+if (( ! ${{precmd_functions[(I)_tinysecrets_hook]}} )); then
+  precmd_functions=(_tinysecrets_hook $precmd_functions)
+fi
+"#,
+            exe = posix_quote(&exe)
+        ),
+        HookShell::Fish => format!(
+            r#"function __tinysecrets_hook --on-event fish_prompt
+    {exe} hook-env fish | source
+end
+"#,
+            exe = fish_quote(&exe)
+        ),
+    };
+
+    print!("{}", script);
+    Ok(())
+}
+
+/// Print shell code that brings the environment in line with the current directory
+pub fn run_hook_env(shell: HookShell) -> Result<()> {
+    let loaded_id = std::env::var(HOOK_ID_ENV_VAR).unwrap_or_default();
+    let loaded_keys = std::env::var(HOOK_KEYS_ENV_VAR).unwrap_or_default();
+
+    let config = match Config::found_path()? {
+        Some(path) => Some((config_hash(&path)?, path)),
+        None => None,
+    };
+    let allowed = match &config {
+        Some((hash, _)) => is_allowed(hash)?,
+        None => false,
+    };
+    let id = config.as_ref().map(|(hash, _)| {
+        let status = if allowed { "loaded" } else { "blocked" };
+        format!("{}:{}", status, hash)
+    });
+
+    if id.as_deref().unwrap_or("") == loaded_id {
+        return Ok(());
+    }
+
+    let mut script = Zeroizing::new(String::new());
+    let had_keys = !loaded_keys.is_empty();
+    let saved = saved_values();
+    for key in loaded_keys.split_whitespace() {
+        match saved.get(key) {
+            Some(value) => script.push_str(&shell.export(key, value)),
+            None => script.push_str(&shell.unset(key)),
+        }
+    }
+    script.push_str(&shell.unset(HOOK_SAVED_ENV_VAR));
+
+    let Some((_, path)) = config else {
+        script.push_str(&shell.unset(HOOK_ID_ENV_VAR));
+        script.push_str(&shell.unset(HOOK_KEYS_ENV_VAR));
+        print!("{}", *script);
+        if had_keys {
+            eprintln!("{} tinysecrets: unloaded secrets", ui::note());
+        }
+        return Ok(());
+    };
+
+    let mut keys = Vec::new();
+    if allowed {
+        match load_secrets(&path) {
+            Ok((label, secrets)) => {
+                let mut shadowed = BTreeMap::new();
+                for (key, value) in secrets.iter() {
+                    // The shell's own value, not one loaded for the directory just left
+                    let own = if loaded_keys.split_whitespace().any(|k| k == key) {
+                        saved.get(key).cloned()
+                    } else {
+                        std::env::var(key).ok()
+                    };
+                    if let Some(own) = own {
+                        shadowed.insert(key.as_str(), own);
+                    }
+                    script.push_str(&shell.export(key, value));
+                    keys.push(key.as_str());
+                }
+                if !shadowed.is_empty() {
+                    let json = serde_json::to_string(&shadowed)?;
+                    script.push_str(&shell.export(HOOK_SAVED_ENV_VAR, &json));
+                }
+                eprintln!(
+                    "{} tinysecrets: loaded {} secrets for {}",
+                    ui::ok(),
+                    secrets.len().to_string().bold(),
+                    label
+                );
+                script.push_str(&shell.export(HOOK_KEYS_ENV_VAR, &keys.join(" ")));
+            }
+            Err(e) => {
+                std::eprintln!("{} tinysecrets: {:#}", ui::fail(), e);
+                eprintln!(
+                    "  {} leave and re-enter the directory to retry",
+                    ui::branch()
+                );
+                script.push_str(&shell.unset(HOOK_KEYS_ENV_VAR));
+            }
+        }
+    } else {
+        eprintln!(
+            "{} tinysecrets: {} is not allowed to load secrets. Run {} to approve it.",
+            ui::warn(),
+            path.display(),
+            "tinysecrets allow".cyan()
+        );
+        script.push_str(&shell.unset(HOOK_KEYS_ENV_VAR));
+    }
+    script.push_str(&shell.export(HOOK_ID_ENV_VAR, id.as_deref().unwrap_or("")));
+
+    print!("{}", *script);
+    Ok(())
+}
+
+/// Secrets for the project/environment named by the config, with defaults applied
+fn load_secrets(path: &Path) -> Result<(String, SecretValues)> {
+    let resolver = ConfigResolver::new()?;
+    let project = resolver.project(None)?;
+    let environment = resolver.environment(None)?;
+
+    let store = open_store()?;
+    let mut secrets = store
+        .get_all(&project, &environment)
+        .with_context(|| format!("Failed to load secrets for {}", path.display()))?;
+    drop(store);
+    if let Some(config) = resolver.config() {
+        config.apply_defaults(&mut secrets);
+    }
+
+    if let Some((key, _)) = secrets.iter().find(|(key, _)| !is_shell_name(key)) {
+        anyhow::bail!("{} is not a valid shell variable name", key);
+    }
+    check_env_policy(secrets.iter().map(|(k, _)| k.as_str()))?;
+
+    Ok((format!("{}/{}", project, environment), secrets))
+}
+
+/// Approve the config found from `dir` (or the current directory)
+pub fn run_allow(dir: Option<&Path>) -> Result<()> {
+    let path = config_in(dir)?;
+    let hash = config_hash(&path)?;
+
+    let mut entries = read_allow_list()?;
+    entries.retain(|(_, p)| p != &path);
+    entries.push((hash, path.clone()));
+    write_allow_list(&entries)?;
+
+    eprintln!(
+        "{} Allowed {} (secrets load on the next prompt)",
+        ui::ok(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Revoke approval for the config found from `dir` (or the current directory)
+pub fn run_deny(dir: Option<&Path>) -> Result<()> {
+    let path = config_in(dir)?;
+
+    let mut entries = read_allow_list()?;
+    let before = entries.len();
+    entries.retain(|(_, p)| p != &path);
+    if entries.len() == before {
+        eprintln!("{} {} was not allowed", ui::note(), path.display());
+        return Ok(());
+    }
+    write_allow_list(&entries)?;
+
+    eprintln!("{} Denied {}", ui::ok(), path.display());
+    Ok(())
+}
+
+/// The config file a directory would load: its own or the nearest ancestor's
+fn config_in(dir: Option<&Path>) -> Result<PathBuf> {
+    if let Some(dir) = dir {
+        std::env::set_current_dir(dir)
+            .with_context(|| format!("Failed to enter {}", dir.display()))?;
+    }
+    let path = Config::found_path()?
+        .context("No .tinysecrets.toml found in this directory or its parents")?;
+    path.canonicalize()
+        .with_context(|| format!("Failed to resolve {}", path.display()))
+}
+
+/// Hash of a config's location and contents, so moving or editing it revokes approval
+fn config_hash(path: &Path) -> Result<String> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", path.display()))?;
+    let contents =
+        std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hash_config(&path, &contents))
+}
+
+fn hash_config(path: &Path, contents: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(contents);
+    hasher
+        .finalize()
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        })
+}
+
+/// The values the hook's exports shadowed, from `HOOK_SAVED_ENV_VAR`
+fn saved_values() -> BTreeMap<String, String> {
+    std::env::var(HOOK_SAVED_ENV_VAR)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn allow_file() -> Result<PathBuf> {
+    let config = GlobalConfig::path()?;
+    Ok(config.with_file_name(ALLOW_FILE))
+}
+
+fn is_allowed(hash: &str) -> Result<bool> {
+    Ok(read_allow_list()?.iter().any(|(h, _)| h == hash))
+}
+
+fn read_allow_list() -> Result<Vec<(String, PathBuf)>> {
+    let path = allow_file()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(parse_allow_list(&contents))
+}
+
+fn parse_allow_list(contents: &str) -> Vec<(String, PathBuf)> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(hash, path)| (hash.to_string(), PathBuf::from(path)))
+        .collect()
+}
+
+fn write_allow_list(entries: &[(String, PathBuf)]) -> Result<()> {
+    let path = allow_file()?;
+    if let Some(parent) = path.parent() {
+        permissions::create_private_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let contents: String = entries
+        .iter()
+        .map(|(hash, p)| format!("{} {}\n", hash, p.display()))
+        .collect();
+    permissions::create_private_file(&path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn is_shell_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Single-quote for sh/bash/zsh
+pub(crate) fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Single-quote for fish (which only treats \\ and \' as escapes inside quotes)
+pub(crate) fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(posix_quote("it's $HOME"), r"'it'\''s $HOME'");
+        assert_eq!(fish_quote(r"it's a\b"), r"'it\'s a\\b'");
+        assert_eq!(
+            HookShell::Zsh.export("API_KEY", "a b"),
+            "export API_KEY='a b';"
+        );
+        assert_eq!(HookShell::Fish.unset("API_KEY"), "set -e API_KEY;");
+    }
+
+    #[test]
+    fn test_shell_names() {
+        assert!(is_shell_name("API_KEY"));
+        assert!(is_shell_name("_private1"));
+        assert!(!is_shell_name("1PASSWORD"));
+        assert!(!is_shell_name("my-key"));
+        assert!(!is_shell_name(""));
+    }
+
+    #[test]
+    fn test_config_hash_covers_path_and_contents() {
+        let a = hash_config(Path::new("/a/.tinysecrets.toml"), b"project = \"x\"");
+        assert_eq!(a.len(), 64);
+        assert_ne!(
+            a,
+            hash_config(Path::new("/b/.tinysecrets.toml"), b"project = \"x\"")
+        );
+        assert_ne!(
+            a,
+            hash_config(Path::new("/a/.tinysecrets.toml"), b"project = \"y\"")
+        );
+    }
+
+    #[test]
+    fn test_parse_allow_list() {
+        let entries = parse_allow_list("abc /home/me/my app/.tinysecrets.toml\n\n");
+        assert_eq!(
+            entries,
+            vec![(
+                "abc".to_string(),
+                PathBuf::from("/home/me/my app/.tinysecrets.toml")
+            )]
+        );
+    }
+}
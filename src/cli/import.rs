@@ -1,37 +1,245 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
+use sha2::{Digest, Sha256};
 use std::fs;
+use std::process::Command;
 
-use crate::cli::prompt_passphrase;
-use crate::store::{ExportBundle, Store};
+use crate::cli::{open_store, resolve_conflicts, OnConflict};
+use crate::compat;
+use crate::store::{ExportBundle, ImportTarget, Section};
+use crate::ui;
 
-pub fn run(input: &str) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+/// Download a bundle over HTTPS (via curl, which handles proxies and system CAs)
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--proto", "=https", "--proto-redir", "=https"])
+        .arg(url)
+        .output()
+        .context("Failed to run curl (is it installed?)")?;
 
-    let json =
-        fs::read_to_string(input).context(format!("Failed to read input file: {}", input))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Failed to download {}: {}", url, stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+/// A signer key given inline (`ed25519:...`) or as a file holding one
+fn read_signer(signer: &str) -> Result<String> {
+    if signer.starts_with("ed25519:") {
+        return Ok(signer.to_string());
+    }
+    fs::read_to_string(signer).with_context(|| format!("Failed to read signer key {}", signer))
+}
+
+/// Hex-encoded SHA-256 of the data
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
 
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    input: &str,
+    sha256: Option<&str>,
+    verify_signer: Option<&str>,
+    on_conflict: Option<OnConflict>,
+    force: bool,
+    as_project: Option<&str>,
+    as_environment: Option<&str>,
+    allow_untagged: bool,
+) -> Result<()> {
+    let data = if input.starts_with("https://") {
+        if sha256.is_none() {
+            eprintln!(
+                "{} Importing from a URL without {} - the bundle's integrity isn't pinned",
+                ui::warn(),
+                "--sha256".cyan()
+            );
+        }
+        fetch(input)?
+    } else if input.starts_with("http://") {
+        anyhow::bail!("Refusing to download a bundle over plain HTTP. Use an https:// URL.");
+    } else {
+        fs::read(input).context(format!("Failed to read input file: {}", input))?
+    };
+
+    // Verify the pinned checksum before parsing anything
+    if let Some(expected) = sha256 {
+        let actual = sha256_hex(&data);
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            anyhow::bail!(
+                "Checksum mismatch for {}\n  expected: {}\n  actual:   {}",
+                input,
+                expected.trim(),
+                actual
+            );
+        }
+        eprintln!("{} Checksum verified", ui::ok());
+    }
+
+    let json = String::from_utf8(data).context("Bundle is not valid UTF-8")?;
     let bundle: ExportBundle =
         serde_json::from_str(&json).context("Failed to parse export bundle (invalid format)")?;
+    compat::check_bundle_version(bundle.version)?;
+
+    // The signature is public-key, so it's checked before asking for the passphrase
+    match (verify_signer, &bundle.signature) {
+        (Some(signer), _) => {
+            let signer = read_signer(signer)?;
+            bundle.verify_signer(&signer)?;
+            eprintln!("{} Signature verified ({})", ui::ok(), signer.trim().cyan());
+        }
+        (None, Some(signature)) => eprintln!(
+            "{} Bundle is signed by {}; pass {} to check it",
+            ui::note(),
+            signature.signer,
+            "--verify-signer <key>".cyan()
+        ),
+        (None, None) => {}
+    }
+
+    let store = open_store()?;
+
+    if bundle.integrity.is_none() && allow_untagged {
+        eprintln!(
+            "{} Bundle has no integrity tag (exported by an older tinysecrets); only individual values are verified",
+            ui::warn()
+        );
+    }
 
-    eprintln!(
-        "{} Importing {}/{} ({} secrets)...",
-        "→".cyan(),
-        bundle.project.cyan(),
-        bundle.environment.yellow(),
-        bundle.secrets.len()
-    );
+    let sections = bundle.sections();
+    let targets = remap(&sections, as_project, as_environment)?;
 
-    let imported = store.import(&bundle)?;
+    // Conflicts are settled per section before anything is written
+    let mut targets: Vec<ImportTarget> = targets
+        .into_iter()
+        .map(|(project, environment)| ImportTarget {
+            project,
+            environment,
+            overwrite: true,
+        })
+        .collect();
+    let mut skipped = Vec::with_capacity(sections.len());
+    for (section, target) in sections.iter().zip(&mut targets) {
+        if (section.project, section.environment)
+            == (target.project.as_str(), target.environment.as_str())
+        {
+            eprintln!(
+                "{} Importing {}/{} ({} secrets)...",
+                ui::arrow(),
+                section.project.cyan(),
+                section.environment.yellow(),
+                section.secrets.len()
+            );
+        } else {
+            eprintln!(
+                "{} Importing {}/{} as {}/{} ({} secrets)...",
+                ui::arrow(),
+                section.project.cyan(),
+                section.environment.yellow(),
+                target.project.cyan(),
+                target.environment.yellow(),
+                section.secrets.len()
+            );
+        }
 
-    eprintln!(
-        "{} Imported {} secrets into {}/{}",
-        "✓".green(),
-        imported.to_string().bold(),
-        bundle.project.cyan(),
-        bundle.environment.yellow()
-    );
+        let keys: Vec<&str> = section.secrets.iter().map(|s| s.key.as_str()).collect();
+        let conflicts = store.existing_keys(&target.project, &target.environment, &keys)?;
+        target.overwrite = resolve_conflicts(&conflicts, on_conflict, force)?;
+        skipped.push(if target.overwrite { 0 } else { conflicts.len() });
+    }
+
+    let imported = store.import(
+        &bundle,
+        &format!("bundle:{}", input),
+        &targets,
+        allow_untagged,
+    )?;
+
+    for ((target, imported), skipped) in targets.iter().zip(imported).zip(skipped) {
+        eprintln!(
+            "{} Imported {} secrets into {}/{}",
+            ui::ok(),
+            imported.written.to_string().bold(),
+            target.project.cyan(),
+            target.environment.yellow()
+        );
+        if imported.unchanged > 0 {
+            eprintln!(
+                "{} {} unchanged (same value already stored)",
+                ui::note(),
+                imported.unchanged
+            );
+        }
+        if skipped > 0 {
+            eprintln!("{} Skipped {} existing keys", ui::note(), skipped);
+        }
+    }
 
     Ok(())
 }
+
+/// Where each section goes: its own project/environment unless renamed
+fn remap(
+    sections: &[Section],
+    as_project: Option<&str>,
+    as_environment: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    let targets: Vec<(String, String)> = sections
+        .iter()
+        .map(|s| {
+            (
+                as_project.unwrap_or(s.project).to_string(),
+                as_environment.unwrap_or(s.environment).to_string(),
+            )
+        })
+        .collect();
+    let mut unique = targets.clone();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != targets.len() {
+        anyhow::bail!(
+            "Renaming would import several sections of the bundle into the same environment"
+        );
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_remap() {
+        let section = |project, environment| Section {
+            project,
+            environment,
+            secrets: &[],
+        };
+        let one = [section("myapp", "prod")];
+        assert_eq!(
+            remap(&one, None, Some("prod-mirror")).unwrap(),
+            vec![("myapp".to_string(), "prod-mirror".to_string())]
+        );
+        assert_eq!(
+            remap(&one, Some("clone"), None).unwrap(),
+            vec![("clone".to_string(), "prod".to_string())]
+        );
+
+        let two = [section("myapp", "dev"), section("myapp", "prod")];
+        assert_eq!(remap(&two, Some("clone"), None).unwrap().len(), 2);
+        assert!(remap(&two, None, Some("staging")).is_err());
+    }
+}
@@ -1,57 +1,282 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::io::{self, BufRead, IsTerminal};
+use std::io::{self, IsTerminal, Read};
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::{check_key_names, check_value_rules, open_store, resolve_conflicts, OnConflict};
+use crate::config::Config;
+use crate::pattern::glob_match;
+use crate::sops;
+use crate::ui;
+use crate::yaml_env;
 
-/// Parse a line into key-value pair
+/// What `import-env` reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// Guess from the file extension, then the content
+    Auto,
+    /// KEY=VALUE lines (also `KEY: VALUE` and `export KEY=VALUE`)
+    Dotenv,
+    /// A `{"KEY": "value"}` object (e.g. `vercel env pull --json`)
+    Json,
+    /// A flat YAML map, or docker-compose `environment:` blocks
+    Yaml,
+}
+
+impl InputFormat {
+    /// Resolve `Auto` from the file name, then from what the input looks like
+    fn detect(self, file: Option<&str>, input: &str) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        let name = file.unwrap_or_default().to_lowercase();
+        if name.ends_with(".json") {
+            return Self::Json;
+        }
+        if name.ends_with(".yaml") || name.ends_with(".yml") {
+            return Self::Yaml;
+        }
+        let trimmed = input.trim_start();
+        if trimmed.starts_with('{') {
+            Self::Json
+        } else if trimmed.starts_with("---")
+            || input.lines().any(|line| line.trim() == "environment:")
+        {
+            Self::Yaml
+        } else {
+            Self::Dotenv
+        }
+    }
+}
+
+/// How to rename and filter keys from a raw provider dump.
+/// Filters see keys as they appear in the input; then `--map` renames a key
+/// outright, or else the prefix is stripped, the key uppercased, and the prefix added.
+#[derive(Debug, Default, clap::Args)]
+pub struct KeyTransform {
+    /// Only import keys matching these globs (e.g. 'APP_*')
+    #[arg(long, value_delimiter = ',', value_name = "GLOB")]
+    pub only: Vec<String>,
+    /// Skip keys matching these globs
+    #[arg(long, value_delimiter = ',', value_name = "GLOB")]
+    pub exclude: Vec<String>,
+    /// Rename a key (repeatable); mapped keys skip the other transformations
+    #[arg(long, value_name = "OLD=NEW", value_parser = parse_mapping)]
+    pub map: Vec<(String, String)>,
+    /// Remove this prefix from keys that have it
+    #[arg(long, value_name = "PREFIX")]
+    pub strip_prefix: Option<String>,
+    /// Uppercase keys
+    #[arg(long)]
+    pub uppercase: bool,
+    /// Add this prefix to every key
+    #[arg(long, value_name = "PREFIX")]
+    pub prefix: Option<String>,
+}
+
+impl KeyTransform {
+    /// The key to store under, or None if the key is filtered out
+    pub fn apply(&self, key: &str) -> Option<String> {
+        if !self.only.is_empty() && !self.only.iter().any(|g| glob_match(g, key)) {
+            return None;
+        }
+        if self.exclude.iter().any(|g| glob_match(g, key)) {
+            return None;
+        }
+        if let Some((_, new)) = self.map.iter().find(|(old, _)| old == key) {
+            return Some(new.clone());
+        }
+
+        let mut key = match &self.strip_prefix {
+            Some(prefix) => match key.strip_prefix(prefix.as_str()) {
+                Some(rest) if !rest.is_empty() => rest.to_string(),
+                _ => key.to_string(),
+            },
+            None => key.to_string(),
+        };
+        if self.uppercase {
+            key = key.to_uppercase();
+        }
+        if let Some(prefix) = &self.prefix {
+            key.insert_str(0, prefix);
+        }
+        Some(key)
+    }
+}
+
+pub fn parse_mapping(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+            Ok((old.trim().to_string(), new.trim().to_string()))
+        }
+        _ => Err(format!("expected OLD=NEW, got '{}'", s)),
+    }
+}
+
+/// A line that isn't a `KEY=VALUE` entry
+#[derive(Debug, PartialEq, Eq)]
+pub struct BadLine {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    pub reason: &'static str,
+}
+
+/// Parse dotenv-style input into key-value pairs, in order
 /// Supports multiple formats:
 /// - KEY=VALUE (dotenv style)
 /// - KEY: VALUE (heroku config style)
 /// - KEY:VALUE (compact)
 /// - export KEY=VALUE (shell export)
-fn parse_line(line: &str) -> Option<(String, String)> {
-    let line = line.trim();
+///
+/// Values may be double-quoted (with `\n`, `\t`, `\"` and friends, spanning
+/// lines), single- or backtick-quoted (literal, spanning lines) or bare, where
+/// ` # comment` ends the value. A bad line is reported and parsing resumes on
+/// the line after it.
+pub fn parse_dotenv(input: &str) -> Vec<Result<(String, String), BadLine>> {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut entries = Vec::new();
+    let mut i = 0;
 
-    // Skip empty lines and comments
-    if line.is_empty() || line.starts_with('#') {
-        return None;
-    }
+    while i < lines.len() {
+        let number = i + 1;
+        let line = lines[i].trim();
+        i += 1;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let bad = |reason| BadLine {
+            line: number,
+            text: line.to_string(),
+            reason,
+        };
 
-    // Remove 'export ' prefix if present
-    let line = line.strip_prefix("export ").unwrap_or(line);
+        let rest = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+        let key_end = rest
+            .find(|c: char| c == '=' || c == ':' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        let after_key = rest[key_end..].trim_start();
+        let raw = match after_key
+            .strip_prefix('=')
+            .or_else(|| after_key.strip_prefix(':'))
+        {
+            Some(raw) if !key.is_empty() => raw.trim_start(),
+            _ => {
+                entries.push(Err(bad("expected KEY=VALUE")));
+                continue;
+            }
+        };
 
-    // Try KEY=VALUE first
-    if let Some((key, value)) = line.split_once('=') {
-        let key = key.trim();
-        let value = value.trim();
-        // Remove surrounding quotes if present
-        let value = value
-            .strip_prefix('"')
-            .and_then(|v| v.strip_suffix('"'))
-            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
-            .unwrap_or(value);
+        let quote = match raw.chars().next() {
+            Some(q @ ('"' | '\'' | '`')) => q,
+            _ => {
+                entries.push(Ok((key.to_string(), unquoted_value(raw))));
+                continue;
+            }
+        };
 
-        if !key.is_empty() {
-            return Some((key.to_string(), value.to_string()));
+        // Quoted values run until the closing quote, on this line or a later one
+        let mut text = raw[1..].to_string();
+        let mut next = i;
+        let closed = loop {
+            if let Some(end) = closing_quote(&text, quote) {
+                break Some(end);
+            }
+            let Some(more) = lines.get(next) else {
+                break None;
+            };
+            text.push('\n');
+            text.push_str(more);
+            next += 1;
+        };
+        let Some(end) = closed else {
+            entries.push(Err(bad("unterminated quote")));
+            continue;
+        };
+        let trailing = text[end + 1..].trim();
+        if !trailing.is_empty() && !trailing.starts_with('#') {
+            entries.push(Err(bad("unexpected text after the closing quote")));
+            continue;
         }
+        i = next;
+        let value = &text[..end];
+        let value = match quote {
+            '"' => unescape(value),
+            _ => value.to_string(),
+        };
+        entries.push(Ok((key.to_string(), value)));
     }
 
-    // Try KEY: VALUE (heroku style)
-    if let Some((key, value)) = line.split_once(':') {
-        let key = key.trim();
-        let value = value.trim();
+    entries
+}
 
-        if !key.is_empty() && !key.contains(' ') {
-            return Some((key.to_string(), value.to_string()));
+/// A bare value, up to a ` #` comment
+fn unquoted_value(raw: &str) -> String {
+    let end = raw
+        .char_indices()
+        .find(|&(i, c)| c == '#' && raw[..i].ends_with(char::is_whitespace))
+        .map(|(i, _)| i)
+        .unwrap_or(raw.len());
+    raw[..end].trim_end().to_string()
+}
+
+/// Byte offset of the quote that closes `text` (backslash escapes count inside
+/// double quotes only)
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote == '"' {
+            escaped = true;
+        } else if c == quote {
+            return Some(i);
         }
     }
-
     None
 }
 
-pub fn run(project: &str, environment: &str, file: Option<&str>) -> Result<()> {
+/// Expand the escapes dotenv allows in double quotes; others are kept as written
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c @ ('"' | '\\' | '$' | '\'' | '`')) => out.push(c),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    project: &str,
+    environment: &str,
+    file: Option<&str>,
+    format: InputFormat,
+    source: Option<&str>,
+    transform: &KeyTransform,
+    on_conflict: Option<OnConflict>,
+    force: bool,
+    atomic: bool,
+    no_verify: bool,
+    config: Option<&Config>,
+) -> Result<()> {
     // Check if we have input
     let stdin = io::stdin();
 
@@ -66,55 +291,210 @@ pub fn run(project: &str, environment: &str, file: Option<&str>) -> Result<()> {
         );
     }
 
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+    let store = open_store()?;
+    let source = match (source, file) {
+        (Some(source), _) => source.to_string(),
+        (None, Some(path)) => format!("file:{}", path),
+        (None, None) => "stdin".to_string(),
+    };
 
-    let lines: Vec<String> = match file {
-        Some(path) => std::fs::read_to_string(path)
-            .context(format!("Failed to read file: {}", path))?
-            .lines()
-            .map(String::from)
-            .collect(),
-        None => stdin
-            .lock()
-            .lines()
-            .collect::<Result<Vec<_>, _>>()
-            .context("Failed to read from stdin")?,
+    let input = match file {
+        Some(path) => {
+            std::fs::read_to_string(path).context(format!("Failed to read file: {}", path))?
+        }
+        None => {
+            let mut input = String::new();
+            stdin
+                .lock()
+                .read_to_string(&mut input)
+                .context("Failed to read from stdin")?;
+            input
+        }
     };
 
-    let mut imported = 0;
+    let mut parsed: Vec<(String, String)> = Vec::new();
     let mut skipped = 0;
+    let mut filtered = 0;
+
+    let entries = match format.detect(file, &input) {
+        InputFormat::Json => {
+            let document: serde_json::Value =
+                serde_json::from_str(&input).context("Input isn't valid JSON")?;
+            sops::flatten(&document)?.into_iter().map(Ok).collect()
+        }
+        InputFormat::Yaml => yaml_env::parse(&input)
+            .context("Couldn't parse YAML input")?
+            .into_iter()
+            .map(Ok)
+            .collect(),
+        InputFormat::Dotenv | InputFormat::Auto => parse_dotenv(&input),
+    };
 
-    for line in lines {
-        if let Some((key, value)) = parse_line(&line) {
-            store.set(project, environment, &key, &value, None)?;
-            eprintln!("  {} {}", "✓".green(), key.bold());
-            imported += 1;
-        } else if !line.trim().is_empty() && !line.trim().starts_with('#') {
+    for entry in entries {
+        let (original, value) = match entry {
+            Ok(pair) => pair,
+            Err(bad) => {
+                if atomic {
+                    anyhow::bail!(
+                        "Couldn't parse line {}: {} ({}; --atomic: nothing was imported)",
+                        bad.line,
+                        bad.text,
+                        bad.reason
+                    );
+                }
+                eprintln!(
+                    "  {} {} (line {}: {})",
+                    ui::note(),
+                    bad.text.dimmed(),
+                    bad.line,
+                    bad.reason
+                );
+                skipped += 1;
+                continue;
+            }
+        };
+        let Some(key) = transform.apply(&original) else {
+            filtered += 1;
+            continue;
+        };
+        if let Some(existing) = parsed.iter_mut().find(|(k, _)| *k == key) {
             eprintln!(
-                "  {} {} (couldn't parse)",
-                "○".yellow(),
-                line.trim().dimmed()
+                "  {} {} appears more than once (using the last value)",
+                ui::warn(),
+                key.bold()
             );
-            skipped += 1;
+            existing.1 = value;
+        } else {
+            parsed.push((key, value));
         }
     }
 
+    if !no_verify {
+        let misnamed: Vec<String> =
+            check_key_names(config, parsed.iter().map(|(k, _)| k.as_str()))?
+                .into_iter()
+                .map(String::from)
+                .collect();
+        if atomic && !misnamed.is_empty() {
+            anyhow::bail!(
+                "{} breaks the key naming policy (--atomic: nothing was imported; --no-verify to import anyway)",
+                misnamed.join(", ")
+            );
+        }
+        parsed.retain(|(key, _)| !misnamed.contains(key));
+    }
+
+    let mut rejected = Vec::new();
+    for (key, value) in &parsed {
+        if !check_value_rules(config, key, value.as_bytes())? {
+            rejected.push(key.clone());
+        }
+    }
+    if atomic && !rejected.is_empty() {
+        anyhow::bail!(
+            "{} breaks the [validate] rules in .tinysecrets.toml (--atomic: nothing was imported)",
+            rejected.join(", ")
+        );
+    }
+    parsed.retain(|(key, _)| !rejected.contains(key));
+
+    if !no_verify {
+        let dangling = store.unresolvable_refs(project, environment, &parsed);
+        if atomic && !dangling.is_empty() {
+            let keys: Vec<&str> = dangling.iter().map(|(key, _)| key.as_str()).collect();
+            anyhow::bail!(
+                "{} refer to secrets that aren't set (--atomic: nothing was imported; --no-verify to import anyway)",
+                keys.join(", ")
+            );
+        }
+        for (key, e) in &dangling {
+            std::eprintln!("  {} {} ({:#}, skipped)", ui::fail(), key.bold(), e);
+        }
+        parsed.retain(|(key, _)| !dangling.iter().any(|(k, _)| k == key));
+    }
+
+    let keys: Vec<&str> = parsed.iter().map(|(k, _)| k.as_str()).collect();
+    let conflicts = store.existing_keys(project, environment, &keys)?;
+    let overwrite = resolve_conflicts(&conflicts, on_conflict, force)?;
+
+    let mut exists_skipped = Vec::new();
+    parsed.retain(|(key, _)| {
+        let skip = !overwrite && conflicts.contains(key);
+        if skip {
+            exists_skipped.push(key.clone());
+        }
+        !skip
+    });
+
+    // One transaction for the whole import
+    let total = parsed.len();
+    let result = store.set_many(
+        project,
+        environment,
+        &parsed,
+        Some(&source),
+        atomic,
+        &mut |done| ui::progress("Encrypting", done, total),
+    );
+    let result = if atomic {
+        result.context("--atomic: nothing was imported")?
+    } else {
+        result?
+    };
+    let failed = result.failed;
+    let unchanged = result.unchanged;
+
+    for key in &exists_skipped {
+        eprintln!("  {} {} (exists, skipped)", ui::note(), key.bold());
+    }
+    for (key, _) in &parsed {
+        match failed.iter().find(|(k, _)| k == key) {
+            Some((_, e)) => std::eprintln!("  {} {} ({:#})", ui::fail(), key.bold(), e),
+            None if unchanged.contains(key) => {
+                eprintln!("  {} {} (unchanged)", ui::note(), key.bold())
+            }
+            None => eprintln!("  {} {}", ui::ok(), key.bold()),
+        }
+    }
+    let imported = total - failed.len() - unchanged.len();
+
     eprintln!();
     if imported > 0 {
         eprintln!(
             "{} Imported {} secrets into {}/{}",
-            "✓".green(),
+            ui::ok(),
             imported.to_string().bold(),
             project.cyan(),
             environment.yellow()
         );
     }
+    if !unchanged.is_empty() {
+        eprintln!(
+            "{} {} unchanged (same value already stored)",
+            ui::note(),
+            unchanged.len()
+        );
+    }
     if skipped > 0 {
-        eprintln!("{} Skipped {} unparseable lines", "○".yellow(), skipped);
+        eprintln!("{} Skipped {} unparseable lines", ui::note(), skipped);
+    }
+    if filtered > 0 {
+        eprintln!("{} Filtered out {} keys", ui::note(), filtered);
     }
-    if imported == 0 && skipped == 0 {
-        eprintln!("{} No secrets found in input", "○".yellow());
+    if imported == 0
+        && skipped == 0
+        && filtered == 0
+        && failed.is_empty()
+        && unchanged.is_empty()
+        && rejected.is_empty()
+    {
+        eprintln!("{} No secrets found in input", ui::note());
+    }
+    if !rejected.is_empty() || !failed.is_empty() {
+        anyhow::bail!(
+            "{} secrets couldn't be imported (the rest were; use --atomic for all or nothing)",
+            rejected.len() + failed.len()
+        );
     }
 
     Ok(())
@@ -124,6 +504,11 @@ pub fn run(project: &str, environment: &str, file: Option<&str>) -> Result<()> {
 mod tests {
     use super::*;
 
+    /// The single entry on a line, if it has one
+    fn parse_line(line: &str) -> Option<(String, String)> {
+        parse_dotenv(line).into_iter().next().and_then(Result::ok)
+    }
+
     #[test]
     fn test_parse_dotenv() {
         assert_eq!(
@@ -193,6 +578,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_detect_format() {
+        let auto = InputFormat::Auto;
+        assert_eq!(auto.detect(Some("env.json"), ""), InputFormat::Json);
+        assert_eq!(
+            auto.detect(Some("docker-compose.yml"), ""),
+            InputFormat::Yaml
+        );
+        assert_eq!(auto.detect(None, "  {\"A\": \"1\"}"), InputFormat::Json);
+        assert_eq!(
+            auto.detect(None, "services:\n  web:\n    environment:\n"),
+            InputFormat::Yaml
+        );
+        assert_eq!(auto.detect(None, "A=1\nB: 2\n"), InputFormat::Dotenv);
+        assert_eq!(
+            InputFormat::Yaml.detect(Some(".env"), "A=1"),
+            InputFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn test_key_transform() {
+        let transform = KeyTransform {
+            only: vec!["APP_*".into(), "db_*".into()],
+            exclude: vec!["*_DEBUG".into()],
+            map: vec![("APP_SECRET".into(), "SESSION_SECRET".into())],
+            strip_prefix: Some("APP_".into()),
+            uppercase: true,
+            prefix: Some("MY_".into()),
+        };
+        assert_eq!(transform.apply("APP_TOKEN"), Some("MY_TOKEN".into()));
+        assert_eq!(transform.apply("db_url"), Some("MY_DB_URL".into()));
+        assert_eq!(transform.apply("APP_SECRET"), Some("SESSION_SECRET".into()));
+        assert_eq!(transform.apply("APP_DEBUG"), None);
+        assert_eq!(transform.apply("OTHER"), None);
+        // Stripping never leaves an empty key
+        assert_eq!(transform.apply("APP_"), Some("MY_APP_".into()));
+
+        assert_eq!(KeyTransform::default().apply("key"), Some("key".into()));
+    }
+
+    #[test]
+    fn test_parse_mapping() {
+        assert_eq!(
+            parse_mapping("OLD=NEW"),
+            Ok(("OLD".to_string(), "NEW".to_string()))
+        );
+        assert!(parse_mapping("OLD").is_err());
+        assert!(parse_mapping("=NEW").is_err());
+    }
+
     #[test]
     fn test_empty_value() {
         assert_eq!(
@@ -200,4 +636,72 @@ mod tests {
             Some(("EMPTY_VAR".to_string(), "".to_string()))
         );
     }
+
+    #[test]
+    fn test_multiline_values() {
+        let input = "CERT=\"-----BEGIN-----\nabc\n-----END-----\"\n\
+                     KEY='line1\nline2'\n\
+                     ESCAPED=\"line1\\nline2\"\n\
+                     NEXT=ok\n";
+        let entries: Vec<_> = parse_dotenv(input)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("CERT".into(), "-----BEGIN-----\nabc\n-----END-----".into()),
+                ("KEY".into(), "line1\nline2".into()),
+                ("ESCAPED".into(), "line1\nline2".into()),
+                ("NEXT".into(), "ok".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quotes_and_comments() {
+        assert_eq!(
+            parse_line(r#"JSON="{\"a\": \"b # c\"}" # trailing"#),
+            Some(("JSON".into(), r#"{"a": "b # c"}"#.into()))
+        );
+        assert_eq!(
+            parse_line("PASS='p#ss\\n'"),
+            Some(("PASS".into(), "p#ss\\n".into()))
+        );
+        assert_eq!(
+            parse_line("URL=http://x/#anchor # comment"),
+            Some(("URL".into(), "http://x/#anchor".into()))
+        );
+        assert_eq!(
+            parse_line(r#"PRICE="\$5 \q""#),
+            Some(("PRICE".into(), r"$5 \q".into()))
+        );
+    }
+
+    #[test]
+    fn test_bad_lines() {
+        let entries = parse_dotenv("not a pair\nX=\"a\"b\nOPEN=\"never closed\nAFTER=1\n");
+        assert_eq!(
+            entries,
+            vec![
+                Err(BadLine {
+                    line: 1,
+                    text: "not a pair".into(),
+                    reason: "expected KEY=VALUE",
+                }),
+                Err(BadLine {
+                    line: 2,
+                    text: "X=\"a\"b".into(),
+                    reason: "unexpected text after the closing quote",
+                }),
+                Err(BadLine {
+                    line: 3,
+                    text: "OPEN=\"never closed".into(),
+                    reason: "unterminated quote",
+                }),
+                // Parsing picks up again after an unterminated quote
+                Ok(("AFTER".into(), "1".into())),
+            ]
+        );
+    }
 }
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use clap::Args;
+use colored::Colorize;
+
+use crate::cli::import_env::KeyTransform;
+use crate::cli::import_sops::import_pairs;
+use crate::cli::{open_store, OnConflict};
+use crate::platforms::{self, Platform};
+use crate::ui;
+
+/// What `import-vercel`, `import-netlify` and `import-railway` share
+#[derive(Args, Debug)]
+pub struct PlatformImport {
+    /// Project name (uses .tinysecrets.toml if not specified)
+    #[arg(short, long)]
+    pub project: Option<String>,
+    /// Environment (uses .tinysecrets.toml if not specified)
+    #[arg(short, long)]
+    pub environment: Option<String>,
+    /// The platform's environment to pull (defaults to this environment; prod,
+    /// dev and staging are translated to the platform's names)
+    #[arg(long, value_name = "NAME")]
+    pub remote_env: Option<String>,
+    /// Keep the variables the platform sets itself (VERCEL_*, RAILWAY_*, ...)
+    #[arg(long)]
+    pub include_system: bool,
+    #[command(flatten)]
+    pub transform: KeyTransform,
+    /// What to do with keys that already exist (asks on a terminal if not set)
+    #[arg(long, value_enum)]
+    pub on_conflict: Option<OnConflict>,
+    /// Overwrite existing keys without asking (same as --on-conflict overwrite)
+    #[arg(short = 'y', long, conflicts_with = "on_conflict")]
+    pub force: bool,
+    /// Extra arguments for the platform CLI, e.g. -- --scope my-team
+    #[arg(last = true, value_name = "CLI_ARGS")]
+    pub args: Vec<String>,
+}
+
+pub fn run(
+    platform: Platform,
+    project: &str,
+    environment: &str,
+    options: &PlatformImport,
+) -> Result<()> {
+    let remote = options
+        .remote_env
+        .clone()
+        .unwrap_or_else(|| platform.remote_environment(environment));
+
+    // Pull first, so a missing CLI or login fails before the passphrase prompt
+    eprintln!(
+        "{} Pulling {} variables from {}",
+        ui::arrow(),
+        remote.yellow(),
+        platform.name().bold()
+    );
+    let mut pairs = platforms::pull(platform, &remote, &options.args)?;
+
+    let total = pairs.len();
+    if !options.include_system {
+        pairs.retain(|(key, _)| !platform.is_system_var(key));
+    }
+    let system = total - pairs.len();
+
+    // Vercel pulls sensitive variables as empty strings; importing those
+    // would blank out the real values
+    if platform == Platform::Vercel {
+        pairs.retain(|(key, value)| {
+            if value.is_empty() {
+                eprintln!(
+                    "  {} {} (empty on Vercel, likely sensitive; skipped)",
+                    ui::note(),
+                    key.bold()
+                );
+            }
+            !value.is_empty()
+        });
+    }
+    if system > 0 {
+        eprintln!(
+            "{} Left out {} {} system variables (--include-system keeps them)",
+            ui::note(),
+            system,
+            platform.name()
+        );
+    }
+
+    let source = format!("{}:{}", platform.name().to_lowercase(), remote);
+    let origin = format!("{} ({})", platform.name(), remote);
+    let store = open_store()?;
+    import_pairs(
+        &store,
+        project,
+        environment,
+        pairs,
+        &source,
+        &origin,
+        &options.transform,
+        options.on_conflict,
+        options.force,
+    )
+}
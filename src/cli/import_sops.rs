@@ -0,0 +1,123 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use crate::cli::import_env::KeyTransform;
+use crate::cli::{open_store, resolve_conflicts, OnConflict};
+use crate::sops;
+use crate::store::Store;
+use crate::ui;
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    file: &Path,
+    transform: &KeyTransform,
+    on_conflict: Option<OnConflict>,
+    force: bool,
+) -> Result<()> {
+    // Decrypt first, so a missing sops or key fails before the passphrase prompt
+    let decrypted = sops::decrypt(file)?;
+    let store = open_store()?;
+    let source = format!("sops:{}", file.display());
+    import_pairs(
+        &store,
+        project,
+        environment,
+        decrypted,
+        &source,
+        &file.display().to_string(),
+        transform,
+        on_conflict,
+        force,
+    )
+}
+
+/// Write already-parsed pairs (after `transform`) into an environment, resolving
+/// conflicts with existing keys. `origin` names where they came from in messages.
+#[allow(clippy::too_many_arguments)]
+pub fn import_pairs(
+    store: &Store,
+    project: &str,
+    environment: &str,
+    pairs: Vec<(String, String)>,
+    source: &str,
+    origin: &str,
+    transform: &KeyTransform,
+    on_conflict: Option<OnConflict>,
+    force: bool,
+) -> Result<()> {
+    let mut parsed: Vec<(String, String)> = Vec::new();
+    let mut filtered = 0;
+    for (original, value) in pairs {
+        let Some(key) = transform.apply(&original) else {
+            filtered += 1;
+            continue;
+        };
+        if let Some(existing) = parsed.iter_mut().find(|(k, _)| *k == key) {
+            eprintln!(
+                "  {} {} appears more than once (using the last value)",
+                ui::warn(),
+                key.bold()
+            );
+            existing.1 = value;
+        } else {
+            parsed.push((key, value));
+        }
+    }
+
+    let keys: Vec<&str> = parsed.iter().map(|(k, _)| k.as_str()).collect();
+    let conflicts = store.existing_keys(project, environment, &keys)?;
+    let overwrite = resolve_conflicts(&conflicts, on_conflict, force)?;
+
+    let mut imported = 0;
+    let mut unchanged = 0;
+    for (key, value) in &parsed {
+        if !overwrite && conflicts.contains(key) {
+            eprintln!("  {} {} (exists, skipped)", ui::note(), key.bold());
+            continue;
+        }
+        let written = store.set_bytes(
+            project,
+            environment,
+            key,
+            value.as_bytes(),
+            None,
+            Some(source),
+        )?;
+        if written {
+            eprintln!("  {} {}", ui::ok(), key.bold());
+            imported += 1;
+        } else {
+            eprintln!("  {} {} (unchanged)", ui::note(), key.bold());
+            unchanged += 1;
+        }
+    }
+
+    eprintln!();
+    if imported > 0 {
+        eprintln!(
+            "{} Imported {} secrets from {} into {}/{}",
+            ui::ok(),
+            imported.to_string().bold(),
+            origin,
+            project.cyan(),
+            environment.yellow()
+        );
+    }
+    if unchanged > 0 {
+        eprintln!(
+            "{} {} unchanged (same value already stored)",
+            ui::note(),
+            unchanged
+        );
+    }
+    if filtered > 0 {
+        eprintln!("{} Filtered out {} keys", ui::note(), filtered);
+    }
+    if imported == 0 && unchanged == 0 && filtered == 0 {
+        eprintln!("{} No secrets found in {}", ui::note(), origin);
+    }
+
+    Ok(())
+}
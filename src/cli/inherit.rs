@@ -0,0 +1,36 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::open_store;
+use crate::ui;
+
+pub fn run(project: &str, environment: &str, parent: Option<&str>) -> Result<()> {
+    let store = open_store()?;
+
+    store.set_parent(project, environment, parent)?;
+
+    match parent {
+        Some(parent) => {
+            eprintln!(
+                "{} {}/{} now inherits from {}",
+                ui::ok(),
+                project.cyan(),
+                environment.yellow(),
+                parent.yellow()
+            );
+            eprintln!(
+                "  Keys set in {} override {}",
+                environment.yellow(),
+                store.lineage(project, parent)?.join(" -> ").dimmed()
+            );
+        }
+        None => eprintln!(
+            "{} {}/{} no longer inherits from another environment",
+            ui::ok(),
+            project.cyan(),
+            environment.yellow()
+        ),
+    }
+
+    Ok(())
+}
@@ -1,51 +1,86 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
+use std::path::{Path, PathBuf};
 
-use crate::cli::prompt_new_passphrase;
+use crate::cli::{prompt_new_passphrase, warn_if_synced};
+use crate::config::GlobalConfig;
 use crate::store::Store;
+use crate::ui;
+
+pub fn run(
+    path: Option<PathBuf>,
+    passphrase_file: Option<&Path>,
+    offer_keychain: bool,
+    full_encryption: bool,
+) -> Result<()> {
+    let custom_path = match path {
+        Some(path) => {
+            let path = std::path::absolute(&path)
+                .with_context(|| format!("Invalid store path: {}", path.display()))?;
+            Store::set_path_override(path.clone())?;
+            Some(path)
+        }
+        None => None,
+    };
 
-pub fn run() -> Result<()> {
     if Store::exists()? {
-        let path = Store::default_path()?;
-        eprintln!(
+        let path = Store::path()?;
+        std::eprintln!(
             "{} Store already exists at {}",
-            "✗".red(),
+            ui::fail(),
             path.display().to_string().yellow()
         );
         eprintln!("  Use other tinysecrets commands to manage your secrets.");
         return Ok(());
     }
 
-    let passphrase = prompt_new_passphrase()?;
-    let _store = Store::init(passphrase)?;
+    let passphrase = prompt_new_passphrase(passphrase_file, offer_keychain)?;
+    let _store = Store::init(passphrase, full_encryption)?;
 
-    let path = Store::default_path()?;
+    let path = Store::path()?;
 
     eprintln!();
     eprintln!(
         "{} Secrets store created at {}",
-        "✓".green(),
+        ui::ok(),
         path.display().to_string().cyan()
     );
+    if full_encryption {
+        eprintln!("  The whole database file is encrypted (SQLCipher)");
+    }
+
+    // Remember a custom location so later commands find the store without --store
+    if let Some(custom) = custom_path {
+        let mut config = GlobalConfig::load()?;
+        config.remember_store_path(custom)?;
+        let config_path = config.save()?;
+        eprintln!(
+            "  Location saved in {}",
+            config_path.display().to_string().dimmed()
+        );
+    }
     eprintln!();
     eprintln!("{}", "Quick start:".bold());
     eprintln!(
         "  {} set a secret    tinysecrets set myapp staging DATABASE_URL",
-        "→".cyan()
+        ui::arrow()
     );
     eprintln!(
         "  {} get a secret    tinysecrets get myapp staging DATABASE_URL",
-        "→".cyan()
+        ui::arrow()
     );
     eprintln!(
         "  {} run with secrets tinysecrets run -p myapp -e staging -- npm start",
-        "→".cyan()
+        ui::arrow()
     );
     eprintln!();
     eprintln!(
-        "{}",
-        "⚠  Remember your passphrase! It cannot be recovered.".yellow()
+        "{} {}",
+        ui::warn(),
+        "Remember your passphrase! It cannot be recovered.".yellow()
     );
 
+    warn_if_synced(&path)?;
+
     Ok(())
 }
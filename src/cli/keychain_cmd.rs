@@ -1,40 +1,270 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
+use secrecy::SecretString;
+use std::io::BufRead;
+use zeroize::Zeroizing;
 
 use crate::cli::KeychainAction;
-use crate::keychain;
+use crate::config::GlobalConfig;
+use crate::keychain::{self, Backend};
+use crate::store::{Access, Store, StoreError};
+use crate::ui;
+use crate::unlock_file::{self, Wrap};
 
 pub fn run(action: KeychainAction) -> Result<()> {
     match action {
         KeychainAction::Status => status(),
         KeychainAction::Clear => clear(),
+        KeychainAction::Store { stdin } => store(stdin),
+        KeychainAction::SetTtl { ttl } => set_ttl(&ttl),
+        KeychainAction::RequirePresence { setting } => require_presence(setting == "on"),
+        KeychainAction::SetBackend { backend, wrap } => set_backend(backend, wrap),
     }
 }
 
 fn status() -> Result<()> {
-    if keychain::has_passphrase() {
-        eprintln!("🔑 Passphrase is stored in system keychain");
-        eprintln!("  Commands will use it automatically.");
+    let config = GlobalConfig::load()?.keychain;
+    if let Some((passphrase, expires)) = keychain::get_entry()? {
+        match config.backend {
+            Backend::System => eprintln!(
+                "{}Passphrase is stored in system keychain",
+                ui::icon("🔑 ", "")
+            ),
+            Backend::File => eprintln!(
+                "{}Passphrase is stored in {}, encrypted with {}",
+                ui::icon("🔑 ", ""),
+                unlock_file::path()?.display(),
+                unlock_file::wrap()?.unwrap_or_default().describe()
+            ),
+        }
+        let unlocks = report_check(&passphrase)?;
+        if keychain::is_presence_protected()? {
+            eprintln!("  Reading it needs Touch ID or your login password.");
+        } else if config.require_presence && config.backend == Backend::System {
+            eprintln!(
+                "  {} Saved without Touch ID protection; run {} to add it",
+                ui::warn(),
+                "tinysecrets keychain require-presence on".cyan()
+            );
+        }
+        if keychain::uses_legacy_entry()? {
+            eprintln!(
+                "  {} Saved by an older version for every store; it becomes this store's own entry once it unlocks it",
+                ui::note()
+            );
+        }
+        if let Some(expires) = expires {
+            eprintln!(
+                "  Expires in {} ({})",
+                keychain::format_ttl(expires - chrono::Utc::now()).bold(),
+                expires
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            );
+        }
         eprintln!();
+        if !unlocks {
+            eprintln!("  To replace it: {}", "tinysecrets keychain store".cyan());
+            std::process::exit(1);
+        }
         eprintln!("  To remove: {}", "tinysecrets keychain clear".cyan());
     } else {
-        eprintln!("{} No passphrase stored in keychain", "○".yellow());
+        eprintln!("{} No passphrase stored in keychain", ui::note());
         eprintln!("  You'll be prompted each time you run a command.");
         eprintln!();
-        eprintln!("  To save: run any command and answer 'y' when asked.");
+        eprintln!(
+            "  To save: {}, or run any command and answer 'y' when asked.",
+            "tinysecrets keychain store".cyan()
+        );
     }
     Ok(())
 }
 
+/// Say whether `passphrase` unlocks the active store; false if it doesn't
+fn report_check(passphrase: &SecretString) -> Result<bool> {
+    let path = Store::path()?;
+    match Store::check_passphrase(passphrase) {
+        Ok(Some(Access::Full)) => {
+            eprintln!("  {} It unlocks the store at {}", ui::ok(), path.display());
+            eprintln!("  Commands will use it automatically.");
+            Ok(true)
+        }
+        Ok(Some(Access::ReadOnly)) => {
+            eprintln!(
+                "  {} It's the read-only passphrase for {}: commands that change secrets will fail",
+                ui::info(),
+                path.display()
+            );
+            Ok(true)
+        }
+        Ok(None) => {
+            std::eprintln!(
+                "  {} It doesn't unlock the store at {} (was the passphrase changed?)",
+                ui::fail(),
+                path.display()
+            );
+            Ok(false)
+        }
+        Err(e) if matches!(e.downcast_ref(), Some(StoreError::Missing)) => {
+            eprintln!(
+                "  {} No store at {} to check it against",
+                ui::note(),
+                path.display()
+            );
+            Ok(true)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Save a passphrase to the keychain once it's known to unlock the store
+fn store(stdin: bool) -> Result<()> {
+    let passphrase = if stdin {
+        let mut line = Zeroizing::new(String::new());
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("Failed to read passphrase from stdin")?;
+        SecretString::new(line.trim_end_matches(['\r', '\n']).to_string())
+    } else {
+        SecretString::new(rpassword::prompt_password("Passphrase: ")?)
+    };
+
+    match Store::check_passphrase(&passphrase)? {
+        Some(access) => {
+            let expires = keychain::store_passphrase(&passphrase)?;
+            eprintln!("{} Passphrase saved to keychain", ui::ok());
+            if access == Access::ReadOnly {
+                eprintln!(
+                    "  {} This is the read-only passphrase; commands that change secrets will fail",
+                    ui::info()
+                );
+            }
+            if let Some(expires) = expires {
+                eprintln!(
+                    "  Expires in {}",
+                    keychain::format_ttl(expires - chrono::Utc::now()).bold()
+                );
+            }
+            Ok(())
+        }
+        None => Err(StoreError::InvalidPassphrase.into()),
+    }
+}
+
 fn clear() -> Result<()> {
     match keychain::delete_passphrase()? {
         true => {
-            eprintln!("{} Passphrase removed from keychain", "✓".green());
+            eprintln!("{} Passphrase removed from keychain", ui::ok());
             eprintln!("  You'll be prompted for passphrase on next command.");
         }
         false => {
-            eprintln!("{} No passphrase was stored in keychain", "○".yellow());
+            eprintln!("{} No passphrase was stored in keychain", ui::note());
         }
     }
     Ok(())
 }
+
+fn set_ttl(ttl: &str) -> Result<()> {
+    let ttl = match ttl {
+        "off" | "none" | "never" => None,
+        ttl => Some((ttl.to_string(), keychain::parse_ttl(ttl)?)),
+    };
+
+    let mut config = GlobalConfig::load()?;
+    config.keychain.ttl = ttl.as_ref().map(|(text, _)| text.clone());
+    config.save()?;
+    let updated = keychain::set_expiry(ttl.as_ref().map(|(_, duration)| *duration))?;
+
+    match &ttl {
+        Some((text, _)) => {
+            eprintln!(
+                "{} Saved passphrases now expire after {}",
+                ui::ok(),
+                text.bold()
+            );
+            if updated {
+                eprintln!("  The current entry expires {} from now.", text);
+            }
+            eprintln!("  After that you'll be prompted again (and asked to re-save it).");
+        }
+        None => {
+            eprintln!("{} Saved passphrases no longer expire", ui::ok());
+        }
+    }
+    Ok(())
+}
+
+fn require_presence(on: bool) -> Result<()> {
+    if on && !cfg!(target_os = "macos") {
+        anyhow::bail!("Touch ID protection for the keychain entry is only supported on macOS");
+    }
+    if on && GlobalConfig::load()?.keychain.backend == Backend::File {
+        anyhow::bail!(
+            "Touch ID protection needs the system keychain; run `tinysecrets keychain set-backend system` first"
+        );
+    }
+
+    // Read the entry before the setting changes, then save it again so it
+    // moves to (or out of) the protected keychain
+    let current = keychain::get_entry()?;
+    let mut config = GlobalConfig::load()?;
+    config.keychain.require_presence = on;
+    config.save()?;
+    let updated = match current {
+        Some((passphrase, expires)) => {
+            keychain::save_entry(&passphrase, expires)?;
+            true
+        }
+        None => false,
+    };
+
+    if on {
+        eprintln!(
+            "{} Reading the saved passphrase now needs Touch ID or your login password",
+            ui::ok()
+        );
+    } else {
+        eprintln!(
+            "{} The saved passphrase is read without asking again",
+            ui::ok()
+        );
+    }
+    if updated {
+        eprintln!("  The current entry was saved again with the new setting.");
+    }
+    Ok(())
+}
+
+fn set_backend(backend: Backend, wrap: Option<Wrap>) -> Result<()> {
+    if backend == Backend::System && wrap.is_some() {
+        anyhow::bail!("--wrap only applies to the file backend");
+    }
+    let moved = keychain::set_backend(backend, wrap)?;
+
+    match backend {
+        Backend::System => eprintln!("{} Passphrases are saved in the system keychain", ui::ok()),
+        Backend::File => {
+            let wrap = match unlock_file::wrap()? {
+                Some(wrap) => wrap,
+                None => GlobalConfig::load()?.keychain.file_wrap,
+            };
+            eprintln!(
+                "{} Passphrases are saved in {}, encrypted with {}",
+                ui::ok(),
+                unlock_file::path()?.display().to_string().cyan(),
+                wrap.describe()
+            );
+            if wrap == Wrap::Passphrase {
+                eprintln!(
+                    "  Without a terminal, set {} to unlock it.",
+                    unlock_file::PASSPHRASE_ENV_VAR.cyan()
+                );
+            }
+        }
+    }
+    if moved {
+        eprintln!("  The current entry was moved there.");
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::{key_policy, open_store};
+use crate::config::Config;
+use crate::lint::LintAction;
+use crate::ui;
+
+/// Report stored keys whose names break the `[key_policy]` naming convention,
+/// in one project or (`project` None) the whole store
+pub fn run(project: Option<&str>, config: Option<&Config>) -> Result<()> {
+    let policy = key_policy(config)?;
+    let Some(pattern) = policy
+        .pattern
+        .as_deref()
+        .filter(|_| policy.on_mismatch != LintAction::Off)
+    else {
+        eprintln!(
+            "{} No key naming policy. Add `pattern = \"^[A-Z][A-Z0-9_]*$\"` under [key_policy] in .tinysecrets.toml or the global config",
+            ui::note()
+        );
+        return Ok(());
+    };
+
+    let store = open_store()?;
+    let entries = store.list(project, None)?;
+    let nonconforming = policy.nonconforming(entries.iter().map(|e| e.key.as_str()))?;
+    let scope = project.map_or("the store".to_string(), |p| p.cyan().to_string());
+
+    if nonconforming.is_empty() {
+        eprintln!(
+            "{} All {} keys in {} match the naming policy /{}/",
+            ui::ok(),
+            entries.len(),
+            scope,
+            pattern
+        );
+        return Ok(());
+    }
+
+    // Only a `deny` policy fails the command; `warn` just reports
+    let deny = policy.on_mismatch == LintAction::Deny;
+    std::eprintln!(
+        "{} {} key(s) in {} don't match the naming policy /{}/:",
+        if deny { ui::fail() } else { ui::warn() },
+        nonconforming.len(),
+        scope,
+        pattern
+    );
+    for entry in entries
+        .iter()
+        .filter(|e| nonconforming.contains(&e.key.as_str()))
+    {
+        std::eprintln!(
+            "  {} {}/{}/{}",
+            ui::bullet(),
+            entry.project.cyan(),
+            entry.environment.yellow(),
+            entry.key.bold()
+        );
+    }
+    if deny {
+        std::process::exit(1);
+    }
+    Ok(())
+}
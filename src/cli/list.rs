@@ -1,47 +1,261 @@
 use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
 use colored::Colorize;
+use std::io::IsTerminal;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::{open_store, ListSort};
+use crate::store::SecretEntry;
+use crate::ui;
 
-pub fn run(project: Option<&str>, environment: Option<&str>) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+pub fn run(
+    project: Option<&str>,
+    environment: Option<&str>,
+    long: bool,
+    flat: bool,
+    sort: ListSort,
+    changed_since: Option<Duration>,
+) -> Result<()> {
+    let store = open_store()?;
 
-    let entries = store.list(project, environment)?;
+    // A single environment shows its effective keys, including inherited ones
+    let mut entries = match (project, environment) {
+        (Some(p), Some(e)) => store.resolved_entries(p, e)?,
+        _ => store.list(project, environment)?,
+    };
+
+    let now = Utc::now();
+    if let Some(age) = changed_since {
+        entries.retain(|entry| entry.updated_at >= now - age);
+    }
 
     if entries.is_empty() {
-        eprintln!("{} No secrets found", "○".yellow());
+        eprintln!("{} No secrets found", ui::note());
         return Ok(());
     }
 
-    // Group by project/environment
+    // Group by project/environment (inherited entries under the requested environment)
+    let mut groups: Vec<(String, String, Vec<SecretEntry>)> = Vec::new();
+    for entry in entries {
+        let env = environment.unwrap_or(&entry.environment).to_string();
+        match groups.last_mut() {
+            Some((p, e, group)) if *p == entry.project && *e == env => group.push(entry),
+            _ => groups.push((entry.project.clone(), env, vec![entry])),
+        }
+    }
+
+    // Headings go to stderr when piped, so stdout has exactly one line per secret
+    let piped = !std::io::stdout().is_terminal();
+    let decoration = |line: String| {
+        if piped {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+
     let mut current_project = String::new();
-    let mut current_env = String::new();
+    for (project, env, mut group) in groups {
+        if sort == ListSort::Updated {
+            group.sort_by_key(|entry| std::cmp::Reverse(entry.updated_at));
+        }
 
-    for entry in entries {
-        if entry.project != current_project {
+        if flat {
+            for entry in &group {
+                println!("{}", flat_line(&project, &env, entry));
+            }
+            continue;
+        }
+
+        if project != current_project {
             if !current_project.is_empty() {
-                println!();
+                decoration(String::new());
             }
-            current_project = entry.project.clone();
-            current_env = String::new();
-            println!("📦 {}", entry.project.cyan().bold());
+            decoration(format!(
+                "{}{}",
+                ui::icon("📦 ", "Project: "),
+                project.cyan().bold()
+            ));
+            current_project = project;
         }
+        decoration(format!("  {} {}", ui::branch(), env.yellow()));
 
-        if entry.environment != current_env {
-            current_env = entry.environment.clone();
-            println!("  {} {}", "└".dimmed(), entry.environment.yellow());
+        if long {
+            print_table(&env, &group, now, &decoration);
+        } else {
+            for entry in &group {
+                let version_str = if entry.environment == env {
+                    format!("v{}", entry.version)
+                } else {
+                    format!("v{} (from {})", entry.version, entry.environment)
+                };
+                println!(
+                    "    {} {} {}",
+                    ui::bullet(),
+                    entry.key.bold(),
+                    version_str.dimmed()
+                );
+            }
         }
+    }
+
+    Ok(())
+}
+
+/// `project/env/KEY  vN  2024-05-01T12:00:00Z`, for grep/sort/awk
+fn flat_line(project: &str, env: &str, entry: &SecretEntry) -> String {
+    format!(
+        "{}/{}/{}  v{}  {}",
+        project,
+        env,
+        entry.key,
+        entry.version,
+        entry.updated_at.format("%Y-%m-%dT%H:%M:%SZ")
+    )
+}
+
+/// Aligned KEY / VERSION / UPDATED / SOURCE / DESCRIPTION table for one environment
+fn print_table(
+    env: &str,
+    entries: &[SecretEntry],
+    now: DateTime<Utc>,
+    decoration: &dyn Fn(String),
+) {
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| {
+            let version = if entry.environment == env {
+                format!("v{}", entry.version)
+            } else {
+                format!("v{} ({})", entry.version, entry.environment)
+            };
+            let description = entry
+                .description
+                .as_deref()
+                .and_then(|d| d.lines().next())
+                .unwrap_or("")
+                .to_string();
+            [
+                entry.key.clone(),
+                version,
+                relative_time(entry.updated_at, now),
+                entry.source.clone().unwrap_or_else(|| "-".to_string()),
+                description,
+            ]
+        })
+        .collect();
+
+    let header = ["KEY", "VERSION", "UPDATED", "SOURCE", "DESCRIPTION"];
+    let widths: Vec<usize> = (0..4)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
 
-        let version_str = format!("v{}", entry.version);
+    // Pad before coloring so escape codes don't throw off alignment
+    let pad = |text: &str, i: usize| format!("{:<width$}", text, width = widths[i]);
+    decoration(format!(
+        "    {}  {}  {}  {}  {}",
+        pad(header[0], 0).dimmed(),
+        pad(header[1], 1).dimmed(),
+        pad(header[2], 2).dimmed(),
+        pad(header[3], 3).dimmed(),
+        header[4].dimmed()
+    ));
+    for [key, version, updated, source, description] in &rows {
         println!(
-            "    {} {} {}",
-            "•".dimmed(),
-            entry.key.bold(),
-            version_str.dimmed()
+            "    {}  {}  {}  {}  {}",
+            pad(key, 0).bold(),
+            pad(version, 1).dimmed(),
+            pad(updated, 2),
+            pad(source, 3).dimmed(),
+            description.dimmed()
         );
     }
+}
 
-    Ok(())
+/// Human-friendly age of a timestamp ("just now", "5m ago", "3d ago")
+pub fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - time).num_seconds().max(0);
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86_399 => format!("{}h ago", seconds / 3600),
+        86_400..=2_591_999 => format!("{}d ago", seconds / 86_400),
+        _ => time.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// Parse an age like `30m`, `12h`, `7d` or `2w`
+pub fn parse_age(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let n: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid age '{}' (expected e.g. 30m, 12h, 7d, 2w)", input))?;
+
+    match unit {
+        "m" => Ok(Duration::minutes(n)),
+        "h" => Ok(Duration::hours(n)),
+        "d" => Ok(Duration::days(n)),
+        "w" => Ok(Duration::weeks(n)),
+        _ => Err(format!(
+            "invalid age unit in '{}' (use m, h, d or w)",
+            input
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30m"), Ok(Duration::minutes(30)));
+        assert_eq!(parse_age("7d"), Ok(Duration::days(7)));
+        assert_eq!(parse_age("2w"), Ok(Duration::weeks(2)));
+        assert!(parse_age("7").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3y").is_err());
+    }
+
+    #[test]
+    fn test_flat_line() {
+        let updated_at = DateTime::parse_from_rfc3339("2024-05-01T12:00:00+02:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let entry = SecretEntry {
+            project: "api".to_string(),
+            environment: "base".to_string(),
+            key: "DB_URL".to_string(),
+            description: None,
+            created_at: updated_at,
+            updated_at,
+            version: 3,
+            source: None,
+            changed_by: None,
+            reason: None,
+            git: None,
+        };
+        assert_eq!(
+            flat_line("api", "prod", &entry),
+            "api/prod/DB_URL  v3  2024-05-01T10:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now = Utc::now();
+        assert_eq!(relative_time(now, now), "just now");
+        assert_eq!(relative_time(now - Duration::minutes(5), now), "5m ago");
+        assert_eq!(relative_time(now - Duration::hours(3), now), "3h ago");
+        assert_eq!(relative_time(now - Duration::days(2), now), "2d ago");
+    }
 }
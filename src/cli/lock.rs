@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::Path;
+
+use crate::cli::open_store;
+use crate::lockfile::Lockfile;
+use crate::store::Store;
+use crate::ui;
+
+/// Write the environment's current versions as a lockfile, to stdout or `output`
+pub fn run(project: &str, environment: &str, output: Option<&Path>) -> Result<()> {
+    let store = open_store()?;
+
+    let lock = store.lock(project, environment)?;
+    if lock.keys.is_empty() {
+        anyhow::bail!("No secrets in {}/{} to lock", project, environment);
+    }
+    let text = lock.to_toml()?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, &text)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!(
+                "{} Locked {} keys of {}/{} in {}",
+                ui::ok(),
+                lock.keys.len().to_string().bold(),
+                project.cyan(),
+                environment.yellow(),
+                path.display().to_string().cyan()
+            );
+        }
+        None => {
+            print!("{}", text);
+            eprintln!(
+                "{} Locked {} keys of {}/{}",
+                ui::ok(),
+                lock.keys.len().to_string().bold(),
+                project.cyan(),
+                environment.yellow()
+            );
+        }
+    }
+    eprintln!(
+        "  {}",
+        "`run --locked <file>` injects exactly these versions, even after rotation.".dimmed()
+    );
+    Ok(())
+}
+
+/// Read every value at the versions locked in `path` from here on
+/// (`run --locked`); returns the lockfile's project and environment
+pub fn pin(path: &Path) -> Result<(String, String)> {
+    let lock = Lockfile::read(path)?;
+    eprintln!(
+        "{} Using the versions locked in {}",
+        ui::info(),
+        path.display().to_string().cyan()
+    );
+    let pinned = (lock.project.clone(), lock.environment.clone());
+    Store::set_locked(lock);
+    Ok(pinned)
+}
@@ -1,105 +1,144 @@
 //! Migrate secrets from legacy age format to fast ChaCha20 format
 
 use anyhow::Result;
-use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use colored::Colorize;
 use rusqlite::params;
-use secrecy::ExposeSecret;
 
-use crate::cli::prompt_passphrase;
-use crate::crypto::{self, MasterKey};
-use crate::store::Store;
+use crate::cli::open_store;
+use crate::crypto;
+use crate::ui;
 
-pub fn run() -> Result<()> {
+/// Tables holding encrypted values, current secrets first
+const TABLES: [&str; 2] = ["secrets", "secret_history"];
+
+/// A legacy-format value awaiting re-encryption
+struct LegacyValue {
+    table: &'static str,
+    id: i64,
+    label: String,
+    encrypted: String,
+}
+
+pub fn run(dry_run: bool) -> Result<()> {
     eprintln!(
         "{}",
-        "🔄 Migrating secrets to fast encryption format...".cyan()
+        format!(
+            "{}Migrating secrets to fast encryption format{}...",
+            ui::icon("🔄 ", ""),
+            if dry_run { " (dry run)" } else { "" }
+        )
+        .cyan()
     );
     eprintln!();
 
-    let passphrase = prompt_passphrase()?;
-
-    // Open store (this derives the master key)
-    let store = Store::open(passphrase.clone())?;
+    // Open store (this derives and caches the master key)
+    let store = open_store()?;
 
     // Get raw connection for direct queries
     let conn = store.connection();
 
-    // Get salt for master key derivation
-    let salt_b64: String = conn.query_row(
-        "SELECT value FROM metadata WHERE key = 'encryption_salt'",
-        [],
-        |row| row.get(0),
-    )?;
-    let salt_vec = BASE64.decode(&salt_b64)?;
-    let salt: [u8; 32] = salt_vec
-        .try_into()
-        .map_err(|_| anyhow::anyhow!("Invalid salt"))?;
-    let master_key = MasterKey::derive(&passphrase, &salt)?;
-
-    // Get all secrets
-    let mut stmt =
-        conn.prepare("SELECT id, project, environment, key, encrypted_value FROM secrets")?;
-
-    let secrets: Vec<(i64, String, String, String, String)> = stmt
-        .query_map([], |row| {
+    let mut legacy = Vec::new();
+    let mut already_new = 0;
+    for table in TABLES {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, project, environment, key, version, encrypted_value FROM {}",
+            table
+        ))?;
+        let rows = stmt.query_map([], |row| {
             Ok((
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-                row.get(3)?,
-                row.get(4)?,
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, String>(5)?,
             ))
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    let total = secrets.len();
-    let mut migrated = 0;
-    let mut already_new = 0;
-
-    for (id, project, env, key, encrypted) in secrets {
-        // Check if already in new format (starts with version byte 0x02 after base64 decode)
-        let data = BASE64.decode(&encrypted)?;
-
-        if !data.is_empty() && data[0] == 2 {
-            // Already in new format
-            already_new += 1;
-            continue;
+        })?;
+
+        for row in rows {
+            let (id, project, env, key, version, encrypted) = row?;
+            let key = store.key_name(&key)?;
+            if crypto::is_current_format(&encrypted) {
+                already_new += 1;
+                continue;
+            }
+            let label = match table {
+                "secrets" => format!("{}/{}/{}", project, env, key),
+                _ => format!("{}/{}/{} (v{})", project, env, key, version),
+            };
+            legacy.push(LegacyValue {
+                table,
+                id,
+                label,
+                encrypted,
+            });
         }
+    }
 
-        // Decrypt with legacy format
-        let decrypted = crypto::decrypt(&encrypted, &master_key, &passphrase)?;
+    let total = legacy.len();
 
-        // Re-encrypt with new fast format
-        let new_encrypted = crypto::encrypt(decrypted.expose_secret(), &master_key)?;
+    if dry_run {
+        for value in &legacy {
+            eprintln!("  {} {}", ui::arrow(), value.label);
+        }
+        if total > 0 {
+            eprintln!();
+        }
+        eprintln!(
+            "{} Would migrate {} values to fast format",
+            ui::info(),
+            total.to_string().bold()
+        );
+        if already_new > 0 {
+            eprintln!("   ({} are already in new format)", already_new);
+        }
+        return Ok(());
+    }
 
-        // Update in database
-        conn.execute(
-            "UPDATE secrets SET encrypted_value = ?1 WHERE id = ?2",
-            params![new_encrypted, id],
+    // All or nothing: an interrupted migration leaves the store untouched
+    let tx = store.immediate_transaction()?;
+    for (done, value) in legacy.iter().enumerate() {
+        // Decrypt with legacy format, re-encrypt with new fast format
+        let decrypted = store.decrypt_value(&value.encrypted)?;
+        let new_encrypted = store.encrypt_value(&decrypted)?;
+
+        tx.execute(
+            &format!(
+                "UPDATE {} SET encrypted_value = ?1 WHERE id = ?2",
+                value.table
+            ),
+            params![new_encrypted, value.id],
         )?;
 
-        migrated += 1;
-        eprint!(
-            "\r  {} {}/{} - {}/{}/{}          ",
-            "✓".green(),
-            migrated,
-            total - already_new,
-            project,
-            env,
-            key
-        );
+        let progress = format!("  {} {}/{} - {}", ui::ok(), done + 1, total, value.label);
+        // Accessible mode prints one line per secret instead of redrawing in place
+        if ui::accessible() {
+            eprintln!("{}", progress);
+        } else if !ui::quiet() {
+            eprint!("\r{}          ", progress);
+        }
     }
+    if total > 0 {
+        store.rechain_history(
+            &tx,
+            "migrate",
+            &format!("re-encrypted {} legacy values", total),
+        )?;
+    }
+    tx.commit()?;
 
-    if migrated > 0 {
-        eprintln!();
+    if total > 0 {
+        store.record_writer()?;
+        if !ui::accessible() {
+            eprintln!();
+        }
     }
 
     eprintln!();
     eprintln!(
-        "{} Migrated {} secrets to fast format",
-        "✅".green(),
-        migrated.to_string().bold()
+        "{} Migrated {} values to fast format",
+        ui::ok(),
+        total.to_string().bold()
     );
     if already_new > 0 {
         eprintln!("   ({} were already in new format)", already_new);
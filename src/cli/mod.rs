@@ -1,21 +1,56 @@
+pub mod audit;
+pub mod backup;
+pub mod check;
+pub mod compat;
 pub mod config;
+pub mod db_cmd;
 pub mod delete;
+pub mod describe;
+pub mod doctor;
+pub mod edit;
 pub mod envs;
 pub mod examples;
 pub mod export;
 pub mod get;
+pub mod has;
 pub mod history;
+pub mod hook;
 pub mod import;
 pub mod import_env;
+pub mod import_platform;
+pub mod import_sops;
+pub mod inherit;
 pub mod init;
 pub mod keychain_cmd;
+pub mod lint_cmd;
 pub mod list;
+pub mod lock;
 pub mod migrate;
+pub mod pick;
+pub mod plugin_cmd;
 pub mod projects;
+pub mod push_gha;
+pub mod push_heroku;
+pub mod render;
 pub mod run;
+pub mod scaffold;
+pub mod scan;
+pub mod search;
+pub mod security_cmd;
+pub mod serve;
 pub mod set;
+pub mod setup;
+pub mod shell;
+pub mod stats;
+pub mod store_cmd;
+pub mod task;
+pub mod trash;
+pub mod verify;
+pub mod viewer_cmd;
+pub mod watch;
 
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "tinysecrets")]
@@ -45,6 +80,42 @@ BULK IMPORT:
   cat .env | tinysecrets import-env -p myapp -e dev
 "#)]
 pub struct Cli {
+    /// Use the store at this path (overrides TINYSECRETS_STORE and the global config)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub store: Option<PathBuf>,
+
+    /// Use a [profiles.<NAME>] entry from the global config
+    #[arg(long, global = true, value_name = "NAME", env = "TINYSECRETS_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Only print errors (no status messages or warnings)
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log store operations and timings to stderr; -vv adds every SQL statement
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// How to print errors: text, or one JSON object (code, status, message) on stderr
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        env = "TINYSECRETS_ERROR_FORMAT",
+        default_value_t = ErrorFormat::Text
+    )]
+    pub error_format: ErrorFormat,
+
+    /// Open a store that other users can write to, or use a passphrase file
+    /// they can read (refused otherwise)
+    #[arg(
+        long,
+        global = true,
+        env = "TINYSECRETS_INSECURE_OK",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    pub insecure_ok: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -53,7 +124,38 @@ pub struct Cli {
 pub enum Commands {
     /// Initialize a new secrets store
     #[command(visible_alias = "i")]
-    Init,
+    Init {
+        /// Create the store at this path and remember it in the global config
+        #[arg(long, value_name = "PATH")]
+        path: Option<PathBuf>,
+        /// Read the new passphrase from this file instead of prompting (trailing newline ignored)
+        #[arg(long, value_name = "PATH")]
+        passphrase_file: Option<PathBuf>,
+        /// Don't offer to save the passphrase to the system keychain
+        #[arg(long)]
+        no_keychain: bool,
+        /// Encrypt the whole database file (SQLCipher), hiding key names,
+        /// projects and timestamps too. Needs a build with `--features sqlcipher`.
+        #[arg(long)]
+        full_encryption: bool,
+    },
+
+    /// Guided first-time setup: create or unlock the store, pick a project and
+    /// environment, write .tinysecrets.toml and import an existing .env
+    Setup {
+        /// Use this project instead of asking
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Use this environment instead of asking
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Offer to import this dotenv file (default: .env in the current directory)
+        #[arg(long, value_name = "FILE")]
+        env_file: Option<PathBuf>,
+        /// When creating the store, encrypt the whole database file (see `init`)
+        #[arg(long)]
+        full_encryption: bool,
+    },
 
     /// Set a secret value
     #[command(visible_alias = "s")]
@@ -68,6 +170,44 @@ pub enum Commands {
         key: String,
         /// Secret value (opens $EDITOR if not provided)
         value: Option<String>,
+        /// Read the value from stdin (keeps it out of shell history and `ps`)
+        #[arg(long, conflicts_with = "value")]
+        stdin: bool,
+        /// Read the value from a file, byte for byte (binary-safe, e.g. .p12 keystores)
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["value", "stdin"])]
+        from_file: Option<PathBuf>,
+        /// Read a JSON document from a file, checking it parses (read fields with `get --path`)
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["value", "stdin", "from_file"])]
+        json_from_file: Option<PathBuf>,
+        /// Type the value at a hidden multi-line prompt instead of in $EDITOR (no temp file)
+        #[arg(long, conflicts_with_all = ["value", "stdin", "from_file", "json_from_file"])]
+        no_editor_temp: bool,
+        /// Attach a description/note to the secret
+        #[arg(short, long)]
+        description: Option<String>,
+        /// Why the value is changing, recorded in history (e.g. "rotated after incident")
+        #[arg(long)]
+        reason: Option<String>,
+        /// Set the key even if its name breaks the [key_policy] naming convention
+        /// or a {{ref:...}} in the value doesn't resolve
+        #[arg(long)]
+        no_verify: bool,
+    },
+
+    /// Edit a secret's value in $EDITOR, starting from the current value; saves a
+    /// new version only if it changed
+    Edit {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Secret key name
+        key: String,
+        /// Why the value is changing, recorded in history
+        #[arg(long)]
+        reason: Option<String>,
     },
 
     /// Get a secret value
@@ -80,10 +220,75 @@ pub enum Commands {
         #[arg(short, long)]
         environment: Option<String>,
         /// Secret key name
-        key: String,
+        #[arg(required_unless_present = "format")]
+        key: Option<String>,
         /// Get a specific version (from history)
         #[arg(long, visible_alias = "rev")]
         version: Option<i32>,
+        /// Show metadata (version, timestamps, description) instead of the value
+        #[arg(long, conflicts_with = "version")]
+        info: bool,
+        /// Render a template of {{KEY}} placeholders instead of a single key
+        #[arg(long, conflicts_with_all = ["key", "version", "info"])]
+        format: Option<String>,
+        /// Output the value base64-encoded
+        #[arg(long, group = "encoding")]
+        base64: bool,
+        /// Output the value hex-encoded
+        #[arg(long, group = "encoding")]
+        hex: bool,
+        /// Output the value as a quoted JSON string
+        #[arg(long, group = "encoding")]
+        json_string: bool,
+        /// Trim surrounding whitespace from the value before encoding
+        #[arg(long)]
+        trim: bool,
+        /// Don't print a trailing newline
+        #[arg(short = 'n', long)]
+        no_newline: bool,
+        /// Print {{ref:...}} references as stored instead of resolving them
+        #[arg(long)]
+        no_resolve: bool,
+        /// Extract a field from a JSON value with a jq-style path, e.g. .credentials.password
+        #[arg(long, value_name = "PATH", requires = "key", conflicts_with_all = ["format", "info"])]
+        path: Option<String>,
+        /// Write the value to a file instead of stdout (binary-safe)
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["format", "info"])]
+        to_file: Option<PathBuf>,
+        /// Octal permissions for --to-file
+        #[arg(long, default_value = "600", requires = "to_file", value_parser = get::parse_mode)]
+        mode: u32,
+        /// Print nothing; exit 0 if the key is set, 1 if not (same as `has`)
+        #[arg(long, requires = "key", conflicts_with_all = ["version", "info", "format", "to_file"])]
+        exists: bool,
+    },
+
+    /// Exit 0 if a secret is set and 1 if not, without printing or decrypting it
+    Has {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Secret key name
+        key: String,
+    },
+
+    /// Render a template file with {{KEY}} placeholders filled from secrets
+    Render {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Template file
+        #[arg(short, long)]
+        template: String,
+        /// Write to a file (created with mode 600) instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
     },
 
     /// List secrets
@@ -95,11 +300,161 @@ pub enum Commands {
         /// Filter by environment
         #[arg(short, long)]
         environment: Option<String>,
+        /// Show a table with versions, update times and descriptions
+        #[arg(short, long)]
+        long: bool,
+        /// Group secrets under project and environment headings (the default)
+        #[arg(long, conflicts_with = "flat")]
+        tree: bool,
+        /// One `project/env/KEY  vN  updated` line per secret, for grep/sort/awk
+        #[arg(long, conflicts_with = "long")]
+        flat: bool,
+        /// Order keys within each environment
+        #[arg(long, value_enum, default_value_t = ListSort::Key)]
+        sort: ListSort,
+        /// Only show secrets updated within this long (e.g. 30m, 12h, 7d, 2w)
+        #[arg(long, value_name = "AGE", value_parser = list::parse_age)]
+        changed_since: Option<chrono::Duration>,
+    },
+
+    /// Write the current version of every key in an environment to a lockfile,
+    /// for `run --locked`
+    Lock {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Print an event whenever a secret is added, changed or deleted
+    Watch {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Seconds between checks
+        #[arg(long, value_name = "SECS", default_value_t = 2,
+              value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+        /// One JSON object per line
+        #[arg(long)]
+        json: bool,
+        /// Command to run for each event, e.g. -- ./reload.sh
+        #[arg(last = true, value_name = "HOOK")]
+        hook: Vec<String>,
+    },
+
+    /// Search key names and descriptions across all projects/environments
+    #[command(visible_alias = "find")]
+    Search {
+        /// Substring or glob (e.g. 'STRIPE_*'), case-insensitive
+        pattern: String,
+        /// Only search this project
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Only search this environment
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Also search decrypted values (matches are reported, values never printed)
+        #[arg(long)]
+        values: bool,
+    },
+
+    /// Fuzzy-find a key and print its value (or copy or edit it)
+    Pick {
+        /// Start with this search typed in
+        query: Option<String>,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Copy the value to the clipboard instead of printing it
+        #[arg(short, long, conflicts_with = "edit")]
+        copy: bool,
+        /// Open the value in $EDITOR and save a new version if it changed
+        #[arg(long)]
+        edit: bool,
+    },
+
+    /// Edit a secret's description in $EDITOR (value is unchanged)
+    Describe {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Secret key name
+        key: String,
+        /// New description (opens $EDITOR if not provided, empty clears it)
+        description: Option<String>,
+    },
+
+    /// Make an environment inherit keys from a parent environment
+    Inherit {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Parent environment (omit with --none to stop inheriting)
+        #[arg(required_unless_present = "none")]
+        parent: Option<String>,
+        /// Stop inheriting from the current parent
+        #[arg(long, conflicts_with = "parent")]
+        none: bool,
     },
 
     /// Delete a secret
     #[command(visible_alias = "rm")]
     Delete {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Secret key name, or a glob like 'STRIPE_*' to delete every match
+        key: String,
+        /// Delete without asking for confirmation
+        #[arg(short = 'y', long)]
+        force: bool,
+    },
+
+    /// Delete an environment and all of its secrets (they go to the trash)
+    DeleteEnv {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Delete without asking for confirmation
+        #[arg(short = 'y', long)]
+        force: bool,
+    },
+
+    /// Delete a project and every secret in it (they go to the trash)
+    DeleteProject {
+        /// Project name
+        project: String,
+        /// Delete without asking for confirmation
+        #[arg(short = 'y', long)]
+        force: bool,
+    },
+
+    /// Restore a deleted secret from the trash (its history comes back with it)
+    Undelete {
         /// Project name (uses .tinysecrets.toml if not specified)
         #[arg(short, long)]
         project: Option<String>,
@@ -110,6 +465,12 @@ pub enum Commands {
         key: String,
     },
 
+    /// List or purge deleted secrets
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+
     /// Run a command with secrets injected as environment variables
     #[command(visible_alias = "r")]
     Run {
@@ -119,28 +480,301 @@ pub enum Commands {
         /// Environment (uses .tinysecrets.toml if not specified)
         #[arg(short, long)]
         environment: Option<String>,
+        /// Forward secrets into a `docker run/create/exec` or `docker compose run` container
+        #[arg(long)]
+        docker: bool,
+        /// Run once per environment in parallel (comma-separated), prefixing output
+        #[arg(long, value_delimiter = ',', conflicts_with = "environment")]
+        each_env: Vec<String>,
+        /// Fail before running if any key listed in `required` is missing
+        #[arg(long)]
+        check: bool,
+        /// Expand `${OTHER_KEY}` and `${env:NAME}` in values before injecting them
+        #[arg(long)]
+        interpolate: bool,
+        /// Pass secrets as files (one per key) in a private temp dir created under DIR
+        /// instead of env vars; $CREDENTIALS_DIRECTORY points at it. Removed on exit
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["docker", "each_env"])]
+        as_files: Option<PathBuf>,
+        /// Write KEY's value to a new 0600 file at PATH and set $KEY to the path, for tools
+        /// that only read files; removed when the command exits (repeatable)
+        #[arg(long, value_name = "KEY=PATH", value_parser = run::parse_mount,
+              conflicts_with_all = ["docker", "each_env", "as_files"])]
+        mount: Vec<(String, PathBuf)>,
+        /// Start the command with only its secrets plus PATH, HOME, USER, LOGNAME, SHELL,
+        /// TERM, LANG, LC_ALL, TZ and TMPDIR from this environment
+        #[arg(long, visible_alias = "env-clear")]
+        isolated: bool,
+        /// Also pass NAME through with --isolated (repeatable)
+        #[arg(long, value_name = "NAME", requires = "isolated")]
+        keep_env: Vec<String>,
+        /// Replace secret values in the command's output with ***KEY***, e.g. for CI logs
+        #[arg(long, conflicts_with_all = ["each_env", "as_files", "mount"])]
+        mask_output: bool,
+        /// Use the values as they were at this time (2024-05-01, '2024-05-01 14:30',
+        /// RFC 3339, or an age like 30d), taken from history
+        #[arg(long, value_name = "WHEN", value_parser = history::parse_as_of)]
+        as_of: Option<chrono::DateTime<chrono::Utc>>,
+        /// Inject exactly the versions in this lockfile (from `tinysecrets lock`),
+        /// even if secrets were rotated since
+        #[arg(long, value_name = "FILE",
+              conflicts_with_all = ["project", "environment", "each_env", "as_of"])]
+        locked: Option<PathBuf>,
         /// Command and arguments to run
         #[arg(last = true, required = true)]
         command: Vec<String>,
     },
 
-    /// Export secrets to an encrypted bundle
-    Export {
+    /// Run the `up` command from [commands] in .tinysecrets.toml with secrets injected
+    Up {
         /// Project name (uses .tinysecrets.toml if not specified)
         #[arg(short, long)]
         project: Option<String>,
         /// Environment (uses .tinysecrets.toml if not specified)
         #[arg(short, long)]
         environment: Option<String>,
-        /// Output file (stdout if not specified)
+        /// Fail before running if any key listed in `required` is missing
+        #[arg(long)]
+        check: bool,
+        /// Extra arguments for the command
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    /// Run a named command from [commands] in .tinysecrets.toml with secrets injected
+    X {
+        /// Command name
+        name: String,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Fail before running if any key listed in `required` is missing
+        #[arg(long)]
+        check: bool,
+        /// Extra arguments for the command
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    /// Verify the environment defines every key listed in `required` (.tinysecrets.toml)
+    Check {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+    },
+
+    /// Report stored keys whose names break the [key_policy] naming convention
+    Lint {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Lint every project in the store
+        #[arg(long, conflicts_with = "project")]
+        all: bool,
+    },
+
+    /// Look for risky patterns in the store's values
+    Audit {
+        /// Report values shared between environments or projects (e.g. prod and
+        /// staging using the same credential)
+        #[arg(long, required = true)]
+        duplicates: bool,
+        /// Only audit this project (default: the whole store)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Ignore values shorter than this (ports, flags, region names)
+        #[arg(long, value_name = "CHARS", default_value_t = 8)]
+        min_length: usize,
+    },
+
+    /// Scan files for plaintext copies of the project's stored secret values
+    Scan {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Files or directories to scan (default: current directory)
+        #[arg(conflicts_with = "staged")]
+        paths: Vec<PathBuf>,
+        /// Scan lines added in the git index (for a pre-commit hook)
+        #[arg(long)]
+        staged: bool,
+    },
+
+    /// Start an interactive subshell with secrets exported
+    #[command(visible_alias = "sh")]
+    Shell {
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+    },
+
+    /// Print a shell hook that loads secrets when entering a project directory
+    Hook {
+        /// Shell to print the hook for (add `eval "$(tinysecrets hook zsh)"` to your rc file)
+        #[arg(value_enum)]
+        shell: hook::HookShell,
+    },
+
+    /// Print shell code to sync the environment with the current directory (used by `hook`)
+    #[command(hide = true)]
+    HookEnv {
+        #[arg(value_enum)]
+        shell: hook::HookShell,
+    },
+
+    /// Let the shell hook load secrets for this directory's .tinysecrets.toml
+    Allow {
+        /// Directory to allow (default: current directory)
+        path: Option<PathBuf>,
+    },
+
+    /// Stop the shell hook from loading secrets for this directory's .tinysecrets.toml
+    Deny {
+        /// Directory to deny (default: current directory)
+        path: Option<PathBuf>,
+    },
+
+    /// Set a Heroku app's config vars from an environment (previews the changes first)
+    PushHeroku {
+        /// Heroku app name
+        #[arg(short, long)]
+        app: String,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
         #[arg(short, long)]
+        environment: Option<String>,
+        /// Only push keys matching these globs (e.g. 'STRIPE_*')
+        #[arg(long, value_delimiter = ',', value_name = "GLOB")]
+        only: Vec<String>,
+        /// Show what would change without pushing
+        #[arg(long)]
+        dry_run: bool,
+        /// Push without asking for confirmation
+        #[arg(short = 'y', long)]
+        force: bool,
+    },
+
+    /// Create or update GitHub Actions secrets from an environment
+    PushGha {
+        /// GitHub repository (owner/name)
+        #[arg(long)]
+        repo: String,
+        /// Write to this GitHub deployment environment instead of repository secrets
+        #[arg(long, value_name = "NAME")]
+        gh_environment: Option<String>,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Store a key under a different GitHub secret name (repeatable)
+        #[arg(long, value_name = "KEY=NAME", value_parser = import_env::parse_mapping)]
+        map: Vec<(String, String)>,
+        /// Only push keys matching these globs (e.g. 'DEPLOY_*')
+        #[arg(long, value_delimiter = ',', value_name = "GLOB")]
+        only: Vec<String>,
+        /// Show what would be written without pushing
+        #[arg(long)]
+        dry_run: bool,
+        /// Push without asking for confirmation
+        #[arg(short = 'y', long)]
+        force: bool,
+    },
+
+    /// Serve a local, token-authenticated HTTP API for editors and SDKs
+    Serve {
+        /// Loopback address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+        /// Reject requests that change secrets
+        #[arg(long)]
+        read_only: bool,
+        /// Where to write the access token (default: serve.token next to the store)
+        #[arg(long, value_name = "PATH")]
+        token_file: Option<PathBuf>,
+    },
+
+    /// Export secrets to an encrypted bundle
+    Export {
+        /// Project name (uses .tinysecrets.toml if not specified). Without -e,
+        /// exports every environment in the project
+        #[arg(short, long, conflicts_with = "all")]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified); repeat for several
+        #[arg(short, long, conflicts_with = "all")]
+        environment: Vec<String>,
+        /// Export every project and environment in the store
+        #[arg(long)]
+        all: bool,
+        /// Output file (stdout if not specified)
+        #[arg(short, long, required_if_eq("format", "sops"))]
         output: Option<String>,
+        /// Bundle (encrypted with your passphrase), a SOPS file for sops users, or
+        /// plaintext .env, .tfvars or Terraform JSON
+        #[arg(long, value_enum, default_value_t = ExportFormat::Bundle)]
+        format: ExportFormat,
+        /// Only export keys matching these globs (e.g. 'STRIPE_*,SENDGRID_*')
+        #[arg(long, value_delimiter = ',', value_name = "GLOB")]
+        only: Vec<String>,
+        /// Leave out keys matching these globs (e.g. '*_TEST')
+        #[arg(long, value_delimiter = ',', value_name = "GLOB")]
+        exclude: Vec<String>,
+        /// Directory for --format systemd-creds (created 0700 if missing)
+        #[arg(long, value_name = "DIR", conflicts_with = "output")]
+        out_dir: Option<PathBuf>,
+        /// With a plaintext format, expand `${OTHER_KEY}` and `${env:NAME}` in values
+        #[arg(long)]
+        interpolate: bool,
+        /// Sign the bundle with this store's key, so recipients can check who
+        /// sent it (`security signing-key` shows the public key)
+        #[arg(long)]
+        sign: bool,
+        /// Export the values as they were at this time (2024-05-01, '2024-05-01 14:30',
+        /// RFC 3339, or an age like 30d), taken from history
+        #[arg(long, value_name = "WHEN", value_parser = history::parse_as_of)]
+        as_of: Option<chrono::DateTime<chrono::Utc>>,
+        /// Extra arguments for `sops --encrypt`, e.g. -- --age <recipient>
+        #[arg(last = true, value_name = "SOPS_ARGS")]
+        sops_args: Vec<String>,
     },
 
     /// Import secrets from an encrypted bundle
     Import {
-        /// Input file path
+        /// Input file path or https:// URL
         input: String,
+        /// Expected SHA-256 of the bundle (hex); verified before parsing
+        #[arg(long)]
+        sha256: Option<String>,
+        /// Require a signature by this key (ed25519:..., or a file holding it)
+        #[arg(long, value_name = "KEY")]
+        verify_signer: Option<String>,
+        /// What to do with keys that already exist (asks on a terminal if not set)
+        #[arg(long, value_enum)]
+        on_conflict: Option<OnConflict>,
+        /// Overwrite existing keys without asking (same as --on-conflict overwrite)
+        #[arg(short = 'y', long, conflicts_with = "on_conflict")]
+        force: bool,
+        /// Import into this project instead of the one in the bundle
+        #[arg(long)]
+        as_project: Option<String>,
+        /// Import into this environment instead of the one in the bundle
+        #[arg(long)]
+        as_environment: Option<String>,
+        /// Import a bundle from before integrity tags (v3 or older) that has none
+        #[arg(long)]
+        allow_untagged: bool,
     },
 
     /// Import environment variables from stdin or file
@@ -155,6 +789,72 @@ pub enum Commands {
         /// Read from file instead of stdin
         #[arg(short, long)]
         file: Option<String>,
+        /// Input format (auto: by file extension, then content)
+        #[arg(long, value_enum, default_value = "auto")]
+        format: import_env::InputFormat,
+        /// Where the values came from, recorded per secret (e.g. heroku, aws-ssm:/myapp).
+        /// Defaults to file:<path> or stdin
+        #[arg(long)]
+        source: Option<String>,
+        #[command(flatten)]
+        transform: import_env::KeyTransform,
+        /// What to do with keys that already exist (asks on a terminal if not set)
+        #[arg(long, value_enum)]
+        on_conflict: Option<OnConflict>,
+        /// Overwrite existing keys without asking (same as --on-conflict overwrite)
+        #[arg(short = 'y', long, conflicts_with = "on_conflict")]
+        force: bool,
+        /// Import everything or nothing: any unparseable line or value that can't be stored aborts
+        #[arg(long)]
+        atomic: bool,
+        /// Import keys whose names break the [key_policy] naming convention, or
+        /// whose {{ref:...}} references don't resolve
+        #[arg(long)]
+        no_verify: bool,
+    },
+
+    /// Import key/value pairs from a SOPS-encrypted file (decrypted with the sops CLI)
+    ImportSops {
+        /// SOPS file (YAML, JSON, dotenv or INI)
+        file: PathBuf,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        #[command(flatten)]
+        transform: import_env::KeyTransform,
+        /// What to do with keys that already exist (asks on a terminal if not set)
+        #[arg(long, value_enum)]
+        on_conflict: Option<OnConflict>,
+        /// Overwrite existing keys without asking (same as --on-conflict overwrite)
+        #[arg(short = 'y', long, conflicts_with = "on_conflict")]
+        force: bool,
+    },
+
+    /// Import a Vercel project's environment variables (pulled with the vercel CLI)
+    ImportVercel(import_platform::PlatformImport),
+
+    /// Import a Netlify site's environment variables (listed with the netlify CLI)
+    ImportNetlify(import_platform::PlatformImport),
+
+    /// Import a Railway service's variables (listed with the railway CLI)
+    ImportRailway(import_platform::PlatformImport),
+
+    /// Create an environment's secrets from a template, generating or prompting for each value
+    Scaffold {
+        /// Template file listing [[secret]] entries (key, description, required, generate, default)
+        template: PathBuf,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Never prompt: use generated values and defaults, and fail if a required key has neither
+        #[arg(long)]
+        no_input: bool,
     },
 
     /// Show secret history
@@ -173,16 +873,35 @@ pub enum Commands {
         /// Show the actual values
         #[arg(short, long)]
         show: bool,
+        /// Show what changed between consecutive versions (masked unless --show)
+        #[arg(long)]
+        diff: bool,
+        /// Only this version: prints its value with --show
+        #[arg(long, visible_alias = "rev", conflicts_with = "diff")]
+        version: Option<i32>,
     },
 
-    /// List all projects
-    Projects,
+    /// List all projects with their secret counts and last update
+    Projects {
+        /// Print a JSON array instead
+        #[arg(long)]
+        json: bool,
+    },
 
-    /// List environments for a project
+    /// List environments for a project with their secret counts and last update
     Envs {
         /// Project name (uses .tinysecrets.toml if not specified)
         #[arg(short, long)]
         project: Option<String>,
+        /// Print a JSON array instead
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Import from or export to third-party providers via tinysecrets-plugin-* executables
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
     },
 
     /// Manage system keychain integration
@@ -191,6 +910,18 @@ pub enum Commands {
         action: KeychainAction,
     },
 
+    /// Manage the read-only (viewer) passphrase, e.g. for CI
+    Viewer {
+        #[command(subcommand)]
+        action: ViewerAction,
+    },
+
+    /// Manage other ways to unlock the store (SSH keys through ssh-agent)
+    Security {
+        #[command(subcommand)]
+        action: SecurityAction,
+    },
+
     /// Manage local project configuration (.tinysecrets.toml)
     #[command(visible_alias = "c")]
     Config {
@@ -203,7 +934,271 @@ pub enum Commands {
     Examples,
 
     /// Migrate secrets from legacy format to fast encryption
-    Migrate,
+    Migrate {
+        /// Report what would be migrated without changing the store
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Check version compatibility of the store or an export bundle
+    Compat {
+        #[command(subcommand)]
+        action: CompatAction,
+    },
+
+    /// Manage the store file itself
+    Store {
+        #[command(subcommand)]
+        action: StoreAction,
+    },
+
+    /// Back up or restore the whole store (every project, all history)
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+
+    /// Inspect the store database, or switch it to or from full-file encryption
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+
+    /// Check the store, keychain, editor and locale, and suggest fixes
+    Doctor,
+
+    /// Check that every stored value (history included) decrypts
+    Verify {
+        /// Also check the history and audit log hash chains for rewritten,
+        /// reordered or removed rows, and print an anchor for the latest row
+        #[arg(long)]
+        chain: bool,
+        /// Check that history still matches an anchor printed by an earlier `--chain`
+        #[arg(long, value_name = "ID:HASH", requires = "chain")]
+        anchor: Option<String>,
+    },
+
+    /// Show secret and history counts per environment, store size, and the
+    /// least recently changed secrets (no passphrase needed)
+    Stats {
+        /// How many of the least recently changed secrets to list
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        oldest: usize,
+    },
+}
+
+/// Ordering for `list`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListSort {
+    /// Alphabetically by key
+    Key,
+    /// Most recently updated first
+    Updated,
+}
+
+/// What `export` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// A tinysecrets bundle, encrypted with your passphrase
+    Bundle,
+    /// A SOPS file (YAML unless the output name ends in .json, .env or .ini)
+    Sops,
+    /// Plaintext KEY=value lines
+    Dotenv,
+    /// Plaintext Terraform/OpenTofu `key = "value"` assignments (.tfvars)
+    Tfvars,
+    /// Plaintext `{"KEY": "value"}` for Terraform's `external` data source or .tfvars.json
+    TfJson,
+    /// One plaintext file per secret in --out-dir, for systemd's LoadCredential=
+    SystemdCreds,
+}
+
+/// How errors are printed (`--error-format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// A human-readable message
+    Text,
+    /// `{"code": "not_found", "status": 2, "message": "..."}`
+    Json,
+}
+
+/// How imports treat keys that already exist in the target environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnConflict {
+    /// Keep the existing value
+    Skip,
+    /// Replace the existing value (the old one stays in history)
+    Overwrite,
+    /// Abort the import without changing anything
+    Fail,
+}
+
+#[derive(Subcommand)]
+pub enum CompatAction {
+    /// Report which versions wrote the store (or a bundle) and whether this binary can read it
+    Check {
+        /// Export bundle to check (checks the store if omitted)
+        bundle: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum StoreAction {
+    /// Move the store to a new location and remember it in the global config
+    Move {
+        /// New path for the store database file
+        destination: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupAction {
+    /// Write the store to one archive encrypted with its passphrase
+    Export {
+        /// Archive to write, e.g. full-backup.tsb
+        #[arg(short, long, value_name = "FILE")]
+        file: PathBuf,
+        /// Overwrite FILE if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Restore the store from an archive made by `backup export`
+    Import {
+        /// Archive to restore
+        #[arg(short, long, value_name = "FILE")]
+        file: PathBuf,
+        /// Replace an existing store (it's kept as a .bak file)
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbAction {
+    /// Show schema and crypto versions, and any pending migrations
+    Status,
+    /// Encrypt the whole database file (SQLCipher), not just the values
+    Encrypt,
+    /// Turn a fully encrypted store back into a plain SQLite file
+    Decrypt,
+}
+
+#[derive(Subcommand)]
+pub enum PluginAction {
+    /// List plugins found on PATH
+    #[command(visible_alias = "ls")]
+    List,
+    /// Import secrets from a plugin (recorded with source plugin:<name>)
+    Pull {
+        /// Plugin name (runs tinysecrets-plugin-<name>)
+        plugin: String,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// What to do with keys that already exist (asks on a terminal if not set)
+        #[arg(long, value_enum)]
+        on_conflict: Option<OnConflict>,
+        /// Overwrite existing keys without asking (same as --on-conflict overwrite)
+        #[arg(short = 'y', long, conflicts_with = "on_conflict")]
+        force: bool,
+        /// Arguments passed through to the plugin
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Send an environment's secrets to a plugin
+    Push {
+        /// Plugin name (runs tinysecrets-plugin-<name>)
+        plugin: String,
+        /// Project name (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment (uses .tinysecrets.toml if not specified)
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Arguments passed through to the plugin
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// Show deleted secrets that can be restored with `undelete`
+    List {
+        /// Only this project
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Only this environment
+        #[arg(short, long)]
+        environment: Option<String>,
+    },
+    /// Permanently delete trashed secrets and their history
+    Empty {
+        /// Only those deleted longer ago than this (e.g. 30d, 2w)
+        #[arg(long, value_name = "AGE", value_parser = list::parse_age)]
+        older_than: Option<chrono::Duration>,
+        /// Only this project
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Only this environment
+        #[arg(short, long)]
+        environment: Option<String>,
+        /// Purge without asking for confirmation
+        #[arg(short = 'y', long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ViewerAction {
+    /// Add or replace the read-only passphrase (can run/get/export, not set/delete)
+    Set {
+        /// Read the new passphrase from stdin instead of prompting
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Remove the read-only passphrase
+    Remove {
+        /// Remove without asking for confirmation
+        #[arg(short = 'y', long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SecurityAction {
+    /// Let a key unlock the store, e.g. `add-unlock ssh ~/.ssh/id_ed25519.pub`
+    AddUnlock {
+        method: UnlockMethod,
+        /// Public key file (the key must be loaded in ssh-agent)
+        public_key: PathBuf,
+    },
+    /// Stop a key from unlocking the store
+    RemoveUnlock {
+        method: UnlockMethod,
+        /// Public key file or fingerprint (SHA256:...)
+        key: String,
+    },
+    /// List the keys that can unlock the store
+    Unlocks,
+    /// Store key names and descriptions encrypted, so a copy of the database
+    /// doesn't reveal what the secrets are
+    HideNames,
+    /// Store key names and descriptions in plain text again
+    ShowNames,
+    /// Print the public key `export --sign` signs bundles with, for recipients
+    /// to pass to `import --verify-signer`
+    SigningKey,
+}
+
+/// Ways to unlock the store besides the passphrase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UnlockMethod {
+    /// An Ed25519 or RSA key held by ssh-agent
+    Ssh,
 }
 
 #[derive(Subcommand)]
@@ -212,6 +1207,32 @@ pub enum KeychainAction {
     Status,
     /// Remove passphrase from keychain
     Clear,
+    /// Prompt for the passphrase, check it unlocks the store, and save it
+    Store {
+        /// Read the passphrase from stdin instead of prompting
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Make the saved passphrase expire after a while, e.g. 8h (`off` for never)
+    SetTtl {
+        /// Lifetime like 30m, 8h or 7d, or `off`
+        ttl: String,
+    },
+    /// Need Touch ID (or the login password) to read the saved passphrase (macOS)
+    RequirePresence {
+        /// `on` or `off`
+        #[arg(value_parser = ["on", "off"])]
+        setting: String,
+    },
+    /// Save the passphrase in the system keychain, or in an encrypted file for
+    /// headless machines without one
+    SetBackend {
+        #[arg(value_enum)]
+        backend: crate::keychain::Backend,
+        /// What encrypts the file (default: this machine's ID)
+        #[arg(long, value_enum)]
+        wrap: Option<crate::unlock_file::Wrap>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -223,8 +1244,15 @@ pub enum ConfigAction {
         /// Default environment (optional)
         environment: Option<String>,
     },
-    /// Show current configuration
-    Show,
+    /// Show the effective project, environment and store, and where each comes from
+    Show {
+        /// Show how this project name would resolve
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Show how this environment name would resolve
+        #[arg(short, long)]
+        environment: Option<String>,
+    },
     /// Update configuration values
     Set {
         /// Set default project
@@ -237,102 +1265,599 @@ pub enum ConfigAction {
 }
 
 /// Prompt for passphrase with confirmation for new stores
-/// In CI (env var set), uses that passphrase without prompting
-pub fn prompt_new_passphrase() -> anyhow::Result<secrecy::SecretString> {
+/// In CI (`passphrase_file` given or env var set), uses that passphrase without prompting,
+/// as it does a profile's `passphrase_command`. Either way the passphrase must meet the
+/// `[passphrase_policy]` in the global config.
+pub fn prompt_new_passphrase(
+    passphrase_file: Option<&std::path::Path>,
+    offer_keychain: bool,
+) -> anyhow::Result<secrecy::SecretString> {
+    use anyhow::Context;
     use colored::Colorize;
+    use secrecy::ExposeSecret;
+
+    let policy = crate::config::GlobalConfig::load()?.passphrase_policy;
+
+    if let Some(path) = passphrase_file {
+        crate::permissions::check_passphrase_file(path)?;
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read passphrase file {}", path.display()))?;
+        let pass = contents.trim_end_matches(['\n', '\r']).to_string();
+        if pass.is_empty() {
+            anyhow::bail!("Passphrase file {} is empty", path.display());
+        }
+        policy
+            .check(&pass)
+            .with_context(|| format!("{} doesn't meet the passphrase policy", path.display()))?;
+        eprintln!(
+            "{}Using passphrase from {} for new store",
+            crate::ui::icon("🔐 ", ""),
+            path.display().to_string().cyan()
+        );
+        return Ok(secrecy::SecretString::new(pass));
+    }
 
     // Check environment variable first (for CI/automation)
     if let Ok(pass) = std::env::var(PASSPHRASE_ENV_VAR) {
         if !pass.is_empty() {
-            if pass.len() < 8 {
-                anyhow::bail!("Passphrase must be at least 8 characters");
-            }
+            policy.check(&pass).with_context(|| {
+                format!("{} doesn't meet the passphrase policy", PASSPHRASE_ENV_VAR)
+            })?;
             eprintln!(
-                "🔐 Using passphrase from {} for new store",
+                "{}Using passphrase from {} for new store",
+                crate::ui::icon("🔐 ", ""),
                 PASSPHRASE_ENV_VAR.cyan()
             );
             return Ok(secrecy::SecretString::new(pass));
         }
     }
 
+    // Then the profile's passphrase command
+    if let Some((name, profile)) = crate::config::active_profile() {
+        if let Some(command) = &profile.passphrase_command {
+            let pass = passphrase_from_command(command)
+                .with_context(|| format!("passphrase_command for profile {} failed", name))?;
+            policy.check(pass.expose_secret()).with_context(|| {
+                format!(
+                    "Profile {}'s passphrase doesn't meet the passphrase policy",
+                    name
+                )
+            })?;
+            eprintln!(
+                "{}Using passphrase from profile {} for new store",
+                crate::ui::icon("🔐 ", ""),
+                name.cyan()
+            );
+            return Ok(pass);
+        }
+    }
+
     // Interactive mode
     eprintln!("{}", "Creating new secrets store...".cyan());
     eprintln!();
 
     let pass1 = rpassword::prompt_password("Enter passphrase: ")?;
+    let strength = crate::strength::estimate(&pass1);
+    eprintln!("  {}", strength_meter(&strength));
+    policy.check(&pass1)?;
     let pass2 = rpassword::prompt_password("Confirm passphrase: ")?;
 
     if pass1 != pass2 {
         anyhow::bail!("Passphrases do not match");
     }
 
-    if pass1.len() < 8 {
-        anyhow::bail!("Passphrase must be at least 8 characters");
-    }
-
     let passphrase = secrecy::SecretString::new(pass1);
 
     // Offer to save to keychain
-    eprintln!();
-    eprint!("Save passphrase to system keychain? [Y/n] ");
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    let input = input.trim().to_lowercase();
-
-    if input.is_empty() || input == "y" || input == "yes" {
-        match crate::keychain::store_passphrase(&passphrase) {
-            Ok(()) => eprintln!("{} Passphrase saved to keychain", "✓".green()),
-            Err(e) => eprintln!("{} Could not save to keychain: {}", "⚠".yellow(), e),
-        }
+    if offer_keychain {
+        eprintln!();
+        offer_keychain_save(&passphrase, "Save passphrase to system keychain?")?;
     }
 
     Ok(passphrase)
 }
 
+/// One-line strength meter, e.g. `Strength: ■■■□□ fair (about 42 bits)`
+fn strength_meter(strength: &crate::strength::Strength) -> String {
+    use colored::Colorize;
+
+    let score = strength.score() as usize;
+    let bar = if crate::ui::accessible() {
+        String::new()
+    } else {
+        format!("{}{} ", "■".repeat(score + 1), "□".repeat(4 - score))
+    };
+    let rating = format!("{}{}", bar, strength.label());
+    let rating = match score {
+        0 | 1 => rating.red(),
+        2 => rating.yellow(),
+        _ => rating.green(),
+    };
+    let mut meter = format!("Strength: {} (about {:.0} bits)", rating, strength.bits);
+    if let Some(weakness) = strength.weaknesses.first() {
+        meter.push_str(&format!(" - {}", weakness.advice()).dimmed().to_string());
+    }
+    meter
+}
+
 /// Environment variable name for passphrase (CI/automation)
 pub const PASSPHRASE_ENV_VAR: &str = "TINYSECRETS_PASSPHRASE";
 
-/// Prompt for existing passphrase
-/// Priority: 1) env var, 2) keychain, 3) interactive prompt
-pub fn prompt_passphrase() -> anyhow::Result<secrecy::SecretString> {
+/// Where an unlock passphrase came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassphraseSource {
+    EnvVar,
+    /// The active profile's `passphrase_command`
+    Command,
+    Keychain,
+    Prompt,
+}
+
+/// Find the passphrase without offering to save it
+/// Priority: 1) env var, 2) profile's passphrase_command, 3) keychain, 4) interactive prompt
+pub fn resolve_passphrase() -> anyhow::Result<(secrecy::SecretString, PassphraseSource)> {
+    use anyhow::Context;
     use colored::Colorize;
 
     // 1. Check environment variable first (for CI/automation)
     if let Ok(pass) = std::env::var(PASSPHRASE_ENV_VAR) {
         if !pass.is_empty() {
-            eprintln!("🔐 Using passphrase from {}", PASSPHRASE_ENV_VAR.cyan());
-            return Ok(secrecy::SecretString::new(pass));
+            eprintln!(
+                "{}Using passphrase from {}",
+                crate::ui::icon("🔐 ", ""),
+                PASSPHRASE_ENV_VAR.cyan()
+            );
+            return Ok((secrecy::SecretString::new(pass), PassphraseSource::EnvVar));
+        }
+    }
+
+    // 2. Ask the profile's passphrase command
+    if let Some((name, profile)) = crate::config::active_profile() {
+        if let Some(command) = &profile.passphrase_command {
+            let pass = passphrase_from_command(command)
+                .with_context(|| format!("passphrase_command for profile {} failed", name))?;
+            eprintln!(
+                "{}Using passphrase from profile {}",
+                crate::ui::icon("🔐 ", ""),
+                name.cyan()
+            );
+            return Ok((pass, PassphraseSource::Command));
         }
     }
 
-    // 2. Try keychain
+    // 3. Try keychain
     match crate::keychain::get_passphrase() {
         Ok(Some(passphrase)) => {
-            eprintln!("🔑 Using passphrase from keychain");
-            return Ok(passphrase);
+            eprintln!(
+                "{}Using passphrase from keychain",
+                crate::ui::icon("🔑 ", "")
+            );
+            return Ok((passphrase, PassphraseSource::Keychain));
         }
         Ok(None) => {} // No stored passphrase, prompt
         Err(e) => {
-            eprintln!("{} Keychain error: {}", "⚠".yellow(), e);
+            eprintln!("{} Keychain error: {}", crate::ui::warn(), e);
         }
     }
 
-    // 3. Interactive prompt
+    // 4. Interactive prompt
     let pass = rpassword::prompt_password("Passphrase: ")?;
-    let passphrase = secrecy::SecretString::new(pass);
+    Ok((secrecy::SecretString::new(pass), PassphraseSource::Prompt))
+}
+
+/// Run `command` through `sh -c` and take its first line of output as the
+/// passphrase (e.g. `pass show tinysecrets/work`)
+fn passphrase_from_command(command: &str) -> anyhow::Result<secrecy::SecretString> {
+    use anyhow::Context;
+
+    let output = std::process::Command::new("sh")
+        .args(["-c", command])
+        .stdin(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run `{}`", command))?;
+    let stdout = zeroize::Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        anyhow::bail!("`{}` exited with {}", command, output.status);
+    }
+    let stdout = std::str::from_utf8(&stdout).context("Passphrase isn't valid UTF-8")?;
+    let pass = stdout.lines().next().unwrap_or_default();
+    if pass.is_empty() {
+        anyhow::bail!("`{}` printed no passphrase", command);
+    }
+    Ok(secrecy::SecretString::new(pass.to_string()))
+}
 
-    // Offer to save for next time
-    eprint!("Save to keychain for next time? [Y/n] ");
+/// Ask a yes/no question (default yes) and save the passphrase to the keychain if accepted
+fn offer_keychain_save(passphrase: &secrecy::SecretString, question: &str) -> anyhow::Result<()> {
+    eprint!("{} [Y/n] ", question);
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     let input = input.trim().to_lowercase();
 
     if input.is_empty() || input == "y" || input == "yes" {
-        match crate::keychain::store_passphrase(&passphrase) {
-            Ok(()) => eprintln!("{} Passphrase saved to keychain", "✓".green()),
-            Err(e) => eprintln!("{} Could not save to keychain: {}", "⚠".yellow(), e),
+        match crate::keychain::store_passphrase(passphrase) {
+            Ok(_) => eprintln!("{} Passphrase saved to keychain", crate::ui::ok()),
+            Err(e) => eprintln!("{} Could not save to keychain: {}", crate::ui::warn(), e),
         }
     }
 
-    Ok(passphrase)
+    Ok(())
+}
+
+/// Ask a yes/no question on the controlling terminal (default no).
+/// Returns None when there's no terminal to ask on, e.g. in CI.
+fn confirm(question: &str) -> anyhow::Result<Option<bool>> {
+    use std::io::{BufRead, IsTerminal};
+
+    if !std::io::stderr().is_terminal() {
+        return Ok(None);
+    }
+    // Read from the tty rather than stdin, which may be the data being imported
+    let Ok(tty) = std::fs::File::open("/dev/tty") else {
+        return Ok(None);
+    };
+
+    eprint!("{} [y/N] ", question);
+    let mut input = String::new();
+    std::io::BufReader::new(tty).read_line(&mut input)?;
+    let input = input.trim().to_lowercase();
+    Ok(Some(input == "y" || input == "yes"))
+}
+
+/// Confirm a destructive action unless `force` is set. Exits if the user declines;
+/// fails without a terminal so scripts have to opt in with --force.
+pub fn confirm_destructive(question: &str, force: bool) -> anyhow::Result<()> {
+    if force {
+        return Ok(());
+    }
+    match confirm(question)? {
+        Some(true) => Ok(()),
+        Some(false) => {
+            eprintln!("{} Cancelled", crate::ui::note());
+            std::process::exit(1);
+        }
+        None => anyhow::bail!(
+            "Confirmation needed but there's no terminal to ask on. Pass --force (-y) to proceed."
+        ),
+    }
+}
+
+/// Decide whether an import overwrites `conflicts` (keys that already exist).
+/// Without an explicit policy, lists the keys and asks.
+pub fn resolve_conflicts(
+    conflicts: &[String],
+    on_conflict: Option<OnConflict>,
+    force: bool,
+) -> anyhow::Result<bool> {
+    use colored::Colorize;
+
+    if conflicts.is_empty() {
+        return Ok(true);
+    }
+
+    let list = || {
+        for key in conflicts {
+            std::eprintln!("  {} {}", crate::ui::bullet(), key.bold());
+        }
+    };
+
+    match on_conflict {
+        Some(OnConflict::Skip) => Ok(false),
+        Some(OnConflict::Overwrite) => Ok(true),
+        Some(OnConflict::Fail) => Err(crate::errors::conflict(format!(
+            "{} keys already exist: {}",
+            conflicts.len(),
+            conflicts.join(", ")
+        ))),
+        None => {
+            if !force {
+                // Context for the prompt, so it's shown even with -q
+                std::eprintln!(
+                    "{} {} keys already exist and would be overwritten:",
+                    crate::ui::warn(),
+                    conflicts.len()
+                );
+                list();
+            }
+            confirm_destructive("Overwrite them?", force).map_err(|e| {
+                e.context("Use --on-conflict skip|overwrite|fail to choose a policy")
+            })?;
+            Ok(true)
+        }
+    }
+}
+
+/// Unlock the store with a registered SSH key or the passphrase, warning about
+/// risky store locations
+pub fn open_store() -> anyhow::Result<crate::store::Store> {
+    use crate::store::Store;
+
+    let path = Store::path()?;
+    if path.exists() {
+        crate::permissions::check_store(&path)?;
+    }
+
+    // An explicit passphrase wins; otherwise a registered SSH key in the agent
+    let from_env = std::env::var(PASSPHRASE_ENV_VAR).is_ok_and(|p| !p.is_empty());
+    let ssh_unlock = if from_env {
+        None
+    } else {
+        Store::open_with_ssh_agent()?
+    };
+    let store = match ssh_unlock {
+        Some((store, key)) => {
+            eprintln!(
+                "{}Unlocked with SSH key {}",
+                crate::ui::icon("🔑 ", ""),
+                key.label()
+            );
+            store
+        }
+        None => open_with_passphrase()?,
+    };
+    if store.access() == crate::store::Access::ReadOnly {
+        eprintln!(
+            "{} Unlocked with the read-only passphrase",
+            crate::ui::info()
+        );
+    }
+    if let Some(backup) = store.migration_backup() {
+        eprintln!(
+            "{} Upgraded store schema to v{} (backup at {})",
+            crate::ui::note(),
+            crate::migrations::SCHEMA_VERSION,
+            backup.display()
+        );
+    }
+    warn_if_synced(&crate::store::Store::path()?)?;
+    warn_if_newer_writer(&store);
+    Ok(store)
+}
+
+/// Give a fully encrypted store its passphrase up front, for commands that
+/// read it without unlocking (`has`, `stats`, `db status`)
+pub fn provide_database_key() -> anyhow::Result<()> {
+    use crate::store::Store;
+
+    if Store::fully_encrypted()? {
+        let (passphrase, _) = resolve_passphrase()?;
+        Store::set_database_key(passphrase);
+    }
+    Ok(())
+}
+
+/// Find the passphrase (env var, keychain or prompt) and open the store with it.
+/// A wrong passphrase from the keychain or the prompt is asked for again, up to
+/// `[unlock] prompt_retries` times. A stale keychain entry (e.g. after a
+/// passphrase rotation) is then offered for update, or for removal if no
+/// retry worked.
+fn open_with_passphrase() -> anyhow::Result<crate::store::Store> {
+    use crate::store::{Store, StoreError};
+
+    let (passphrase, source) = resolve_passphrase()?;
+    let mut error = match Store::open(passphrase.clone()) {
+        Ok(store) => {
+            match source {
+                PassphraseSource::Prompt => {
+                    offer_keychain_save(&passphrase, "Save to keychain for next time?")?
+                }
+                // It unlocked this store, so a pre-per-store entry belongs to it
+                PassphraseSource::Keychain => {
+                    if let Err(e) = crate::keychain::adopt_legacy_entry() {
+                        eprintln!("{} Keychain error: {}", crate::ui::warn(), e);
+                    }
+                }
+                _ => {}
+            }
+            return Ok(store);
+        }
+        Err(e) => e,
+    };
+    let interactive = matches!(
+        source,
+        PassphraseSource::Keychain | PassphraseSource::Prompt
+    );
+    if !interactive || !matches!(error.downcast_ref(), Some(StoreError::InvalidPassphrase)) {
+        return Err(error);
+    }
+
+    let stale_keychain = source == PassphraseSource::Keychain;
+    let mut tries = crate::config::GlobalConfig::load()?.unlock.prompt_retries;
+    if stale_keychain {
+        eprintln!(
+            "{} Passphrase stored in keychain doesn't unlock this store",
+            crate::ui::warn()
+        );
+        // The keychain's guess doesn't use up one of the user's tries
+        tries += 1;
+    }
+    for attempt in 0..tries {
+        if attempt > 0 || !stale_keychain {
+            let left = tries - attempt;
+            std::eprintln!(
+                "{} Wrong passphrase ({} {} left)",
+                crate::ui::fail(),
+                left,
+                if left == 1 { "try" } else { "tries" }
+            );
+        }
+        let passphrase = secrecy::SecretString::new(rpassword::prompt_password("Passphrase: ")?);
+        match Store::open(passphrase.clone()) {
+            Ok(store) => {
+                let question = match stale_keychain {
+                    true => "Update keychain entry?",
+                    false => "Save to keychain for next time?",
+                };
+                offer_keychain_save(&passphrase, question)?;
+                return Ok(store);
+            }
+            Err(e) if matches!(e.downcast_ref(), Some(StoreError::InvalidPassphrase)) => error = e,
+            Err(e) => return Err(e),
+        }
+    }
+
+    if stale_keychain && confirm("Remove the passphrase stored in the keychain?")? == Some(true) {
+        match crate::keychain::delete_passphrase() {
+            Ok(_) => eprintln!("{} Passphrase removed from keychain", crate::ui::ok()),
+            Err(e) => eprintln!("{} Keychain error: {}", crate::ui::warn(), e),
+        }
+    }
+    Err(error)
+}
+
+/// Warn when the store was last written by a newer tinysecrets than this one
+fn warn_if_newer_writer(store: &crate::store::Store) {
+    use colored::Colorize;
+
+    if let Some(version) = store.written_by_version() {
+        if crate::compat::is_newer_than_cli(&version) {
+            eprintln!(
+                "{} Store was last written by tinysecrets {} (you have {}). Consider upgrading.",
+                crate::ui::warn(),
+                version.cyan(),
+                crate::compat::CLI_VERSION
+            );
+        }
+    }
+}
+
+/// Warn when the store lives inside a cloud-synced folder (unless silenced in config)
+pub fn warn_if_synced(path: &std::path::Path) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let Some(provider) = crate::store::synced_folder_provider(path) else {
+        return Ok(());
+    };
+    if !crate::config::GlobalConfig::load()?.warnings.synced_folder {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} Store is inside a {} folder: {}",
+        crate::ui::warn(),
+        provider,
+        path.display().to_string().cyan()
+    );
+    eprintln!("  Cloud sync can corrupt SQLite databases (partial uploads, conflicting copies).");
+    eprintln!(
+        "  Move it with: {}",
+        "tinysecrets store move ~/.tinysecrets/store.db".cyan()
+    );
+    eprintln!(
+        "  Silence this warning with {} under {} in {}",
+        "synced_folder = false".cyan(),
+        "[warnings]".cyan(),
+        crate::config::GlobalConfig::path()?.display()
+    );
+    Ok(())
+}
+
+/// Check a value against `[validate]` rules in .tinysecrets.toml, printing
+/// each problem. Returns false if a `deny` rule was broken.
+pub fn check_value_rules(
+    config: Option<&crate::config::Config>,
+    key: &str,
+    value: &[u8],
+) -> anyhow::Result<bool> {
+    use crate::lint::LintAction;
+
+    let Some(config) = config else {
+        return Ok(true);
+    };
+    let mut allowed = true;
+    for violation in crate::validate::check(&config.validate, key, value)? {
+        match violation.action {
+            LintAction::Off => {}
+            LintAction::Warn => eprintln!("{} {}", crate::ui::warn(), violation.message),
+            LintAction::Deny => {
+                std::eprintln!("{} {}", crate::ui::fail(), violation.message);
+                allowed = false;
+            }
+        }
+    }
+    Ok(allowed)
+}
+
+/// The `[key_policy]` naming convention that applies: the one in
+/// .tinysecrets.toml, else the global config's
+pub fn key_policy(
+    config: Option<&crate::config::Config>,
+) -> anyhow::Result<crate::lint::KeyPolicy> {
+    match config.and_then(|c| c.key_policy.clone()) {
+        Some(policy) => Ok(policy),
+        None => Ok(crate::config::GlobalConfig::load()?.key_policy),
+    }
+}
+
+/// Check new key names against the `[key_policy]` naming convention, printing
+/// each one that breaks it. Returns the keys a `deny` policy rejects.
+pub fn check_key_names<'a>(
+    config: Option<&crate::config::Config>,
+    keys: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<Vec<&'a str>> {
+    use crate::lint::LintAction;
+
+    let policy = key_policy(config)?;
+    let nonconforming = policy.nonconforming(keys)?;
+    let pattern = policy.pattern.as_deref().unwrap_or_default();
+    for key in &nonconforming {
+        match policy.on_mismatch {
+            LintAction::Off => {}
+            LintAction::Warn => eprintln!(
+                "{} {} doesn't match the key naming policy /{}/",
+                crate::ui::warn(),
+                key,
+                pattern
+            ),
+            LintAction::Deny => std::eprintln!(
+                "{} {} doesn't match the key naming policy /{}/",
+                crate::ui::fail(),
+                key,
+                pattern
+            ),
+        }
+    }
+    Ok(match policy.on_mismatch {
+        LintAction::Deny => nonconforming,
+        _ => Vec::new(),
+    })
+}
+
+/// Lint keys about to be injected/exported against the `[env_policy]` config.
+/// Warns or fails depending on the policy for each kind of finding.
+pub fn check_env_policy<'a>(keys: impl IntoIterator<Item = &'a str>) -> anyhow::Result<()> {
+    use crate::lint::{lint_keys, Finding, LintAction};
+
+    let policy = crate::config::GlobalConfig::load()?.env_policy;
+    let mut denied = false;
+
+    for finding in lint_keys(keys, &policy.allow) {
+        let (action, message) = match &finding {
+            Finding::Collision(group) => (
+                policy.on_collision,
+                format!("Keys differ only by case: {}", group.join(", ")),
+            ),
+            Finding::Dangerous(key) => (
+                policy.on_dangerous,
+                format!("{} overrides a variable that changes how programs run", key),
+            ),
+        };
+        match action {
+            LintAction::Off => {}
+            LintAction::Warn => eprintln!("{} {}", crate::ui::warn(), message),
+            LintAction::Deny => {
+                std::eprintln!("{} {}", crate::ui::fail(), message);
+                denied = true;
+            }
+        }
+    }
+
+    if denied {
+        anyhow::bail!(
+            "Refusing to continue due to [env_policy] in {}",
+            crate::config::GlobalConfig::path()?.display()
+        );
+    }
+    Ok(())
 }
@@ -0,0 +1,413 @@
+//! `tinysecrets pick`: fuzzy-find a key, then print, copy or edit its value
+//!
+//! The picker draws on /dev/tty rather than stdout, so `tinysecrets pick | jq`
+//! gets just the value.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs::{File, OpenOptions};
+use std::io::{IsTerminal, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::{Command, Stdio};
+
+use crate::cli::{edit, open_store};
+use crate::config::{Config, GlobalConfig};
+use crate::git;
+use crate::pattern::fuzzy_match;
+use crate::refs::{self, SecretPath};
+use crate::store::{SecretEntry, Store};
+use crate::ui;
+
+/// What to do with the picked secret
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickAction {
+    Print,
+    Copy,
+    Edit,
+}
+
+/// Most matches listed under the prompt at once
+const MAX_ROWS: usize = 10;
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    query: Option<&str>,
+    action: PickAction,
+    config: Option<&Config>,
+) -> Result<()> {
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .context("pick needs a terminal to show the picker")?;
+
+    let mut store = open_store()?;
+    let entries = store.resolved_entries(project, environment)?;
+    if entries.is_empty() {
+        anyhow::bail!("No secrets in {}/{}", project, environment);
+    }
+
+    let picked = Picker::new(&entries, query.unwrap_or_default()).run(&mut tty)?;
+    let Some(entry) = picked else {
+        // Like fzf: a cancelled pick exits 130 so scripts can tell
+        std::process::exit(130);
+    };
+    let key = entry.key.as_str();
+    let value = store
+        .get(project, environment, key)?
+        .with_context(|| format!("Secret not found: {}/{}/{}", project, environment, key))?;
+
+    match action {
+        PickAction::Print => print_value(&store, project, environment, key, &value),
+        PickAction::Copy => {
+            let value = resolved(&store, project, environment, key, &value)?;
+            copy_to_clipboard(&value)?;
+            eprintln!("{} Copied {} to the clipboard", ui::ok(), key.bold());
+            Ok(())
+        }
+        PickAction::Edit => {
+            if GlobalConfig::load()?.history.record_git {
+                store.set_git_context(git::context());
+            }
+            edit::edit_value(&store, project, environment, key, &value, config)
+        }
+    }
+}
+
+/// The value with any `{{ref:...}}` references resolved
+fn resolved(
+    store: &Store,
+    project: &str,
+    environment: &str,
+    key: &str,
+    value: &[u8],
+) -> Result<zeroize::Zeroizing<Vec<u8>>> {
+    match std::str::from_utf8(value) {
+        Ok(text) if refs::contains_refs(text) => {
+            let from = SecretPath::new(project, environment, key);
+            Ok(zeroize::Zeroizing::new(
+                store.resolve_refs(text, &from)?.into_bytes(),
+            ))
+        }
+        _ => Ok(zeroize::Zeroizing::new(value.to_vec())),
+    }
+}
+
+fn print_value(
+    store: &Store,
+    project: &str,
+    environment: &str,
+    key: &str,
+    value: &[u8],
+) -> Result<()> {
+    let value = resolved(store, project, environment, key, value)?;
+    let is_text = std::str::from_utf8(&value).is_ok();
+    if !is_text && std::io::stdout().is_terminal() {
+        anyhow::bail!(
+            "{} holds binary data. Use `get {} --to-file` or redirect stdout.",
+            key,
+            key
+        );
+    }
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&value)?;
+    if is_text {
+        stdout.write_all(b"\n")?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Hand the value to the platform's clipboard tool
+fn copy_to_clipboard(value: &[u8]) -> Result<()> {
+    const TOOLS: &[&[&str]] = &[
+        &["pbcopy"],
+        &["wl-copy"],
+        &["xclip", "-selection", "clipboard"],
+        &["xsel", "--clipboard", "--input"],
+    ];
+    for tool in TOOLS {
+        let Ok(mut child) = Command::new(tool[0])
+            .args(&tool[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(value)?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    anyhow::bail!("No clipboard tool found (pbcopy, wl-copy, xclip or xsel)")
+}
+
+/// A key press, as far as the picker cares
+#[derive(Debug, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Backspace,
+    ClearQuery,
+    Up,
+    Down,
+    Enter,
+    Cancel,
+    Other,
+}
+
+struct Picker<'a> {
+    entries: &'a [SecretEntry],
+    query: String,
+    /// Matching entries, best first, with the positions that matched
+    matches: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+}
+
+impl<'a> Picker<'a> {
+    fn new(entries: &'a [SecretEntry], query: &str) -> Self {
+        let mut picker = Self {
+            entries,
+            query: query.to_string(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        picker.filter();
+        picker
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(i64, usize, Vec<usize>)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| {
+                fuzzy_match(&self.query, &entry.key).map(|(score, positions)| (score, i, positions))
+            })
+            .collect();
+        // Best score first; entries are already sorted by key for ties
+        scored.sort_by_key(|(score, i, _)| (std::cmp::Reverse(*score), *i));
+        self.matches = scored.into_iter().map(|(_, i, p)| (i, p)).collect();
+        self.selected = 0;
+    }
+
+    /// Let the user pick; None if they cancelled
+    fn run(mut self, tty: &mut File) -> Result<Option<&'a SecretEntry>> {
+        let raw = RawMode::enable(tty.as_raw_fd())?;
+        let rows = MAX_ROWS.min(raw.height().saturating_sub(2)).max(1);
+        let result = loop {
+            self.draw(tty, rows, raw.width())?;
+            match read_key(tty)? {
+                Key::Char(c) => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                Key::Backspace => {
+                    self.query.pop();
+                    self.filter();
+                }
+                Key::ClearQuery => {
+                    self.query.clear();
+                    self.filter();
+                }
+                Key::Up => self.selected = self.selected.saturating_sub(1),
+                Key::Down => {
+                    if self.selected + 1 < self.matches.len() {
+                        self.selected += 1;
+                    }
+                }
+                Key::Enter => {
+                    if let Some((i, _)) = self.matches.get(self.selected) {
+                        break Some(&self.entries[*i]);
+                    }
+                }
+                Key::Cancel => break None,
+                Key::Other => {}
+            }
+        };
+        // Leave the terminal as we found it
+        write!(tty, "\r\x1b[J")?;
+        tty.flush()?;
+        Ok(result)
+    }
+
+    /// The prompt, then up to `rows` matches and a count, with the cursor
+    /// left after the query
+    fn draw(&self, tty: &mut File, rows: usize, width: usize) -> Result<()> {
+        let mut out = String::from("\r\x1b[J");
+        out.push_str(&format!("{} {}", ">".cyan().bold(), self.query));
+
+        let first = self.selected.saturating_sub(rows - 1);
+        for (row, (i, positions)) in self.matches.iter().enumerate().skip(first).take(rows) {
+            let entry = &self.entries[*i];
+            let marker = if row == self.selected {
+                "›".cyan().bold().to_string()
+            } else {
+                " ".to_string()
+            };
+            out.push_str(&format!("\n{} ", marker));
+
+            // Key with the matched characters highlighted, then the description
+            let mut room = width.saturating_sub(3);
+            for (n, c) in entry.key.chars().take(room).enumerate() {
+                if positions.contains(&n) {
+                    out.push_str(&c.to_string().yellow().bold().to_string());
+                } else if row == self.selected {
+                    out.push_str(&c.to_string().bold().to_string());
+                } else {
+                    out.push(c);
+                }
+            }
+            room = room.saturating_sub(entry.key.chars().count() + 2);
+            if let Some(description) = entry.description.as_deref().filter(|_| room > 3) {
+                let description: String = description
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .chars()
+                    .take(room)
+                    .collect();
+                out.push_str(&format!("  {}", description.dimmed()));
+            }
+        }
+        let shown = self.matches.len().saturating_sub(first).min(rows);
+        out.push_str(&format!(
+            "\n  {}",
+            format!("{}/{}", self.matches.len(), self.entries.len()).dimmed()
+        ));
+
+        // Back up to the prompt line, just after the query
+        out.push_str(&format!("\x1b[{}A\r", shown + 1));
+        out.push_str(&format!("\x1b[{}C", 2 + self.query.chars().count()));
+        tty.write_all(out.as_bytes())?;
+        tty.flush()?;
+        Ok(())
+    }
+}
+
+/// Read one key press from a terminal in raw mode
+fn read_key(tty: &mut File) -> Result<Key> {
+    let byte = read_byte(tty)?;
+    Ok(match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x03 | 0x07 => Key::Cancel,                 // Ctrl-C, Ctrl-G
+        0x7f | 0x08 => Key::Backspace,              // Backspace, Ctrl-H
+        0x15 => Key::ClearQuery,                    // Ctrl-U
+        0x10 => Key::Up,                            // Ctrl-P
+        0x0e | b'\t' => Key::Down,                  // Ctrl-N, Tab
+        0x1b if !byte_waiting(tty)? => Key::Cancel, // a lone Esc
+        0x1b => match read_byte(tty)? {
+            b'[' | b'O' => {
+                // Arrow keys are ESC [ A/B; skip the parameters of anything else
+                let mut last = read_byte(tty)?;
+                while !(0x40..=0x7e).contains(&last) {
+                    last = read_byte(tty)?;
+                }
+                match last {
+                    b'A' => Key::Up,
+                    b'B' => Key::Down,
+                    _ => Key::Other,
+                }
+            }
+            _ => Key::Other,
+        },
+        b if b < 0x20 => Key::Other,
+        b => {
+            // Collect the rest of a UTF-8 sequence
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                bytes.push(read_byte(tty)?);
+            }
+            match std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        }
+    })
+}
+
+fn read_byte(tty: &mut File) -> Result<u8> {
+    let mut byte = [0u8];
+    tty.read_exact(&mut byte)
+        .context("Failed to read from the terminal")?;
+    Ok(byte[0])
+}
+
+/// Whether more input arrives within a moment (telling Esc from an escape sequence)
+fn byte_waiting(tty: &File) -> Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: tty.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: one valid pollfd
+    let ready = unsafe { libc::poll(&mut fd, 1, 30) };
+    Ok(ready > 0)
+}
+
+/// The terminal in raw mode (no echo, no line buffering, no signals from
+/// Ctrl-C), restored on drop
+struct RawMode {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enable(fd: RawFd) -> Result<Self> {
+        // SAFETY: termios is plain data, filled in by tcgetattr
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("Failed to read terminal settings");
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: fd is an open terminal and raw a valid termios
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to set terminal mode");
+        }
+        Ok(Self { fd, saved })
+    }
+
+    fn size(&self) -> (usize, usize) {
+        // SAFETY: winsize is plain data, filled in by the ioctl
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(self.fd, libc::TIOCGWINSZ, &mut size) } != 0 || size.ws_col == 0 {
+            return (80, 24);
+        }
+        (size.ws_col as usize, size.ws_row as usize)
+    }
+
+    fn width(&self) -> usize {
+        self.size().0
+    }
+
+    fn height(&self) -> usize {
+        self.size().1
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `enable`
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::{open_store, resolve_conflicts, OnConflict, PluginAction};
+use crate::config::ConfigResolver;
+use crate::plugins::{self, ExternalPlugin, PluginSecret, SecretSink, SecretSource};
+use crate::ui;
+
+pub fn run(action: PluginAction) -> Result<()> {
+    match action {
+        PluginAction::List => list(),
+        PluginAction::Pull {
+            plugin,
+            project,
+            environment,
+            on_conflict,
+            force,
+            args,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            pull(&plugin, &project, &environment, &args, on_conflict, force)
+        }
+        PluginAction::Push {
+            plugin,
+            project,
+            environment,
+            args,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            push(&plugin, &project, &environment, &args)
+        }
+    }
+}
+
+fn list() -> Result<()> {
+    let found = plugins::discover();
+    if found.is_empty() {
+        eprintln!(
+            "{} No plugins found (executables named {}<name> on PATH)",
+            ui::note(),
+            plugins::PLUGIN_PREFIX.cyan()
+        );
+        return Ok(());
+    }
+
+    for plugin in found {
+        match plugin.describe() {
+            Ok(info) => {
+                let capabilities = if info.capabilities.is_empty() {
+                    "-".to_string()
+                } else {
+                    info.capabilities.join(", ")
+                };
+                println!(
+                    "{} {} {}",
+                    plugin.name.bold(),
+                    format!("[{}]", capabilities).dimmed(),
+                    info.description.unwrap_or_default()
+                );
+            }
+            Err(e) => println!("{} {} {:#}", plugin.name.bold(), ui::warn(), e),
+        }
+        println!(
+            "  {} {}",
+            ui::branch(),
+            plugin.path.display().to_string().dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn pull(
+    name: &str,
+    project: &str,
+    environment: &str,
+    args: &[String],
+    on_conflict: Option<OnConflict>,
+    force: bool,
+) -> Result<()> {
+    let plugin = ExternalPlugin::find(name)?;
+    let store = open_store()?;
+
+    eprintln!(
+        "{} Pulling {}/{} from {}...",
+        ui::arrow(),
+        project.cyan(),
+        environment.yellow(),
+        name.bold()
+    );
+    let secrets = plugin.pull(project, environment, args)?;
+
+    let keys: Vec<&str> = secrets.iter().map(|s| s.key.as_str()).collect();
+    let conflicts = store.existing_keys(project, environment, &keys)?;
+    let overwrite = resolve_conflicts(&conflicts, on_conflict, force)?;
+
+    let source = format!("plugin:{}", name);
+    let mut imported = 0;
+    let mut unchanged = 0;
+    for secret in &secrets {
+        if !overwrite && conflicts.contains(&secret.key) {
+            eprintln!("  {} {} (exists, skipped)", ui::note(), secret.key.bold());
+            continue;
+        }
+        let written = store.set_bytes(
+            project,
+            environment,
+            &secret.key,
+            secret.value.as_bytes(),
+            secret.description.as_deref(),
+            Some(&source),
+        )?;
+        if written {
+            eprintln!("  {} {}", ui::ok(), secret.key.bold());
+            imported += 1;
+        } else {
+            eprintln!("  {} {} (unchanged)", ui::note(), secret.key.bold());
+            unchanged += 1;
+        }
+    }
+
+    eprintln!();
+    eprintln!(
+        "{} Imported {} secrets into {}/{}",
+        ui::ok(),
+        imported.to_string().bold(),
+        project.cyan(),
+        environment.yellow()
+    );
+    if unchanged > 0 {
+        eprintln!(
+            "{} {} unchanged (same value already stored)",
+            ui::note(),
+            unchanged
+        );
+    }
+    Ok(())
+}
+
+fn push(name: &str, project: &str, environment: &str, args: &[String]) -> Result<()> {
+    let plugin = ExternalPlugin::find(name)?;
+    let store = open_store()?;
+
+    let entries = store.resolved_entries(project, environment)?;
+    let values = store.get_all(project, environment)?;
+    let secrets: Vec<PluginSecret> = values
+        .iter()
+        .map(|(key, value)| {
+            let description = entries
+                .iter()
+                .find(|e| &e.key == key)
+                .and_then(|e| e.description.clone());
+            PluginSecret {
+                key: key.clone(),
+                value: value.clone(),
+                description,
+            }
+        })
+        .collect();
+
+    if secrets.is_empty() {
+        eprintln!(
+            "{} No secrets in {}/{}",
+            ui::note(),
+            project.cyan(),
+            environment.yellow()
+        );
+        return Ok(());
+    }
+
+    plugin.push(project, environment, args, &secrets)?;
+    eprintln!(
+        "{} Pushed {} secrets from {}/{} to {}",
+        ui::ok(),
+        secrets.len().to_string().bold(),
+        project.cyan(),
+        environment.yellow(),
+        name.bold()
+    );
+    Ok(())
+}
@@ -1,24 +1,46 @@
 use anyhow::Result;
+use chrono::Utc;
 use colored::Colorize;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::list::relative_time;
+use crate::cli::open_store;
+use crate::ui;
 
-pub fn run() -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+pub fn run(json: bool) -> Result<()> {
+    let store = open_store()?;
 
-    let projects = store.list_projects()?;
+    let projects = store.project_summaries()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&projects)?);
+        return Ok(());
+    }
 
     if projects.is_empty() {
-        eprintln!("{} No projects found", "○".yellow());
+        eprintln!("{} No projects found", ui::note());
         eprintln!("  Create your first secret with: ts set <project> <env> <key>");
         return Ok(());
     }
 
+    let now = Utc::now();
+    let width = projects
+        .iter()
+        .map(|p| p.name.chars().count())
+        .max()
+        .unwrap_or(0);
     println!("{}", "Projects:".bold());
     for project in projects {
-        println!("  📦 {}", project.cyan());
+        let updated = project
+            .last_updated
+            .map(|t| format!(", updated {}", relative_time(t, now)))
+            .unwrap_or_default();
+        println!(
+            "  {}{:<width$}  {}",
+            ui::icon("📦 ", "- "),
+            project.name.cyan(),
+            format!("{} secret(s){}", project.secrets, updated).dimmed(),
+            width = width
+        );
     }
 
     Ok(())
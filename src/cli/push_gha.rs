@@ -0,0 +1,258 @@
+//! Push an environment's secrets to GitHub Actions repository or environment secrets
+//!
+//! Values are encrypted to the repository's public key as libsodium sealed boxes
+//! (`crypto::seal`) before they leave the machine, as the GitHub API requires.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use colored::Colorize;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+use crate::cli::{confirm_destructive, open_store};
+use crate::crypto;
+use crate::pattern::glob_match;
+use crate::ui;
+
+const GITHUB_API: &str = "https://api.github.com";
+const TOKEN_ENV_VARS: &[&str] = &["GITHUB_TOKEN", "GH_TOKEN"];
+const PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+struct PublicKey {
+    key_id: String,
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct SecretList {
+    secrets: Vec<SecretName>,
+}
+
+#[derive(Deserialize)]
+struct SecretName {
+    name: String,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    project: &str,
+    environment: &str,
+    repo: &str,
+    gh_environment: Option<&str>,
+    map: &[(String, String)],
+    only: &[String],
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    if repo.split('/').count() != 2 || repo.split('/').any(str::is_empty) {
+        anyhow::bail!("Expected --repo as owner/name, got '{}'", repo);
+    }
+
+    let store = open_store()?;
+    let secrets: Zeroizing<Vec<(String, String)>> = Zeroizing::new(
+        store
+            .get_all(project, environment)?
+            .iter()
+            .filter(|(key, _)| only.is_empty() || only.iter().any(|g| glob_match(g, key)))
+            .map(|(key, value)| (secret_name(key, map), value.clone()))
+            .collect(),
+    );
+    drop(store);
+
+    if secrets.is_empty() {
+        eprintln!(
+            "{} No secrets to push from {}/{}",
+            ui::note(),
+            project.cyan(),
+            environment.yellow()
+        );
+        return Ok(());
+    }
+    let invalid: Vec<&str> = secrets
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| !is_valid_name(name))
+        .collect();
+    if !invalid.is_empty() {
+        anyhow::bail!(
+            "Not valid GitHub secret names: {} (use letters, digits and _, not starting with GITHUB_ or a digit; rename with --map KEY=NAME)",
+            invalid.join(", ")
+        );
+    }
+
+    let token = github_token()?;
+    let secrets_url = match gh_environment {
+        Some(env) => format!("{}/repos/{}/environments/{}/secrets", GITHUB_API, repo, env),
+        None => format!("{}/repos/{}/actions/secrets", GITHUB_API, repo),
+    };
+    let existing = existing_names(&secrets_url, &token)?;
+
+    let target = match gh_environment {
+        Some(env) => format!("{} (environment {})", repo, env),
+        None => repo.to_string(),
+    };
+    eprintln!(
+        "{} {}/{} {} GitHub Actions secrets for {}",
+        ui::arrow(),
+        project.cyan(),
+        environment.yellow(),
+        ui::arrow(),
+        target.bold()
+    );
+    for (name, _) in secrets.iter() {
+        if existing.contains(name) {
+            eprintln!("  {} {}", "~".yellow(), name.bold());
+        } else {
+            eprintln!("  {} {}", "+".green(), name.bold());
+        }
+    }
+    let remote_only = existing
+        .iter()
+        .filter(|name| !secrets.iter().any(|(n, _)| n == *name))
+        .count();
+    if remote_only > 0 {
+        eprintln!(
+            "  {} {} secrets only on GitHub (left alone)",
+            ui::note(),
+            remote_only
+        );
+    }
+    eprintln!(
+        "  {} GitHub doesn't reveal secret values, so existing ones (~) are always rewritten",
+        ui::note()
+    );
+
+    if dry_run {
+        eprintln!("{} Dry run - nothing pushed", ui::note());
+        return Ok(());
+    }
+    confirm_destructive(
+        &format!("Write {} secrets to {}?", secrets.len(), target),
+        force,
+    )?;
+
+    let public_key: PublicKey = serde_json::from_slice(&github_request(
+        "GET",
+        &format!("{}/public-key", secrets_url),
+        &token,
+        None,
+    )?)
+    .context("Unexpected public key response from GitHub")?;
+    let key: [u8; 32] = BASE64
+        .decode(&public_key.key)
+        .ok()
+        .and_then(|k| k.try_into().ok())
+        .context("GitHub returned an invalid public key")?;
+
+    for (name, value) in secrets.iter() {
+        let sealed = crypto::seal(&key, value.as_bytes())?;
+        let body = serde_json::json!({
+            "encrypted_value": BASE64.encode(sealed),
+            "key_id": public_key.key_id,
+        });
+        github_request(
+            "PUT",
+            &format!("{}/{}", secrets_url, name),
+            &token,
+            Some(&body.to_string()),
+        )
+        .with_context(|| format!("Failed to write {}", name))?;
+        eprintln!("  {} {}", ui::ok(), name.bold());
+    }
+
+    eprintln!(
+        "{} Wrote {} secrets to {}",
+        ui::ok(),
+        secrets.len().to_string().bold(),
+        target.bold()
+    );
+    Ok(())
+}
+
+/// GitHub secret name for a key: `--map` if given, else the key (GitHub stores names uppercase)
+fn secret_name(key: &str, map: &[(String, String)]) -> String {
+    map.iter()
+        .find(|(from, _)| from == key)
+        .map(|(_, to)| to.as_str())
+        .unwrap_or(key)
+        .to_uppercase()
+}
+
+/// GitHub's rules: alphanumerics and underscores, no leading digit, no GITHUB_ prefix
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("GITHUB_")
+}
+
+/// Names of the secrets already set (uppercase, as GitHub reports them)
+fn existing_names(secrets_url: &str, token: &str) -> Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    for page in 1.. {
+        let url = format!("{}?per_page={}&page={}", secrets_url, PAGE_SIZE, page);
+        let list: SecretList = serde_json::from_slice(&github_request("GET", &url, token, None)?)
+            .context("Unexpected secrets list response from GitHub")?;
+        let count = list.secrets.len();
+        names.extend(list.secrets.into_iter().map(|s| s.name));
+        if count < PAGE_SIZE {
+            break;
+        }
+    }
+    Ok(names)
+}
+
+/// API token from GITHUB_TOKEN/GH_TOKEN, or the logged-in GitHub CLI
+fn github_token() -> Result<String> {
+    for var in TOKEN_ENV_VARS {
+        if let Ok(token) = std::env::var(var) {
+            if !token.is_empty() {
+                return Ok(token);
+            }
+        }
+    }
+
+    let output = Command::new("gh")
+        .args(["auth", "token"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .context("No GitHub credentials: set GITHUB_TOKEN or log in with `gh auth login`")?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Call the GitHub REST API and return the response body
+fn github_request(method: &str, url: &str, token: &str, body: Option<&str>) -> Result<Vec<u8>> {
+    let headers = [
+        "Accept: application/vnd.github+json".to_string(),
+        "X-GitHub-Api-Version: 2022-11-28".to_string(),
+        format!("Authorization: Bearer {}", token),
+    ];
+    crate::api::request(method, url, &headers, body).context("GitHub API request failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_name() {
+        let map = vec![("stripe_key".to_string(), "STRIPE_SECRET_KEY".to_string())];
+        assert_eq!(secret_name("stripe_key", &map), "STRIPE_SECRET_KEY");
+        assert_eq!(secret_name("database_url", &map), "DATABASE_URL");
+    }
+
+    #[test]
+    fn test_valid_names() {
+        assert!(is_valid_name("DATABASE_URL"));
+        assert!(is_valid_name("_PRIVATE"));
+        assert!(!is_valid_name("GITHUB_TOKEN"));
+        assert!(!is_valid_name("1PASSWORD"));
+        assert!(!is_valid_name("MY-KEY"));
+        assert!(!is_valid_name(""));
+    }
+}
@@ -0,0 +1,190 @@
+//! Push an environment's secrets to a Heroku app's config vars
+//!
+//! Talks to the Heroku Platform API through curl (see `crate::api`).
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+use crate::cli::{confirm_destructive, open_store};
+use crate::pattern::glob_match;
+use crate::ui;
+
+const HEROKU_API: &str = "https://api.heroku.com";
+const TOKEN_ENV_VAR: &str = "HEROKU_API_KEY";
+
+/// How a config var would change on Heroku
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Changed,
+}
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    app: &str,
+    only: &[String],
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let store = open_store()?;
+    let mut local = store.get_all(project, environment)?;
+    local.retain(|(key, _)| only.is_empty() || only.iter().any(|g| glob_match(g, key)));
+    drop(store);
+
+    if local.is_empty() {
+        eprintln!(
+            "{} No secrets to push from {}/{}",
+            ui::note(),
+            project.cyan(),
+            environment.yellow()
+        );
+        return Ok(());
+    }
+
+    let token = heroku_token()?;
+    let url = format!("{}/apps/{}/config-vars", HEROKU_API, app);
+    let remote: BTreeMap<String, Option<String>> =
+        serde_json::from_slice(&heroku_request("GET", &url, &token, None)?)
+            .context("Unexpected response from the Heroku API")?;
+    let remote: BTreeMap<String, String> = remote
+        .into_iter()
+        .map(|(key, value)| (key, value.unwrap_or_default()))
+        .collect();
+
+    let changes = diff(&local, &remote);
+    let unchanged = local.len() - changes.len();
+    let remote_only = remote
+        .keys()
+        .filter(|k| !local.iter().any(|(key, _)| key == *k))
+        .count();
+
+    eprintln!(
+        "{} {}/{} {} Heroku app {}",
+        ui::arrow(),
+        project.cyan(),
+        environment.yellow(),
+        ui::arrow(),
+        app.bold()
+    );
+    for (key, change) in &changes {
+        match change {
+            Change::Added => eprintln!("  {} {}", "+".green(), key.bold()),
+            Change::Changed => eprintln!("  {} {}", "~".yellow(), key.bold()),
+        }
+    }
+    if unchanged > 0 {
+        eprintln!("  {} {} unchanged", ui::note(), unchanged);
+    }
+    if remote_only > 0 {
+        eprintln!(
+            "  {} {} config vars only on Heroku (left alone)",
+            ui::note(),
+            remote_only
+        );
+    }
+
+    if changes.is_empty() {
+        eprintln!("{} Heroku is up to date", ui::ok());
+        return Ok(());
+    }
+    if dry_run {
+        eprintln!("{} Dry run - nothing pushed", ui::note());
+        return Ok(());
+    }
+
+    confirm_destructive(
+        &format!("Update {} config vars on {}?", changes.len(), app),
+        force,
+    )?;
+
+    let patch: BTreeMap<&str, &str> = local
+        .iter()
+        .filter(|(key, _)| changes.iter().any(|(changed, _)| changed == key))
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let body = Zeroizing::new(serde_json::to_string(&patch)?);
+    heroku_request("PATCH", &url, &token, Some(&body))?;
+
+    eprintln!(
+        "{} Updated {} config vars on {} (the app restarts to pick them up)",
+        ui::ok(),
+        changes.len().to_string().bold(),
+        app.bold()
+    );
+    Ok(())
+}
+
+/// Keys whose value on Heroku differs from (or is missing compared to) the local one
+fn diff(local: &[(String, String)], remote: &BTreeMap<String, String>) -> Vec<(String, Change)> {
+    local
+        .iter()
+        .filter_map(|(key, value)| match remote.get(key) {
+            None => Some((key.clone(), Change::Added)),
+            Some(existing) if existing != value => Some((key.clone(), Change::Changed)),
+            Some(_) => None,
+        })
+        .collect()
+}
+
+/// API token from HEROKU_API_KEY, or the logged-in Heroku CLI
+fn heroku_token() -> Result<String> {
+    if let Ok(token) = std::env::var(TOKEN_ENV_VAR) {
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+
+    let output = Command::new("heroku")
+        .args(["auth:token"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .with_context(|| {
+            format!(
+                "No Heroku credentials: set {} or log in with `heroku login`",
+                TOKEN_ENV_VAR
+            )
+        })?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Call the Heroku API and return the response body
+fn heroku_request(method: &str, url: &str, token: &str, body: Option<&str>) -> Result<Vec<u8>> {
+    let headers = [
+        "Accept: application/vnd.heroku+json; version=3".to_string(),
+        format!("Authorization: Bearer {}", token),
+    ];
+    crate::api::request(method, url, &headers, body).context("Heroku API request failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let local: Vec<(String, String)> = map(&[("A", "1"), ("B", "2"), ("C", "3")])
+            .into_iter()
+            .collect();
+        let remote = map(&[("A", "1"), ("B", "old"), ("D", "4")]);
+        assert_eq!(
+            diff(&local, &remote),
+            vec![
+                ("B".to_string(), Change::Changed),
+                ("C".to_string(), Change::Added)
+            ]
+        );
+    }
+}
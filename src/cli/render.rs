@@ -0,0 +1,104 @@
+//! Render templates with `{{KEY}}` placeholders substituted from secrets
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::cli::open_store;
+use crate::permissions;
+use crate::ui;
+
+/// Substitute every `{{KEY}}` (whitespace inside braces allowed) with its secret value.
+/// Fails listing all placeholders that have no matching secret.
+pub fn render_template(template: &str, secrets: &HashMap<&str, &str>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut missing: Vec<String> = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            anyhow::bail!("Unclosed '{{{{' in template");
+        };
+
+        let key = after[..end].trim();
+        match secrets.get(key) {
+            Some(value) => output.push_str(value),
+            None => {
+                if !missing.iter().any(|m| m == key) {
+                    missing.push(key.to_string());
+                }
+            }
+        }
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Template references missing secrets: {}",
+            missing.join(", ")
+        );
+    }
+
+    Ok(output)
+}
+
+pub fn run(project: &str, environment: &str, template: &str, output: Option<&str>) -> Result<()> {
+    let template = std::fs::read_to_string(template)
+        .context(format!("Failed to read template: {}", template))?;
+
+    let store = open_store()?;
+    let secrets = store.get_all(project, environment)?;
+    let lookup: HashMap<&str, &str> = secrets
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let rendered = Zeroizing::new(render_template(&template, &lookup)?);
+
+    match output {
+        Some(path) => {
+            // Rendered files contain plaintext secrets - keep them owner-only
+            let mut file = permissions::create_private_file(Path::new(path))
+                .context(format!("Failed to create output file: {}", path))?;
+            file.write_all(rendered.as_bytes())?;
+
+            eprintln!("{} Rendered {} (mode 600)", ui::ok(), path.cyan());
+        }
+        None => print!("{}", *rendered),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> HashMap<&'static str, &'static str> {
+        HashMap::from([("DB_USER", "admin"), ("DB_PASS", "s3cret")])
+    }
+
+    #[test]
+    fn test_render_substitutes() {
+        assert_eq!(
+            render_template("{{DB_USER}}:{{ DB_PASS }}@localhost", &secrets()).unwrap(),
+            "admin:s3cret@localhost"
+        );
+    }
+
+    #[test]
+    fn test_render_missing_keys() {
+        let err = render_template("{{DB_USER}} {{NOPE}} {{NOPE}}", &secrets()).unwrap_err();
+        assert_eq!(err.to_string(), "Template references missing secrets: NOPE");
+    }
+
+    #[test]
+    fn test_render_unclosed() {
+        assert!(render_template("{{DB_USER", &secrets()).is_err());
+    }
+}
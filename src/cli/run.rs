@@ -1,55 +1,618 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::os::unix::process::CommandExt;
-use std::process::Command;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use zeroize::Zeroizing;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::check::require_keys;
+use crate::cli::export::write_credential_files;
+use crate::cli::{check_env_policy, open_store};
+use crate::config::Config;
+use crate::errors;
+use crate::interpolate;
+use crate::mask::Masker;
+use crate::permissions::{self, ScratchDir};
+use crate::scan::MIN_SECRET_LEN;
+use crate::store::SecretValues;
+use crate::ui;
 
-pub fn run(project: &str, environment: &str, command: &[String]) -> Result<()> {
+/// Variables `run --isolated` still passes through from our environment
+const ISOLATED_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "LANG", "LC_ALL", "TZ", "TMPDIR",
+];
+
+/// Docker subcommands that start a container and accept `--env`
+const DOCKER_CONTAINER_COMMANDS: &[&str] = &["run", "create", "exec"];
+
+/// Insert `--env KEY` flags after the container subcommand of a docker command line.
+/// Only names are passed: docker reads the values from its own environment, so they
+/// never show up in the process listing or a temporary env file.
+fn docker_command(command: &[String], keys: &[&str]) -> Result<Vec<String>> {
+    let position = command
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find(|(_, arg)| DOCKER_CONTAINER_COMMANDS.contains(&arg.as_str()))
+        .map(|(i, _)| i)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "--docker needs a `docker run`, `docker create`, `docker exec`, or `docker compose run` command"
+            )
+        })?;
+
+    let mut args = command[..=position].to_vec();
+    for key in keys {
+        args.push("--env".to_string());
+        args.push(key.to_string());
+    }
+    args.extend_from_slice(&command[position + 1..]);
+    Ok(args)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    project: &str,
+    environment: &str,
+    command: &[String],
+    docker: bool,
+    config: Option<&Config>,
+    check: bool,
+    interpolate: bool,
+    isolated: Option<&[String]>,
+) -> Result<()> {
     if command.is_empty() {
         anyhow::bail!("No command specified");
     }
 
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+    let secrets = load(project, environment, config, check, interpolate)?;
+
+    // Build the command with injected environment variables
+    let command = if docker {
+        let keys: Vec<&str> = secrets.iter().map(|(k, _)| k.as_str()).collect();
+        docker_command(command, &keys)?
+    } else {
+        command.to_vec()
+    };
+    let program = &command[0];
+    let args = &command[1..];
 
-    let secrets = store.get_all(project, environment)?;
+    // Use std::process::Command with exec() to replace the current process
+    // This way secrets are only in process memory, never written anywhere
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    if let Some(keep) = isolated {
+        isolate(&mut cmd, keep);
+    }
+
+    // Inject secrets as environment variables
+    for (key, value) in secrets.iter() {
+        cmd.env(key, value);
+    }
+
+    // exec replaces the current process - this doesn't return on success
+    let err = cmd.exec();
+
+    // If we get here, exec failed
+    Err(err).context(format!("Failed to execute: {}", program))
+}
+
+/// Load an environment for `run`: required keys, defaults, interpolation and
+/// the env var policy
+fn load(
+    project: &str,
+    environment: &str,
+    config: Option<&Config>,
+    check: bool,
+    interpolate: bool,
+) -> Result<SecretValues> {
+    let store = open_store()?;
+
+    let mut secrets = store.get_all(project, environment)?;
+    if let Some(config) = config {
+        if check {
+            require_keys(project, environment, &secrets, config);
+        }
+        config.apply_defaults(&mut secrets);
+    }
+    if interpolate {
+        secrets = expand(&secrets)?;
+    }
+    check_env_policy(secrets.iter().map(|(k, _)| k.as_str()))?;
 
     if secrets.is_empty() {
         eprintln!(
             "{} No secrets found for {}/{}",
-            "⚠".yellow(),
+            ui::warn(),
             project.cyan(),
             environment.yellow()
         );
     } else {
         eprintln!(
             "{} Loaded {} secrets for {}/{}",
-            "✓".green(),
+            ui::ok(),
             secrets.len().to_string().bold(),
             project.cyan(),
             environment.yellow()
         );
     }
+    Ok(secrets)
+}
 
-    // Build the command with injected environment variables
-    let program = &command[0];
-    let args = &command[1..];
+/// The child `run --as-files`/`--mount`/`--mask-output` is waiting on, for forwarding signals
+static CHILD: AtomicI32 = AtomicI32::new(0);
 
-    // Use std::process::Command with exec() to replace the current process
-    // This way secrets are only in process memory, never written anywhere
-    let mut cmd = Command::new(program);
-    cmd.args(args);
+/// Pass SIGTERM/SIGHUP on to the child; Ctrl-C already reaches it through the
+/// terminal. Either way we stay alive to clean up after it.
+extern "C" fn forward_signal(signal: libc::c_int) {
+    let pid = CHILD.load(Ordering::SeqCst);
+    if pid > 0 && (signal == libc::SIGTERM || signal == libc::SIGHUP) {
+        unsafe { libc::kill(pid, signal) };
+    }
+}
 
-    // Inject secrets as environment variables
-    for (key, value) in &secrets {
+/// Run the command with secrets as files in a private directory under
+/// `parent`, named by $CREDENTIALS_DIRECTORY (as under systemd's
+/// `LoadCredential=`). The files are shredded when the command exits, and
+/// its exit status is passed on.
+#[allow(clippy::too_many_arguments)]
+pub fn run_as_files(
+    project: &str,
+    environment: &str,
+    command: &[String],
+    parent: &Path,
+    config: Option<&Config>,
+    check: bool,
+    interpolate: bool,
+    isolated: Option<&[String]>,
+) -> Result<()> {
+    if command.is_empty() {
+        anyhow::bail!("No command specified");
+    }
+
+    let secrets = load(project, environment, config, check, interpolate)?;
+    let scratch = ScratchDir::new_in(parent).with_context(|| {
+        format!(
+            "Failed to create a private directory in {}",
+            parent.display()
+        )
+    })?;
+    write_credential_files(scratch.path(), &secrets)?;
+    drop(secrets);
+
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..]);
+    if let Some(keep) = isolated {
+        isolate(&mut cmd, keep);
+    }
+    cmd.env("CREDENTIALS_DIRECTORY", scratch.path());
+    let status = wait_for(cmd, &command[0]);
+
+    // process::exit skips destructors, so clean up first
+    drop(scratch);
+    exit_like(status?)
+}
+
+/// Run the command with chosen secrets written to files (`--mount KEY=PATH`):
+/// each file is created 0600 (never over an existing one), the KEY env var
+/// holds its path instead of the value, and it's shredded when the command
+/// exits. Other secrets are injected as usual.
+#[allow(clippy::too_many_arguments)]
+pub fn run_mounted(
+    project: &str,
+    environment: &str,
+    command: &[String],
+    mounts: &[(String, PathBuf)],
+    config: Option<&Config>,
+    check: bool,
+    interpolate: bool,
+    isolated: Option<&[String]>,
+) -> Result<()> {
+    if command.is_empty() {
+        anyhow::bail!("No command specified");
+    }
+
+    let secrets = load(project, environment, config, check, interpolate)?;
+    let mut mounted = MountedFiles(Vec::new());
+    for (key, path) in mounts {
+        let Some((_, value)) = secrets.iter().find(|(k, _)| k == key) else {
+            return Err(errors::not_found(format!(
+                "Secret not found: {}/{}/{} (for --mount)",
+                project, environment, key
+            )));
+        };
+        let mut file = permissions::create_new_private_file(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        mounted.0.push(path.clone());
+        file.write_all(value.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..]);
+    if let Some(keep) = isolated {
+        isolate(&mut cmd, keep);
+    }
+    for (key, value) in secrets.iter() {
+        match mounts.iter().find(|(k, _)| k == key) {
+            Some((_, path)) => cmd.env(key, path),
+            None => cmd.env(key, value),
+        };
+    }
+    drop(secrets);
+    let status = wait_for(cmd, &command[0]);
+
+    drop(mounted);
+    exit_like(status?)
+}
+
+/// Run the command with its stdout and stderr piped through a filter that
+/// replaces loaded values with `***KEY***` (`--mask-output`), e.g. for CI
+/// logs. Its exit status is passed on.
+#[allow(clippy::too_many_arguments)]
+pub fn run_masked(
+    project: &str,
+    environment: &str,
+    command: &[String],
+    docker: bool,
+    config: Option<&Config>,
+    check: bool,
+    interpolate: bool,
+    isolated: Option<&[String]>,
+) -> Result<()> {
+    if command.is_empty() {
+        anyhow::bail!("No command specified");
+    }
+
+    let secrets = load(project, environment, config, check, interpolate)?;
+    let mut masker = Masker::default();
+    let short: Vec<&str> = secrets
+        .iter()
+        .filter(|(key, value)| !masker.add(key, value))
+        .map(|(key, _)| key.as_str())
+        .collect();
+    if !short.is_empty() {
+        eprintln!(
+            "{} Not masking {} (shorter than {} characters)",
+            ui::note(),
+            short.join(", "),
+            MIN_SECRET_LEN
+        );
+    }
+
+    let command = if docker {
+        let keys: Vec<&str> = secrets.iter().map(|(k, _)| k.as_str()).collect();
+        docker_command(command, &keys)?
+    } else {
+        command.to_vec()
+    };
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(keep) = isolated {
+        isolate(&mut cmd, keep);
+    }
+    for (key, value) in secrets.iter() {
         cmd.env(key, value);
     }
+    drop(secrets);
 
-    // exec replaces the current process - this doesn't return on success
-    let err = cmd.exec();
+    let mut child = spawn(cmd, &command[0])?;
+    let masker = Arc::new(masker);
+    let stdout = mask_lines(
+        child.stdout.take().expect("stdout is piped"),
+        Arc::clone(&masker),
+        false,
+    );
+    let stderr = mask_lines(child.stderr.take().expect("stderr is piped"), masker, true);
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to execute: {}", command[0]));
+    let _ = stdout.join();
+    let _ = stderr.join();
+    exit_like(status?)
+}
 
-    // If we get here, exec failed
-    Err(err).context(format!("Failed to execute: {}", program))
+/// Copy a child's output stream to ours a line at a time, masked
+fn mask_lines(
+    stream: impl Read + Send + 'static,
+    masker: Arc<Masker>,
+    to_stderr: bool,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut line = Zeroizing::new(Vec::new());
+        while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+            let masked = masker.mask(&line);
+            let written = if to_stderr {
+                std::io::stderr().write_all(&masked)
+            } else {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&masked).and_then(|_| stdout.flush())
+            };
+            if written.is_err() {
+                return;
+            }
+            line.clear();
+        }
+    })
+}
+
+/// Files written by `--mount`, shredded and removed on drop
+struct MountedFiles(Vec<PathBuf>);
+
+impl Drop for MountedFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = permissions::shred(path);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Parse `--mount KEY=PATH`
+pub fn parse_mount(input: &str) -> Result<(String, PathBuf), String> {
+    match input.split_once('=') {
+        Some((key, path)) if !key.is_empty() && !path.is_empty() => {
+            Ok((key.to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected KEY=PATH, got '{}'", input)),
+    }
+}
+
+/// Spawn the command and wait for it, staying alive through signals so the
+/// caller can clean up afterwards
+fn wait_for(cmd: Command, program: &str) -> Result<ExitStatus> {
+    spawn(cmd, program)?
+        .wait()
+        .with_context(|| format!("Failed to execute: {}", program))
+}
+
+/// Spawn the command, forwarding SIGTERM/SIGHUP to it from here on
+fn spawn(mut cmd: Command, program: &str) -> Result<Child> {
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT] {
+        // Handlers (unlike ignored signals) reset to the default in the child
+        unsafe { libc::signal(signal, forward_signal as *const () as libc::sighandler_t) };
+    }
+    let child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to execute: {}", program))?;
+    CHILD.store(child.id() as i32, Ordering::SeqCst);
+    Ok(child)
+}
+
+/// Start the child with an empty environment apart from `ISOLATED_ENV` and
+/// the `--keep-env` names (`run --isolated`); secrets are added afterwards
+fn isolate(cmd: &mut Command, keep: &[String]) {
+    cmd.env_clear().envs(inherited(std::env::vars_os(), keep));
+}
+
+/// The variables from `vars` that `--isolated` passes through
+fn inherited(
+    vars: impl Iterator<Item = (OsString, OsString)>,
+    keep: &[String],
+) -> Vec<(OsString, OsString)> {
+    vars.filter(|(name, _)| {
+        name.to_str().is_some_and(|name| {
+            ISOLATED_ENV.contains(&name) || keep.iter().any(|kept| kept == name)
+        })
+    })
+    .collect()
+}
+
+/// Exit with the child's status (128 + N if signal N killed it)
+fn exit_like(status: ExitStatus) -> ! {
+    std::process::exit(
+        status
+            .code()
+            .or_else(|| status.signal().map(|signal| 128 + signal))
+            .unwrap_or(1),
+    )
+}
+
+/// Run the command once per environment in parallel, prefixing each output line
+/// with the environment name. Exits non-zero if any run failed.
+#[allow(clippy::too_many_arguments)]
+pub fn run_each_env(
+    project: &str,
+    environments: &[String],
+    command: &[String],
+    docker: bool,
+    config: Option<&Config>,
+    check: bool,
+    interpolate: bool,
+    isolated: Option<&[String]>,
+) -> Result<()> {
+    if command.is_empty() {
+        anyhow::bail!("No command specified");
+    }
+
+    let store = open_store()?;
+
+    // Load every environment first so --check fails before anything starts
+    let mut loaded = Vec::new();
+    for environment in environments {
+        let mut secrets = store.get_all(project, environment)?;
+        if let Some(config) = config {
+            if check {
+                require_keys(project, environment, &secrets, config);
+            }
+            config.apply_defaults(&mut secrets);
+        }
+        if interpolate {
+            secrets = expand(&secrets)?;
+        }
+        loaded.push((environment, secrets));
+    }
+
+    let mut children = Vec::new();
+    for (environment, secrets) in loaded {
+        check_env_policy(secrets.iter().map(|(k, _)| k.as_str()))?;
+        if secrets.is_empty() {
+            eprintln!(
+                "{} No secrets found for {}/{}",
+                ui::warn(),
+                project.cyan(),
+                environment.yellow()
+            );
+        }
+
+        let command = if docker {
+            let keys: Vec<&str> = secrets.iter().map(|(k, _)| k.as_str()).collect();
+            docker_command(command, &keys)?
+        } else {
+            command.to_vec()
+        };
+
+        let mut cmd = Command::new(&command[0]);
+        cmd.args(&command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(keep) = isolated {
+            isolate(&mut cmd, keep);
+        }
+        for (key, value) in secrets.iter() {
+            cmd.env(key, value);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .context(format!("Failed to execute: {}", command[0]))?;
+        let prefix = format!("[{}]", environment);
+        let width = environments.iter().map(|e| e.len()).max().unwrap_or(0) + 2;
+        let stdout = forward_lines(
+            child.stdout.take().expect("stdout is piped"),
+            prefix.clone(),
+            width,
+            false,
+        );
+        let stderr = forward_lines(
+            child.stderr.take().expect("stderr is piped"),
+            prefix,
+            width,
+            true,
+        );
+        children.push((environment, child, stdout, stderr));
+    }
+    drop(store);
+
+    let mut failed = Vec::new();
+    for (environment, mut child, stdout, stderr) in children {
+        let status = child.wait()?;
+        let _ = stdout.join();
+        let _ = stderr.join();
+        if !status.success() {
+            failed.push((environment, status.code()));
+        }
+    }
+
+    eprintln!();
+    if failed.is_empty() {
+        eprintln!(
+            "{} Succeeded in all {} environments",
+            ui::ok(),
+            environments.len()
+        );
+        return Ok(());
+    }
+
+    for (environment, code) in &failed {
+        let code = code.map_or("signal".to_string(), |c| format!("exit {}", c));
+        std::eprintln!("{} {} failed ({})", ui::fail(), environment.yellow(), code);
+    }
+    std::process::exit(1);
+}
+
+/// Expand `${KEY}` and `${env:NAME}` references (`--interpolate`)
+pub fn expand(secrets: &[(String, String)]) -> Result<SecretValues> {
+    interpolate::expand(secrets, &|name| std::env::var(name).ok()).map(Zeroizing::new)
+}
+
+/// Copy lines from a child's output stream to ours with an environment prefix
+fn forward_lines(
+    stream: impl Read + Send + 'static,
+    prefix: String,
+    width: usize,
+    to_stderr: bool,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            let line = format!("{:<width$} {}", prefix.dimmed(), line, width = width);
+            if to_stderr {
+                let _ = writeln!(std::io::stderr(), "{}", line);
+            } else {
+                let _ = writeln!(std::io::stdout(), "{}", line);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_docker_run() {
+        assert_eq!(
+            docker_command(&args("docker run --rm app:latest"), &["A", "B"]).unwrap(),
+            args("docker run --env A --env B --rm app:latest")
+        );
+    }
+
+    #[test]
+    fn test_docker_compose_run() {
+        assert_eq!(
+            docker_command(&args("docker compose run web"), &["A"]).unwrap(),
+            args("docker compose run --env A web")
+        );
+    }
+
+    #[test]
+    fn test_parse_mount() {
+        assert_eq!(
+            parse_mount("GCP_SA_JSON=/tmp/sa.json"),
+            Ok(("GCP_SA_JSON".to_string(), PathBuf::from("/tmp/sa.json")))
+        );
+        assert!(parse_mount("GCP_SA_JSON").is_err());
+        assert!(parse_mount("=/tmp/sa.json").is_err());
+        assert!(parse_mount("KEY=").is_err());
+    }
+
+    #[test]
+    fn test_inherited() {
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("AWS_SECRET_ACCESS_KEY", "ambient"),
+            ("TINYSECRETS_PASSPHRASE", "hunter2"),
+            ("HOME", "/home/me"),
+            ("SSH_AUTH_SOCK", "/tmp/agent"),
+        ]
+        .map(|(k, v)| (OsString::from(k), OsString::from(v)));
+        let names = |keep: &[String]| -> Vec<String> {
+            inherited(vars.clone().into_iter(), keep)
+                .into_iter()
+                .map(|(k, _)| k.into_string().unwrap())
+                .collect()
+        };
+        assert_eq!(names(&[]), ["PATH", "HOME"]);
+        assert_eq!(
+            names(&["SSH_AUTH_SOCK".to_string()]),
+            ["PATH", "HOME", "SSH_AUTH_SOCK"]
+        );
+    }
+
+    #[test]
+    fn test_docker_unsupported() {
+        assert!(docker_command(&args("docker compose up"), &["A"]).is_err());
+    }
 }
@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use colored::Colorize;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::RngCore;
+use serde::Deserialize;
+use std::io::IsTerminal;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::cli::{check_value_rules, open_store};
+use crate::config::Config;
+use crate::ui;
+
+/// A scaffold template: the keys a new service needs, in prompt order
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Template {
+    #[serde(default, rename = "secret")]
+    secrets: Vec<TemplateSecret>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateSecret {
+    key: String,
+    #[serde(default)]
+    description: Option<String>,
+    /// Refuse to finish without a value
+    #[serde(default)]
+    required: bool,
+    /// Fill the value with random data instead of asking
+    #[serde(default)]
+    generate: Option<Generator>,
+    /// Length of a generated value in characters
+    #[serde(default)]
+    length: Option<usize>,
+    /// Used when nothing is entered at the prompt
+    #[serde(default)]
+    default: Option<String>,
+}
+
+/// Kinds of random value a template can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Generator {
+    /// A-Z, a-z and 0-9
+    Alphanumeric,
+    /// Lowercase hex digits
+    Hex,
+    /// URL-safe base64 without padding
+    Base64,
+    /// A random (version 4) UUID; `length` doesn't apply
+    Uuid,
+}
+
+const DEFAULT_LENGTH: usize = 32;
+
+impl Generator {
+    fn generate(self, length: usize) -> Zeroizing<String> {
+        let mut rng = rand::thread_rng();
+        let random_bytes = |count: usize| {
+            let mut bytes = Zeroizing::new(vec![0u8; count]);
+            rand::thread_rng().fill_bytes(&mut bytes);
+            bytes
+        };
+        let mut value = Zeroizing::new(match self {
+            Generator::Alphanumeric => Alphanumeric.sample_string(&mut rng, length),
+            Generator::Hex => random_bytes(length.div_ceil(2))
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+            Generator::Base64 => base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .encode(random_bytes(length.div_ceil(4) * 3).as_slice()),
+            Generator::Uuid => {
+                let mut bytes = random_bytes(16);
+                bytes[6] = (bytes[6] & 0x0f) | 0x40;
+                bytes[8] = (bytes[8] & 0x3f) | 0x80;
+                let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                return Zeroizing::new(format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                ));
+            }
+        });
+        value.truncate(length);
+        value
+    }
+}
+
+/// How a value was filled in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    Generated,
+    Entered,
+    Default,
+}
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    template_path: &Path,
+    no_input: bool,
+    config: Option<&Config>,
+) -> Result<()> {
+    let template = load_template(template_path)?;
+    let interactive = !no_input && std::io::stderr().is_terminal();
+
+    let store = open_store()?;
+
+    // Collect every value before writing, so a missing required key leaves
+    // the environment untouched
+    let mut values = Vec::new();
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+    let mut missing = Vec::new();
+    for secret in &template.secrets {
+        if store.entry(project, environment, &secret.key)?.is_some() {
+            kept.push(secret.key.as_str());
+            continue;
+        }
+        let filled = match secret.generate {
+            Some(generator) => Some((
+                generator.generate(secret.length.unwrap_or(DEFAULT_LENGTH)),
+                Origin::Generated,
+            )),
+            None if interactive => prompt(secret)?,
+            None => secret
+                .default
+                .clone()
+                .map(|value| (Zeroizing::new(value), Origin::Default)),
+        };
+        match filled {
+            Some((value, origin)) => {
+                if !check_value_rules(config, &secret.key, value.as_bytes())? {
+                    anyhow::bail!(
+                        "{} breaks the [validate] rules in .tinysecrets.toml",
+                        secret.key
+                    );
+                }
+                values.push((secret, value, origin));
+            }
+            None if secret.required => missing.push(secret.key.as_str()),
+            None => skipped.push(secret.key.as_str()),
+        }
+    }
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "No value for required key(s) {}{}",
+            missing.join(", "),
+            if interactive {
+                ""
+            } else {
+                " (run in a terminal to be prompted, or set them first)"
+            }
+        );
+    }
+
+    for (secret, value, _) in &values {
+        store.set_bytes(
+            project,
+            environment,
+            &secret.key,
+            value.as_bytes(),
+            secret.description.as_deref(),
+            Some("scaffold"),
+        )?;
+    }
+
+    let count = |origin| values.iter().filter(|(_, _, o)| *o == origin).count();
+    eprintln!(
+        "{} Scaffolded {}/{} from {}: {} generated, {} entered, {} default(s)",
+        ui::ok(),
+        project.cyan(),
+        environment.yellow(),
+        template_path.display(),
+        count(Origin::Generated),
+        count(Origin::Entered),
+        count(Origin::Default)
+    );
+    if !kept.is_empty() {
+        eprintln!(
+            "{} Already set, left unchanged: {}",
+            ui::note(),
+            kept.join(", ")
+        );
+    }
+    if !skipped.is_empty() {
+        eprintln!(
+            "{} Optional, left unset: {}",
+            ui::note(),
+            skipped.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+fn load_template(path: &Path) -> Result<Template> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_template(&contents).with_context(|| format!("Invalid template {}", path.display()))
+}
+
+fn parse_template(contents: &str) -> Result<Template> {
+    let template: Template = toml::from_str(contents)?;
+    let mut seen = std::collections::HashSet::new();
+    for secret in &template.secrets {
+        if !seen.insert(secret.key.as_str()) {
+            anyhow::bail!("{} is listed more than once", secret.key);
+        }
+        if secret.generate.is_some() && secret.default.is_some() {
+            anyhow::bail!("{} has both `generate` and `default`", secret.key);
+        }
+        if secret.length == Some(0) {
+            anyhow::bail!("{} has `length = 0`", secret.key);
+        }
+    }
+    Ok(template)
+}
+
+/// Ask for a value without echo; None if left empty with no default
+fn prompt(secret: &TemplateSecret) -> Result<Option<(Zeroizing<String>, Origin)>> {
+    let mut question = secret.key.bold().to_string();
+    if let Some(description) = &secret.description {
+        question.push_str(&format!(" ({})", description));
+    }
+    match (&secret.default, secret.required) {
+        (Some(_), _) => question.push_str(&" [enter for default]".dimmed().to_string()),
+        (None, false) => question.push_str(&" [enter to skip]".dimmed().to_string()),
+        (None, true) => {}
+    }
+    question.push_str(": ");
+
+    loop {
+        let value = Zeroizing::new(
+            rpassword::prompt_password(&question)
+                .with_context(|| format!("Failed to read {}", secret.key))?,
+        );
+        if !value.is_empty() {
+            return Ok(Some((value, Origin::Entered)));
+        }
+        if let Some(default) = &secret.default {
+            return Ok(Some((Zeroizing::new(default.clone()), Origin::Default)));
+        }
+        if !secret.required {
+            return Ok(None);
+        }
+        std::eprintln!("{} {} is required", ui::fail(), secret.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        let template = parse_template(
+            r#"
+            [[secret]]
+            key = "DATABASE_URL"
+            description = "Postgres connection string"
+            required = true
+
+            [[secret]]
+            key = "SESSION_SECRET"
+            generate = "hex"
+            length = 64
+
+            [[secret]]
+            key = "LOG_LEVEL"
+            default = "info"
+            "#,
+        )
+        .unwrap();
+        let keys: Vec<&str> = template.secrets.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, ["DATABASE_URL", "SESSION_SECRET", "LOG_LEVEL"]);
+        assert!(template.secrets[0].required);
+        assert_eq!(template.secrets[1].generate, Some(Generator::Hex));
+
+        assert!(parse_template("[[secret]]\nkey = \"A\"\n[[secret]]\nkey = \"A\"\n").is_err());
+        assert!(
+            parse_template("[[secret]]\nkey = \"A\"\ngenerate = \"hex\"\ndefault = \"x\"\n")
+                .is_err()
+        );
+        assert!(parse_template("[[secret]]\nkey = \"A\"\nrequird = true\n").is_err());
+    }
+
+    #[test]
+    fn test_generate() {
+        let alnum = Generator::Alphanumeric.generate(40);
+        assert_eq!(alnum.len(), 40);
+        assert!(alnum.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        let hex = Generator::Hex.generate(33);
+        assert_eq!(hex.len(), 33);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let b64 = Generator::Base64.generate(43);
+        assert_eq!(b64.len(), 43);
+        assert!(b64
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+        let uuid = Generator::Uuid.generate(DEFAULT_LENGTH);
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+
+        assert_ne!(
+            *Generator::Alphanumeric.generate(32),
+            *Generator::Alphanumeric.generate(32)
+        );
+    }
+}
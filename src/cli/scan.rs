@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cli::open_store;
+use crate::scan::{Scanner, MIN_SECRET_LEN};
+use crate::ui;
+
+/// Directories never worth scanning
+const SKIP_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// Files larger than this are skipped (build artifacts, dumps)
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+pub fn run(project: &str, paths: &[PathBuf], staged: bool) -> Result<()> {
+    let scanner = build_scanner(project)?;
+    if scanner.is_empty() {
+        eprintln!(
+            "{} No secrets long enough to scan for in {}",
+            ui::note(),
+            project.cyan()
+        );
+        return Ok(());
+    }
+
+    let mut leaks = 0;
+    let mut report = |path: &str, line: usize, label: &str| {
+        leaks += 1;
+        println!(
+            "{}:{}: {} {}/{}",
+            path,
+            line,
+            "contains".dimmed(),
+            project.cyan(),
+            label.yellow()
+        );
+    };
+
+    if staged {
+        let diff = git(&["diff", "--cached", "-U0", "--no-color", "--no-ext-diff"])?;
+        for (path, lines) in added_lines(&diff) {
+            let text: Vec<&str> = lines.iter().map(|(_, text)| text.as_str()).collect();
+            for finding in scanner.scan(&text.join("\n")) {
+                report(&path, lines[finding.line - 1].0, &finding.label);
+            }
+        }
+    } else {
+        let default = [PathBuf::from(".")];
+        let roots = if paths.is_empty() {
+            &default[..]
+        } else {
+            paths
+        };
+        for root in roots {
+            walk(root, &mut |path| {
+                let Some(text) = read_text(path)? else {
+                    return Ok(());
+                };
+                for finding in scanner.scan(&text) {
+                    report(&path.display().to_string(), finding.line, &finding.label);
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    if leaks > 0 {
+        eprintln!();
+        std::eprintln!(
+            "{} Found {} stored secret value(s) in plaintext",
+            ui::fail(),
+            leaks
+        );
+        std::process::exit(1);
+    }
+
+    eprintln!("{} No stored secret values found", ui::ok());
+    Ok(())
+}
+
+/// Fingerprint every value in every environment of the project
+fn build_scanner(project: &str) -> Result<Scanner> {
+    let store = open_store()?;
+    let mut scanner = Scanner::default();
+    let mut skipped = 0;
+
+    for environment in store.list_environments(project)? {
+        for (key, value) in store.get_all(project, &environment)?.iter() {
+            if !scanner.add(&format!("{}/{}", environment, key), value) {
+                skipped += 1;
+            }
+        }
+    }
+
+    if skipped > 0 {
+        eprintln!(
+            "{} Skipping {} values shorter than {} characters",
+            ui::note(),
+            skipped,
+            MIN_SECRET_LEN
+        );
+    }
+    Ok(scanner)
+}
+
+/// Call `visit` for every regular file under `path`
+fn walk(path: &Path, visit: &mut dyn FnMut(&Path) -> Result<()>) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    if metadata.is_file() {
+        return visit(path);
+    }
+    if !metadata.is_dir() {
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        if SKIP_DIRS.iter().any(|d| name == *d) {
+            continue;
+        }
+        walk(&entry.path(), visit)?;
+    }
+    Ok(())
+}
+
+/// Read a file as text, or None if it's too large or looks binary
+fn read_text(path: &Path) -> Result<Option<String>> {
+    if std::fs::metadata(path)?.len() > MAX_FILE_SIZE {
+        return Ok(None);
+    }
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if bytes.iter().take(8192).any(|&b| b == 0) {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Added lines per file in a unified diff, with their line numbers in the new file
+fn added_lines(diff: &str) -> Vec<(String, Vec<(usize, String)>)> {
+    let mut files: Vec<(String, Vec<(usize, String)>)> = Vec::new();
+    let mut in_header = false;
+    let mut next_line = 0;
+
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            in_header = true;
+        } else if in_header && line.starts_with("+++ ") {
+            let path = &line[4..];
+            let path = path.strip_prefix("b/").unwrap_or(path);
+            files.push((path.to_string(), Vec::new()));
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            in_header = false;
+            // "@@ -a,b +c,d @@": new-file lines start at c
+            next_line = hunk
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('+'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|start| start.parse().ok())
+                .unwrap_or(0);
+        } else if in_header {
+            continue;
+        } else if let Some(text) = line.strip_prefix('+') {
+            if let Some((_, lines)) = files.last_mut() {
+                lines.push((next_line, text.to_string()));
+            }
+            next_line += 1;
+        } else if line.starts_with(' ') {
+            next_line += 1;
+        }
+    }
+
+    files.retain(|(path, lines)| path != "/dev/null" && !lines.is_empty());
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_added_lines() {
+        let diff = "\
+diff --git a/app.js b/app.js
+index 1234..5678 100644
+--- a/app.js
++++ b/app.js
+@@ -3,0 +4,2 @@ fn
++const key = 'x';
+++++ tricky
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+        assert_eq!(
+            added_lines(diff),
+            vec![(
+                "app.js".to_string(),
+                vec![
+                    (4, "const key = 'x';".to_string()),
+                    (5, "+++ tricky".to_string())
+                ]
+            )]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::open_store;
+use crate::pattern::{glob_match, is_glob};
+use crate::ui;
+
+/// Case-insensitive match: glob if the pattern has wildcards, substring otherwise
+fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    if is_glob(&pattern) {
+        glob_match(&pattern, &text)
+    } else {
+        text.contains(&pattern)
+    }
+}
+
+pub fn run(
+    pattern: &str,
+    project: Option<&str>,
+    environment: Option<&str>,
+    values: bool,
+) -> Result<()> {
+    let store = open_store()?;
+
+    if values {
+        eprintln!(
+            "{} Searching decrypted values - matching values are never printed",
+            ui::warn()
+        );
+    }
+
+    let mut found = 0;
+    for entry in store.list(project, environment)? {
+        let mut fields = Vec::new();
+
+        if matches(pattern, &entry.key) {
+            fields.push("key");
+        }
+        if entry
+            .description
+            .as_deref()
+            .is_some_and(|d| matches(pattern, d))
+        {
+            fields.push("description");
+        }
+        if values {
+            if let Some(value) = store.get(&entry.project, &entry.environment, &entry.key)? {
+                // Binary values can't match a text pattern
+                if std::str::from_utf8(&value).is_ok_and(|v| matches(pattern, v)) {
+                    fields.push("value");
+                }
+            }
+        }
+
+        if fields.is_empty() {
+            continue;
+        }
+
+        found += 1;
+        println!(
+            "{}/{}/{}  {}",
+            entry.project.cyan(),
+            entry.environment.yellow(),
+            entry.key.bold(),
+            format!("({})", fields.join(", ")).dimmed()
+        );
+    }
+
+    if found == 0 {
+        eprintln!("{} No matches for '{}'", ui::note(), pattern);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::Path;
+
+use crate::cli::{open_store, SecurityAction, UnlockMethod};
+use crate::ssh_agent::PublicKey;
+use crate::store::Store;
+use crate::ui;
+
+pub fn run(action: SecurityAction) -> Result<()> {
+    match action {
+        SecurityAction::AddUnlock { method, public_key } => match method {
+            UnlockMethod::Ssh => add_ssh(&public_key),
+        },
+        SecurityAction::RemoveUnlock { method, key } => match method {
+            UnlockMethod::Ssh => remove_ssh(&key),
+        },
+        SecurityAction::Unlocks => list(),
+        SecurityAction::HideNames => hide_names(true),
+        SecurityAction::ShowNames => hide_names(false),
+        SecurityAction::SigningKey => signing_key(),
+    }
+}
+
+fn read_public_key(path: &Path) -> Result<PublicKey> {
+    let line = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    PublicKey::parse(&line).with_context(|| format!("{} isn't an SSH public key", path.display()))
+}
+
+fn add_ssh(path: &Path) -> Result<()> {
+    let key = read_public_key(path)?;
+    key.check_supported()?;
+    let store = open_store()?;
+
+    store.add_ssh_unlock(&key)?;
+    eprintln!(
+        "{} {} can now unlock the store",
+        ui::ok(),
+        key.label().bold()
+    );
+    eprintln!("  {}", key.fingerprint().dimmed());
+    eprintln!(
+        "  While it's loaded in ssh-agent, commands won't ask for the passphrase. {} still wins.",
+        crate::cli::PASSPHRASE_ENV_VAR.cyan()
+    );
+    Ok(())
+}
+
+fn remove_ssh(key: &str) -> Result<()> {
+    let fingerprint = if key.starts_with("SHA256:") {
+        key.to_string()
+    } else {
+        read_public_key(Path::new(key))?.fingerprint()
+    };
+    let store = open_store()?;
+
+    if store.remove_ssh_unlock(&fingerprint)? {
+        eprintln!(
+            "{} {} can no longer unlock the store",
+            ui::ok(),
+            fingerprint
+        );
+    } else {
+        eprintln!("{} {} wasn't registered", ui::note(), fingerprint);
+    }
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    let store = open_store()?;
+    let keys = store.ssh_unlocks()?;
+
+    if keys.is_empty() {
+        eprintln!("{} Only the passphrase unlocks this store", ui::note());
+        eprintln!(
+            "  To add an SSH key: {}",
+            "tinysecrets security add-unlock ssh ~/.ssh/id_ed25519.pub".cyan()
+        );
+        return Ok(());
+    }
+    for key in keys {
+        println!(
+            "ssh  {}  {}  {}",
+            key.fingerprint(),
+            key.algorithm,
+            key.comment
+        );
+    }
+    Ok(())
+}
+
+fn hide_names(hide: bool) -> Result<()> {
+    let mut store = open_store()?;
+    if !store.set_hide_key_names(hide)? {
+        eprintln!(
+            "{} Key names are already {}",
+            ui::note(),
+            if hide { "hidden" } else { "shown" }
+        );
+        return Ok(());
+    }
+
+    if hide {
+        eprintln!(
+            "{} Key names and descriptions are now encrypted in the store",
+            ui::ok()
+        );
+        eprintln!(
+            "  `has` now unlocks the store to look keys up, and `stats` shows them as (hidden)"
+        );
+        let copies = Store::leftover_copies()?;
+        if !copies.is_empty() {
+            eprintln!(
+                "{} These earlier copies of the store still hold the names in plain text:",
+                ui::warn()
+            );
+            for copy in &copies {
+                eprintln!("  {} {}", ui::bullet(), copy.display());
+            }
+        }
+        eprintln!(
+            "{} Archives from `backup export` made before now still hold them too; \
+             delete them and export again",
+            ui::warn()
+        );
+    } else {
+        eprintln!(
+            "{} Key names and descriptions are stored in plain text again",
+            ui::ok()
+        );
+    }
+    Ok(())
+}
+
+fn signing_key() -> Result<()> {
+    let store = open_store()?;
+
+    println!("{}", store.signing_public_key()?);
+    eprintln!(
+        "  {}",
+        "Share this with whoever imports your bundles; they check it with \
+         `import --verify-signer <key>`."
+            .dimmed()
+    );
+    Ok(())
+}
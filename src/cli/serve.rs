@@ -0,0 +1,511 @@
+//! Local HTTP API for tools that can't shell out (IDE plugins, language SDKs)
+//!
+//! The server only binds loopback addresses and every request must carry
+//! `Authorization: Bearer <token>`, checked before any body is read. The token is
+//! written to a 0600 file next to the store when the server starts, so only the
+//! local user can read it, and removed when it stops. Requests are handled one at
+//! a time on the store connection opened (and unlocked) at startup.
+//!
+//! Routes, with project/environment/key percent-encoded:
+//!   GET    /v1/projects/{project}/envs/{env}/secrets         metadata, no values
+//!   GET    /v1/projects/{project}/envs/{env}/secrets/{key}   one value
+//!   PUT    /v1/projects/{project}/envs/{env}/secrets/{key}   set (body is the value)
+//!   DELETE /v1/projects/{project}/envs/{env}/secrets/{key}   delete
+//!   GET    /v1/projects/{project}/envs/{env}/env             KEY -> value, as `run` sees it
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use colored::Colorize;
+use rand::RngCore;
+use serde_json::{json, Value};
+use std::ffi::CString;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::cli::open_store;
+use crate::permissions;
+use crate::store::{Access, Store, MAX_VALUE_SIZE};
+use crate::ui;
+
+/// Token to use instead of generating one (e.g. shared with a launcher)
+const TOKEN_ENV_VAR: &str = "TINYSECRETS_SERVE_TOKEN";
+/// Upper bound on the request line plus headers
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+/// Slow or idle clients are dropped so they can't hold up the single worker
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// A parsed path: which project/environment, and what within it
+#[derive(Debug, PartialEq, Eq)]
+struct Route {
+    project: String,
+    environment: String,
+    endpoint: Endpoint,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Endpoint {
+    List,
+    Secret(String),
+    Env,
+}
+
+/// An error response
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for HttpError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(500, format!("{:#}", e))
+    }
+}
+
+pub fn run(listen: &str, read_only: bool, token_file: Option<&Path>) -> Result<()> {
+    let addr: SocketAddr = listen.parse().with_context(|| {
+        format!(
+            "Invalid listen address: {} (expected e.g. 127.0.0.1:7878)",
+            listen
+        )
+    })?;
+    if !addr.ip().is_loopback() {
+        anyhow::bail!(
+            "Refusing to listen on {}: the API only binds loopback addresses (127.0.0.1 or ::1)",
+            addr
+        );
+    }
+
+    let store = open_store()?;
+    // The read-only passphrase can't write anyway, so say so up front
+    let read_only = read_only || store.access() == Access::ReadOnly;
+
+    let token = match std::env::var(TOKEN_ENV_VAR) {
+        Ok(token) if !token.is_empty() => token,
+        _ => generate_token(),
+    };
+    let token_file = match token_file {
+        Some(path) => path.to_path_buf(),
+        None => default_token_file()?,
+    };
+    let _token_file = TokenFile::write(&token_file, &token)?;
+
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+
+    eprintln!(
+        "{} Serving secrets on {}{}",
+        ui::ok(),
+        format!("http://{}", addr).cyan(),
+        if read_only { " (read-only)" } else { "" }
+    );
+    eprintln!(
+        "  {} token in {} (send as {})",
+        ui::branch(),
+        token_file.display(),
+        "Authorization: Bearer <token>".cyan()
+    );
+    eprintln!("  {} Ctrl-C to stop", ui::branch());
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => handle(&store, stream, &token, read_only),
+            Err(e) => eprintln!("{} Connection failed: {}", ui::warn(), e),
+        }
+    }
+    Ok(())
+}
+
+fn handle(store: &Store, mut stream: TcpStream, token: &str, read_only: bool) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+
+    let result = match stream.try_clone() {
+        Ok(reader) => read_request(&mut BufReader::new(reader), token),
+        Err(e) => Err(HttpError::new(500, e.to_string())),
+    };
+    let (label, response) = match result {
+        Ok(request) => {
+            let label = format!("{} {}", request.method, request.path);
+            (label, respond(store, &request, read_only))
+        }
+        Err(e) => ("-".to_string(), Err(e)),
+    };
+
+    let (status, body) = match response {
+        Ok((status, body)) => (status, body),
+        Err(e) => (e.status, json!({ "error": e.message })),
+    };
+    eprintln!("  {} {} {}", ui::arrow(), label, status);
+
+    let body = body.to_string();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+}
+
+fn respond(store: &Store, request: &Request, read_only: bool) -> Result<(u16, Value), HttpError> {
+    let Route {
+        project,
+        environment,
+        endpoint,
+    } = parse_route(&request.path)?;
+    match (request.method.as_str(), endpoint) {
+        ("GET", Endpoint::List) => {
+            let entries = store.resolved_entries(&project, &environment)?;
+            Ok((200, json!({ "secrets": entries })))
+        }
+        ("GET", Endpoint::Env) => {
+            let env: serde_json::Map<String, Value> = store
+                .get_all(&project, &environment)?
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                .collect();
+            Ok((200, Value::Object(env)))
+        }
+        ("GET", Endpoint::Secret(key)) => {
+            let value = store
+                .get(&project, &environment, &key)?
+                .ok_or_else(|| HttpError::new(404, format!("Secret not found: {}", key)))?;
+            let value = match std::str::from_utf8(&value) {
+                Ok(text) => json!({ "key": key, "value": text }),
+                Err(_) => json!({
+                    "key": key,
+                    "value_base64": base64::engine::general_purpose::STANDARD.encode(&*value),
+                }),
+            };
+            Ok((200, value))
+        }
+        ("PUT" | "DELETE", Endpoint::Secret(_)) if read_only => {
+            Err(HttpError::new(403, "Server is read-only"))
+        }
+        ("PUT", Endpoint::Secret(key)) => {
+            if request.body.is_empty() {
+                return Err(HttpError::new(400, "Secret value cannot be empty"));
+            }
+            let existed = store.entry(&project, &environment, &key)?.is_some();
+            store.set_bytes(&project, &environment, &key, &request.body, None, None)?;
+            Ok((if existed { 200 } else { 201 }, json!({ "key": key })))
+        }
+        ("DELETE", Endpoint::Secret(key)) => {
+            if store.delete(&project, &environment, &key)? {
+                Ok((200, json!({ "key": key })))
+            } else {
+                Err(HttpError::new(404, format!("Secret not found: {}", key)))
+            }
+        }
+        _ => Err(HttpError::new(405, "Method not allowed")),
+    }
+}
+
+/// Read one HTTP/1.1 request (head plus a Content-Length body)
+/// Read a request whose head carries `token`. Anyone else is turned away
+/// before their body is read.
+fn read_request(reader: &mut impl BufRead, token: &str) -> Result<Request, HttpError> {
+    let mut head_size = 0;
+    let mut read_line = |reader: &mut dyn BufRead| -> Result<String, HttpError> {
+        let mut line = String::new();
+        let limit = (MAX_HEAD_SIZE - head_size) as u64 + 1;
+        reader
+            .take(limit)
+            .read_line(&mut line)
+            .map_err(|e| HttpError::new(400, e.to_string()))?;
+        head_size += line.len();
+        if head_size > MAX_HEAD_SIZE {
+            return Err(HttpError::new(431, "Request head too large"));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    };
+
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(path), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(HttpError::new(400, "Malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut authorization = None;
+    let mut content_length = 0;
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(HttpError::new(400, "Malformed header"));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "authorization" => authorization = Some(value.to_string()),
+            "content-length" => {
+                content_length = value
+                    .parse()
+                    .map_err(|_| HttpError::new(400, "Invalid Content-Length"))?
+            }
+            _ => {}
+        }
+    }
+
+    let presented = authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+    if !tokens_match(presented.as_bytes(), token.as_bytes()) {
+        return Err(HttpError::new(401, "Missing or invalid bearer token"));
+    }
+
+    if content_length > MAX_VALUE_SIZE {
+        return Err(HttpError::new(413, "Value too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| HttpError::new(400, e.to_string()))?;
+
+    Ok(Request { method, path, body })
+}
+
+fn parse_route(path: &str) -> Result<Route, HttpError> {
+    let not_found = || HttpError::new(404, format!("No such endpoint: {}", path));
+    let path = path.split('?').next().unwrap_or(path);
+    let segments = path
+        .strip_prefix("/v1/projects/")
+        .ok_or_else(not_found)?
+        .split('/')
+        .map(percent_decode)
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| HttpError::new(400, "Invalid percent-encoding in path"))?;
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(not_found());
+    }
+
+    let [project, envs, environment, rest @ ..] = segments.as_slice() else {
+        return Err(not_found());
+    };
+    let endpoint = match rest {
+        [secrets] if secrets == "secrets" => Endpoint::List,
+        [secrets, key] if secrets == "secrets" => Endpoint::Secret(key.clone()),
+        [env] if env == "env" => Endpoint::Env,
+        _ => return Err(not_found()),
+    };
+    if envs != "envs" {
+        return Err(not_found());
+    }
+
+    Ok(Route {
+        project: project.clone(),
+        environment: environment.clone(),
+        endpoint,
+    })
+}
+
+/// Decode `%XX` escapes; None if malformed or not UTF-8
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Compare without short-circuiting, so timing doesn't leak how much of a guess matched
+fn tokens_match(presented: &[u8], expected: &[u8]) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn default_token_file() -> Result<PathBuf> {
+    Ok(Store::path()?.with_file_name("serve.token"))
+}
+
+/// The token file, removed when the server stops: on return, or from a
+/// signal handler for Ctrl-C, SIGTERM and SIGHUP
+struct TokenFile(PathBuf);
+
+/// Token file path for the signal handler, which can only make raw syscalls
+static TOKEN_FILE: OnceLock<CString> = OnceLock::new();
+
+extern "C" fn remove_token_and_exit(signal: libc::c_int) {
+    if let Some(path) = TOKEN_FILE.get() {
+        unsafe { libc::unlink(path.as_ptr()) };
+    }
+    unsafe { libc::_exit(128 + signal) };
+}
+
+impl TokenFile {
+    /// Write the token readable only by the current user
+    fn write(path: &Path, token: &str) -> Result<Self> {
+        // A fresh file, so nothing left at the path (a link, a file others can
+        // open) gets the token
+        let _ = std::fs::remove_file(path);
+        let mut file = permissions::create_new_private_file(path)
+            .with_context(|| format!("Failed to write token file {}", path.display()))?;
+        writeln!(file, "{}", token)?;
+
+        if let Ok(raw) = CString::new(path.as_os_str().as_bytes()) {
+            let _ = TOKEN_FILE.set(raw);
+            for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+                unsafe {
+                    libc::signal(
+                        signal,
+                        remove_token_and_exit as *const () as libc::sighandler_t,
+                    )
+                };
+            }
+        }
+        Ok(Self(path.to_path_buf()))
+    }
+}
+
+impl Drop for TokenFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request() {
+        let raw = "PUT /v1/projects/api/envs/dev/secrets/KEY HTTP/1.1\r\nHost: localhost\r\nauthorization: Bearer abc\r\nContent-Length: 5\r\n\r\nhello";
+        let request = read_request(&mut raw.as_bytes(), "abc").ok().unwrap();
+        assert_eq!(
+            request,
+            Request {
+                method: "PUT".into(),
+                path: "/v1/projects/api/envs/dev/secrets/KEY".into(),
+                body: b"hello".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_request_checks_token_before_body() {
+        // The promised body never arrives; a wrong token is refused without waiting for it
+        let raw = "PUT /v1/projects/api/envs/dev/secrets/KEY HTTP/1.1\r\nAuthorization: Bearer nope\r\nContent-Length: 1000000\r\n\r\n";
+        let err = read_request(&mut raw.as_bytes(), "abc").err().unwrap();
+        assert_eq!(err.status, 401);
+        let raw = "GET / HTTP/1.1\r\n\r\n";
+        assert_eq!(
+            read_request(&mut raw.as_bytes(), "abc")
+                .err()
+                .unwrap()
+                .status,
+            401
+        );
+    }
+
+    #[test]
+    fn test_read_request_rejects_oversized_head() {
+        let raw = format!(
+            "GET / HTTP/1.1\r\nX-Pad: {}\r\n\r\n",
+            "a".repeat(MAX_HEAD_SIZE)
+        );
+        let err = read_request(&mut raw.as_bytes(), "abc").err().unwrap();
+        assert_eq!(err.status, 431);
+    }
+
+    #[test]
+    fn test_parse_route() {
+        let route = |project: &str, environment: &str, endpoint| Route {
+            project: project.into(),
+            environment: environment.into(),
+            endpoint,
+        };
+        assert_eq!(
+            parse_route("/v1/projects/my%20app/envs/dev/secrets").ok(),
+            Some(route("my app", "dev", Endpoint::List))
+        );
+        assert_eq!(
+            parse_route("/v1/projects/api/envs/prod/secrets/DB_URL?x=1").ok(),
+            Some(route("api", "prod", Endpoint::Secret("DB_URL".into())))
+        );
+        assert_eq!(
+            parse_route("/v1/projects/api/envs/prod/env").ok(),
+            Some(route("api", "prod", Endpoint::Env))
+        );
+        assert_eq!(parse_route("/v1/projects/api").err().unwrap().status, 404);
+        assert_eq!(
+            parse_route("/v1/projects/api/envs//secrets")
+                .err()
+                .unwrap()
+                .status,
+            404
+        );
+        assert_eq!(
+            parse_route("/v1/projects/a%zz/envs/dev/env")
+                .err()
+                .unwrap()
+                .status,
+            400
+        );
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match(b"secret-token", b"secret-token"));
+        assert!(!tokens_match(b"secret-tokem", b"secret-token"));
+        assert!(!tokens_match(b"secret", b"secret-token"));
+        assert!(!tokens_match(b"", b"secret-token"));
+    }
+}
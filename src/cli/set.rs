@@ -1,23 +1,72 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
+use std::io::{IsTerminal, Read};
+use std::path::Path;
 
-use crate::cli::prompt_passphrase;
-use crate::store::Store;
+use crate::cli::{check_key_names, check_value_rules, open_store};
+use crate::config::{Config, GlobalConfig};
+use crate::git;
+use crate::permissions;
+use crate::ui;
 
-pub fn run(project: &str, environment: &str, key: &str, value: Option<&str>) -> Result<()> {
-    let passphrase = prompt_passphrase()?;
-    let store = Store::open(passphrase)?;
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    project: &str,
+    environment: &str,
+    key: &str,
+    value: Option<&str>,
+    stdin: bool,
+    from_file: Option<&Path>,
+    json_from_file: Option<&Path>,
+    no_editor_temp: bool,
+    description: Option<&str>,
+    reason: Option<&str>,
+    no_verify: bool,
+    config: Option<&Config>,
+) -> Result<()> {
+    // Read piped input before anything else touches the terminal
+    let piped = if stdin {
+        Some(read_stdin()?)
+    } else if no_editor_temp {
+        Some(read_hidden_lines()?)
+    } else {
+        None
+    };
+
+    if !no_verify && !check_key_names(config, [key])?.is_empty() {
+        anyhow::bail!(
+            "{} breaks the key naming policy (use --no-verify to set it anyway)",
+            key
+        );
+    }
 
-    let secret_value = match value {
-        Some(v) => v.to_string(),
-        None => {
+    let mut store = open_store()?;
+    store.set_reason(reason);
+    if GlobalConfig::load()?.history.record_git {
+        store.set_git_context(git::context());
+    }
+
+    let secret_value: Vec<u8> = match (value, piped, from_file, json_from_file) {
+        (Some(v), _, _, _) => v.as_bytes().to_vec(),
+        (None, Some(v), _, _) => v.into_bytes(),
+        (None, None, Some(path), _) => {
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?
+        }
+        (None, None, None, Some(path)) => read_json_file(path)?,
+        (None, None, None, None) => {
             // Open editor for multiline/sensitive input
-            let template = format!(
+            let mut template = format!(
                 "# Enter the value for {}/{}/{}\n# Lines starting with # will be ignored\n",
                 project, environment, key
             );
+            if store.get(project, environment, key)?.is_some() {
+                template.push_str(&format!(
+                    "# To change the current value instead, use `tinysecrets edit {}`\n",
+                    key
+                ));
+            }
 
-            let edited = edit::edit(&template)
+            let edited = permissions::edit(&template)
                 .context("Failed to open editor. Set $EDITOR or pass value directly.")?;
 
             // Filter out comments and trim
@@ -27,23 +76,54 @@ pub fn run(project: &str, environment: &str, key: &str, value: Option<&str>) ->
                 .collect::<Vec<_>>()
                 .join("\n")
                 .trim()
-                .to_string()
+                .as_bytes()
+                .to_vec()
         }
     };
 
     if secret_value.is_empty() {
         anyhow::bail!("Secret value cannot be empty");
     }
+    if !check_value_rules(config, key, &secret_value)? {
+        anyhow::bail!("{} breaks the [validate] rules in .tinysecrets.toml", key);
+    }
+
+    // Check `{{ref:...}}` references now, so a typo doesn't break `run` later
+    if let Ok(text) = std::str::from_utf8(&secret_value) {
+        let values = [(key.to_string(), text.to_string())];
+        if let Some((_, e)) = store.unresolvable_refs(project, environment, &values).pop() {
+            if !no_verify {
+                anyhow::bail!(
+                    "{:#} (escape a literal one as \\{{{{ref:, or use --no-verify to set it anyway)",
+                    e
+                );
+            }
+            eprintln!("{} {:#}", ui::warn(), e);
+        }
+    }
 
-    // Check if updating existing
-    let existing = store.get(project, environment, key)?;
+    // Check if updating existing (in this environment, not inherited)
+    let existing = store.entry(project, environment, key)?;
 
-    store.set(project, environment, key, &secret_value, None)?;
+    let written = store.set_bytes(project, environment, key, &secret_value, description, None)?;
 
-    if existing.is_some() {
+    if !written {
+        eprintln!(
+            "{} {}/{}/{} already has this value (no new version){}",
+            ui::note(),
+            project.cyan(),
+            environment.yellow(),
+            key.bold(),
+            if description.is_some() {
+                "; description updated"
+            } else {
+                ""
+            }
+        );
+    } else if existing.is_some() {
         eprintln!(
             "{} Updated {}/{}/{}",
-            "✓".green(),
+            ui::ok(),
             project.cyan(),
             environment.yellow(),
             key.bold()
@@ -51,7 +131,7 @@ pub fn run(project: &str, environment: &str, key: &str, value: Option<&str>) ->
     } else {
         eprintln!(
             "{} Created {}/{}/{}",
-            "✓".green(),
+            ui::ok(),
             project.cyan(),
             environment.yellow(),
             key.bold()
@@ -60,3 +140,89 @@ pub fn run(project: &str, environment: &str, key: &str, value: Option<&str>) ->
 
     Ok(())
 }
+
+/// Read a JSON document, refusing anything that doesn't parse
+fn read_json_file(path: &Path) -> Result<Vec<u8>> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice::<serde::de::IgnoredAny>(&contents)
+        .with_context(|| format!("{} isn't valid JSON", path.display()))?;
+    Ok(contents.trim_ascii_end().to_vec())
+}
+
+/// Read the value from stdin, prompting without echo if it's a terminal
+fn read_stdin() -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        return rpassword::prompt_password("Value: ").context("Failed to read value");
+    }
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("Failed to read value from stdin")?;
+    Ok(strip_trailing_newline(input))
+}
+
+/// Read a multi-line value without echo, ending at Ctrl-D on an empty line
+fn read_hidden_lines() -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("--no-editor-temp needs a terminal; pipe the value with --stdin instead");
+    }
+    std::eprintln!("Enter the value (hidden), then Ctrl-D on an empty line:");
+    collect_lines(rpassword::read_password).context("Failed to read value")
+}
+
+/// Join lines from `next_line` until end of input, dropping trailing blank lines
+fn collect_lines(
+    mut next_line: impl FnMut() -> std::io::Result<String>,
+) -> std::io::Result<String> {
+    let mut lines = Vec::new();
+    loop {
+        match next_line() {
+            Ok(line) => lines.push(line),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Drop a single trailing newline (as added by `echo` or `openssl rand`)
+fn strip_trailing_newline(mut value: String) -> String {
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_trailing_newline() {
+        assert_eq!(strip_trailing_newline("abc\n".into()), "abc");
+        assert_eq!(strip_trailing_newline("abc\r\n".into()), "abc");
+        assert_eq!(strip_trailing_newline("a\nb\n\n".into()), "a\nb\n");
+        assert_eq!(strip_trailing_newline("abc".into()), "abc");
+    }
+
+    #[test]
+    fn test_collect_lines() {
+        let mut input = vec!["-----BEGIN KEY-----", "abc", "-----END KEY-----", ""].into_iter();
+        let value = collect_lines(|| {
+            input
+                .next()
+                .map(String::from)
+                .ok_or_else(|| std::io::ErrorKind::UnexpectedEof.into())
+        })
+        .unwrap();
+        assert_eq!(value, "-----BEGIN KEY-----\nabc\n-----END KEY-----");
+    }
+}
@@ -0,0 +1,273 @@
+//! `tinysecrets setup`: one guided path from nothing to a working directory
+//!
+//! Creates (or unlocks) the store, picks a project and environment, points
+//! `.tinysecrets.toml` at them and offers to import an existing `.env`.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::import_env::{parse_dotenv, KeyTransform};
+use crate::cli::import_sops::import_pairs;
+use crate::cli::{open_store, prompt_new_passphrase, warn_if_synced};
+use crate::config::Config;
+use crate::store::{Store, Summary};
+use crate::ui;
+
+pub fn run(
+    project: Option<&str>,
+    environment: Option<&str>,
+    env_file: Option<&Path>,
+    full_encryption: bool,
+) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        anyhow::bail!(
+            "setup asks questions, so it needs a terminal. In scripts, use \
+             `tinysecrets init` and `tinysecrets config init <project> [environment]`"
+        );
+    }
+    let config = if Config::config_path()?.exists() {
+        Config::load()?
+    } else {
+        None
+    };
+
+    // 1. The store
+    step(1, "Store");
+    let path = Store::path()?;
+    let store = if Store::exists()? {
+        eprintln!("  Found a store at {}", path.display().to_string().cyan());
+        open_store()?
+    } else {
+        eprintln!(
+            "  No store yet; creating one at {}",
+            path.display().to_string().cyan()
+        );
+        let passphrase = prompt_new_passphrase(None, true)?;
+        let store = Store::init(passphrase, full_encryption)?;
+        eprintln!("  {} Store created", ui::ok());
+        eprintln!(
+            "  {} {}",
+            ui::warn(),
+            "Remember your passphrase! It cannot be recovered.".yellow()
+        );
+        warn_if_synced(&path)?;
+        store
+    };
+
+    // 2. Project and environment
+    step(2, "Project and environment");
+    let project = match project {
+        Some(project) => project.to_string(),
+        None => {
+            let default = config
+                .as_ref()
+                .and_then(|c| c.project.clone())
+                .or_else(directory_name)
+                .unwrap_or_else(|| "myapp".to_string());
+            choose("Project", &store.project_summaries()?, &default)?
+        }
+    };
+    let environment = match environment {
+        Some(environment) => environment.to_string(),
+        None => {
+            let default = config
+                .as_ref()
+                .and_then(|c| c.environment.clone())
+                .unwrap_or_else(|| "dev".to_string());
+            choose(
+                "Environment",
+                &store.environment_summaries(&project)?,
+                &default,
+            )?
+        }
+    };
+
+    // 3. .tinysecrets.toml
+    step(3, "Project config");
+    let unchanged = config.as_ref().is_some_and(|c| {
+        c.project.as_deref() == Some(project.as_str())
+            && c.environment.as_deref() == Some(environment.as_str())
+    });
+    if unchanged {
+        eprintln!(
+            "  {} {} already points at {}/{}",
+            ui::ok(),
+            Config::config_path()?.display().to_string().cyan(),
+            project.cyan(),
+            environment.yellow()
+        );
+    } else {
+        // Keep whatever else an existing file configures
+        let mut config = config.unwrap_or_default();
+        config.project = Some(project.clone());
+        config.environment = Some(environment.clone());
+        let saved = config.save()?;
+        eprintln!(
+            "  {} Wrote {} (project {}, environment {})",
+            ui::ok(),
+            saved.display().to_string().cyan(),
+            project.cyan(),
+            environment.yellow()
+        );
+    }
+
+    // 4. An existing .env
+    step(4, "Existing secrets");
+    let env_file = env_file
+        .map(Path::to_path_buf)
+        .or_else(|| Some(PathBuf::from(".env")).filter(|p| p.is_file()));
+    match env_file {
+        Some(file) => import_env_file(&store, &project, &environment, &file)?,
+        None => eprintln!("  {} No .env file here to import", ui::note()),
+    }
+
+    eprintln!();
+    eprintln!("{}", "All set. Next:".bold());
+    eprintln!(
+        "  {} add a secret      tinysecrets set DATABASE_URL",
+        ui::arrow()
+    );
+    eprintln!("  {} list them         tinysecrets list", ui::arrow());
+    eprintln!(
+        "  {} run with secrets  tinysecrets run -- npm start",
+        ui::arrow()
+    );
+    Ok(())
+}
+
+fn step(number: usize, title: &str) {
+    eprintln!();
+    eprintln!("{}", format!("{}. {}", number, title).bold());
+}
+
+/// Offer to import a dotenv file; lines that don't parse are listed and skipped
+fn import_env_file(store: &Store, project: &str, environment: &str, file: &Path) -> Result<()> {
+    let contents = zeroize::Zeroizing::new(
+        std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?,
+    );
+    let mut pairs = Vec::new();
+    for entry in parse_dotenv(&contents) {
+        match entry {
+            Ok(pair) => pairs.push(pair),
+            Err(bad) => eprintln!(
+                "  {} {} (line {}: {})",
+                ui::note(),
+                bad.text.dimmed(),
+                bad.line,
+                bad.reason
+            ),
+        }
+    }
+    if pairs.is_empty() {
+        eprintln!("  {} {} has no entries", ui::note(), file.display());
+        return Ok(());
+    }
+
+    let question = format!(
+        "  Import {} keys from {} into {}/{}?",
+        pairs.len(),
+        file.display(),
+        project,
+        environment
+    );
+    if !ask_yes(&question)? {
+        eprintln!("  {} Skipped", ui::note());
+        return Ok(());
+    }
+    import_pairs(
+        store,
+        project,
+        environment,
+        pairs,
+        &format!("file:{}", file.display()),
+        &file.display().to_string(),
+        &KeyTransform::default(),
+        None,
+        false,
+    )
+}
+
+/// Pick one of `existing` by number, or type a name (enter takes `default`)
+fn choose(label: &str, existing: &[Summary], default: &str) -> Result<String> {
+    if !existing.is_empty() {
+        for (i, summary) in existing.iter().enumerate() {
+            eprintln!(
+                "  {:>2}) {}  {}",
+                i + 1,
+                summary.name,
+                format!("{} secret(s)", summary.secrets).dimmed()
+            );
+        }
+    }
+    loop {
+        let hint = if existing.is_empty() {
+            "name"
+        } else {
+            "number or name"
+        };
+        let answer = ask(&format!("  {} ({}) [{}]: ", label, hint, default.cyan()))?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(default.to_string());
+        }
+        if let Ok(n) = answer.parse::<usize>() {
+            if let Some(summary) = n.checked_sub(1).and_then(|i| existing.get(i)) {
+                return Ok(summary.name.clone());
+            }
+        }
+        match valid_name(answer) {
+            Ok(()) => return Ok(answer.to_string()),
+            Err(reason) => std::eprintln!("  {} {}", ui::fail(), reason),
+        }
+    }
+}
+
+/// Names end up in paths, URLs and shell commands, so keep them simple
+fn valid_name(name: &str) -> Result<(), &'static str> {
+    if name.starts_with('-') {
+        return Err("Names can't start with '-'");
+    }
+    if name.chars().any(|c| c.is_whitespace() || c == '/') {
+        return Err("Names can't contain spaces or '/'");
+    }
+    Ok(())
+}
+
+/// The current directory's name, as a default project
+fn directory_name() -> Option<String> {
+    let dir = std::env::current_dir().ok()?;
+    let name = dir.file_name()?.to_str()?;
+    valid_name(name).ok().map(|_| name.to_string())
+}
+
+fn ask(question: &str) -> Result<String> {
+    std::eprint!("{}", question);
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().lock().read_line(&mut input)?;
+    Ok(input)
+}
+
+/// A yes/no question, default yes
+fn ask_yes(question: &str) -> Result<bool> {
+    let input = ask(&format!("{} [Y/n] ", question))?;
+    let input = input.trim().to_lowercase();
+    Ok(input.is_empty() || input == "y" || input == "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_name() {
+        assert!(valid_name("api").is_ok());
+        assert!(valid_name("web-app_2").is_ok());
+        assert!(valid_name("my app").is_err());
+        assert!(valid_name("api/prod").is_err());
+        assert!(valid_name("-p").is_err());
+    }
+}
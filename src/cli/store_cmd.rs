@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::PathBuf;
+
+use crate::cli::StoreAction;
+use crate::config::GlobalConfig;
+use crate::store::Store;
+use crate::ui;
+
+pub fn run(action: StoreAction) -> Result<()> {
+    match action {
+        StoreAction::Move { destination } => move_store(&destination),
+    }
+}
+
+fn move_store(destination: &str) -> Result<()> {
+    let from = Store::path()?;
+    // Saved in the global config, so it mustn't depend on where this ran
+    let to = std::path::absolute(expand_home(destination)?)
+        .with_context(|| format!("Invalid store path: {}", destination))?;
+
+    Store::relocate(&from, &to)?;
+
+    let mut config = GlobalConfig::load()?;
+    config.remember_store_path(to.clone())?;
+    let config_path = config.save()?;
+
+    eprintln!(
+        "{} Moved store to {}",
+        ui::ok(),
+        to.display().to_string().cyan()
+    );
+    eprintln!(
+        "  Location saved in {}",
+        config_path.display().to_string().dimmed()
+    );
+
+    if let Some(provider) = crate::store::synced_folder_provider(&to) {
+        eprintln!(
+            "{} New location is still inside a {} folder",
+            ui::warn(),
+            provider
+        );
+    }
+
+    Ok(())
+}
+
+/// Expand a leading `~/` to the home directory
+fn expand_home(path: &str) -> Result<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(dirs::home_dir()
+            .context("Could not find home directory")?
+            .join(rest)),
+        None => Ok(PathBuf::from(path)),
+    }
+}
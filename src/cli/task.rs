@@ -0,0 +1,78 @@
+//! `up` and `x <name>`: project-local commands from `[commands]` in .tinysecrets.toml
+
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::run;
+use crate::config::Config;
+use crate::ui;
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    name: &str,
+    args: &[String],
+    config: Option<&Config>,
+    check: bool,
+) -> Result<()> {
+    let Some(config) = config else {
+        anyhow::bail!(
+            "No .tinysecrets.toml found. Define commands under [commands], e.g.\n\n  [commands]\n  {} = \"npm run dev\"",
+            name
+        );
+    };
+    let Some(command) = config.command(name, environment) else {
+        let mut available: Vec<&str> = config.commands.keys().map(String::as_str).collect();
+        available.retain(|n| config.command(n, environment).is_some());
+        if available.is_empty() {
+            anyhow::bail!(
+                "No command '{}' for {}. Add one under [commands] in .tinysecrets.toml",
+                name,
+                environment
+            );
+        }
+        anyhow::bail!(
+            "No command '{}' for {} (available: {})",
+            name,
+            environment,
+            available.join(", ")
+        );
+    };
+
+    eprintln!("{} {}", ui::arrow(), command.bold());
+    run::run(
+        project,
+        environment,
+        &shell_command(command, args),
+        false,
+        Some(config),
+        check,
+        false,
+        None,
+    )
+}
+
+/// Run `command` through `sh -c`, passing `args` on as "$@"
+fn shell_command(command: &str, args: &[String]) -> Vec<String> {
+    let mut argv = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!("{} \"$@\"", command),
+        "sh".to_string(),
+    ];
+    argv.extend_from_slice(args);
+    argv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_command() {
+        assert_eq!(
+            shell_command("npm test", &["--watch".to_string()]),
+            vec!["sh", "-c", "npm test \"$@\"", "sh", "--watch"]
+        );
+    }
+}
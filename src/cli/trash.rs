@@ -0,0 +1,124 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use colored::Colorize;
+
+use crate::cli::list::relative_time;
+use crate::cli::{confirm_destructive, open_store, TrashAction};
+use crate::config::ConfigResolver;
+use crate::errors;
+use crate::ui;
+
+pub fn run(action: TrashAction) -> Result<()> {
+    match action {
+        TrashAction::List {
+            project,
+            environment,
+        } => list(project.as_deref(), environment.as_deref()),
+        TrashAction::Empty {
+            older_than,
+            project,
+            environment,
+            force,
+        } => empty(
+            older_than,
+            project.as_deref(),
+            environment.as_deref(),
+            force,
+        ),
+    }
+}
+
+/// Bring back a deleted secret with the value it was deleted with
+pub fn run_undelete(project: &str, environment: &str, key: &str) -> Result<()> {
+    let store = open_store()?;
+
+    match store.undelete(project, environment, key)? {
+        Some(version) => {
+            eprintln!(
+                "{} Restored {}/{}/{} as v{}",
+                ui::ok(),
+                project.cyan(),
+                environment.yellow(),
+                key.bold(),
+                version
+            );
+            Ok(())
+        }
+        None => Err(errors::not_found(format!(
+            "{}/{}/{} isn't in the trash (see `tinysecrets trash list`)",
+            project, environment, key
+        ))),
+    }
+}
+
+fn list(project: Option<&str>, environment: Option<&str>) -> Result<()> {
+    let store = open_store()?;
+    let project = project
+        .map(|p| ConfigResolver::new().map(|r| r.alias(p)))
+        .transpose()?;
+    let trashed = store.trash(project.as_deref(), environment)?;
+
+    if trashed.is_empty() {
+        eprintln!("{} Trash is empty", ui::note());
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    for secret in &trashed {
+        let mut line = format!(
+            "{}/{}/{}  {}  {}",
+            secret.project.cyan(),
+            secret.environment.yellow(),
+            secret.key.bold(),
+            format!("v{}", secret.version).dimmed(),
+            format!("deleted {}", relative_time(secret.deleted_at, now)).dimmed()
+        );
+        if let Some(description) = &secret.description {
+            line.push_str(&format!("  {}", description.italic()));
+        }
+        println!("{}", line);
+    }
+    eprintln!(
+        "{} Restore one with `tinysecrets undelete -p <project> -e <env> <KEY>`",
+        ui::info()
+    );
+    Ok(())
+}
+
+fn empty(
+    older_than: Option<Duration>,
+    project: Option<&str>,
+    environment: Option<&str>,
+    force: bool,
+) -> Result<()> {
+    let store = open_store()?;
+    let project = project
+        .map(|p| ConfigResolver::new().map(|r| r.alias(p)))
+        .transpose()?;
+    let cutoff = older_than.map(|age| Utc::now() - age);
+
+    let count = store
+        .trash(project.as_deref(), environment)?
+        .iter()
+        .filter(|t| cutoff.is_none_or(|cutoff| t.deleted_at < cutoff))
+        .count();
+    if count == 0 {
+        eprintln!("{} Nothing to purge", ui::note());
+        return Ok(());
+    }
+
+    confirm_destructive(
+        &format!(
+            "Permanently delete {} trashed secret(s) and their history?",
+            count
+        ),
+        force,
+    )?;
+    let purged = store.empty_trash(project.as_deref(), environment, cutoff)?;
+    eprintln!(
+        "{} Purged {} secret(s) from the trash",
+        ui::ok(),
+        purged.len().to_string().bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::open_store;
+use crate::store::Store;
+use crate::ui;
+
+pub fn run(chain: bool, anchor: Option<&str>) -> Result<()> {
+    let store = open_store()?;
+
+    let mut failed = verify_values(&store)?;
+    if chain {
+        failed |= verify_chain(&store, anchor)?;
+    }
+
+    if failed {
+        std::eprintln!("{} Verification failed", ui::fail());
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Decrypt every current and historical value; returns whether any failed
+fn verify_values(store: &Store) -> Result<bool> {
+    let mut checked = 0;
+    let mut broken = Vec::new();
+    for table in ["secrets", "secret_history"] {
+        let mut stmt = store.connection().prepare(&format!(
+            "SELECT project, environment, key, version, encrypted_value FROM {} ORDER BY id",
+            table
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+        for row in rows {
+            let (project, environment, key, version, encrypted) = row?;
+            checked += 1;
+            if store.decrypt_bytes(&encrypted).is_err() {
+                let key = store.key_name(&key)?;
+                broken.push(format!("{}/{}/{} v{}", project, environment, key, version));
+            }
+        }
+    }
+
+    if broken.is_empty() {
+        eprintln!(
+            "{} All {} values decrypt",
+            ui::ok(),
+            checked.to_string().bold()
+        );
+        return Ok(false);
+    }
+    std::eprintln!(
+        "{} {} of {} values don't decrypt:",
+        ui::fail(),
+        broken.len(),
+        checked
+    );
+    for label in &broken {
+        std::eprintln!("  {} {}", ui::branch(), label);
+    }
+    Ok(true)
+}
+
+/// Check the history and audit log hash chains; returns whether they're broken
+fn verify_chain(store: &Store, anchor: Option<&str>) -> Result<bool> {
+    let report = store.verify_chain(anchor)?;
+
+    if report.intact() {
+        eprintln!(
+            "{} History chain intact ({} history rows, {} audit log entries)",
+            ui::ok(),
+            report.history_rows.to_string().bold(),
+            report.audit_entries
+        );
+    } else {
+        std::eprintln!("{} History chain broken:", ui::fail());
+        for problem in &report.problems {
+            std::eprintln!("  {} {}", ui::branch(), problem);
+        }
+    }
+    if report.unchained > 0 {
+        eprintln!(
+            "{} {} history rows predate chaining; the next change to the store chains them",
+            ui::note(),
+            report.unchained
+        );
+    }
+    if anchor.is_some() && report.intact() {
+        eprintln!("{} History matches the anchor", ui::ok());
+    }
+    if let Some(anchor) = report.anchor.as_ref().filter(|_| report.intact()) {
+        println!("{}", anchor);
+        eprintln!(
+            "  {}",
+            "Keep this anchor outside the store; `verify --chain --anchor <it>` later proves \
+             history up to here wasn't rewritten."
+                .dimmed()
+        );
+    }
+    Ok(!report.intact())
+}
@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use secrecy::{ExposeSecret, SecretString};
+use std::io::BufRead;
+use zeroize::Zeroizing;
+
+use crate::cli::{confirm_destructive, open_store, ViewerAction, PASSPHRASE_ENV_VAR};
+use crate::config::GlobalConfig;
+use crate::ui;
+
+pub fn run(action: ViewerAction) -> Result<()> {
+    match action {
+        ViewerAction::Set { stdin } => set(stdin),
+        ViewerAction::Remove { force } => remove(force),
+    }
+}
+
+fn set(stdin: bool) -> Result<()> {
+    let store = open_store()?;
+    store.require_write()?;
+
+    let passphrase = if stdin {
+        let mut line = Zeroizing::new(String::new());
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("Failed to read passphrase from stdin")?;
+        SecretString::new(line.trim_end_matches(['\r', '\n']).to_string())
+    } else {
+        let pass1 = rpassword::prompt_password("New read-only passphrase: ")?;
+        let pass2 = rpassword::prompt_password("Confirm read-only passphrase: ")?;
+        if pass1 != pass2 {
+            anyhow::bail!("Passphrases do not match");
+        }
+        SecretString::new(pass1)
+    };
+    GlobalConfig::load()?
+        .passphrase_policy
+        .check(passphrase.expose_secret())?;
+
+    store.set_read_only_passphrase(&passphrase)?;
+    eprintln!("{} Read-only passphrase set", ui::ok());
+    eprintln!(
+        "  It can {} and {} but not {} or {}. Hand it to CI as {}.",
+        "run".cyan(),
+        "get".cyan(),
+        "set".cyan(),
+        "delete".cyan(),
+        PASSPHRASE_ENV_VAR.cyan()
+    );
+    Ok(())
+}
+
+fn remove(force: bool) -> Result<()> {
+    let store = open_store()?;
+    store.require_write()?;
+
+    confirm_destructive(
+        "Remove the read-only passphrase? Anything using it will stop working.",
+        force,
+    )?;
+    if store.remove_read_only_passphrase()? {
+        eprintln!("{} Read-only passphrase removed", ui::ok());
+    } else {
+        eprintln!("{} No read-only passphrase was set", ui::note());
+    }
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! `tinysecrets watch`: report secrets being added, changed or deleted
+//!
+//! Polls SQLite's `data_version`, which only moves when another connection
+//! commits, so an idle store costs one pragma per interval. Only metadata is
+//! compared; values are never decrypted.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::cli::open_store;
+use crate::store::{SecretEntry, Store};
+use crate::ui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Added,
+    Changed,
+    Deleted,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Added => "added",
+            Kind::Changed => "changed",
+            Kind::Deleted => "deleted",
+        }
+    }
+}
+
+/// One change, printed as a line (or JSON object) and passed to the hook
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Event {
+    event: Kind,
+    project: String,
+    environment: String,
+    key: String,
+    /// The new version (None once deleted)
+    version: Option<i32>,
+    /// Where the key is defined now, if it's inherited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inherited_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed_by: Option<String>,
+    at: DateTime<Utc>,
+}
+
+/// What's compared between polls, per key
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Seen {
+    environment: String,
+    version: i32,
+    changed_by: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+pub fn run(
+    project: &str,
+    environment: &str,
+    interval: u64,
+    json: bool,
+    hook: &[String],
+) -> Result<()> {
+    let store = open_store()?;
+
+    let mut data_version = store.data_version()?;
+    let mut seen = snapshot(&store, project, environment)?;
+    eprintln!(
+        "{} Watching {}/{} ({} keys); Ctrl-C to stop",
+        ui::info(),
+        project.cyan(),
+        environment.yellow(),
+        seen.len()
+    );
+
+    loop {
+        std::thread::sleep(Duration::from_secs(interval));
+        let current = store.data_version()?;
+        if current == data_version {
+            continue;
+        }
+        data_version = current;
+
+        let next = snapshot(&store, project, environment)?;
+        for event in changes(project, environment, &seen, &next, Utc::now()) {
+            report(&event, json)?;
+            if !hook.is_empty() {
+                run_hook(hook, &event);
+            }
+        }
+        seen = next;
+    }
+}
+
+fn snapshot(store: &Store, project: &str, environment: &str) -> Result<BTreeMap<String, Seen>> {
+    Ok(store
+        .resolved_entries(project, environment)?
+        .into_iter()
+        .map(|entry: SecretEntry| {
+            (
+                entry.key,
+                Seen {
+                    environment: entry.environment,
+                    version: entry.version,
+                    changed_by: entry.changed_by,
+                    updated_at: entry.updated_at,
+                },
+            )
+        })
+        .collect())
+}
+
+/// The events that turn `before` into `after`, in key order
+fn changes(
+    project: &str,
+    environment: &str,
+    before: &BTreeMap<String, Seen>,
+    after: &BTreeMap<String, Seen>,
+    now: DateTime<Utc>,
+) -> Vec<Event> {
+    let event = |kind, key: &str, seen: Option<&Seen>| Event {
+        event: kind,
+        project: project.to_string(),
+        environment: environment.to_string(),
+        key: key.to_string(),
+        version: seen.map(|s| s.version),
+        inherited_from: seen
+            .map(|s| s.environment.clone())
+            .filter(|env| env != environment),
+        changed_by: seen.and_then(|s| s.changed_by.clone()),
+        at: seen.map_or(now, |s| s.updated_at),
+    };
+
+    let mut events = Vec::new();
+    for (key, seen) in after {
+        match before.get(key) {
+            None => events.push(event(Kind::Added, key, Some(seen))),
+            Some(old) if old != seen => events.push(event(Kind::Changed, key, Some(seen))),
+            Some(_) => {}
+        }
+    }
+    for key in before.keys().filter(|key| !after.contains_key(*key)) {
+        events.push(event(Kind::Deleted, key, None));
+    }
+    events.sort_by(|a, b| a.key.cmp(&b.key));
+    events
+}
+
+fn report(event: &Event, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string(event)?);
+        return Ok(());
+    }
+    let kind = match event.event {
+        Kind::Added => event.event.name().green(),
+        Kind::Changed => event.event.name().yellow(),
+        Kind::Deleted => event.event.name().red(),
+    };
+    let mut line = format!(
+        "{} {:<7} {}/{}/{}",
+        event.at.format("%Y-%m-%dT%H:%M:%SZ"),
+        kind,
+        event.project,
+        event.environment,
+        event.key.bold()
+    );
+    if let Some(version) = event.version {
+        line.push_str(&format!(" v{}", version));
+    }
+    if let Some(parent) = &event.inherited_from {
+        line.push_str(&format!(" (from {})", parent));
+    }
+    if let Some(by) = &event.changed_by {
+        line.push_str(&format!(" by {}", by).dimmed().to_string());
+    }
+    println!("{}", line);
+    Ok(())
+}
+
+/// Run the hook for one event, described in TINYSECRETS_EVENT_* variables
+/// (never the value). A failing hook is reported and watching goes on.
+fn run_hook(hook: &[String], event: &Event) {
+    let mut cmd = Command::new(&hook[0]);
+    cmd.args(&hook[1..])
+        .env("TINYSECRETS_EVENT", event.event.name())
+        .env("TINYSECRETS_EVENT_PROJECT", &event.project)
+        .env("TINYSECRETS_EVENT_ENVIRONMENT", &event.environment)
+        .env("TINYSECRETS_EVENT_KEY", &event.key)
+        .env(
+            "TINYSECRETS_EVENT_VERSION",
+            event.version.map(|v| v.to_string()).unwrap_or_default(),
+        );
+    match cmd
+        .status()
+        .with_context(|| format!("Failed to run {}", hook[0]))
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!(
+            "{} Hook {} for {} {}",
+            ui::warn(),
+            status,
+            event.event.name(),
+            event.key
+        ),
+        Err(e) => eprintln!("{} {:#}", ui::warn(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seen(environment: &str, version: i32) -> Seen {
+        Seen {
+            environment: environment.to_string(),
+            version,
+            changed_by: None,
+            updated_at: DateTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_changes() {
+        let before = BTreeMap::from([
+            ("API_KEY".to_string(), seen("prod", 1)),
+            ("DB_URL".to_string(), seen("base", 2)),
+            ("OLD".to_string(), seen("prod", 1)),
+        ]);
+        let after = BTreeMap::from([
+            ("API_KEY".to_string(), seen("prod", 2)),
+            ("DB_URL".to_string(), seen("base", 2)),
+            ("NEW".to_string(), seen("base", 1)),
+        ]);
+
+        let events = changes("app", "prod", &before, &after, Utc::now());
+        let summary: Vec<(Kind, &str, Option<i32>)> = events
+            .iter()
+            .map(|e| (e.event, e.key.as_str(), e.version))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Kind::Changed, "API_KEY", Some(2)),
+                (Kind::Added, "NEW", Some(1)),
+                (Kind::Deleted, "OLD", None),
+            ]
+        );
+        assert_eq!(events[1].inherited_from.as_deref(), Some("base"));
+        assert_eq!(events[0].inherited_from, None);
+        assert!(changes("app", "prod", &after, &after, Utc::now()).is_empty());
+
+        let json = serde_json::to_string(&events[2]).unwrap();
+        assert!(json.contains(r#""event":"deleted""#));
+        assert!(json.contains(r#""version":null"#));
+    }
+}
@@ -0,0 +1,88 @@
+//! Version compatibility between the CLI, stores, and export bundles
+//!
+//! Stores record which tinysecrets version last wrote to them and bundles
+//! record which version exported them, so mixed-version teams get a warning
+//! instead of a silent data-format mismatch.
+
+/// Version of this binary
+pub const CLI_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Newest export bundle format this binary understands
+/// (v2 carries the KDF salt and an HMAC passphrase check instead of age verification,
+/// v3 adds multi-section bundles, v4 always carries an integrity tag)
+pub const BUNDLE_FORMAT_VERSION: i32 = 4;
+
+/// Format written for single-environment bundles
+pub const SINGLE_BUNDLE_FORMAT_VERSION: i32 = 4;
+
+/// First format whose bundles must carry an integrity tag. Older ones are
+/// only imported untagged with `--allow-untagged`.
+pub const TAGGED_BUNDLE_FORMAT_VERSION: i32 = 4;
+
+/// Refuse a bundle format this binary can't read: a newer one, or a version
+/// that was never written (a damaged or hand-edited bundle)
+pub fn check_bundle_version(version: i32) -> anyhow::Result<()> {
+    if version > BUNDLE_FORMAT_VERSION {
+        anyhow::bail!(
+            "Bundle format v{} is newer than this tinysecrets supports (v{}). Upgrade tinysecrets to import it.",
+            version,
+            BUNDLE_FORMAT_VERSION
+        );
+    }
+    if version < 1 {
+        anyhow::bail!(
+            "Unknown bundle format v{}; the bundle may be damaged",
+            version
+        );
+    }
+    Ok(())
+}
+
+/// Parse a `major.minor.patch` version (pre-release suffixes are ignored)
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// True if `version` is newer than this binary
+pub fn is_newer_than_cli(version: &str) -> bool {
+    match (parse_version(version), parse_version(CLI_VERSION)) {
+        (Some(other), Some(ours)) => other > ours,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("0.7.1"), Some((0, 7, 1)));
+        assert_eq!(parse_version("v1.2"), Some((1, 2, 0)));
+        assert_eq!(parse_version("1.0.0-beta.1"), Some((1, 0, 0)));
+        assert_eq!(parse_version("garbage"), None);
+    }
+
+    #[test]
+    fn test_check_bundle_version() {
+        for version in 1..=BUNDLE_FORMAT_VERSION {
+            assert!(check_bundle_version(version).is_ok());
+        }
+        assert!(check_bundle_version(0).is_err());
+        assert!(check_bundle_version(-1).is_err());
+        assert!(check_bundle_version(BUNDLE_FORMAT_VERSION + 1).is_err());
+    }
+
+    #[test]
+    fn test_is_newer_than_cli() {
+        assert!(is_newer_than_cli("999.0.0"));
+        assert!(!is_newer_than_cli("0.0.1"));
+        assert!(!is_newer_than_cli(CLI_VERSION));
+    }
+}
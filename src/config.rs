@@ -1,15 +1,34 @@
-//! Local project configuration for TinySecrets
+//! Configuration for TinySecrets
 //!
 //! Reads `.tinysecrets.toml` from the current directory to provide
-//! default project and environment values.
+//! default project and environment values, and the user-level
+//! `~/.config/tinysecrets/config.toml` for machine-wide settings.
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::attempts::UnlockPolicy;
+use crate::keychain::KeychainConfig;
+use crate::lint::{EnvPolicy, KeyPolicy};
+use crate::strength::PassphrasePolicy;
+use crate::ui::UiConfig;
+use crate::validate::Rule;
 
 const CONFIG_FILE: &str = ".tinysecrets.toml";
+const GLOBAL_CONFIG_FILE: &str = "config.toml";
 const PROJECT_ENV_VAR: &str = "TINYSECRETS_PROJECT";
 const ENVIRONMENT_ENV_VAR: &str = "TINYSECRETS_ENV";
+const MACHINE_ENV_VAR: &str = "TINYSECRETS_MACHINE";
+const CHANGED_BY_ENV_VAR: &str = "TINYSECRETS_CHANGED_BY";
+
+/// The profile chosen for this process (see `select_profile`)
+static PROFILE: OnceLock<Option<(String, Profile)>> = OnceLock::new();
+
+/// Special environment name that resolves to a per-hostname environment
+pub const MACHINE_ENVIRONMENT: &str = "@machine";
 
 /// Local project configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -18,6 +37,47 @@ pub struct Config {
     pub project: Option<String>,
     /// Default environment
     pub environment: Option<String>,
+    /// Keys every environment must define (checked by `check` and `run --check`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    /// Fallback values for keys an environment doesn't define
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defaults: BTreeMap<String, String>,
+    /// Named commands for `up` and `x <name>`, run with secrets injected
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub commands: BTreeMap<String, CommandSpec>,
+    /// Short local names for store projects (`api = "acme-api-service"`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Monorepo subdirectories (relative to this file) and the project and
+    /// environment to use inside each
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub workspace: BTreeMap<String, WorkspaceMember>,
+    /// Rules values must follow, by key or glob (`"*_URL" = { format = "url" }`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub validate: BTreeMap<String, Rule>,
+    /// Naming convention for keys, in place of the global `[key_policy]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_policy: Option<KeyPolicy>,
+}
+
+/// The `[workspace]` entry for the current directory, with its key
+type MatchedMember = (String, WorkspaceMember);
+
+/// A `[workspace."services/api"]` entry; unset fields fall back to the top level
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WorkspaceMember {
+    pub project: Option<String>,
+    pub environment: Option<String>,
+}
+
+/// A `[commands]` entry: one command line, or one per environment with an
+/// optional `default` for the rest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandSpec {
+    Simple(String),
+    PerEnvironment(BTreeMap<String, String>),
 }
 
 impl Config {
@@ -36,6 +96,49 @@ impl Config {
         }
     }
 
+    /// The nearest config file, loaded with the current directory's
+    /// `[workspace]` entry applied over the top-level project and environment,
+    /// plus that entry
+    fn locate() -> Result<Option<(PathBuf, Self, Option<MatchedMember>)>> {
+        let (Some(path), Some(mut config)) = (Self::find_config_file()?, Self::load()?) else {
+            return Ok(None);
+        };
+        let cwd = std::env::current_dir().context("Failed to get current directory")?;
+        let root = path.parent().unwrap_or(Path::new(""));
+        let member = cwd
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| config.workspace_member(relative))
+            .map(|(entry, member)| (entry.to_string(), member.clone()));
+        if let Some((entry, member)) = &member {
+            log::debug!("Workspace member {}", entry);
+            if member.project.is_some() {
+                config.project = member.project.clone();
+            }
+            if member.environment.is_some() {
+                config.environment = member.environment.clone();
+            }
+        }
+        Ok(Some((path, config, member)))
+    }
+
+    /// The `[workspace]` entry covering `dir` (relative to the config file);
+    /// the deepest one wins
+    pub fn workspace_member(&self, dir: &Path) -> Option<(&str, &WorkspaceMember)> {
+        let normalize = |path: &Path| -> PathBuf {
+            path.components()
+                .filter(|c| !matches!(c, Component::CurDir))
+                .collect()
+        };
+        let dir = normalize(dir);
+        self.workspace
+            .iter()
+            .map(|(path, member)| (path, normalize(Path::new(path)), member))
+            .filter(|(_, member_dir, _)| dir.starts_with(member_dir))
+            .max_by_key(|(_, member_dir, _)| member_dir.components().count())
+            .map(|(path, _, member)| (path.as_str(), member))
+    }
+
     /// Find config file by walking up from current directory
     fn find_config_file() -> Result<Option<PathBuf>> {
         let cwd = std::env::current_dir().context("Failed to get current directory")?;
@@ -74,45 +177,146 @@ impl Config {
         let config = Config {
             project: Some(project.to_string()),
             environment: environment.map(String::from),
+            ..Default::default()
         };
         config.save()
     }
 
+    /// Required keys that are neither present nor covered by a default
+    pub fn missing_keys<'a>(&self, present: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let present: Vec<&str> = present.into_iter().collect();
+        self.required
+            .iter()
+            .filter(|key| !present.contains(&key.as_str()) && !self.defaults.contains_key(*key))
+            .cloned()
+            .collect()
+    }
+
+    /// Add `[defaults]` entries for keys not already in `secrets`
+    pub fn apply_defaults(&self, secrets: &mut Vec<(String, String)>) {
+        for (key, value) in &self.defaults {
+            if !secrets.iter().any(|(k, _)| k == key) {
+                secrets.push((key.clone(), value.clone()));
+            }
+        }
+    }
+
+    /// The store project a name refers to: its alias target, or the name itself
+    pub fn resolve_project(&self, name: &str) -> String {
+        self.aliases
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// The command line for `name` in `environment`, if one is configured
+    pub fn command(&self, name: &str, environment: &str) -> Option<&str> {
+        match self.commands.get(name)? {
+            CommandSpec::Simple(command) => Some(command),
+            CommandSpec::PerEnvironment(commands) => commands
+                .get(environment)
+                .or_else(|| commands.get("default"))
+                .map(String::as_str),
+        }
+    }
+
     /// Get the path to the found config file (if any)
     pub fn found_path() -> Result<Option<PathBuf>> {
         Self::find_config_file()
     }
 }
 
+/// Where a project or environment setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// -p/--project or -e/--environment
+    Flag,
+    EnvVar(&'static str),
+    /// Top level of a .tinysecrets.toml
+    File(PathBuf),
+    /// A `[workspace]` entry in a .tinysecrets.toml
+    Workspace(PathBuf, String),
+    /// The active `[profiles]` entry in the global config
+    Profile(String),
+}
+
+impl Source {
+    pub fn label(&self) -> String {
+        match self {
+            Source::Flag => "command-line flag".to_string(),
+            Source::EnvVar(name) => name.to_string(),
+            Source::File(path) => path.display().to_string(),
+            Source::Workspace(path, entry) => {
+                format!("[workspace.\"{}\"] in {}", entry, path.display())
+            }
+            Source::Profile(name) => format!("profile {}", name),
+        }
+    }
+}
+
 /// Helper to resolve project/environment from CLI args or config
 pub struct ConfigResolver {
     config: Option<Config>,
+    /// The config file, if one was found
+    path: Option<PathBuf>,
+    /// The `[workspace]` entry for the current directory
+    member: Option<MatchedMember>,
 }
 
 impl ConfigResolver {
     pub fn new() -> Result<Self> {
-        let config = Config::load()?;
-        Ok(Self { config })
+        Ok(match Config::locate()? {
+            Some((path, config, member)) => Self {
+                config: Some(config),
+                path: Some(path),
+                member,
+            },
+            None => Self {
+                config: None,
+                path: None,
+                member: None,
+            },
+        })
     }
 
-    /// Resolve project: CLI arg > env var > config file
+    /// Resolve project: CLI arg > env var > config file > profile, then `[aliases]`
     pub fn project(&self, cli_arg: Option<&str>) -> Result<String> {
+        self.project_source(cli_arg)
+            .map(|(name, _)| self.alias(&name))
+    }
+
+    /// The store project an `[aliases]` name points at (other names pass through)
+    pub fn alias(&self, name: &str) -> String {
+        let Some(config) = &self.config else {
+            return name.to_string();
+        };
+        let project = config.resolve_project(name);
+        if project != name {
+            log::debug!("Project alias {} -> {}", name, project);
+        }
+        project
+    }
+
+    /// The project name before aliases are applied, and where it came from
+    pub fn project_source(&self, cli_arg: Option<&str>) -> Result<(String, Source)> {
         // 1. CLI arg takes precedence
         if let Some(p) = cli_arg {
-            return Ok(p.to_string());
+            return Ok((p.to_string(), Source::Flag));
         }
 
         // 2. Check environment variable
         if let Ok(p) = std::env::var(PROJECT_ENV_VAR) {
             if !p.is_empty() {
-                return Ok(p);
+                return Ok((p, Source::EnvVar(PROJECT_ENV_VAR)));
             }
         }
 
-        // 3. Fall back to config file
+        // 3. Fall back to config file, then the active profile
         self.config
             .as_ref()
             .and_then(|c| c.project.clone())
+            .map(|p| (p, self.file_source(|m| m.project.is_some())))
+            .or_else(|| profile_default(|p| p.project.clone()))
             .ok_or_else(|| {
                 anyhow::anyhow!(
                     "No project specified. Use -p/--project, set {}, or create a {} file",
@@ -122,24 +326,33 @@ impl ConfigResolver {
             })
     }
 
-    /// Resolve environment: CLI arg > env var > config file
+    /// Resolve environment: CLI arg > env var > config file > profile
+    /// (`@machine` is expanded to this host's environment)
     pub fn environment(&self, cli_arg: Option<&str>) -> Result<String> {
+        self.environment_source(cli_arg)
+            .and_then(|(name, _)| expand_environment(name))
+    }
+
+    /// The environment name before `@machine` is expanded, and where it came from
+    pub fn environment_source(&self, cli_arg: Option<&str>) -> Result<(String, Source)> {
         // 1. CLI arg takes precedence
         if let Some(e) = cli_arg {
-            return Ok(e.to_string());
+            return Ok((e.to_string(), Source::Flag));
         }
 
         // 2. Check environment variable
         if let Ok(e) = std::env::var(ENVIRONMENT_ENV_VAR) {
             if !e.is_empty() {
-                return Ok(e);
+                return Ok((e, Source::EnvVar(ENVIRONMENT_ENV_VAR)));
             }
         }
 
-        // 3. Fall back to config file
+        // 3. Fall back to config file, then the active profile
         self.config
             .as_ref()
             .and_then(|c| c.environment.clone())
+            .map(|e| (e, self.file_source(|m| m.environment.is_some())))
+            .or_else(|| profile_default(|p| p.environment.clone()))
             .ok_or_else(|| {
                 anyhow::anyhow!(
                     "No environment specified. Use -e/--environment, set {}, or create a {} file",
@@ -149,8 +362,439 @@ impl ConfigResolver {
             })
     }
 
+    /// The workspace entry if it set the value, otherwise the file's top level
+    fn file_source(&self, set_by: impl Fn(&WorkspaceMember) -> bool) -> Source {
+        let path = self.path.clone().unwrap_or_default();
+        match &self.member {
+            Some((entry, member)) if set_by(member) => Source::Workspace(path, entry.clone()),
+            _ => Source::File(path),
+        }
+    }
+
+    /// The config file in use, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
     /// Get the loaded config (if any)
     pub fn config(&self) -> Option<&Config> {
         self.config.as_ref()
     }
 }
+
+/// A project or environment from the active profile, tagged with its source
+fn profile_default(field: impl Fn(&Profile) -> Option<String>) -> Option<(String, Source)> {
+    let (name, profile) = active_profile()?;
+    field(profile).map(|value| (value, Source::Profile(name.clone())))
+}
+
+/// Choose the profile for this process: `--profile`/TINYSECRETS_PROFILE, or
+/// the global config's `default_profile`. Call once, before anything reads
+/// settings a profile can override.
+pub fn select_profile(name: Option<&str>) -> Result<()> {
+    let global = GlobalConfig::load()?;
+    let selected = match name.or(global.default_profile.as_deref()) {
+        Some(name) => {
+            let profile = global.profile(name)?.clone();
+            log::debug!("Profile {}", name);
+            Some((name.to_string(), profile))
+        }
+        None => None,
+    };
+    PROFILE
+        .set(selected)
+        .map_err(|_| anyhow::anyhow!("Profile selected more than once"))
+}
+
+/// The profile in use, if any
+pub fn active_profile() -> Option<&'static (String, Profile)> {
+    PROFILE.get().and_then(Option::as_ref)
+}
+
+/// Expand the special `@machine` environment to `host-<hostname>`
+pub fn expand_environment(environment: String) -> Result<String> {
+    if environment == MACHINE_ENVIRONMENT {
+        machine_environment()
+    } else {
+        Ok(environment)
+    }
+}
+
+/// Name of this machine's private environment (`host-<hostname>`).
+/// The hostname can be overridden with TINYSECRETS_MACHINE.
+pub fn machine_environment() -> Result<String> {
+    let name = match std::env::var(MACHINE_ENV_VAR) {
+        Ok(name) if !name.is_empty() => name,
+        _ => hostname()?,
+    };
+    Ok(format!("host-{}", sanitize_hostname(&name)))
+}
+
+/// Who to record as making changes: TINYSECRETS_CHANGED_BY, or `user@hostname`
+pub fn changed_by() -> String {
+    if let Ok(name) = std::env::var(CHANGED_BY_ENV_VAR) {
+        if !name.trim().is_empty() {
+            return name.trim().to_string();
+        }
+    }
+    let user = ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let host = hostname()
+        .map(|h| h.split('.').next().unwrap_or_default().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    format!("{}@{}", user, host)
+}
+
+fn hostname() -> Result<String> {
+    if let Ok(name) = std::env::var("HOSTNAME") {
+        if !name.is_empty() {
+            return Ok(name);
+        }
+    }
+    if let Ok(name) = std::fs::read_to_string("/etc/hostname") {
+        if !name.trim().is_empty() {
+            return Ok(name.trim().to_string());
+        }
+    }
+    let output = std::process::Command::new("hostname")
+        .output()
+        .context("Failed to determine hostname. Set TINYSECRETS_MACHINE instead.")?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if name.is_empty() {
+        anyhow::bail!("Failed to determine hostname. Set TINYSECRETS_MACHINE instead.");
+    }
+    Ok(name)
+}
+
+/// Lowercase, drop the domain part, and replace anything unusual with `-`
+fn sanitize_hostname(name: &str) -> String {
+    let short = name.trim().split('.').next().unwrap_or_default();
+    short
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// User-level configuration (~/.config/tinysecrets/config.toml)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GlobalConfig {
+    /// Custom store location (set by `tinysecrets store move`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_path: Option<PathBuf>,
+    /// Warning toggles
+    #[serde(default)]
+    pub warnings: WarningsConfig,
+    /// Key linting policy for `run`/`export`
+    #[serde(default)]
+    pub env_policy: EnvPolicy,
+    /// Naming convention for new keys
+    #[serde(default)]
+    pub key_policy: KeyPolicy,
+    /// Minimum strength for new store passphrases
+    #[serde(default)]
+    pub passphrase_policy: PassphrasePolicy,
+    /// Output preferences
+    #[serde(default)]
+    pub ui: UiConfig,
+    /// What to record with each change
+    #[serde(default)]
+    pub history: HistoryConfig,
+    /// Rate limiting for wrong passphrases
+    #[serde(default)]
+    pub unlock: UnlockPolicy,
+    /// Keychain entry lifetime
+    #[serde(default)]
+    pub keychain: KeychainConfig,
+    /// Profile to use when neither --profile nor TINYSECRETS_PROFILE is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    /// Named sets of settings, selected with `--profile <name>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A `[profiles.<name>]` entry. Set fields override the rest of the global
+/// config; a `.tinysecrets.toml` still wins for project and environment.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Profile {
+    /// Store to use instead of `store_path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_path: Option<PathBuf>,
+    /// Project when no flag, env var or config file names one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Environment when no flag, env var or config file names one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// Shell command that prints the passphrase (tried before the keychain)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase_command: Option<String>,
+    /// Output preferences in place of the top-level `[ui]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ui: Option<UiConfig>,
+}
+
+/// `[history]` section: extra context stored with each secret change
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HistoryConfig {
+    /// Record the git repo, branch and commit when `set` runs inside a checkout
+    #[serde(default)]
+    pub record_git: bool,
+}
+
+/// Toggles for non-fatal warnings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarningsConfig {
+    /// Warn when the store lives inside a cloud-synced folder
+    #[serde(default = "default_true")]
+    pub synced_folder: bool,
+    /// Warn when other users can read the store or its directory
+    #[serde(default = "default_true")]
+    pub loose_permissions: bool,
+}
+
+impl Default for WarningsConfig {
+    fn default() -> Self {
+        Self {
+            synced_folder: true,
+            loose_permissions: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl GlobalConfig {
+    /// Path to the user-level config file ($XDG_CONFIG_HOME or ~/.config)
+    pub fn path() -> Result<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => dirs::home_dir()
+                .context("Could not find home directory")?
+                .join(".config"),
+        };
+        Ok(base.join("tinysecrets").join(GLOBAL_CONFIG_FILE))
+    }
+
+    /// Load the user-level config, or defaults if it doesn't exist
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// The `[profiles]` entry called `name`
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            let hint = if known.is_empty() {
+                "no profiles are defined".to_string()
+            } else {
+                format!("defined: {}", known.join(", "))
+            };
+            crate::errors::not_found(format!("Unknown profile '{}' ({})", name, hint))
+        })
+    }
+
+    /// Remember where the store lives: in the active profile if there is one,
+    /// otherwise as `store_path` (cleared for the default location)
+    pub fn remember_store_path(&mut self, path: PathBuf) -> Result<()> {
+        match active_profile() {
+            Some((name, _)) => {
+                self.profiles.entry(name.clone()).or_default().store_path = Some(path);
+            }
+            None => {
+                self.store_path = (path != crate::store::Store::default_path()?).then_some(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the user-level config
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let contents = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_hostname() {
+        assert_eq!(
+            sanitize_hostname("Alices-MacBook-Pro.local"),
+            "alices-macbook-pro"
+        );
+        assert_eq!(sanitize_hostname("build box 01\n"), "build-box-01");
+    }
+
+    #[test]
+    fn test_missing_keys() {
+        let config: Config = toml::from_str(
+            r#"
+            required = ["DATABASE_URL", "API_KEY", "LOG_LEVEL"]
+            [defaults]
+            LOG_LEVEL = "info"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.missing_keys(["DATABASE_URL"]), vec!["API_KEY"]);
+        assert!(config.missing_keys(["DATABASE_URL", "API_KEY"]).is_empty());
+    }
+
+    #[test]
+    fn test_commands() {
+        let config: Config = toml::from_str(
+            r#"
+            [commands]
+            up = "npm run dev"
+
+            [commands.migrate]
+            default = "npm run migrate"
+            prod = "npm run migrate -- --safe"
+
+            [commands.seed]
+            dev = "npm run seed"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.command("up", "prod"), Some("npm run dev"));
+        assert_eq!(config.command("migrate", "dev"), Some("npm run migrate"));
+        assert_eq!(
+            config.command("migrate", "prod"),
+            Some("npm run migrate -- --safe")
+        );
+        assert_eq!(config.command("seed", "dev"), Some("npm run seed"));
+        assert_eq!(config.command("seed", "prod"), None);
+        assert_eq!(config.command("deploy", "dev"), None);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let config: Config = toml::from_str(
+            r#"
+            project = "api"
+
+            [aliases]
+            api = "acme-api-service"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.resolve_project("api"), "acme-api-service");
+        assert_eq!(config.resolve_project("billing"), "billing");
+
+        let resolver = ConfigResolver {
+            config: Some(config),
+            path: None,
+            member: None,
+        };
+        assert_eq!(resolver.project(Some("api")).unwrap(), "acme-api-service");
+        assert_eq!(resolver.project(Some("other")).unwrap(), "other");
+    }
+
+    #[test]
+    fn test_workspace_member() {
+        let config: Config = toml::from_str(
+            r#"
+            project = "acme"
+
+            [workspace."services/api"]
+            project = "acme-api"
+
+            [workspace."./services/api/admin"]
+            project = "acme-admin"
+            environment = "staging"
+
+            [workspace."services/web"]
+            project = "acme-web"
+            "#,
+        )
+        .unwrap();
+        let member = |dir: &str| {
+            config
+                .workspace_member(Path::new(dir))
+                .map(|(path, _)| path)
+        };
+
+        assert_eq!(member("services/api"), Some("services/api"));
+        assert_eq!(member("services/api/src/handlers"), Some("services/api"));
+        assert_eq!(
+            member("services/api/admin/ui"),
+            Some("./services/api/admin")
+        );
+        // Whole components only
+        assert_eq!(member("services/api-v2"), None);
+        assert_eq!(member(""), None);
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let mut config = Config::default();
+        config.defaults.insert("A".into(), "default".into());
+        config.defaults.insert("B".into(), "default".into());
+
+        let mut secrets = vec![("A".to_string(), "set".to_string())];
+        config.apply_defaults(&mut secrets);
+        assert_eq!(
+            secrets,
+            vec![
+                ("A".to_string(), "set".to_string()),
+                ("B".to_string(), "default".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config: GlobalConfig = toml::from_str(
+            r#"
+            store_path = "/home/me/store.db"
+            default_profile = "personal"
+
+            [profiles.personal]
+            project = "blog"
+
+            [profiles.work]
+            store_path = "/work/secrets.db"
+            environment = "dev"
+            passphrase_command = "pass show tinysecrets/work"
+            ui = { accessible = true }
+            "#,
+        )
+        .unwrap();
+
+        let work = config.profile("work").unwrap();
+        assert_eq!(work.store_path, Some(PathBuf::from("/work/secrets.db")));
+        assert_eq!(work.environment.as_deref(), Some("dev"));
+        assert!(work.ui.as_ref().is_some_and(|ui| ui.accessible));
+        assert!(config.profile("personal").unwrap().ui.is_none());
+
+        let err = config.profile("wrok").unwrap_err().to_string();
+        assert!(err.contains("defined: personal, work"), "{}", err);
+    }
+}
@@ -12,10 +12,13 @@ use chacha20poly1305::{
     aead::{Aead, KeyInit},
     ChaCha20Poly1305, Nonce,
 };
+use hmac::{Hmac, Mac};
 use rand::RngCore;
 use scrypt::{scrypt, Params};
 use secrecy::{ExposeSecret, Secret, SecretString};
-use std::io::{Read, Write};
+use sha2::Sha256;
+use std::io::Read;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Current encryption format version
 const CRYPTO_VERSION: u8 = 2;
@@ -30,11 +33,144 @@ const SCRYPT_R: u32 = 8;
 const SCRYPT_P: u32 = 1;
 
 /// Salt for key derivation (fixed per-store, stored in metadata)
-const SALT_LEN: usize = 32;
+pub const SALT_LEN: usize = 32;
+
+/// Heap bytes that are wiped on drop and, where the OS allows, locked into RAM so
+/// they can't be written to swap. Holds the master key and decrypted values.
+pub struct SecureBytes {
+    bytes: Box<[u8]>,
+    locked: bool,
+}
+
+impl SecureBytes {
+    /// Allocate `len` zeroed bytes (fill them through `expose_secret_mut`)
+    pub fn zeroed(len: usize) -> Self {
+        let bytes = vec![0u8; len].into_boxed_slice();
+        let locked = memlock::lock(&bytes);
+        Self { bytes, locked }
+    }
+
+    /// Copy `data` into locked memory and wipe the original
+    pub fn take(mut data: Vec<u8>) -> Self {
+        let mut secure = Self::zeroed(data.len());
+        secure.bytes.copy_from_slice(&data);
+        data.zeroize();
+        secure
+    }
+
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn expose_secret_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+impl Drop for SecureBytes {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        if self.locked {
+            memlock::unlock(&self.bytes);
+        }
+    }
+}
+
+impl std::fmt::Debug for SecureBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecureBytes([REDACTED])")
+    }
+}
+
+/// Best-effort page locking; failure (e.g. RLIMIT_MEMLOCK) just means no locking.
+/// Page locks don't nest, so each page counts the live allocations on it and is
+/// only unlocked when the last one goes: a short-lived value freed next to the
+/// master key mustn't unlock the key's page.
+mod memlock {
+    #[cfg(unix)]
+    use std::collections::BTreeMap;
+    #[cfg(unix)]
+    use std::sync::Mutex;
+
+    /// Locked page address -> allocations using it
+    #[cfg(unix)]
+    static PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+    #[cfg(unix)]
+    fn page_size() -> usize {
+        // SAFETY: sysconf has no preconditions
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    /// Start addresses of the pages `bytes` spans
+    #[cfg(unix)]
+    fn pages(bytes: &[u8]) -> impl Iterator<Item = usize> {
+        let size = page_size();
+        let start = bytes.as_ptr() as usize;
+        (start / size * size..start + bytes.len()).step_by(size)
+    }
+
+    #[cfg(unix)]
+    pub fn lock(bytes: &[u8]) -> bool {
+        if bytes.is_empty() {
+            return false;
+        }
+        let mut locked = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: the range is a live allocation owned by the caller
+        if unsafe { libc::mlock(bytes.as_ptr().cast(), bytes.len()) } != 0 {
+            return false;
+        }
+        for page in pages(bytes) {
+            *locked.entry(page).or_insert(0) += 1;
+        }
+        true
+    }
+
+    #[cfg(unix)]
+    pub fn unlock(bytes: &[u8]) {
+        let mut locked = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        for page in pages(bytes) {
+            match locked.get_mut(&page) {
+                Some(count) if *count > 1 => *count -= 1,
+                _ => {
+                    locked.remove(&page);
+                    // SAFETY: the page was locked by `lock` and holds part of
+                    // the caller's live allocation
+                    unsafe {
+                        libc::munlock(page as *const libc::c_void, page_size());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether every page under `bytes` is still locked
+    #[cfg(all(unix, test))]
+    pub fn is_locked(bytes: &[u8]) -> bool {
+        let locked = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        pages(bytes).all(|page| locked.contains_key(&page))
+    }
+
+    #[cfg(not(unix))]
+    pub fn lock(_bytes: &[u8]) -> bool {
+        false
+    }
+
+    #[cfg(not(unix))]
+    pub fn unlock(_bytes: &[u8]) {}
+}
 
 /// Derived master key for fast encryption
 pub struct MasterKey {
-    key: [u8; 32],
+    key: SecureBytes,
+}
+
+impl Clone for MasterKey {
+    fn clone(&self) -> Self {
+        Self {
+            key: SecureBytes::take(self.key.expose_secret().to_vec()),
+        }
+    }
 }
 
 impl MasterKey {
@@ -43,12 +179,12 @@ impl MasterKey {
         let params = Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, 32)
             .map_err(|e| anyhow::anyhow!("Invalid scrypt params: {}", e))?;
 
-        let mut key = [0u8; 32];
+        let mut key = SecureBytes::zeroed(32);
         scrypt(
             passphrase.expose_secret().as_bytes(),
             salt,
             &params,
-            &mut key,
+            key.expose_secret_mut(),
         )
         .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
 
@@ -61,12 +197,48 @@ impl MasterKey {
         rand::thread_rng().fill_bytes(&mut salt);
         salt
     }
+
+    /// Derive a wrapping key from an SSH signature over `challenge`
+    /// (HMAC-SHA256 keyed with the challenge, as in HKDF-extract)
+    pub fn from_signature(signature: &[u8], challenge: &[u8]) -> Self {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(challenge).expect("HMAC accepts any key length");
+        mac.update(SIGNATURE_LABEL);
+        mac.update(signature);
+        Self {
+            key: SecureBytes::take(mac.finalize().into_bytes().to_vec()),
+        }
+    }
+
+    /// Encrypt this key under another one (e.g. the key of a read-only passphrase)
+    pub fn wrap_with(&self, wrapping_key: &MasterKey) -> Result<String> {
+        encrypt_bytes(self.key.expose_secret(), wrapping_key)
+    }
+
+    /// Recover a key encrypted with `wrap_with`
+    pub fn unwrap_with(wrapped: &str, wrapping_key: &MasterKey) -> Result<Self> {
+        let data = BASE64
+            .decode(wrapped)
+            .context("Failed to decode wrapped key")?;
+        if data.first() != Some(&CRYPTO_VERSION) {
+            anyhow::bail!("Unknown wrapped key format");
+        }
+        let key = decrypt_v2(&data[1..], &cipher(wrapping_key)?)?;
+        if key.expose_secret().len() != 32 {
+            anyhow::bail!("Wrapped key has the wrong length");
+        }
+        Ok(Self { key })
+    }
 }
 
 /// Encrypts plaintext using ChaCha20-Poly1305 (fast)
 pub fn encrypt(plaintext: &str, master_key: &MasterKey) -> Result<String> {
-    let cipher = ChaCha20Poly1305::new_from_slice(&master_key.key)
-        .map_err(|e| anyhow::anyhow!("Failed to create cipher: {}", e))?;
+    encrypt_bytes(plaintext.as_bytes(), master_key)
+}
+
+/// Encrypts arbitrary bytes (binary secrets) using ChaCha20-Poly1305
+pub fn encrypt_bytes(plaintext: &[u8], master_key: &MasterKey) -> Result<String> {
+    let cipher = cipher(master_key)?;
 
     // Generate random nonce
     let mut nonce_bytes = [0u8; 12];
@@ -75,7 +247,7 @@ pub fn encrypt(plaintext: &str, master_key: &MasterKey) -> Result<String> {
 
     // Encrypt
     let ciphertext = cipher
-        .encrypt(nonce, plaintext.as_bytes())
+        .encrypt(nonce, plaintext)
         .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
 
     // Format: version || nonce || ciphertext
@@ -87,32 +259,113 @@ pub fn encrypt(plaintext: &str, master_key: &MasterKey) -> Result<String> {
     Ok(BASE64.encode(&output))
 }
 
-/// Decrypts ciphertext - handles both v2 (fast) and v1 (legacy age) formats
+/// True if the ciphertext already uses the current (fast) format
+pub fn is_current_format(ciphertext: &str) -> bool {
+    BASE64
+        .decode(ciphertext)
+        .map(|data| data.first() == Some(&CRYPTO_VERSION))
+        .unwrap_or(false)
+}
+
+/// Decrypts ciphertext to text - handles both v2 (fast) and v1 (legacy age) formats
 pub fn decrypt(
     ciphertext: &str,
     master_key: &MasterKey,
-    passphrase: &SecretString,
+    passphrase: Option<&SecretString>,
 ) -> Result<Secret<String>> {
-    let data = BASE64
-        .decode(ciphertext)
-        .context("Failed to decode base64 ciphertext")?;
+    let bytes = decrypt_bytes(ciphertext, master_key, passphrase)?;
+    let text = std::str::from_utf8(bytes.expose_secret())
+        .context("Secret holds binary data (not UTF-8) - use `get --to-file` or `--base64`")?;
+    Ok(Secret::new(text.to_string()))
+}
+
+/// Decrypts ciphertext to raw bytes (binary secrets)
+pub fn decrypt_bytes(
+    ciphertext: &str,
+    master_key: &MasterKey,
+    passphrase: Option<&SecretString>,
+) -> Result<SecureBytes> {
+    Decryptor::new(master_key, passphrase)?.decrypt_bytes(ciphertext)
+}
 
-    if data.is_empty() {
-        anyhow::bail!("Empty ciphertext");
+/// Values one thread decrypts before it's worth starting another
+const DECRYPT_CHUNK: usize = 64;
+
+/// Most threads `decrypt_many` starts
+const MAX_DECRYPT_THREADS: usize = 8;
+
+/// The cipher set up once, for decrypting many values with the same key
+pub struct Decryptor<'a> {
+    cipher: ChaCha20Poly1305,
+    passphrase: Option<&'a SecretString>,
+}
+
+impl<'a> Decryptor<'a> {
+    pub fn new(master_key: &MasterKey, passphrase: Option<&'a SecretString>) -> Result<Self> {
+        Ok(Self {
+            cipher: cipher(master_key)?,
+            passphrase,
+        })
     }
 
-    let version = data[0];
+    /// Decrypt one value - handles both v2 (fast) and v1 (legacy age) formats
+    pub fn decrypt_bytes(&self, ciphertext: &str) -> Result<SecureBytes> {
+        let data = BASE64
+            .decode(ciphertext)
+            .context("Failed to decode base64 ciphertext")?;
+
+        if data.is_empty() {
+            anyhow::bail!("Empty ciphertext");
+        }
+
+        let version = data[0];
 
-    match version {
-        CRYPTO_VERSION => decrypt_v2(&data[1..], master_key),
-        LEGACY_VERSION => decrypt_legacy(ciphertext, passphrase),
-        _ if is_age_format(&data) => decrypt_legacy(ciphertext, passphrase),
-        _ => anyhow::bail!("Unknown encryption format version: {}", version),
+        match version {
+            CRYPTO_VERSION => decrypt_v2(&data[1..], &self.cipher),
+            LEGACY_VERSION => decrypt_legacy(ciphertext, self.passphrase),
+            _ if is_age_format(&data) => decrypt_legacy(ciphertext, self.passphrase),
+            _ => anyhow::bail!("Unknown encryption format version: {}", version),
+        }
     }
+
+    /// Decrypt values in order, spread over a few threads when there are
+    /// enough of them (legacy values each run scrypt, so they gain the most)
+    pub fn decrypt_many(&self, ciphertexts: &[&str]) -> Vec<Result<SecureBytes>> {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(MAX_DECRYPT_THREADS)
+            .min(ciphertexts.len().div_ceil(DECRYPT_CHUNK));
+        if threads <= 1 {
+            return ciphertexts.iter().map(|c| self.decrypt_bytes(c)).collect();
+        }
+        let chunk_len = ciphertexts.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = ciphertexts
+                .chunks(chunk_len)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|c| self.decrypt_bytes(c))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("decryption thread panicked"))
+                .collect()
+        })
+    }
+}
+
+fn cipher(key: &MasterKey) -> Result<ChaCha20Poly1305> {
+    ChaCha20Poly1305::new_from_slice(key.key.expose_secret())
+        .map_err(|e| anyhow::anyhow!("Failed to create cipher: {}", e))
 }
 
 /// Decrypt v2 format (ChaCha20-Poly1305)
-fn decrypt_v2(data: &[u8], master_key: &MasterKey) -> Result<Secret<String>> {
+fn decrypt_v2(data: &[u8], cipher: &ChaCha20Poly1305) -> Result<SecureBytes> {
     if data.len() < 12 {
         anyhow::bail!("Ciphertext too short");
     }
@@ -120,15 +373,11 @@ fn decrypt_v2(data: &[u8], master_key: &MasterKey) -> Result<Secret<String>> {
     let nonce = Nonce::from_slice(&data[..12]);
     let ciphertext = &data[12..];
 
-    let cipher = ChaCha20Poly1305::new_from_slice(&master_key.key)
-        .map_err(|e| anyhow::anyhow!("Failed to create cipher: {}", e))?;
-
     let plaintext = cipher
         .decrypt(nonce, ciphertext)
         .map_err(|_| anyhow::anyhow!("Decryption failed - invalid key or corrupted data"))?;
 
-    let text = String::from_utf8(plaintext).context("Decrypted data is not valid UTF-8")?;
-    Ok(Secret::new(text))
+    Ok(SecureBytes::take(plaintext))
 }
 
 /// Check if data looks like age-encrypted format
@@ -139,8 +388,12 @@ fn is_age_format(data: &[u8]) -> bool {
     String::from_utf8_lossy(data).starts_with("age-encryption.org")
 }
 
-/// Decrypt legacy age format (v1)
-fn decrypt_legacy(ciphertext: &str, passphrase: &SecretString) -> Result<Secret<String>> {
+/// Decrypt legacy age format (v1), which needs the passphrase itself
+fn decrypt_legacy(ciphertext: &str, passphrase: Option<&SecretString>) -> Result<SecureBytes> {
+    let passphrase = passphrase.context(
+        "This value is in the legacy format, which needs the passphrase to decrypt (not an SSH key). \
+         Set TINYSECRETS_PASSPHRASE, or run `ts migrate` once with the passphrase.",
+    )?;
     let encrypted = BASE64
         .decode(ciphertext)
         .context("Failed to decode base64 ciphertext")?;
@@ -151,7 +404,7 @@ fn decrypt_legacy(ciphertext: &str, passphrase: &SecretString) -> Result<Secret<
             _ => anyhow::bail!("Expected passphrase-encrypted data"),
         };
 
-    let mut decrypted = vec![];
+    let mut decrypted = Zeroizing::new(vec![]);
     let mut reader = decryptor
         .decrypt(passphrase, None)
         .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))?;
@@ -160,13 +413,194 @@ fn decrypt_legacy(ciphertext: &str, passphrase: &SecretString) -> Result<Secret<
         .read_to_end(&mut decrypted)
         .context("Failed to read decrypted data")?;
 
-    let plaintext = String::from_utf8(decrypted).context("Decrypted data is not valid UTF-8")?;
-    Ok(Secret::new(plaintext))
+    Ok(SecureBytes::take(std::mem::take(&mut *decrypted)))
+}
+
+/// Domain-separation label for keys derived from SSH signatures
+const SIGNATURE_LABEL: &[u8] = b"tinysecrets-ssh-unlock-v1";
+
+/// Prefix identifying a v2 (HMAC) passphrase check
+const CHECK_PREFIX: &str = "v2:";
+
+/// Domain-separation label for the passphrase check
+const CHECK_LABEL: &[u8] = b"tinysecrets-verification-v2";
+
+fn check_mac(master_key: &MasterKey) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master_key.key.expose_secret())
+        .expect("HMAC accepts any key length");
+    mac.update(CHECK_LABEL);
+    mac
 }
 
-/// Derives a verification hash from the passphrase (still uses age for compatibility)
-/// This only runs once when opening the store
+/// Derive a fast passphrase check from the master key: HMAC-SHA256 over a fixed
+/// label. Reveals nothing about the key, and verifying it costs no extra KDF run.
+pub fn derive_check(master_key: &MasterKey) -> String {
+    let tag = check_mac(master_key).finalize().into_bytes();
+    format!("{}{}", CHECK_PREFIX, BASE64.encode(tag))
+}
+
+/// Verify a master key against a stored check (constant-time comparison)
+pub fn verify_check(master_key: &MasterKey, check: &str) -> bool {
+    let Some(encoded) = check.strip_prefix(CHECK_PREFIX) else {
+        return false;
+    };
+    let Ok(tag) = BASE64.decode(encoded) else {
+        return false;
+    };
+    check_mac(master_key).verify_slice(&tag).is_ok()
+}
+
+/// Prefix identifying an HMAC-SHA256 document tag
+const TAG_PREFIX: &str = "hmac-sha256:";
+
+/// Domain-separation label for document tags (export bundles)
+const TAG_LABEL: &[u8] = b"tinysecrets-bundle-integrity-v1";
+
+fn tag_mac(master_key: &MasterKey, document: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master_key.key.expose_secret())
+        .expect("HMAC accepts any key length");
+    mac.update(TAG_LABEL);
+    mac.update(document);
+    mac
+}
+
+/// Tag a document so any change to it (including truncation) is detected
+pub fn tag_document(master_key: &MasterKey, document: &[u8]) -> String {
+    let tag = tag_mac(master_key, document).finalize().into_bytes();
+    format!("{}{}", TAG_PREFIX, BASE64.encode(tag))
+}
+
+/// Verify a document against its tag (constant-time comparison)
+pub fn verify_document(master_key: &MasterKey, document: &[u8], tag: &str) -> bool {
+    let Some(encoded) = tag.strip_prefix(TAG_PREFIX) else {
+        return false;
+    };
+    let Ok(tag) = BASE64.decode(encoded) else {
+        return false;
+    };
+    tag_mac(master_key, document).verify_slice(&tag).is_ok()
+}
+
+/// Domain-separation label for the history hash chain
+const CHAIN_LABEL: &[u8] = b"tinysecrets-history-chain-v1";
+
+/// One link of a tamper-evident chain: HMAC-SHA256 over the previous link and
+/// an entry, as hex. Keyed from the master key, so rewriting history and
+/// rebuilding the chain to match takes the passphrase.
+pub fn chain_hash(master_key: &MasterKey, prev: &str, entry: &[u8]) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master_key.key.expose_secret())
+        .expect("HMAC accepts any key length");
+    mac.update(CHAIN_LABEL);
+    mac.update(&(prev.len() as u64).to_be_bytes());
+    mac.update(prev.as_bytes());
+    mac.update(entry);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Domain-separation label for value fingerprints (lockfiles)
+const FINGERPRINT_LABEL: &[u8] = b"tinysecrets-value-fingerprint-v1";
+
+/// Identify a value without revealing it: HMAC-SHA256 keyed from the master
+/// key, first 16 bytes as hex
+pub fn fingerprint(master_key: &MasterKey, value: &[u8]) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master_key.key.expose_secret())
+        .expect("HMAC accepts any key length");
+    mac.update(FINGERPRINT_LABEL);
+    mac.update(value);
+    mac.finalize().into_bytes()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Prefix identifying an encrypted key name
+const NAME_PREFIX: &str = "n1:";
+
+/// Domain-separation labels for the two key-name subkeys
+const NAME_IV_LABEL: &[u8] = b"tinysecrets-key-name-iv-v1";
+const NAME_KEY_LABEL: &[u8] = b"tinysecrets-key-name-key-v1";
+
+/// Deterministic encryption for key names and other lookup columns (SIV
+/// construction): the nonce is an HMAC of the name, so a name always encrypts
+/// to the same text and `WHERE key = ?` still finds it. Only whether two
+/// names are equal (and their length) shows.
+///
+/// Format: "n1:" || base64(nonce(12) || ciphertext || tag(16))
+pub struct NameCipher {
+    iv_key: SecureBytes,
+    cipher: ChaCha20Poly1305,
+}
+
+impl NameCipher {
+    pub fn new(master_key: &MasterKey) -> Result<Self> {
+        let subkey = |label: &[u8]| {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master_key.key.expose_secret())
+                .expect("HMAC accepts any key length");
+            mac.update(label);
+            MasterKey {
+                key: SecureBytes::take(mac.finalize().into_bytes().to_vec()),
+            }
+        };
+        Ok(Self {
+            iv_key: subkey(NAME_IV_LABEL).key,
+            cipher: cipher(&subkey(NAME_KEY_LABEL))?,
+        })
+    }
+
+    fn nonce(&self, name: &str) -> [u8; 12] {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(self.iv_key.expose_secret())
+            .expect("HMAC accepts any key length");
+        mac.update(name.as_bytes());
+        let mut nonce = [0u8; 12];
+        nonce.copy_from_slice(&mac.finalize().into_bytes()[..12]);
+        nonce
+    }
+
+    pub fn encrypt(&self, name: &str) -> Result<String> {
+        let nonce = self.nonce(name);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), name.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Key name encryption failed"))?;
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        Ok(format!("{}{}", NAME_PREFIX, BASE64.encode(data)))
+    }
+
+    pub fn decrypt(&self, stored: &str) -> Result<String> {
+        let encoded = stored
+            .strip_prefix(NAME_PREFIX)
+            .context("Key name isn't encrypted")?;
+        let data = BASE64
+            .decode(encoded)
+            .context("Failed to decode encrypted key name")?;
+        if data.len() < 12 {
+            anyhow::bail!("Encrypted key name too short");
+        }
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&data[..12]), &data[12..])
+            .map_err(|_| {
+                anyhow::anyhow!("Key name decryption failed - invalid key or corrupted data")
+            })?;
+        let name = String::from_utf8(plaintext).context("Decrypted key name isn't UTF-8")?;
+        // The nonce is what makes lookups work, so it has to be the name's own
+        if self.nonce(&name)[..] != data[..12] {
+            anyhow::bail!("Encrypted key name doesn't match its nonce");
+        }
+        Ok(name)
+    }
+}
+
+/// Derives a legacy age-based verification value (new stores use `derive_check`)
+#[cfg(test)]
 pub fn derive_verification(passphrase: &SecretString) -> Result<String> {
+    use std::io::Write;
+
     let encryptor = age::Encryptor::with_user_passphrase(passphrase.clone());
 
     let mut encrypted = vec![];
@@ -183,7 +617,113 @@ pub fn derive_verification(passphrase: &SecretString) -> Result<String> {
     Ok(BASE64.encode(&encrypted))
 }
 
-/// Verifies the passphrase against a stored verification hash
+/// Encrypt to an X25519 public key as a libsodium sealed box (`crypto_box_seal`),
+/// the format GitHub expects for Actions secrets.
+///
+/// Format: ephemeral_pk(32) || tag(16) || ciphertext, where the XSalsa20-Poly1305
+/// key is HSalsa20(X25519(ephemeral_sk, pk)) and the nonce is
+/// BLAKE2b-192(ephemeral_pk || pk).
+pub fn seal(public_key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>> {
+    use x25519_dalek::{EphemeralSecret, PublicKey};
+
+    let recipient = PublicKey::from(*public_key);
+    let ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+    let ephemeral_pk = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&recipient);
+
+    let mut sealed = ephemeral_pk.as_bytes().to_vec();
+    sealed.extend(
+        box_cipher(shared.as_bytes())
+            .encrypt(&seal_nonce(ephemeral_pk.as_bytes(), public_key), plaintext)
+            .map_err(|_| anyhow::anyhow!("Sealed box encryption failed"))?,
+    );
+    Ok(sealed)
+}
+
+/// XSalsa20-Poly1305 keyed like libsodium's `crypto_box_beforenm`
+fn box_cipher(shared: &[u8; 32]) -> crypto_secretbox::XSalsa20Poly1305 {
+    use salsa20::cipher::{consts::U10, generic_array::GenericArray};
+
+    let key = salsa20::hsalsa::<U10>(GenericArray::from_slice(shared), &GenericArray::default());
+    crypto_secretbox::XSalsa20Poly1305::new(&key)
+}
+
+fn seal_nonce(ephemeral_pk: &[u8; 32], public_key: &[u8; 32]) -> crypto_secretbox::Nonce {
+    use blake2::{digest::consts::U24, Blake2b, Digest};
+
+    Blake2b::<U24>::new()
+        .chain_update(ephemeral_pk)
+        .chain_update(public_key)
+        .finalize()
+}
+
+/// Prefix identifying an Ed25519 public key (bundle signers)
+const SIGNER_PREFIX: &str = "ed25519:";
+
+/// A fresh Ed25519 signing key, as its 32-byte seed
+pub fn generate_signing_key() -> SecureBytes {
+    let mut seed = SecureBytes::zeroed(32);
+    rand::thread_rng().fill_bytes(seed.expose_secret_mut());
+    seed
+}
+
+fn signing_key(seed: &SecureBytes) -> Result<ed25519_dalek::SigningKey> {
+    let seed: &[u8; 32] = seed
+        .expose_secret()
+        .try_into()
+        .context("Signing key must be 32 bytes")?;
+    Ok(ed25519_dalek::SigningKey::from_bytes(seed))
+}
+
+/// The public half of a signing key, as `ed25519:<base64>`
+pub fn signer_public_key(seed: &SecureBytes) -> Result<String> {
+    let public = signing_key(seed)?.verifying_key();
+    Ok(format!(
+        "{}{}",
+        SIGNER_PREFIX,
+        BASE64.encode(public.as_bytes())
+    ))
+}
+
+/// Sign a document; the signature is base64
+pub fn sign_document(seed: &SecureBytes, document: &[u8]) -> Result<String> {
+    use ed25519_dalek::Signer;
+
+    Ok(BASE64.encode(signing_key(seed)?.sign(document).to_bytes()))
+}
+
+/// Parse an `ed25519:<base64>` public key, as printed by `security signing-key`
+pub fn parse_signer(public_key: &str) -> Result<ed25519_dalek::VerifyingKey> {
+    let encoded = public_key
+        .trim()
+        .strip_prefix(SIGNER_PREFIX)
+        .context("Signer keys look like ed25519:<base64>")?;
+    let bytes: [u8; 32] = BASE64
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .context("Signer key isn't 32 bytes of base64")?;
+    ed25519_dalek::VerifyingKey::from_bytes(&bytes).context("Signer key isn't a valid Ed25519 key")
+}
+
+/// Check a document's signature by `signer`
+pub fn verify_signature(
+    signer: &ed25519_dalek::VerifyingKey,
+    document: &[u8],
+    signature: &str,
+) -> bool {
+    let Some(bytes) = BASE64
+        .decode(signature)
+        .ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+    else {
+        return false;
+    };
+    let signature = ed25519_dalek::Signature::from_bytes(&bytes);
+    signer.verify_strict(document, &signature).is_ok()
+}
+
+/// Verifies the passphrase against a legacy age-based verification value (slow)
 pub fn verify_passphrase(passphrase: &SecretString, verification: &str) -> bool {
     let Ok(encrypted) = BASE64.decode(verification) else {
         return false;
@@ -221,11 +761,106 @@ mod tests {
         let plaintext = "my-secret-value";
 
         let encrypted = encrypt(plaintext, &master_key).unwrap();
-        let decrypted = decrypt(&encrypted, &master_key, &passphrase).unwrap();
+        let decrypted = decrypt(&encrypted, &master_key, Some(&passphrase)).unwrap();
 
         assert_eq!(decrypted.expose_secret(), plaintext);
     }
 
+    #[test]
+    fn test_secure_bytes() {
+        let secret = SecureBytes::take(b"hunter2".to_vec());
+        assert_eq!(secret.expose_secret(), b"hunter2");
+        assert_eq!(format!("{:?}", secret), "SecureBytes([REDACTED])");
+        assert!(SecureBytes::zeroed(0).expose_secret().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_memlock_counts_pages() {
+        let buffer = vec![0u8; 64].into_boxed_slice();
+        let (first, second) = buffer.split_at(32);
+        if !memlock::lock(first) || !memlock::lock(second) {
+            return; // No mlock here (e.g. RLIMIT_MEMLOCK is 0)
+        }
+        // The page they share stays locked while either needs it
+        memlock::unlock(first);
+        assert!(memlock::is_locked(second));
+        memlock::unlock(second);
+    }
+
+    #[test]
+    fn test_wrapped_key() {
+        let salt = MasterKey::generate_salt();
+        let full = MasterKey::derive(&SecretString::new("full-passphrase".into()), &salt).unwrap();
+        let viewer =
+            MasterKey::derive(&SecretString::new("viewer-passphrase".into()), &salt).unwrap();
+        let other = MasterKey::derive(&SecretString::new("other".into()), &salt).unwrap();
+
+        let wrapped = full.wrap_with(&viewer).unwrap();
+        let unwrapped = MasterKey::unwrap_with(&wrapped, &viewer).unwrap();
+        assert!(verify_check(&unwrapped, &derive_check(&full)));
+        assert!(MasterKey::unwrap_with(&wrapped, &other).is_err());
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let passphrase = SecretString::new("test-passphrase".to_string());
+        let salt = MasterKey::generate_salt();
+        let master_key = MasterKey::derive(&passphrase, &salt).unwrap();
+        let plaintext = [0x30, 0x82, 0xff, 0x00, 0xfe];
+
+        let encrypted = encrypt_bytes(&plaintext, &master_key).unwrap();
+        let decrypted = decrypt_bytes(&encrypted, &master_key, Some(&passphrase)).unwrap();
+
+        assert_eq!(decrypted.expose_secret(), &plaintext);
+        assert!(decrypt(&encrypted, &master_key, Some(&passphrase)).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_many() {
+        let passphrase = SecretString::new("test-passphrase".to_string());
+        let master_key = MasterKey::derive(&passphrase, &MasterKey::generate_salt()).unwrap();
+        let mut encrypted: Vec<String> = (0..300)
+            .map(|i| encrypt(&format!("value-{}", i), &master_key).unwrap())
+            .collect();
+        encrypted[150] = "garbage".to_string();
+        let ciphertexts: Vec<&str> = encrypted.iter().map(String::as_str).collect();
+
+        let decrypted = Decryptor::new(&master_key, None)
+            .unwrap()
+            .decrypt_many(&ciphertexts);
+        assert_eq!(decrypted.len(), 300);
+        for (i, value) in decrypted.iter().enumerate() {
+            match i {
+                150 => assert!(value.is_err()),
+                _ => assert_eq!(
+                    value.as_ref().unwrap().expose_secret(),
+                    format!("value-{}", i).as_bytes()
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_name_cipher_is_deterministic() {
+        let passphrase = SecretString::new("test-passphrase".to_string());
+        let master_key = MasterKey::derive(&passphrase, &MasterKey::generate_salt()).unwrap();
+        let names = NameCipher::new(&master_key).unwrap();
+
+        let encrypted = names.encrypt("DATABASE_URL").unwrap();
+        assert!(encrypted.starts_with(NAME_PREFIX));
+        assert!(!encrypted.contains("DATABASE"));
+        assert_eq!(encrypted, names.encrypt("DATABASE_URL").unwrap());
+        assert_ne!(encrypted, names.encrypt("DATABASE_URI").unwrap());
+        assert_eq!(names.decrypt(&encrypted).unwrap(), "DATABASE_URL");
+
+        let other = MasterKey::derive(&passphrase, &MasterKey::generate_salt()).unwrap();
+        assert!(NameCipher::new(&other)
+            .unwrap()
+            .decrypt(&encrypted)
+            .is_err());
+    }
+
     #[test]
     fn test_wrong_key_fails() {
         let passphrase1 = SecretString::new("correct-passphrase".to_string());
@@ -236,11 +871,115 @@ mod tests {
         let plaintext = "my-secret-value";
 
         let encrypted = encrypt(plaintext, &key1).unwrap();
-        let result = decrypt(&encrypted, &key2, &passphrase2);
+        let result = decrypt(&encrypted, &key2, Some(&passphrase2));
 
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_check() {
+        let passphrase = SecretString::new("test-passphrase".to_string());
+        let salt = MasterKey::generate_salt();
+        let key = MasterKey::derive(&passphrase, &salt).unwrap();
+        let check = derive_check(&key);
+
+        assert!(verify_check(&key, &check));
+
+        let wrong = MasterKey::derive(&SecretString::new("wrong".to_string()), &salt).unwrap();
+        assert!(!verify_check(&wrong, &check));
+        assert!(!verify_check(&key, "garbage"));
+    }
+
+    #[test]
+    fn test_document_tag() {
+        let salt = MasterKey::generate_salt();
+        let key = MasterKey::derive(&SecretString::new("test-passphrase".into()), &salt).unwrap();
+        let tag = tag_document(&key, b"{\"secrets\":[1,2]}");
+
+        assert!(verify_document(&key, b"{\"secrets\":[1,2]}", &tag));
+        assert!(!verify_document(&key, b"{\"secrets\":[1]}", &tag));
+        assert!(!verify_document(
+            &key,
+            b"{\"secrets\":[1,2]}",
+            &derive_check(&key)
+        ));
+
+        let wrong = MasterKey::derive(&SecretString::new("wrong".into()), &salt).unwrap();
+        assert!(!verify_document(&wrong, b"{\"secrets\":[1,2]}", &tag));
+    }
+
+    #[test]
+    fn test_document_signature() {
+        let seed = generate_signing_key();
+        let signer = parse_signer(&signer_public_key(&seed).unwrap()).unwrap();
+        let signature = sign_document(&seed, b"{\"secrets\":[1,2]}").unwrap();
+
+        assert!(verify_signature(
+            &signer,
+            b"{\"secrets\":[1,2]}",
+            &signature
+        ));
+        assert!(!verify_signature(&signer, b"{\"secrets\":[1]}", &signature));
+        assert!(!verify_signature(
+            &signer,
+            b"{\"secrets\":[1,2]}",
+            "bm9wZQ=="
+        ));
+
+        let other = parse_signer(&signer_public_key(&generate_signing_key()).unwrap()).unwrap();
+        assert!(!verify_signature(
+            &other,
+            b"{\"secrets\":[1,2]}",
+            &signature
+        ));
+        assert!(parse_signer("ssh-ed25519 AAAA").is_err());
+    }
+
+    #[test]
+    fn test_seal_opens_with_recipient_key() {
+        use x25519_dalek::{PublicKey, StaticSecret};
+
+        let recipient = StaticSecret::from([7u8; 32]);
+        let public_key = *PublicKey::from(&recipient).as_bytes();
+
+        let sealed = seal(&public_key, b"ghp_secret").unwrap();
+        assert_eq!(sealed.len(), 32 + 16 + b"ghp_secret".len());
+
+        assert_eq!(unseal(&recipient, &sealed), b"ghp_secret");
+
+        // Fresh ephemeral key every time
+        assert_ne!(sealed, seal(&public_key, b"ghp_secret").unwrap());
+    }
+
+    #[test]
+    fn test_seal_matches_libsodium() {
+        use x25519_dalek::StaticSecret;
+
+        // crypto_box_seal(b"ghp_secret") from libsodium 1.0.18, to the public key
+        // of the secret key [7; 32]
+        let sealed = BASE64
+            .decode(
+                "QmiUeaUUvGUrEJ9zzCtt1z72cjuw4X+CHav2Cr+pLiFd0olJQa5dv0y8fsDb6LBvkN3mn5zNys5xTQ==",
+            )
+            .unwrap();
+        assert_eq!(
+            unseal(&StaticSecret::from([7u8; 32]), &sealed),
+            b"ghp_secret"
+        );
+    }
+
+    /// Open a sealed box the way libsodium's `crypto_box_seal_open` does
+    fn unseal(recipient: &x25519_dalek::StaticSecret, sealed: &[u8]) -> Vec<u8> {
+        use x25519_dalek::PublicKey;
+
+        let public_key = *PublicKey::from(recipient).as_bytes();
+        let ephemeral_pk: [u8; 32] = sealed[..32].try_into().unwrap();
+        let shared = recipient.diffie_hellman(&PublicKey::from(ephemeral_pk));
+        box_cipher(shared.as_bytes())
+            .decrypt(&seal_nonce(&ephemeral_pk, &public_key), &sealed[32..])
+            .unwrap()
+    }
+
     #[test]
     fn test_verification() {
         let passphrase = SecretString::new("test-passphrase".to_string());
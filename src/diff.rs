@@ -0,0 +1,209 @@
+//! Line diffs of secret values, for `history --diff`
+
+/// One line of a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A unified-diff hunk: the lines plus where they start in each version (1-based)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<Line<'a>>,
+}
+
+impl Hunk<'_> {
+    /// The `@@ -a,b +c,d @@` header
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )
+    }
+}
+
+/// Past this many line pairs, skip the LCS table and show a full replacement
+const MAX_CELLS: usize = 4_000_000;
+
+/// Line-by-line diff of `old` and `new` (longest common subsequence)
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    if old.len().saturating_mul(new.len()) > MAX_CELLS {
+        return old
+            .iter()
+            .map(|l| Line::Removed(l))
+            .chain(new.iter().map(|l| Line::Added(l)))
+            .collect();
+    }
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| Line::Removed(l)));
+    lines.extend(new[j..].iter().map(|l| Line::Added(l)));
+    lines
+}
+
+/// Group a diff into hunks with `context` unchanged lines around each change
+pub fn hunks<'a>(lines: &[Line<'a>], context: usize) -> Vec<Hunk<'a>> {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Merge changes whose context windows touch into one range
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            // Line numbers where the hunk starts in each version
+            let (mut old_line, mut new_line) = (1, 1);
+            for line in &lines[..start] {
+                match line {
+                    Line::Same(_) => {
+                        old_line += 1;
+                        new_line += 1;
+                    }
+                    Line::Removed(_) => old_line += 1,
+                    Line::Added(_) => new_line += 1,
+                }
+            }
+            let slice = &lines[start..end];
+            let old_len = slice
+                .iter()
+                .filter(|l| !matches!(l, Line::Added(_)))
+                .count();
+            let new_len = slice
+                .iter()
+                .filter(|l| !matches!(l, Line::Removed(_)))
+                .count();
+            Hunk {
+                old_start: if old_len == 0 { old_line - 1 } else { old_line },
+                old_len,
+                new_start: if new_len == 0 { new_line - 1 } else { new_line },
+                new_len,
+                lines: slice.to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Hide a line's content but keep its shape: each run of letters and digits
+/// becomes `***`, while punctuation and indentation stay
+pub fn mask_line(line: &str) -> String {
+    let mut masked = String::with_capacity(line.len());
+    let mut in_word = false;
+    for c in line.chars() {
+        if c.is_alphanumeric() {
+            if !in_word {
+                masked.push_str("***");
+            }
+            in_word = true;
+        } else {
+            masked.push(c);
+            in_word = false;
+        }
+    }
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "{\n  \"user\": \"app\",\n  \"password\": \"old\"\n}";
+        let new = "{\n  \"user\": \"app\",\n  \"password\": \"new\",\n  \"port\": 5432\n}";
+        assert_eq!(
+            diff_lines(old, new),
+            vec![
+                Line::Same("{"),
+                Line::Same("  \"user\": \"app\","),
+                Line::Removed("  \"password\": \"old\""),
+                Line::Added("  \"password\": \"new\","),
+                Line::Added("  \"port\": 5432"),
+                Line::Same("}"),
+            ]
+        );
+        assert!(diff_lines("same", "same")
+            .iter()
+            .all(|l| matches!(l, Line::Same(_))));
+    }
+
+    #[test]
+    fn test_hunks() {
+        let old: String = (1..=20).map(|i| format!("line{}\n", i)).collect();
+        let new = old.replace("line3\n", "LINE3\n").replace("line18\n", "");
+        let lines = diff_lines(&old, &new);
+        let hunks = hunks(&lines, 2);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header(), "@@ -1,5 +1,5 @@");
+        assert_eq!(hunks[1].header(), "@@ -16,5 +16,4 @@");
+        assert_eq!(
+            hunks[1].lines,
+            vec![
+                Line::Same("line16"),
+                Line::Same("line17"),
+                Line::Removed("line18"),
+                Line::Same("line19"),
+                Line::Same("line20"),
+            ]
+        );
+        assert!(super::hunks(&diff_lines("a", "a"), 3).is_empty());
+    }
+
+    #[test]
+    fn test_mask_line() {
+        assert_eq!(
+            mask_line("  \"password\": \"hunter2\","),
+            "  \"***\": \"***\","
+        );
+        assert_eq!(mask_line("-----BEGIN KEY-----"), "-----*** ***-----");
+        assert_eq!(mask_line(""), "");
+    }
+}
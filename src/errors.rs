@@ -0,0 +1,159 @@
+//! Exit statuses and `--error-format json`
+//!
+//! Failures map to a stable exit status and code name, so wrapper scripts can
+//! tell a missing secret from a wrong passphrase without parsing messages.
+
+use crate::store::StoreError;
+use crate::ui;
+
+/// What went wrong, as far as a calling script needs to know
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// Anything without a more specific code
+    Error,
+    /// The secret, version, environment or project doesn't exist
+    NotFound,
+    /// Wrong passphrase
+    BadPassphrase,
+    /// No store at the configured path
+    StoreMissing,
+    /// Keys already exist (`--on-conflict fail`)
+    Conflict,
+    /// Too many wrong passphrases; wait before trying again
+    LockedOut,
+    /// The change needs the full passphrase, not the read-only one
+    ReadOnly,
+    /// Invalid command-line arguments
+    Usage,
+}
+
+impl ErrorCode {
+    /// Process exit status
+    pub fn status(self) -> i32 {
+        match self {
+            Self::Error => 1,
+            Self::NotFound => 2,
+            Self::BadPassphrase => 3,
+            Self::StoreMissing => 4,
+            Self::Conflict => 5,
+            Self::LockedOut => 6,
+            Self::ReadOnly => 7,
+            Self::Usage => 64,
+        }
+    }
+
+    /// Name used in JSON errors
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::NotFound => "not_found",
+            Self::BadPassphrase => "bad_passphrase",
+            Self::StoreMissing => "store_missing",
+            Self::Conflict => "conflict",
+            Self::LockedOut => "locked_out",
+            Self::ReadOnly => "read_only",
+            Self::Usage => "usage",
+        }
+    }
+}
+
+/// An error with a specific code. Shown as a plain `✗ message` line, like the
+/// other failures commands report themselves.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// e.g. "Secret not found: api/prod/KEY"
+pub fn not_found(message: impl Into<String>) -> anyhow::Error {
+    CodedError {
+        code: ErrorCode::NotFound,
+        message: message.into(),
+    }
+    .into()
+}
+
+/// e.g. "2 keys already exist: A, B"
+pub fn conflict(message: impl Into<String>) -> anyhow::Error {
+    CodedError {
+        code: ErrorCode::Conflict,
+        message: message.into(),
+    }
+    .into()
+}
+
+/// The code for an error, from the first cause that has one
+pub fn classify(error: &anyhow::Error) -> ErrorCode {
+    for cause in error.chain() {
+        if let Some(coded) = cause.downcast_ref::<CodedError>() {
+            return coded.code;
+        }
+        if let Some(store_error) = cause.downcast_ref::<StoreError>() {
+            return match store_error {
+                StoreError::InvalidPassphrase => ErrorCode::BadPassphrase,
+                StoreError::TooManyAttempts(_) => ErrorCode::LockedOut,
+                StoreError::ReadOnly => ErrorCode::ReadOnly,
+                StoreError::Missing => ErrorCode::StoreMissing,
+                StoreError::NamesHidden => ErrorCode::Error,
+            };
+        }
+    }
+    ErrorCode::Error
+}
+
+/// `{"code": "not_found", "status": 2, "message": "..."}`
+pub fn to_json(error: &anyhow::Error) -> String {
+    let code = classify(error);
+    serde_json::json!({
+        "code": code.name(),
+        "status": code.status(),
+        "message": format!("{:#}", error),
+    })
+    .to_string()
+}
+
+/// Print an error to stderr, as text or a JSON line
+pub fn report(error: &anyhow::Error, json: bool) {
+    if json {
+        std::eprintln!("{}", to_json(error));
+    } else if let Some(coded) = error.downcast_ref::<CodedError>() {
+        std::eprintln!("{} {}", ui::fail(), coded.message);
+    } else {
+        // Same as returning the error from main
+        std::eprintln!("Error: {:?}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify(&not_found("Secret not found: a/b/C")),
+            ErrorCode::NotFound
+        );
+        let wrapped = Err::<(), _>(StoreError::InvalidPassphrase)
+            .context("Failed to open store")
+            .unwrap_err();
+        assert_eq!(classify(&wrapped), ErrorCode::BadPassphrase);
+        assert_eq!(
+            classify(&StoreError::Missing.into()),
+            ErrorCode::StoreMissing
+        );
+        assert_eq!(classify(&anyhow::anyhow!("disk full")), ErrorCode::Error);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&conflict("2 keys already exist: A, B"))).unwrap();
+        assert_eq!(json["code"], "conflict");
+        assert_eq!(json["status"], 5);
+        assert_eq!(json["message"], "2 keys already exist: A, B");
+    }
+}
@@ -0,0 +1,229 @@
+//! jq-style paths into JSON-valued secrets
+//!
+//! Supports the subset of jq that's useful for pulling a field out of a blob:
+//! `.` (the whole value), `.field`, `."quoted field"`, `.["quoted field"]` and
+//! `[N]` array indexes (negative counts from the end), chained as in
+//! `.credentials.keys[0].id`.
+
+use anyhow::Result;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Field(String),
+    Index(i64),
+}
+
+/// Look up `path` in `value`
+pub fn query<'a>(value: &'a Value, path: &str) -> Result<&'a Value> {
+    let mut current = value;
+    let mut walked = String::new();
+
+    for segment in parse(path)? {
+        current = match (&segment, current) {
+            (Segment::Field(name), Value::Object(map)) => map
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("No field '{}' at {}", name, at(&walked)))?,
+            (Segment::Index(index), Value::Array(items)) => {
+                let resolved = if *index < 0 {
+                    items.len() as i64 + index
+                } else {
+                    *index
+                };
+                usize::try_from(resolved)
+                    .ok()
+                    .and_then(|i| items.get(i))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Index {} is out of range at {} ({} items)",
+                            index,
+                            at(&walked),
+                            items.len()
+                        )
+                    })?
+            }
+            (Segment::Field(name), other) => anyhow::bail!(
+                "Can't read field '{}' of {} at {}",
+                name,
+                kind(other),
+                at(&walked)
+            ),
+            (Segment::Index(index), other) => anyhow::bail!(
+                "Can't index {} with [{}] at {}",
+                kind(other),
+                index,
+                at(&walked)
+            ),
+        };
+        match &segment {
+            Segment::Field(name) if is_identifier(name) => walked.push_str(&format!(".{}", name)),
+            Segment::Field(name) => walked.push_str(&format!(".{}", Value::from(name.as_str()))),
+            Segment::Index(index) => walked.push_str(&format!("[{}]", index)),
+        }
+    }
+
+    Ok(current)
+}
+
+/// Render a query result for output: strings bare (like `jq -r`), anything else as JSON
+pub fn render(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::String(s) => s.clone(),
+        Value::Object(_) | Value::Array(_) => serde_json::to_string_pretty(value)?,
+        other => other.to_string(),
+    })
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>> {
+    let invalid = |reason: &str| anyhow::anyhow!("Invalid path '{}': {}", path, reason);
+    let path = path.trim();
+    if !path.starts_with('.') {
+        return Err(invalid("paths start with '.', e.g. .credentials.password"));
+    }
+
+    let chars: Vec<char> = path.chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                i += 1;
+                match chars.get(i) {
+                    // `.` on its own, or `.[...]`
+                    None | Some('[') => {}
+                    Some('"') => {
+                        let (name, next) =
+                            parse_quoted(&chars, i).ok_or_else(|| invalid("unclosed quote"))?;
+                        segments.push(Segment::Field(name));
+                        i = next;
+                    }
+                    Some(_) => {
+                        let start = i;
+                        while i < chars.len() && is_identifier_char(chars[i]) {
+                            i += 1;
+                        }
+                        if i == start {
+                            return Err(invalid("expected a field name after '.'"));
+                        }
+                        segments.push(Segment::Field(chars[start..i].iter().collect()));
+                    }
+                }
+            }
+            '[' => {
+                i += 1;
+                if chars.get(i) == Some(&'"') {
+                    let (name, next) =
+                        parse_quoted(&chars, i).ok_or_else(|| invalid("unclosed quote"))?;
+                    segments.push(Segment::Field(name));
+                    i = next;
+                } else {
+                    let start = i;
+                    while i < chars.len() && chars[i] != ']' {
+                        i += 1;
+                    }
+                    let index: String = chars[start..i].iter().collect();
+                    let index = index
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid("array indexes are integers, e.g. [0]"))?;
+                    segments.push(Segment::Index(index));
+                }
+                if chars.get(i) != Some(&']') {
+                    return Err(invalid("missing ']'"));
+                }
+                i += 1;
+            }
+            c => return Err(invalid(&format!("unexpected '{}'", c))),
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Parse a JSON string literal starting at `chars[start] == '"'`; returns it and the index after
+fn parse_quoted(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => {
+                let literal: String = chars[start..=i].iter().collect();
+                return serde_json::from_str(&literal).ok().map(|s| (s, i + 1));
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_identifier_char)
+}
+
+fn at(walked: &str) -> &str {
+    if walked.is_empty() {
+        "."
+    } else {
+        walked
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn get(value: &Value, path: &str) -> Result<String> {
+        render(query(value, path)?)
+    }
+
+    #[test]
+    fn test_query() {
+        let creds = json!({
+            "type": "service_account",
+            "credentials": { "password": "hunter2", "port": 5432 },
+            "keys": [{ "id": "a" }, { "id": "b" }],
+            "odd key.name": true
+        });
+        assert_eq!(get(&creds, ".credentials.password").unwrap(), "hunter2");
+        assert_eq!(get(&creds, ".credentials.port").unwrap(), "5432");
+        assert_eq!(get(&creds, ".keys[1].id").unwrap(), "b");
+        assert_eq!(get(&creds, ".keys[-1].id").unwrap(), "b");
+        assert_eq!(get(&creds, ".keys.[0].id").unwrap(), "a");
+        assert_eq!(get(&creds, ".\"odd key.name\"").unwrap(), "true");
+        assert_eq!(get(&creds, ".[\"odd key.name\"]").unwrap(), "true");
+        assert_eq!(
+            get(&creds, ".").unwrap(),
+            serde_json::to_string_pretty(&creds).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let creds = json!({ "credentials": { "password": "hunter2" }, "keys": [] });
+        let err = query(&creds, ".credentials.user").unwrap_err().to_string();
+        assert_eq!(err, "No field 'user' at .credentials");
+        assert!(query(&creds, ".keys[0]").is_err());
+        assert!(query(&creds, ".credentials.password.x").is_err());
+        assert!(query(&creds, "credentials").is_err());
+        assert!(query(&creds, ".keys[x]").is_err());
+        assert!(query(&creds, ".keys[0").is_err());
+        assert!(query(&creds, ".\"open").is_err());
+    }
+}
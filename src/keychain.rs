@@ -1,137 +1,799 @@
 //! System keychain integration for storing the passphrase securely.
 //!
-//! On macOS: Uses the native `security` CLI for reliable Keychain access
+//! On macOS: Calls the Security framework directly, so the passphrase never shows
+//! up in the argv of a `security` child process (visible to anyone running `ps`)
 //! On other platforms: Uses the keyring crate
+//!
+//! Each store gets its own entry, keyed by the random ID in its metadata, so
+//! a work and a personal store don't overwrite each other's passphrase. The
+//! single entry saved by older versions is still read as a fallback, and is
+//! moved to the store's own entry once it has unlocked that store.
+//!
+//! With `[keychain] require_presence` on macOS, the entry is saved with an
+//! access control that needs Touch ID (or the login password) each time it's
+//! read, instead of being handed over silently once the keychain is unlocked.
+//!
+//! With `[keychain] backend = "file"` the entries go in an encrypted file
+//! instead (see `unlock_file`), for headless machines with no keychain.
 
-use anyhow::{Context, Result};
+use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::config::GlobalConfig;
+use crate::store::Store;
+use crate::unlock_file::{self, Wrap};
 
 const SERVICE_NAME: &str = "tinysecrets";
-const ACCOUNT_NAME: &str = "passphrase";
+/// Account of the entry shared by every store before entries were per store
+const LEGACY_ACCOUNT: &str = "passphrase";
+
+/// Marks an entry that stops working at a unix time: `tinysecrets-expires:<time>:<passphrase>`
+const EXPIRY_PREFIX: &str = "tinysecrets-expires:";
+
+/// `[keychain]` section of the global config
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct KeychainConfig {
+    /// How long a saved passphrase stays usable, e.g. "8h" (no limit if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+    /// Need Touch ID or the login password to read the saved passphrase (macOS only)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_presence: bool,
+    /// Where entries are saved
+    #[serde(default, skip_serializing_if = "Backend::is_system")]
+    pub backend: Backend,
+    /// What a new unlock file is encrypted with
+    #[serde(default, skip_serializing_if = "Wrap::is_machine")]
+    pub file_wrap: Wrap,
+}
+
+/// Where keychain entries are saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// The system keychain (Keychain, Secret Service, Credential Manager)
+    #[default]
+    System,
+    /// An encrypted file, ~/.tinysecrets/unlock.key, for machines without one
+    File,
+}
+
+impl Backend {
+    fn is_system(&self) -> bool {
+        *self == Backend::System
+    }
+}
+
+/// Shown in the Touch ID dialog when a command reads a presence-protected entry
+#[cfg(target_os = "macos")]
+const PRESENCE_PROMPT: &str = "unlock your tinysecrets store";
+
+/// Keychain account for the active store: `store:<id>`, or the legacy shared
+/// account for a store that has no ID yet (created by an older version and
+/// not unlocked since)
+pub fn account() -> Result<String> {
+    Ok(match Store::id()? {
+        Some(id) => format!("store:{}", id),
+        None => LEGACY_ACCOUNT.to_string(),
+    })
+}
+
+/// Store passphrase in system keychain, expiring after the configured TTL.
+/// Returns when it expires.
+pub fn store_passphrase(passphrase: &SecretString) -> Result<Option<DateTime<Utc>>> {
+    let expires = match GlobalConfig::load()?.keychain.ttl {
+        Some(ttl) => Some(Utc::now() + parse_ttl(&ttl)?),
+        None => None,
+    };
+    save_entry(passphrase, expires)?;
+    Ok(expires)
+}
+
+/// Retrieve the active store's passphrase from the system keychain, falling
+/// back to the legacy shared entry. An expired entry is removed and reads as
+/// missing, so the caller prompts again.
+pub fn get_passphrase() -> Result<Option<SecretString>> {
+    Ok(get_entry()?.map(|(passphrase, _)| passphrase))
+}
+
+/// The saved passphrase and when it expires, read once so a presence-protected
+/// entry asks for Touch ID only once
+pub fn get_entry() -> Result<Option<(SecretString, Option<DateTime<Utc>>)>> {
+    let account = account()?;
+    for account in [account.as_str(), LEGACY_ACCOUNT] {
+        let Some(entry) = read_entry(account)? else {
+            continue;
+        };
+        let (passphrase, expires) = unwrap(&entry);
+        if expires.is_some_and(|expires| expires <= Utc::now()) {
+            delete_entry(account)?;
+            return Ok(None);
+        }
+        return Ok(Some((SecretString::new(passphrase.to_string()), expires)));
+    }
+    Ok(None)
+}
+
+/// Whether the passphrase in use is the legacy shared entry (this store has
+/// no entry of its own yet)
+pub fn uses_legacy_entry() -> Result<bool> {
+    let account = account()?;
+    Ok(account == LEGACY_ACCOUNT || (!entry_exists(&account)? && entry_exists(LEGACY_ACCOUNT)?))
+}
+
+/// Move the legacy shared entry to the active store's own entry. Call once
+/// the keychain passphrase has unlocked the store, so the entry lands on the
+/// store it belongs to. Returns whether anything moved.
+pub fn adopt_legacy_entry() -> Result<bool> {
+    adopt_entry(LEGACY_ACCOUNT)
+}
+
+/// Move the entry saved under `from` (e.g. the store's account before
+/// `db encrypt` changed its ID) to the active store's account. Returns
+/// whether anything moved.
+pub fn adopt_entry(from: &str) -> Result<bool> {
+    let account = account()?;
+    if account == from || entry_exists(&account)? {
+        return Ok(false);
+    }
+    let Some(entry) = read_entry(from)? else {
+        return Ok(false);
+    };
+    write_entry(&account, &entry)?;
+    delete_entry(from)?;
+    Ok(true)
+}
+
+/// Re-save the stored passphrase (if any) to expire `ttl` from now, or never.
+/// Returns false if there was nothing to update.
+pub fn set_expiry(ttl: Option<Duration>) -> Result<bool> {
+    let Some(passphrase) = get_passphrase()? else {
+        return Ok(false);
+    };
+    save_entry(&passphrase, ttl.map(|ttl| Utc::now() + ttl))?;
+    Ok(true)
+}
+
+/// Save the passphrase with a given expiry, e.g. to re-save an entry read by
+/// `get_entry` after `require_presence` changes
+pub fn save_entry(passphrase: &SecretString, expires: Option<DateTime<Utc>>) -> Result<()> {
+    write_entry(&account()?, &wrap(passphrase.expose_secret(), expires))
+}
+
+/// Switch where entries are saved (and, for the file, what encrypts it),
+/// moving the active store's entry across. Returns whether an entry moved.
+pub fn set_backend(backend: Backend, wrap: Option<Wrap>) -> Result<bool> {
+    let mut config = GlobalConfig::load()?;
+    let previous = config.keychain.backend;
+    let entry = match previous == backend {
+        true => None,
+        false => get_entry()?,
+    };
+    config.keychain.backend = backend;
+    if let Some(wrap) = wrap {
+        config.keychain.file_wrap = wrap;
+    }
+    config.save()?;
+    if backend == Backend::File {
+        unlock_file::rewrap(config.keychain.file_wrap)?;
+    }
+
+    let Some((passphrase, expires)) = entry else {
+        return Ok(false);
+    };
+    save_entry(&passphrase, expires)?;
+    // The legacy entry may belong to other stores, so only this one moves
+    delete_from(previous, &account()?)?;
+    Ok(true)
+}
 
-/// Store passphrase in system keychain
-pub fn store_passphrase(passphrase: &SecretString) -> Result<()> {
+fn write_entry(account: &str, value: &str) -> Result<()> {
+    let config = GlobalConfig::load()?.keychain;
+    match config.backend {
+        Backend::File => unlock_file::write(account, value, config.file_wrap),
+        Backend::System => write_system(account, value, config.require_presence),
+    }
+}
+
+fn read_entry(account: &str) -> Result<Option<Zeroizing<String>>> {
+    match GlobalConfig::load()?.keychain.backend {
+        Backend::File => unlock_file::read(account),
+        Backend::System => read_system(account),
+    }
+}
+
+fn delete_entry(account: &str) -> Result<bool> {
+    delete_from(GlobalConfig::load()?.keychain.backend, account)
+}
+
+fn write_system(account: &str, value: &str, require_presence: bool) -> Result<()> {
     #[cfg(target_os = "macos")]
     {
-        use std::process::Command;
-
-        // Delete existing entry first (ignore errors)
-        let _ = Command::new("security")
-            .args([
-                "delete-generic-password",
-                "-s",
-                SERVICE_NAME,
-                "-a",
-                ACCOUNT_NAME,
-            ])
-            .output();
-
-        // Add new entry
-        let output = Command::new("security")
-            .args([
-                "add-generic-password",
-                "-s",
-                SERVICE_NAME,
-                "-a",
-                ACCOUNT_NAME,
-                "-w",
-                passphrase.expose_secret(),
-                "-U", // Update if exists
-            ])
-            .output()
-            .context("Failed to run security command")?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Failed to store in keychain: {}", stderr.trim());
+        // Replace rather than duplicate an existing entry, protected or not
+        macos::delete(account)?;
+        macos::delete_protected(account)?;
+        if require_presence {
+            macos::add_protected(account, value.as_bytes())
+        } else {
+            macos::add(account, value.as_bytes())
         }
-        Ok(())
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        let entry = keyring::Entry::new(SERVICE_NAME, ACCOUNT_NAME)
-            .context("Failed to access system keychain")?;
+        if require_presence {
+            anyhow::bail!(
+                "[keychain] require_presence is only supported on macOS; turn it off with `tinysecrets keychain require-presence off`"
+            );
+        }
+        let entry = keyring::Entry::new(SERVICE_NAME, account)
+            .map_err(|e| keyring_error(e, "access system keychain"))?;
         entry
-            .set_password(passphrase.expose_secret())
-            .context("Failed to store passphrase in keychain")?;
-        Ok(())
+            .set_password(value)
+            .map_err(|e| keyring_error(e, "store passphrase in keychain"))
     }
 }
 
-/// Retrieve passphrase from system keychain
-pub fn get_passphrase() -> Result<Option<SecretString>> {
+fn read_system(account: &str) -> Result<Option<Zeroizing<String>>> {
     #[cfg(target_os = "macos")]
     {
-        use std::process::Command;
-
-        let output = Command::new("security")
-            .args([
-                "find-generic-password",
-                "-s",
-                SERVICE_NAME,
-                "-a",
-                ACCOUNT_NAME,
-                "-w", // Output password only
-            ])
-            .output()
-            .context("Failed to run security command")?;
-
-        if output.status.success() {
-            let password = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !password.is_empty() {
-                return Ok(Some(SecretString::new(password)));
-            }
-        }
-        Ok(None)
+        // A protected entry shows the Touch ID dialog here; a missing one doesn't
+        let bytes = match macos::find_protected(account, PRESENCE_PROMPT)? {
+            Some(bytes) => Some(bytes),
+            None => macos::find(account)?,
+        };
+        Ok(bytes
+            .map(|bytes| Zeroizing::new(String::from_utf8_lossy(&bytes).trim().to_string()))
+            .filter(|password| !password.is_empty()))
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        let entry = keyring::Entry::new(SERVICE_NAME, ACCOUNT_NAME)
-            .context("Failed to access system keychain")?;
+        let entry = keyring::Entry::new(SERVICE_NAME, account)
+            .map_err(|e| keyring_error(e, "access system keychain"))?;
         match entry.get_password() {
-            Ok(password) => Ok(Some(SecretString::new(password))),
+            Ok(password) => Ok(Some(Zeroizing::new(password))),
             Err(keyring::Error::NoEntry) => Ok(None),
             Err(keyring::Error::Ambiguous(_)) => Ok(None),
-            Err(e) => Err(e).context("Failed to retrieve passphrase from keychain"),
+            Err(e) => Err(keyring_error(e, "retrieve passphrase from keychain")),
         }
     }
 }
 
-/// Delete passphrase from system keychain
-pub fn delete_passphrase() -> Result<bool> {
+/// Whether `account` has an entry, without reading (and so without asking for
+/// Touch ID for) a protected one
+fn entry_exists(account: &str) -> Result<bool> {
+    if GlobalConfig::load()?.keychain.backend == Backend::File {
+        return unlock_file::exists(account);
+    }
+
     #[cfg(target_os = "macos")]
     {
-        use std::process::Command;
+        Ok(macos::exists_protected(account)? || macos::find(account)?.is_some())
+    }
 
-        let output = Command::new("security")
-            .args([
-                "delete-generic-password",
-                "-s",
-                SERVICE_NAME,
-                "-a",
-                ACCOUNT_NAME,
-            ])
-            .output()
-            .context("Failed to run security command")?;
+    #[cfg(not(target_os = "macos"))]
+    {
+        Ok(read_entry(account)?.is_some())
+    }
+}
+
+/// Whether the saved passphrase needs Touch ID to read
+pub fn is_presence_protected() -> Result<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        if GlobalConfig::load()?.keychain.backend == Backend::File {
+            return Ok(false);
+        }
+        let account = account()?;
+        Ok(macos::exists_protected(&account)? || macos::exists_protected(LEGACY_ACCOUNT)?)
+    }
 
-        Ok(output.status.success())
+    #[cfg(not(target_os = "macos"))]
+    {
+        Ok(false)
+    }
+}
+
+/// Delete the active store's passphrase from the system keychain, along with
+/// the legacy shared entry it would otherwise fall back to
+pub fn delete_passphrase() -> Result<bool> {
+    let account = account()?;
+    let deleted = delete_entry(&account)?;
+    Ok(delete_entry(LEGACY_ACCOUNT)? || deleted)
+}
+
+fn delete_from(backend: Backend, account: &str) -> Result<bool> {
+    if backend == Backend::File {
+        return unlock_file::delete(account);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let protected = macos::delete_protected(account)?;
+        Ok(macos::delete(account)? || protected)
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        let entry = keyring::Entry::new(SERVICE_NAME, ACCOUNT_NAME)
-            .context("Failed to access system keychain")?;
+        let entry = keyring::Entry::new(SERVICE_NAME, account)
+            .map_err(|e| keyring_error(e, "access system keychain"))?;
         match entry.delete_credential() {
             Ok(()) => Ok(true),
             Err(keyring::Error::NoEntry) => Ok(false),
-            Err(e) => Err(e).context("Failed to delete passphrase from keychain"),
+            Err(e) => Err(keyring_error(e, "delete passphrase from keychain")),
         }
     }
 }
 
-/// Check if passphrase is stored in keychain
-pub fn has_passphrase() -> bool {
-    get_passphrase().ok().flatten().is_some()
+/// A keyring failure, pointing at the unlock file when there's no keychain
+/// service to talk to (e.g. a server without a Secret Service daemon)
+#[cfg(not(target_os = "macos"))]
+fn keyring_error(error: keyring::Error, action: &str) -> anyhow::Error {
+    let headless = matches!(
+        error,
+        keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_)
+    );
+    let error = anyhow::Error::new(error).context(format!("Failed to {}", action));
+    match headless {
+        true => error.context(
+            "No system keychain available; on a headless machine use `tinysecrets keychain set-backend file`",
+        ),
+        false => error,
+    }
+}
+
+/// The keychain entry for a passphrase, with its expiry if it has one
+fn wrap(passphrase: &str, expires: Option<DateTime<Utc>>) -> Zeroizing<String> {
+    Zeroizing::new(match expires {
+        Some(expires) => format!("{}{}:{}", EXPIRY_PREFIX, expires.timestamp(), passphrase),
+        None => passphrase.to_string(),
+    })
+}
+
+/// Split a keychain entry into the passphrase and its expiry. Entries saved
+/// without a TTL (or by older versions) are the bare passphrase.
+fn unwrap(entry: &str) -> (&str, Option<DateTime<Utc>>) {
+    entry
+        .strip_prefix(EXPIRY_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(time, passphrase)| {
+            let expires = Utc.timestamp_opt(time.parse().ok()?, 0).single()?;
+            Some((passphrase, Some(expires)))
+        })
+        .unwrap_or((entry, None))
+}
+
+/// Parse a TTL like `30m`, `8h`, `7d` or `1h30m`
+pub fn parse_ttl(ttl: &str) -> Result<Duration> {
+    let invalid = || anyhow::anyhow!("Invalid TTL '{}' (use e.g. 30m, 8h or 7d)", ttl);
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in ttl.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let n: i64 = digits.parse().map_err(|_| invalid())?;
+        digits.clear();
+        total += match c {
+            's' => Duration::seconds(n),
+            'm' => Duration::minutes(n),
+            'h' => Duration::hours(n),
+            'd' => Duration::days(n),
+            'w' => Duration::weeks(n),
+            _ => return Err(invalid()),
+        };
+    }
+    if !digits.is_empty() || total <= Duration::zero() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// Format a duration the way `parse_ttl` reads it, to the minute (`7h59m`)
+pub fn format_ttl(duration: Duration) -> String {
+    let minutes = (duration.num_seconds().max(0) + 59) / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+    let mut out = String::new();
+    for (n, unit) in [(days, 'd'), (hours, 'h'), (minutes, 'm')] {
+        if n > 0 {
+            out.push_str(&format!("{}{}", n, unit));
+        }
+    }
+    if out.is_empty() {
+        out.push_str("0m");
+    }
+    out
+}
+
+/// Generic-password calls into the Security framework: the default (file)
+/// keychain, and the data protection keychain for entries gated on user presence
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::SERVICE_NAME;
+    use anyhow::Result;
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    type OSStatus = i32;
+
+    const ERR_SEC_SUCCESS: OSStatus = 0;
+    const ERR_SEC_ITEM_NOT_FOUND: OSStatus = -25300;
+    const ERR_SEC_USER_CANCELED: OSStatus = -128;
+    const ERR_SEC_AUTH_FAILED: OSStatus = -25293;
+    const ERR_SEC_INTERACTION_NOT_ALLOWED: OSStatus = -25308;
+    /// The binary isn't signed with a keychain access group
+    const ERR_SEC_MISSING_ENTITLEMENT: OSStatus = -34018;
+
+    /// `kSecAccessControlUserPresence`: Touch ID, or the login password as a fallback
+    const USER_PRESENCE: usize = 1 << 0;
+    const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+
+    type CFTypeRef = *const c_void;
+
+    #[link(name = "Security", kind = "framework")]
+    extern "C" {
+        fn SecKeychainAddGenericPassword(
+            keychain: *const c_void,
+            service_name_length: u32,
+            service_name: *const c_char,
+            account_name_length: u32,
+            account_name: *const c_char,
+            password_length: u32,
+            password_data: *const c_void,
+            item_ref: *mut *mut c_void,
+        ) -> OSStatus;
+        fn SecKeychainFindGenericPassword(
+            keychain_or_array: *const c_void,
+            service_name_length: u32,
+            service_name: *const c_char,
+            account_name_length: u32,
+            account_name: *const c_char,
+            password_length: *mut u32,
+            password_data: *mut *mut c_void,
+            item_ref: *mut *mut c_void,
+        ) -> OSStatus;
+        fn SecKeychainItemFreeContent(attr_list: *mut c_void, data: *mut c_void) -> OSStatus;
+        fn SecKeychainItemDelete(item_ref: *mut c_void) -> OSStatus;
+
+        static kSecClass: CFTypeRef;
+        static kSecClassGenericPassword: CFTypeRef;
+        static kSecAttrService: CFTypeRef;
+        static kSecAttrAccount: CFTypeRef;
+        static kSecAttrAccessControl: CFTypeRef;
+        static kSecAttrAccessibleWhenUnlockedThisDeviceOnly: CFTypeRef;
+        static kSecUseDataProtectionKeychain: CFTypeRef;
+        static kSecUseOperationPrompt: CFTypeRef;
+        static kSecValueData: CFTypeRef;
+        static kSecReturnData: CFTypeRef;
+        static kSecMatchLimit: CFTypeRef;
+        static kSecMatchLimitOne: CFTypeRef;
+
+        fn SecAccessControlCreateWithFlags(
+            allocator: CFTypeRef,
+            protection: CFTypeRef,
+            flags: usize,
+            error: *mut CFTypeRef,
+        ) -> CFTypeRef;
+        fn SecItemAdd(attributes: CFTypeRef, result: *mut CFTypeRef) -> OSStatus;
+        fn SecItemCopyMatching(query: CFTypeRef, result: *mut CFTypeRef) -> OSStatus;
+        fn SecItemDelete(query: CFTypeRef) -> OSStatus;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFRelease(cf: *const c_void);
+
+        static kCFBooleanTrue: CFTypeRef;
+        // Opaque structs; only their addresses are used
+        static kCFTypeDictionaryKeyCallBacks: u8;
+        static kCFTypeDictionaryValueCallBacks: u8;
+
+        fn CFDictionaryCreate(
+            allocator: CFTypeRef,
+            keys: *const CFTypeRef,
+            values: *const CFTypeRef,
+            count: isize,
+            key_callbacks: *const c_void,
+            value_callbacks: *const c_void,
+        ) -> CFTypeRef;
+        fn CFStringCreateWithBytes(
+            allocator: CFTypeRef,
+            bytes: *const u8,
+            count: isize,
+            encoding: u32,
+            is_external: u8,
+        ) -> CFTypeRef;
+        fn CFDataCreate(allocator: CFTypeRef, bytes: *const u8, length: isize) -> CFTypeRef;
+        fn CFDataGetLength(data: CFTypeRef) -> isize;
+        fn CFDataGetBytePtr(data: CFTypeRef) -> *const u8;
+    }
+
+    /// A Core Foundation object, released when dropped
+    struct Owned(CFTypeRef);
+
+    impl Drop for Owned {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                // SAFETY: we hold the only reference we were given
+                unsafe { CFRelease(self.0) }
+            }
+        }
+    }
+
+    fn cf_string(text: &str) -> Owned {
+        // SAFETY: the bytes are copied into the new string
+        Owned(unsafe {
+            CFStringCreateWithBytes(
+                ptr::null(),
+                text.as_ptr(),
+                text.len() as isize,
+                CF_STRING_ENCODING_UTF8,
+                0,
+            )
+        })
+    }
+
+    /// A generic-password query for our `account` in the data protection
+    /// keychain, plus `extra` key/value pairs
+    fn query(account: &str, extra: &[(CFTypeRef, CFTypeRef)]) -> Owned {
+        let service = cf_string(SERVICE_NAME);
+        let account = cf_string(account);
+        // SAFETY: the keys and values are live CF objects, which the dictionary retains
+        unsafe {
+            let mut keys = vec![
+                kSecClass,
+                kSecAttrService,
+                kSecAttrAccount,
+                kSecUseDataProtectionKeychain,
+            ];
+            let mut values = vec![
+                kSecClassGenericPassword,
+                service.0,
+                account.0,
+                kCFBooleanTrue,
+            ];
+            for &(key, value) in extra {
+                keys.push(key);
+                values.push(value);
+            }
+            Owned(CFDictionaryCreate(
+                ptr::null(),
+                keys.as_ptr(),
+                values.as_ptr(),
+                keys.len() as isize,
+                ptr::addr_of!(kCFTypeDictionaryKeyCallBacks).cast(),
+                ptr::addr_of!(kCFTypeDictionaryValueCallBacks).cast(),
+            ))
+        }
+    }
+
+    fn check_protected(status: OSStatus, action: &str) -> Result<()> {
+        match status {
+            ERR_SEC_USER_CANCELED => anyhow::bail!("Touch ID was cancelled"),
+            ERR_SEC_AUTH_FAILED => anyhow::bail!("Touch ID or password check failed"),
+            ERR_SEC_MISSING_ENTITLEMENT => anyhow::bail!(
+                "This build of tinysecrets isn't code-signed with a keychain entitlement, which presence-protected entries need"
+            ),
+            status => check(status, action),
+        }
+    }
+
+    fn check(status: OSStatus, action: &str) -> Result<()> {
+        if status != ERR_SEC_SUCCESS {
+            anyhow::bail!("Failed to {} keychain item (OSStatus {})", action, status);
+        }
+        Ok(())
+    }
+
+    pub fn add(account: &str, password: &[u8]) -> Result<()> {
+        // SAFETY: every pointer/length pair describes a live buffer; no item ref is requested
+        let status = unsafe {
+            SecKeychainAddGenericPassword(
+                ptr::null(),
+                SERVICE_NAME.len() as u32,
+                SERVICE_NAME.as_ptr().cast(),
+                account.len() as u32,
+                account.as_ptr().cast(),
+                password.len() as u32,
+                password.as_ptr().cast(),
+                ptr::null_mut(),
+            )
+        };
+        check(status, "store")
+    }
+
+    pub fn find(account: &str) -> Result<Option<Vec<u8>>> {
+        let mut length = 0u32;
+        let mut data: *mut c_void = ptr::null_mut();
+        // SAFETY: out-pointers are valid; the returned buffer is copied, then freed below
+        let status = unsafe {
+            SecKeychainFindGenericPassword(
+                ptr::null(),
+                SERVICE_NAME.len() as u32,
+                SERVICE_NAME.as_ptr().cast(),
+                account.len() as u32,
+                account.as_ptr().cast(),
+                &mut length,
+                &mut data,
+                ptr::null_mut(),
+            )
+        };
+        if status == ERR_SEC_ITEM_NOT_FOUND {
+            return Ok(None);
+        }
+        check(status, "read")?;
+
+        // SAFETY: on success `data` points to `length` bytes owned by the framework
+        let password = unsafe {
+            let password = std::slice::from_raw_parts(data as *const u8, length as usize).to_vec();
+            SecKeychainItemFreeContent(ptr::null_mut(), data);
+            password
+        };
+        Ok(Some(password))
+    }
+
+    pub fn delete(account: &str) -> Result<bool> {
+        let mut item: *mut c_void = ptr::null_mut();
+        // SAFETY: only the item ref is requested; it's released after deleting
+        let status = unsafe {
+            SecKeychainFindGenericPassword(
+                ptr::null(),
+                SERVICE_NAME.len() as u32,
+                SERVICE_NAME.as_ptr().cast(),
+                account.len() as u32,
+                account.as_ptr().cast(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut item,
+            )
+        };
+        if status == ERR_SEC_ITEM_NOT_FOUND {
+            return Ok(false);
+        }
+        check(status, "find")?;
+
+        // SAFETY: `item` is a valid, owned SecKeychainItemRef
+        let status = unsafe {
+            let status = SecKeychainItemDelete(item);
+            CFRelease(item);
+            status
+        };
+        check(status, "delete")?;
+        Ok(true)
+    }
+
+    /// Save an entry that needs user presence to read
+    pub fn add_protected(account: &str, password: &[u8]) -> Result<()> {
+        // SAFETY: the protection class is a framework constant; errors are reported as null
+        let access = Owned(unsafe {
+            SecAccessControlCreateWithFlags(
+                ptr::null(),
+                kSecAttrAccessibleWhenUnlockedThisDeviceOnly,
+                USER_PRESENCE,
+                ptr::null_mut(),
+            )
+        });
+        if access.0.is_null() {
+            anyhow::bail!("Failed to create keychain access control");
+        }
+        // SAFETY: the bytes are copied into the new data object
+        let data =
+            Owned(unsafe { CFDataCreate(ptr::null(), password.as_ptr(), password.len() as isize) });
+        // SAFETY: the attributes dictionary is live for the call; no result is requested
+        let status = unsafe {
+            let attributes = query(
+                account,
+                &[(kSecAttrAccessControl, access.0), (kSecValueData, data.0)],
+            );
+            SecItemAdd(attributes.0, ptr::null_mut())
+        };
+        check_protected(status, "store")
+    }
+
+    /// Read a protected entry, showing the Touch ID dialog with `prompt`.
+    /// An unsigned build can't have saved one, so it reads as missing.
+    pub fn find_protected(account: &str, prompt: &str) -> Result<Option<Vec<u8>>> {
+        let prompt = cf_string(prompt);
+        let mut result: CFTypeRef = ptr::null();
+        // SAFETY: the query is live for the call; `result` is a valid out-pointer
+        let status = unsafe {
+            let query = query(
+                account,
+                &[
+                    (kSecReturnData, kCFBooleanTrue),
+                    (kSecMatchLimit, kSecMatchLimitOne),
+                    (kSecUseOperationPrompt, prompt.0),
+                ],
+            );
+            SecItemCopyMatching(query.0, &mut result)
+        };
+        if matches!(status, ERR_SEC_ITEM_NOT_FOUND | ERR_SEC_MISSING_ENTITLEMENT) {
+            return Ok(None);
+        }
+        check_protected(status, "read")?;
+
+        let result = Owned(result);
+        // SAFETY: on success `result` is a CFData we own
+        let password = unsafe {
+            std::slice::from_raw_parts(
+                CFDataGetBytePtr(result.0),
+                CFDataGetLength(result.0) as usize,
+            )
+            .to_vec()
+        };
+        Ok(Some(password))
+    }
+
+    /// Whether a protected entry exists; asks for nothing, since no data is returned
+    pub fn exists_protected(account: &str) -> Result<bool> {
+        // SAFETY: the query is live for the call; no result is requested
+        let status = unsafe {
+            let query = query(account, &[(kSecMatchLimit, kSecMatchLimitOne)]);
+            SecItemCopyMatching(query.0, ptr::null_mut())
+        };
+        match status {
+            ERR_SEC_ITEM_NOT_FOUND | ERR_SEC_MISSING_ENTITLEMENT => Ok(false),
+            // Found, but matching it would have needed Touch ID
+            ERR_SEC_INTERACTION_NOT_ALLOWED => Ok(true),
+            status => check_protected(status, "find").map(|()| true),
+        }
+    }
+
+    pub fn delete_protected(account: &str) -> Result<bool> {
+        // SAFETY: the query is live for the call
+        let status = unsafe { SecItemDelete(query(account, &[]).0) };
+        match status {
+            ERR_SEC_ITEM_NOT_FOUND | ERR_SEC_MISSING_ENTITLEMENT => Ok(false),
+            status => check_protected(status, "delete").map(|()| true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_unwrap() {
+        let expires = Utc.timestamp_opt(1_900_000_000, 0).unwrap();
+        assert_eq!(
+            unwrap(&wrap("correct:horse", Some(expires))),
+            ("correct:horse", Some(expires))
+        );
+        assert_eq!(
+            unwrap(&wrap("correct:horse", None)),
+            ("correct:horse", None)
+        );
+        // Entries saved before TTLs existed
+        assert_eq!(unwrap("hunter2"), ("hunter2", None));
+        assert_eq!(
+            unwrap("tinysecrets-expires:soon:x"),
+            ("tinysecrets-expires:soon:x", None)
+        );
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("8h").unwrap(), Duration::hours(8));
+        assert_eq!(parse_ttl("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_ttl("7d").unwrap(), Duration::days(7));
+        assert!(parse_ttl("8").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("0m").is_err());
+        assert!(parse_ttl("8x").is_err());
+    }
+
+    #[test]
+    fn test_format_ttl() {
+        assert_eq!(format_ttl(Duration::hours(8)), "8h");
+        assert_eq!(format_ttl(Duration::seconds(8 * 3600 - 30)), "8h");
+        assert_eq!(format_ttl(Duration::minutes(60 * 26 + 5)), "1d2h5m");
+        assert_eq!(format_ttl(Duration::seconds(10)), "1m");
+    }
 }
@@ -0,0 +1,206 @@
+//! Lint environment variable names before injecting them into processes
+//!
+//! Flags keys that collide case-insensitively (Windows and some runtimes treat
+//! `api_key` and `API_KEY` as the same variable) and keys that shadow variables
+//! which change how programs are loaded or run, and key names that break the
+//! `[key_policy]` naming convention.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::pattern::full_match_regex;
+
+/// Variables that alter program loading/execution when overridden
+const DANGEROUS_VARS: &[&str] = &[
+    "PATH",
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "LD_AUDIT",
+    "PYTHONPATH",
+    "PYTHONSTARTUP",
+    "PYTHONHOME",
+    "NODE_OPTIONS",
+    "NODE_PATH",
+    "PERL5LIB",
+    "PERL5OPT",
+    "RUBYLIB",
+    "RUBYOPT",
+    "JAVA_TOOL_OPTIONS",
+    "BASH_ENV",
+    "ENV",
+    "IFS",
+    "SHELL",
+    "HOME",
+    "GIT_SSH_COMMAND",
+];
+
+/// Prefixes of variables that alter program loading
+const DANGEROUS_PREFIXES: &[&str] = &["DYLD_", "LD_"];
+
+/// What to do about a class of lint findings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LintAction {
+    Off,
+    #[default]
+    Warn,
+    Deny,
+}
+
+/// Policy for `run`/`export` key linting (`[env_policy]` in the global config)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EnvPolicy {
+    /// Keys that differ only by case
+    #[serde(default)]
+    pub on_collision: LintAction,
+    /// Keys that shadow loader/runtime variables like LD_PRELOAD or PATH
+    #[serde(default)]
+    pub on_dangerous: LintAction,
+    /// Keys exempt from the dangerous-variable check
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+}
+
+/// Naming convention for keys (`[key_policy]` in the global config, or in
+/// .tinysecrets.toml, which wins). `set` and `import-env` enforce it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyPolicy {
+    /// Regex every key name must match, e.g. `^[A-Z][A-Z0-9_]*$`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// `deny` (the default) rejects a key that doesn't match, `warn` only says so
+    #[serde(default = "default_deny")]
+    pub on_mismatch: LintAction,
+}
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        Self {
+            pattern: None,
+            on_mismatch: default_deny(),
+        }
+    }
+}
+
+fn default_deny() -> LintAction {
+    LintAction::Deny
+}
+
+impl KeyPolicy {
+    /// The keys that don't match the pattern (none without one, or when off)
+    pub fn nonconforming<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<&'a str>> {
+        let Some(pattern) = self.pattern.as_deref() else {
+            return Ok(Vec::new());
+        };
+        if self.on_mismatch == LintAction::Off {
+            return Ok(Vec::new());
+        }
+        let regex = full_match_regex(pattern)
+            .with_context(|| format!("Invalid [key_policy] pattern '{}'", pattern))?;
+        Ok(keys
+            .into_iter()
+            .filter(|key| !regex.is_match(key))
+            .collect())
+    }
+}
+
+/// A problem found in a set of keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// Keys that are equal ignoring case
+    Collision(Vec<String>),
+    /// Key that shadows a sensitive variable
+    Dangerous(String),
+}
+
+/// True if the key shadows a loader/runtime variable
+pub fn is_dangerous(key: &str) -> bool {
+    DANGEROUS_VARS.contains(&key) || DANGEROUS_PREFIXES.iter().any(|p| key.starts_with(p))
+}
+
+/// Lint a set of keys, ignoring `allow`-listed keys for the dangerous check
+pub fn lint_keys<'a>(keys: impl IntoIterator<Item = &'a str>, allow: &[String]) -> Vec<Finding> {
+    let mut by_upper: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut findings = Vec::new();
+
+    for key in keys {
+        if is_dangerous(key) && !allow.iter().any(|a| a == key) {
+            findings.push(Finding::Dangerous(key.to_string()));
+        }
+        by_upper
+            .entry(key.to_uppercase())
+            .or_default()
+            .push(key.to_string());
+    }
+
+    for (_, group) in by_upper {
+        if group.len() > 1 {
+            findings.push(Finding::Collision(group));
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collisions() {
+        let findings = lint_keys(["API_KEY", "api_key", "OTHER"], &[]);
+        assert_eq!(
+            findings,
+            vec![Finding::Collision(vec![
+                "API_KEY".to_string(),
+                "api_key".to_string()
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_dangerous() {
+        let findings = lint_keys(["LD_PRELOAD", "DYLD_INSERT_LIBRARIES", "DATABASE_URL"], &[]);
+        assert_eq!(
+            findings,
+            vec![
+                Finding::Dangerous("LD_PRELOAD".to_string()),
+                Finding::Dangerous("DYLD_INSERT_LIBRARIES".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_policy() {
+        let policy = KeyPolicy {
+            pattern: Some("^[A-Z][A-Z0-9_]*$".to_string()),
+            ..KeyPolicy::default()
+        };
+        let keys = ["API_KEY", "api_key", "2FA_SECRET", "DB-URL", "S3_BUCKET"];
+        assert_eq!(
+            policy.nonconforming(keys).unwrap(),
+            vec!["api_key", "2FA_SECRET", "DB-URL"]
+        );
+
+        assert!(KeyPolicy::default().nonconforming(keys).unwrap().is_empty());
+        let off = KeyPolicy {
+            on_mismatch: LintAction::Off,
+            ..policy.clone()
+        };
+        assert!(off.nonconforming(keys).unwrap().is_empty());
+        let invalid = KeyPolicy {
+            pattern: Some("[A-Z".to_string()),
+            ..policy
+        };
+        assert!(invalid.nonconforming(keys).is_err());
+    }
+
+    #[test]
+    fn test_allow_list() {
+        assert!(lint_keys(["PATH"], &["PATH".to_string()]).is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Secret lockfiles: the exact version of every key an environment injects
+//!
+//! `tinysecrets lock` writes one; `run --locked` injects those versions (from
+//! history if they've been rotated since) instead of the current values. Each
+//! key also records a fingerprint of its value, keyed from the store's master
+//! key, so a lock only matches the value it was made from and reveals nothing
+//! about it.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Written at the top of every lockfile
+const HEADER: &str = concat!(
+    "# Generated by `tinysecrets lock`. Use it with `tinysecrets run --locked <file>`;\n",
+    "# it holds versions and fingerprints, never values.\n\n",
+);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub project: String,
+    pub environment: String,
+    /// The store the versions refer to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    pub locked_at: DateTime<Utc>,
+    #[serde(default)]
+    pub keys: BTreeMap<String, LockedKey>,
+}
+
+/// One key's pinned version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedKey {
+    /// Where the key is defined (a parent, for inherited keys)
+    pub environment: String,
+    pub version: i32,
+    pub fingerprint: String,
+}
+
+impl Lockfile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text)
+            .with_context(|| format!("{} isn't a tinysecrets lockfile", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(format!("{}{}", HEADER, toml::to_string(self)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut keys = BTreeMap::new();
+        keys.insert(
+            "DATABASE_URL".to_string(),
+            LockedKey {
+                environment: "base".to_string(),
+                version: 3,
+                fingerprint: "0123abcd".to_string(),
+            },
+        );
+        let lock = Lockfile {
+            project: "api".to_string(),
+            environment: "prod".to_string(),
+            store: Some("c0ffee".to_string()),
+            locked_at: Utc::now(),
+            keys,
+        };
+
+        let text = lock.to_toml().unwrap();
+        assert!(text.starts_with("# Generated by `tinysecrets lock`"));
+        assert!(text.contains("[keys.DATABASE_URL]"));
+
+        let parsed = Lockfile::parse(&text).unwrap();
+        assert_eq!(parsed.project, "api");
+        assert_eq!(parsed.keys["DATABASE_URL"].version, 3);
+        assert_eq!(parsed.keys["DATABASE_URL"].environment, "base");
+        assert!(Lockfile::parse("project = 1").is_err());
+    }
+}
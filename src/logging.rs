@@ -0,0 +1,70 @@
+//! Diagnostics for `-v` (store operations and timings) and `-vv` (plus every
+//! SQL statement), written to stderr with the time since startup
+//!
+//! Secrets never go through here: SQL is logged as prepared, without the
+//! bound values.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use rusqlite::Connection;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let elapsed = START.get_or_init(Instant::now).elapsed();
+        let target = record
+            .target()
+            .strip_prefix("tinysecrets::")
+            .unwrap_or(record.target());
+        std::eprintln!(
+            "[{:>8.3}s {:<5} {}] {}",
+            elapsed.as_secs_f64(),
+            record.level(),
+            target,
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// Turn on logging for `-v` (1) or `-vv` (2 or more); 0 leaves it off
+pub fn init(verbosity: u8) {
+    START.get_or_init(Instant::now);
+    let level = match verbosity {
+        0 => return,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    if log::set_logger(&Logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Log each statement's SQL and run time on this connection (`-vv`)
+pub fn trace_sql(conn: &mut Connection) {
+    if log::log_enabled!(target: "tinysecrets::sql", Level::Trace) {
+        conn.profile(Some(log_statement));
+    }
+}
+
+fn log_statement(sql: &str, elapsed: Duration) {
+    let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    log::trace!(target: "tinysecrets::sql", "{} ({})", sql, millis(elapsed));
+}
+
+/// `12.3ms`
+pub fn millis(elapsed: Duration) -> String {
+    format!("{:.1}ms", elapsed.as_secs_f64() * 1000.0)
+}
@@ -1,92 +1,803 @@
+/// Status messages: every `eprintln!` in the crate goes through here so `-q`
+/// silences them in one place. Errors (returned from main), prompts (`eprint!`)
+/// and command output (stdout) are unaffected.
+macro_rules! eprintln {
+    ($($arg:tt)*) => {
+        if !$crate::ui::quiet() {
+            ::std::eprintln!($($arg)*)
+        }
+    };
+}
+
+mod api;
+mod attempts;
+mod backup;
+mod chain;
 mod cli;
+mod compat;
 mod config;
 mod crypto;
+mod diff;
+mod errors;
+mod git;
+mod interpolate;
+mod json_path;
 mod keychain;
+mod lint;
+mod lockfile;
+mod logging;
+mod mask;
+mod migrations;
+mod pattern;
+mod permissions;
+mod platforms;
+mod plugins;
+mod refs;
+mod scan;
+mod sops;
+mod ssh_agent;
 mod store;
+mod strength;
+mod ui;
+mod unlock_file;
+mod validate;
+mod yaml_env;
 
 use anyhow::Result;
-use clap::Parser;
-use cli::{Cli, Commands, ConfigAction};
+use clap::{Parser, ValueEnum};
+use cli::{Cli, Commands, CompatAction, ConfigAction};
 use config::ConfigResolver;
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn main() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // Help and --version go to stdout and exit 0
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(errors::ErrorCode::Usage.status());
+        }
+    };
+    let json_errors = cli.error_format == cli::ErrorFormat::Json;
+    if let Err(e) = run(cli) {
+        errors::report(&e, json_errors);
+        std::process::exit(errors::classify(&e).status());
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let json_errors = cli.error_format == cli::ErrorFormat::Json;
+    ui::set_quiet(cli.quiet);
+    permissions::set_insecure_ok(cli.insecure_ok);
+    logging::init(cli.verbose);
+    config::select_profile(cli.profile.as_deref())?;
+    ui::init();
+    log::debug!("tinysecrets {}", env!("CARGO_PKG_VERSION"));
+
+    if let Some(path) = cli.store {
+        store::Store::set_path_override(path)?;
+    }
 
     match cli.command {
-        Commands::Init => cli::init::run()?,
+        Commands::Init {
+            path,
+            passphrase_file,
+            no_keychain,
+            full_encryption,
+        } => cli::init::run(
+            path,
+            passphrase_file.as_deref(),
+            !no_keychain,
+            full_encryption,
+        )?,
+        Commands::Setup {
+            project,
+            environment,
+            env_file,
+            full_encryption,
+        } => cli::setup::run(
+            project.as_deref(),
+            environment.as_deref(),
+            env_file.as_deref(),
+            full_encryption,
+        )?,
         Commands::Set {
             project,
             environment,
             key,
             value,
+            stdin,
+            from_file,
+            json_from_file,
+            no_editor_temp,
+            description,
+            reason,
+            no_verify,
         } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
             let environment = resolver.environment(environment.as_deref())?;
-            cli::set::run(&project, &environment, &key, value.as_deref())?
+            cli::set::run(
+                &project,
+                &environment,
+                &key,
+                value.as_deref(),
+                stdin,
+                from_file.as_deref(),
+                json_from_file.as_deref(),
+                no_editor_temp,
+                description.as_deref(),
+                reason.as_deref(),
+                no_verify,
+                resolver.config(),
+            )?
+        }
+        Commands::Edit {
+            project,
+            environment,
+            key,
+            reason,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::edit::run(
+                &project,
+                &environment,
+                &key,
+                reason.as_deref(),
+                resolver.config(),
+            )?
         }
         Commands::Get {
             project,
             environment,
             key,
             version,
+            info,
+            format,
+            base64,
+            hex,
+            json_string,
+            trim,
+            no_newline,
+            no_resolve,
+            path,
+            to_file,
+            mode,
+            exists,
         } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
             let environment = resolver.environment(environment.as_deref())?;
-            cli::get::run(&project, &environment, &key, version)?
+            let output = cli::get::OutputOptions {
+                encoding: if base64 {
+                    cli::get::Encoding::Base64
+                } else if hex {
+                    cli::get::Encoding::Hex
+                } else if json_string {
+                    cli::get::Encoding::JsonString
+                } else {
+                    cli::get::Encoding::Raw
+                },
+                trim,
+                newline: !no_newline,
+                resolve_refs: !no_resolve,
+                path,
+            };
+            match (format, key) {
+                (None, Some(key)) if exists => {
+                    cli::has::run(&project, &environment, &key, json_errors)
+                }
+                (Some(format), _) => cli::get::run_format(&project, &environment, &format)?,
+                (None, Some(key)) if info => cli::get::run_info(&project, &environment, &key)?,
+                (None, Some(key)) => cli::get::run(
+                    &project,
+                    &environment,
+                    &key,
+                    version,
+                    &output,
+                    to_file.as_deref().map(|path| (path, mode)),
+                )?,
+                (None, None) => unreachable!("clap requires key unless --format is given"),
+            }
+        }
+        Commands::Has {
+            project,
+            environment,
+            key,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::has::run(&project, &environment, &key, json_errors)
+        }
+        Commands::Render {
+            project,
+            environment,
+            template,
+            output,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::render::run(&project, &environment, &template, output.as_deref())?
         }
         Commands::List {
             project,
             environment,
+            long,
+            tree: _,
+            flat,
+            sort,
+            changed_since,
         } => {
             // List can work without project/env (shows all), but use config as default filter
             let resolver = ConfigResolver::new()?;
-            let project = project.or_else(|| resolver.config().and_then(|c| c.project.clone()));
-            let environment =
-                environment.or_else(|| resolver.config().and_then(|c| c.environment.clone()));
-            cli::list::run(project.as_deref(), environment.as_deref())?
+            let project = project
+                .or_else(|| resolver.config().and_then(|c| c.project.clone()))
+                .map(|p| resolver.alias(&p));
+            let environment = environment
+                .or_else(|| resolver.config().and_then(|c| c.environment.clone()))
+                .map(config::expand_environment)
+                .transpose()?;
+            cli::list::run(
+                project.as_deref(),
+                environment.as_deref(),
+                long,
+                flat,
+                sort,
+                changed_since,
+            )?
+        }
+        Commands::Lock {
+            project,
+            environment,
+            output,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::lock::run(&project, &environment, output.as_deref())?
+        }
+        Commands::Watch {
+            project,
+            environment,
+            interval,
+            json,
+            hook,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::watch::run(&project, &environment, interval, json, &hook)?
+        }
+        Commands::Search {
+            pattern,
+            project,
+            environment,
+            values,
+        } => cli::search::run(&pattern, project.as_deref(), environment.as_deref(), values)?,
+        Commands::Pick {
+            query,
+            project,
+            environment,
+            copy,
+            edit,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            let action = if copy {
+                cli::pick::PickAction::Copy
+            } else if edit {
+                cli::pick::PickAction::Edit
+            } else {
+                cli::pick::PickAction::Print
+            };
+            cli::pick::run(
+                &project,
+                &environment,
+                query.as_deref(),
+                action,
+                resolver.config(),
+            )?
+        }
+        Commands::Describe {
+            project,
+            environment,
+            key,
+            description,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::describe::run(&project, &environment, &key, description.as_deref())?
+        }
+        Commands::Inherit {
+            project,
+            environment,
+            parent,
+            none: _,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::inherit::run(&project, &environment, parent.as_deref())?
         }
         Commands::Delete {
             project,
             environment,
             key,
+            force,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::delete::run(&project, &environment, &key, force)?
+        }
+        Commands::DeleteEnv {
+            project,
+            environment,
+            force,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::delete::run_env(&project, &environment, force)?
+        }
+        Commands::DeleteProject { project, force } => {
+            let project = ConfigResolver::new()?.alias(&project);
+            cli::delete::run_project(&project, force)?
+        }
+        Commands::Undelete {
+            project,
+            environment,
+            key,
         } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
             let environment = resolver.environment(environment.as_deref())?;
-            cli::delete::run(&project, &environment, &key)?
+            cli::trash::run_undelete(&project, &environment, &key)?
         }
+        Commands::Trash { action } => cli::trash::run(action)?,
         Commands::Run {
             project,
             environment,
+            docker,
+            each_env,
+            check,
+            interpolate,
+            as_files,
+            mount,
+            isolated,
+            keep_env,
+            mask_output,
+            as_of,
+            locked,
             command,
+        } => {
+            // A lockfile names its own project/environment
+            let (project, environment) = match locked {
+                Some(path) => {
+                    let (project, environment) = cli::lock::pin(&path)?;
+                    (Some(project), Some(environment))
+                }
+                None => (project, environment),
+            };
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            if let Some(at) = as_of {
+                cli::history::pin_as_of(at);
+            }
+            let isolated = isolated.then_some(keep_env.as_slice());
+            if let Some(parent) = as_files {
+                let environment = resolver.environment(environment.as_deref())?;
+                cli::run::run_as_files(
+                    &project,
+                    &environment,
+                    &command,
+                    &parent,
+                    resolver.config(),
+                    check,
+                    interpolate,
+                    isolated,
+                )?
+            } else if !mount.is_empty() {
+                let environment = resolver.environment(environment.as_deref())?;
+                cli::run::run_mounted(
+                    &project,
+                    &environment,
+                    &command,
+                    &mount,
+                    resolver.config(),
+                    check,
+                    interpolate,
+                    isolated,
+                )?
+            } else if mask_output {
+                let environment = resolver.environment(environment.as_deref())?;
+                cli::run::run_masked(
+                    &project,
+                    &environment,
+                    &command,
+                    docker,
+                    resolver.config(),
+                    check,
+                    interpolate,
+                    isolated,
+                )?
+            } else if each_env.is_empty() {
+                let environment = resolver.environment(environment.as_deref())?;
+                cli::run::run(
+                    &project,
+                    &environment,
+                    &command,
+                    docker,
+                    resolver.config(),
+                    check,
+                    interpolate,
+                    isolated,
+                )?
+            } else {
+                let environments = each_env
+                    .into_iter()
+                    .map(config::expand_environment)
+                    .collect::<Result<Vec<_>>>()?;
+                cli::run::run_each_env(
+                    &project,
+                    &environments,
+                    &command,
+                    docker,
+                    resolver.config(),
+                    check,
+                    interpolate,
+                    isolated,
+                )?
+            }
+        }
+        Commands::Up {
+            project,
+            environment,
+            check,
+            args,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::task::run(
+                &project,
+                &environment,
+                "up",
+                &args,
+                resolver.config(),
+                check,
+            )?
+        }
+        Commands::X {
+            name,
+            project,
+            environment,
+            check,
+            args,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::task::run(
+                &project,
+                &environment,
+                &name,
+                &args,
+                resolver.config(),
+                check,
+            )?
+        }
+        Commands::Check {
+            project,
+            environment,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::check::run(&project, &environment, resolver.config())?
+        }
+        Commands::Audit {
+            duplicates: _,
+            project,
+            min_length,
+        } => cli::audit::run(project.as_deref(), min_length)?,
+        Commands::Lint { project, all } => {
+            let resolver = ConfigResolver::new()?;
+            let project = match all {
+                true => None,
+                false => Some(resolver.project(project.as_deref())?),
+            };
+            cli::lint_cmd::run(project.as_deref(), resolver.config())?
+        }
+        Commands::Scan {
+            project,
+            paths,
+            staged,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            cli::scan::run(&project, &paths, staged)?
+        }
+        Commands::Shell {
+            project,
+            environment,
         } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
             let environment = resolver.environment(environment.as_deref())?;
-            cli::run::run(&project, &environment, &command)?
+            cli::shell::run(&project, &environment)?
         }
+        Commands::Hook { shell } => cli::hook::run_hook(shell)?,
+        Commands::HookEnv { shell } => cli::hook::run_hook_env(shell)?,
+        Commands::Allow { path } => cli::hook::run_allow(path.as_deref())?,
+        Commands::Deny { path } => cli::hook::run_deny(path.as_deref())?,
+        Commands::PushHeroku {
+            app,
+            project,
+            environment,
+            only,
+            dry_run,
+            force,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::push_heroku::run(&project, &environment, &app, &only, dry_run, force)?
+        }
+        Commands::PushGha {
+            repo,
+            gh_environment,
+            project,
+            environment,
+            map,
+            only,
+            dry_run,
+            force,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::push_gha::run(
+                &project,
+                &environment,
+                &repo,
+                gh_environment.as_deref(),
+                &map,
+                &only,
+                dry_run,
+                force,
+            )?
+        }
+        Commands::Serve {
+            listen,
+            read_only,
+            token_file,
+        } => cli::serve::run(&listen, read_only, token_file.as_deref())?,
         Commands::Export {
             project,
             environment,
+            all,
             output,
+            format,
+            only,
+            exclude,
+            out_dir,
+            interpolate,
+            sign,
+            as_of,
+            sops_args,
+        } => {
+            if let Some(at) = as_of {
+                cli::history::pin_as_of(at);
+            }
+            // None means the whole store; no environments means the whole project
+            let (project, environments) = if all {
+                (None, Vec::new())
+            } else {
+                let resolver = ConfigResolver::new()?;
+                let explicit_project = project.is_some();
+                let project = resolver.project(project.as_deref())?;
+                let environments = if !environment.is_empty() {
+                    environment
+                        .iter()
+                        .map(|e| resolver.environment(Some(e)))
+                        .collect::<Result<Vec<_>>>()?
+                } else if explicit_project {
+                    Vec::new()
+                } else {
+                    vec![resolver.environment(None)?]
+                };
+                (Some(project), environments)
+            };
+            let plaintext = !matches!(format, cli::ExportFormat::Bundle | cli::ExportFormat::Sops);
+            if interpolate && !plaintext {
+                anyhow::bail!(
+                    "--interpolate is only used with plaintext formats (dotenv, tfvars, tf-json, systemd-creds)"
+                );
+            }
+            if format != cli::ExportFormat::Sops && !sops_args.is_empty() {
+                anyhow::bail!("Arguments after -- are only used with --format sops");
+            }
+            if format != cli::ExportFormat::SystemdCreds && out_dir.is_some() {
+                anyhow::bail!("--out-dir is only used with --format systemd-creds");
+            }
+            if format != cli::ExportFormat::Bundle && sign {
+                anyhow::bail!("--sign is only used with --format bundle");
+            }
+            let filter = pattern::KeyFilter { only, exclude };
+            if format == cli::ExportFormat::Bundle {
+                cli::export::run(
+                    project.as_deref(),
+                    &environments,
+                    &filter,
+                    output.as_deref(),
+                    sign,
+                )?;
+                return Ok(());
+            }
+
+            let (Some(project), [environment]) = (project, environments.as_slice()) else {
+                anyhow::bail!(
+                    "--format {} exports a single environment; pass one -e",
+                    format.to_possible_value().unwrap_or_default().get_name()
+                );
+            };
+            match format {
+                cli::ExportFormat::Sops => cli::export::run_sops(
+                    &project,
+                    environment,
+                    &filter,
+                    std::path::Path::new(output.as_deref().unwrap_or_default()),
+                    &sops_args,
+                )?,
+                cli::ExportFormat::SystemdCreds => {
+                    let Some(dir) = out_dir else {
+                        anyhow::bail!("--format systemd-creds needs --out-dir DIR");
+                    };
+                    cli::export::run_systemd_creds(
+                        &project,
+                        environment,
+                        &filter,
+                        &dir,
+                        interpolate,
+                    )?
+                }
+                _ => cli::export::run_plaintext(
+                    &project,
+                    environment,
+                    &filter,
+                    format,
+                    output.as_deref(),
+                    interpolate,
+                )?,
+            }
+        }
+        Commands::ImportSops {
+            file,
+            project,
+            environment,
+            transform,
+            on_conflict,
+            force,
+        } => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(project.as_deref())?;
+            let environment = resolver.environment(environment.as_deref())?;
+            cli::import_sops::run(
+                &project,
+                &environment,
+                &file,
+                &transform,
+                on_conflict,
+                force,
+            )?
+        }
+        Commands::ImportVercel(options) => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(options.project.as_deref())?;
+            let environment = resolver.environment(options.environment.as_deref())?;
+            cli::import_platform::run(
+                platforms::Platform::Vercel,
+                &project,
+                &environment,
+                &options,
+            )?
+        }
+        Commands::ImportNetlify(options) => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(options.project.as_deref())?;
+            let environment = resolver.environment(options.environment.as_deref())?;
+            cli::import_platform::run(
+                platforms::Platform::Netlify,
+                &project,
+                &environment,
+                &options,
+            )?
+        }
+        Commands::ImportRailway(options) => {
+            let resolver = ConfigResolver::new()?;
+            let project = resolver.project(options.project.as_deref())?;
+            let environment = resolver.environment(options.environment.as_deref())?;
+            cli::import_platform::run(
+                platforms::Platform::Railway,
+                &project,
+                &environment,
+                &options,
+            )?
+        }
+        Commands::Scaffold {
+            template,
+            project,
+            environment,
+            no_input,
         } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
             let environment = resolver.environment(environment.as_deref())?;
-            cli::export::run(&project, &environment, output.as_deref())?
+            cli::scaffold::run(
+                &project,
+                &environment,
+                &template,
+                no_input,
+                resolver.config(),
+            )?
+        }
+        Commands::Import {
+            input,
+            sha256,
+            verify_signer,
+            on_conflict,
+            force,
+            as_project,
+            as_environment,
+            allow_untagged,
+        } => {
+            let as_environment = as_environment.map(config::expand_environment).transpose()?;
+            cli::import::run(
+                &input,
+                sha256.as_deref(),
+                verify_signer.as_deref(),
+                on_conflict,
+                force,
+                as_project.as_deref(),
+                as_environment.as_deref(),
+                allow_untagged,
+            )?
         }
-        Commands::Import { input } => cli::import::run(&input)?,
         Commands::ImportEnv {
             project,
             environment,
             file,
+            format,
+            source,
+            transform,
+            on_conflict,
+            force,
+            atomic,
+            no_verify,
         } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
             let environment = resolver.environment(environment.as_deref())?;
-            cli::import_env::run(&project, &environment, file.as_deref())?
+            cli::import_env::run(
+                &project,
+                &environment,
+                file.as_deref(),
+                format,
+                source.as_deref(),
+                &transform,
+                on_conflict,
+                force,
+                atomic,
+                no_verify,
+                resolver.config(),
+            )?
         }
         Commands::History {
             project,
@@ -94,32 +805,55 @@ fn main() -> Result<()> {
             key,
             limit,
             show,
+            diff,
+            version,
         } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
             let environment = resolver.environment(environment.as_deref())?;
-            cli::history::run(&project, &environment, &key, limit, show)?
+            match version {
+                Some(version) => {
+                    cli::history::run_version(&project, &environment, &key, version, show)?
+                }
+                None if diff => cli::history::run_diff(&project, &environment, &key, limit, show)?,
+                None => cli::history::run(&project, &environment, &key, limit, show)?,
+            }
         }
-        Commands::Projects => cli::projects::run()?,
-        Commands::Envs { project } => {
+        Commands::Projects { json } => cli::projects::run(json)?,
+        Commands::Envs { project, json } => {
             let resolver = ConfigResolver::new()?;
             let project = resolver.project(project.as_deref())?;
-            cli::envs::run(&project)?
+            cli::envs::run(&project, json)?
         }
+        Commands::Plugin { action } => cli::plugin_cmd::run(action)?,
         Commands::Keychain { action } => cli::keychain_cmd::run(action)?,
+        Commands::Viewer { action } => cli::viewer_cmd::run(action)?,
+        Commands::Security { action } => cli::security_cmd::run(action)?,
         Commands::Config { action } => match action {
             ConfigAction::Init {
                 project,
                 environment,
             } => cli::config::run_init(&project, environment.as_deref())?,
-            ConfigAction::Show => cli::config::run_show()?,
+            ConfigAction::Show {
+                project,
+                environment,
+            } => cli::config::run_show(project.as_deref(), environment.as_deref())?,
             ConfigAction::Set {
                 project,
                 environment,
             } => cli::config::run_set(project.as_deref(), environment.as_deref())?,
         },
         Commands::Examples => cli::examples::run(),
-        Commands::Migrate => cli::migrate::run()?,
+        Commands::Migrate { dry_run } => cli::migrate::run(dry_run)?,
+        Commands::Store { action } => cli::store_cmd::run(action)?,
+        Commands::Backup { action } => cli::backup::run(action)?,
+        Commands::Db { action } => cli::db_cmd::run(action)?,
+        Commands::Doctor => cli::doctor::run()?,
+        Commands::Verify { chain, anchor } => cli::verify::run(chain, anchor.as_deref())?,
+        Commands::Stats { oldest } => cli::stats::run(oldest)?,
+        Commands::Compat { action } => match action {
+            CompatAction::Check { bundle } => cli::compat::run_check(bundle.as_deref())?,
+        },
     }
 
     Ok(())
@@ -0,0 +1,104 @@
+//! Output masking for `run --mask-output`: replace stored values in a
+//! child's output with `***KEY***`
+//!
+//! Output is filtered a line at a time, so a multi-line value (a PEM key)
+//! is masked line by line. Values shorter than `scan::MIN_SECRET_LEN` are
+//! left alone, as they'd match ordinary output ("true", "8080").
+
+use zeroize::Zeroizing;
+
+use crate::scan::MIN_SECRET_LEN;
+
+/// Replaces known values in lines of output
+#[derive(Default)]
+pub struct Masker {
+    /// (value, replacement), longest value first so it wins over its substrings
+    patterns: Vec<(Zeroizing<Vec<u8>>, Vec<u8>)>,
+}
+
+impl Masker {
+    /// Add a secret to mask; returns false if it's too short to mask
+    pub fn add(&mut self, key: &str, value: &str) -> bool {
+        let replacement = format!("***{}***", key).into_bytes();
+        let mut added = false;
+        for part in value.lines().map(str::trim) {
+            if part.len() < MIN_SECRET_LEN
+                || self
+                    .patterns
+                    .iter()
+                    .any(|(p, _)| p.as_slice() == part.as_bytes())
+            {
+                continue;
+            }
+            self.patterns.push((
+                Zeroizing::new(part.as_bytes().to_vec()),
+                replacement.clone(),
+            ));
+            added = true;
+        }
+        self.patterns
+            .sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+        added
+    }
+
+    /// `line` with every known value replaced
+    pub fn mask(&self, line: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(line.len());
+        let mut i = 0;
+        while i < line.len() {
+            match self
+                .patterns
+                .iter()
+                .find(|(value, _)| line[i..].starts_with(value))
+            {
+                Some((value, replacement)) => {
+                    out.extend_from_slice(replacement);
+                    i += value.len();
+                }
+                None => {
+                    out.push(line[i]);
+                    i += 1;
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(masker: &Masker, line: &str) -> String {
+        String::from_utf8(masker.mask(line.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_mask() {
+        let mut masker = Masker::default();
+        assert!(masker.add("API_KEY", "sk_live_abc123"));
+        assert!(masker.add("DB_PASS", "hunter2hunter2"));
+        assert!(masker.add("LONGER", "sk_live_abc123_extended"));
+        assert!(!masker.add("PORT", "8080"));
+
+        assert_eq!(
+            mask(
+                &masker,
+                "key=sk_live_abc123 pass=hunter2hunter2 port=8080\n"
+            ),
+            "key=***API_KEY*** pass=***DB_PASS*** port=8080\n"
+        );
+        assert_eq!(mask(&masker, "sk_live_abc123_extended"), "***LONGER***");
+        assert_eq!(mask(&masker, "nothing here"), "nothing here");
+    }
+
+    #[test]
+    fn test_multiline_value() {
+        let mut masker = Masker::default();
+        masker.add(
+            "TLS_KEY",
+            "-----BEGIN KEY-----\nMIIEvQIBADANBgkq\n-----END KEY-----\n",
+        );
+        assert_eq!(mask(&masker, "  MIIEvQIBADANBgkq\n"), "  ***TLS_KEY***\n");
+    }
+}
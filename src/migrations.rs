@@ -0,0 +1,279 @@
+//! Versioned schema migrations
+//!
+//! Each migration upgrades the store from `version - 1` to `version`. When a store
+//! is opened with an older `schema_version`, it is backed up next to the database
+//! and the pending migrations run in order, each in its own transaction.
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::path::{Path, PathBuf};
+
+use crate::store::{load_or_create_salt, new_store_id, read_metadata, write_metadata};
+
+/// Schema version written by this binary (the last migration's version)
+pub const SCHEMA_VERSION: i32 = 9;
+
+/// A single schema upgrade step
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    apply: fn(&Transaction) -> Result<()>,
+}
+
+/// All migrations, in order
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "Per-store key-derivation salt",
+        apply: add_kdf_salt,
+    },
+    Migration {
+        version: 3,
+        description: "Environments table for inheritance",
+        apply: add_environments,
+    },
+    Migration {
+        version: 4,
+        description: "Record where imported secrets came from",
+        apply: add_secret_source,
+    },
+    Migration {
+        version: 5,
+        description: "Record who changed each secret and why",
+        apply: add_changed_by,
+    },
+    Migration {
+        version: 6,
+        description: "Record the git commit secrets were changed from",
+        apply: add_git_context,
+    },
+    Migration {
+        version: 7,
+        description: "Keep deleted secrets' descriptions for undelete",
+        apply: add_history_description,
+    },
+    Migration {
+        version: 8,
+        description: "Store ID for per-store keychain entries",
+        apply: add_store_id,
+    },
+    Migration {
+        version: 9,
+        description: "Hash-chained history and audit log",
+        apply: add_history_chain,
+    },
+];
+
+fn add_kdf_salt(tx: &Transaction) -> Result<()> {
+    load_or_create_salt(tx)?;
+    Ok(())
+}
+
+fn add_environments(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS environments (
+            project TEXT NOT NULL,
+            name TEXT NOT NULL,
+            parent TEXT,
+            PRIMARY KEY(project, name)
+        );",
+    )?;
+    Ok(())
+}
+
+fn add_secret_source(tx: &Transaction) -> Result<()> {
+    if !has_column(tx, "secrets", "source")? {
+        tx.execute_batch("ALTER TABLE secrets ADD COLUMN source TEXT;")?;
+    }
+    Ok(())
+}
+
+fn add_changed_by(tx: &Transaction) -> Result<()> {
+    for table in ["secrets", "secret_history"] {
+        for column in ["changed_by", "reason"] {
+            if !has_column(tx, table, column)? {
+                tx.execute_batch(&format!(
+                    "ALTER TABLE {} ADD COLUMN {} TEXT;",
+                    table, column
+                ))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add_git_context(tx: &Transaction) -> Result<()> {
+    for table in ["secrets", "secret_history"] {
+        if !has_column(tx, table, "git")? {
+            tx.execute_batch(&format!("ALTER TABLE {} ADD COLUMN git TEXT;", table))?;
+        }
+    }
+    Ok(())
+}
+
+fn add_history_description(tx: &Transaction) -> Result<()> {
+    for column in ["description", "source"] {
+        if !has_column(tx, "secret_history", column)? {
+            tx.execute_batch(&format!(
+                "ALTER TABLE secret_history ADD COLUMN {} TEXT;",
+                column
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn add_store_id(tx: &Transaction) -> Result<()> {
+    if read_metadata(tx, "store_id")?.is_none() {
+        write_metadata(tx, "store_id", &new_store_id())?;
+    }
+    Ok(())
+}
+
+fn add_history_chain(tx: &Transaction) -> Result<()> {
+    for column in ["prev_hash", "entry_hash"] {
+        if !has_column(tx, "secret_history", column)? {
+            tx.execute_batch(&format!(
+                "ALTER TABLE secret_history ADD COLUMN {} TEXT;",
+                column
+            ))?;
+        }
+    }
+    // Existing rows are chained by the next write, which has the key
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            at TEXT NOT NULL,
+            action TEXT NOT NULL,
+            detail TEXT,
+            changed_by TEXT,
+            history_head TEXT,
+            prev_hash TEXT,
+            entry_hash TEXT
+        );",
+    )?;
+    Ok(())
+}
+
+/// Whether a table already has a column (stores created from a newer schema.sql do)
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(names.iter().any(|name| name == column))
+}
+
+/// Schema version recorded in the store (stores predating the field are v1)
+pub fn schema_version(conn: &Connection) -> Result<i32> {
+    match read_metadata(conn, "schema_version")? {
+        Some(version) => version
+            .parse()
+            .with_context(|| format!("Invalid schema version in store: {}", version)),
+        None => Ok(1),
+    }
+}
+
+/// Migrations that still need to run for a store at `version`
+pub fn pending(version: i32) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS.iter().filter(move |m| m.version > version)
+}
+
+/// Bring the store up to `SCHEMA_VERSION`, backing it up first.
+/// Returns the backup path if any migrations ran.
+pub fn run(conn: &Connection, path: &Path) -> Result<Option<PathBuf>> {
+    let current = schema_version(conn)?;
+
+    if current > SCHEMA_VERSION {
+        anyhow::bail!(
+            "Store schema v{} is newer than this tinysecrets supports (v{}). Upgrade tinysecrets to open it.",
+            current,
+            SCHEMA_VERSION
+        );
+    }
+    if current == SCHEMA_VERSION {
+        return Ok(None);
+    }
+
+    let backup = backup_path(path, current);
+    conn.execute("VACUUM INTO ?1", [backup.to_string_lossy()])
+        .with_context(|| format!("Failed to back up store to {}", backup.display()))?;
+
+    for migration in pending(current) {
+        log::debug!(
+            "Migrating schema to v{} ({})",
+            migration.version,
+            migration.description
+        );
+        let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        (migration.apply)(&tx).with_context(|| {
+            format!(
+                "Migration to schema v{} ({}) failed; backup at {}",
+                migration.version,
+                migration.description,
+                backup.display()
+            )
+        })?;
+        write_metadata(&tx, "schema_version", &migration.version.to_string())?;
+        tx.commit()?;
+    }
+
+    Ok(Some(backup))
+}
+
+/// Backup file for a store at schema `version` (never overwrites an earlier backup)
+fn backup_path(path: &Path, version: i32) -> PathBuf {
+    let base = format!("{}.schema-v{}.bak", path.display(), version);
+    let mut candidate = PathBuf::from(&base);
+    let mut n = 1;
+    while candidate.exists() {
+        candidate = PathBuf::from(format!("{}.{}", base, n));
+        n += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let versions: Vec<i32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let expected: Vec<i32> = (2..=SCHEMA_VERSION).collect();
+        assert_eq!(versions, expected);
+    }
+
+    #[test]
+    fn test_run_upgrades_v1_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(include_str!("schema.sql")).unwrap();
+
+        let backup = run(&conn, &path).unwrap().unwrap();
+        assert!(backup.exists());
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert!(read_metadata(&conn, "encryption_salt").unwrap().is_some());
+        assert!(has_column(&conn, "secrets", "source").unwrap());
+        assert!(has_column(&conn, "secret_history", "changed_by").unwrap());
+        assert!(has_column(&conn, "secret_history", "git").unwrap());
+        assert!(has_column(&conn, "secret_history", "description").unwrap());
+        assert!(read_metadata(&conn, "store_id").unwrap().is_some());
+        assert!(has_column(&conn, "secret_history", "entry_hash").unwrap());
+
+        // Already current: nothing to do
+        assert!(run(&conn, &path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_run_rejects_newer_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(include_str!("schema.sql")).unwrap();
+        write_metadata(&conn, "schema_version", &(SCHEMA_VERSION + 1).to_string()).unwrap();
+
+        assert!(run(&conn, &path).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Simple glob matching for key names (`*` and `?` wildcards), the fuzzy
+//! matching `pick` uses, and whole-text regexes for `[validate]` and
+//! `[key_policy]`
+
+/// Compile a `[validate]` or `[key_policy]` pattern so it has to match the
+/// whole text. The regex crate runs in linear time, so a pattern from a cloned
+/// repo's .tinysecrets.toml can't hang on (or overflow the stack over) a value.
+pub fn full_match_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    regex::Regex::new(&format!("^(?:{})$", pattern))
+}
+
+/// Match `text` against a glob `pattern` where `*` matches any run of
+/// characters and `?` matches exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<usize> = None;
+    let mut star_t = 0;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some(p);
+            star_t = t;
+            p += 1;
+        } else if let Some(s) = star {
+            // Backtrack: let the last `*` swallow one more character
+            p = s + 1;
+            star_t += 1;
+            t = star_t;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// True if the pattern contains glob wildcards
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Fuzzy-match `query` against `text`: the query's characters must appear in
+/// order (ignoring case). Returns a score (higher is better) and the character
+/// positions that matched, or None if it doesn't match.
+///
+/// Matches right after a `_`, `-`, `.` or `/` (or at the start) and runs of
+/// consecutive matches score higher, so `dbpw` prefers `DB_PASSWORD` over
+/// `DEBUG_PAYMENT_WEBHOOK`.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let text: Vec<char> = text.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut t = 0;
+
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (t..text.len()).find(|&i| text[i].to_lowercase().eq(q.to_lowercase()))?;
+        // Prefer a word start further on, unless this one continues a run
+        let word_start = |i: usize| i == 0 || matches!(text[i - 1], '_' | '-' | '.' | '/');
+        let continues = positions.last() == Some(&found.wrapping_sub(1));
+        let i = if continues || word_start(found) {
+            found
+        } else {
+            (found..text.len())
+                .find(|&i| word_start(i) && text[i].to_lowercase().eq(q.to_lowercase()))
+                .unwrap_or(found)
+        };
+
+        score += 16;
+        if positions.last() == Some(&i.wrapping_sub(1)) {
+            score += 12;
+        } else if word_start(i) {
+            score += 10;
+        }
+        score -= (i - t) as i64;
+        positions.push(i);
+        t = i + 1;
+    }
+    // Shorter keys win ties
+    score -= (text.len() - t) as i64 / 4;
+    Some((score, positions))
+}
+
+/// Which keys a command works on: those matching any `only` glob (every key
+/// if there are none), minus those matching an `exclude` glob
+#[derive(Debug, Default, Clone)]
+pub struct KeyFilter {
+    pub only: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl KeyFilter {
+    pub fn matches(&self, key: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|g| glob_match(g, key)))
+            && !self.exclude.iter().any(|g| glob_match(g, key))
+    }
+
+    /// True if every key passes
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty() && self.exclude.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full(pattern: &str, text: &str) -> bool {
+        full_match_regex(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_full_match_regex() {
+        assert!(full("sk_(live|test)_[A-Za-z0-9]{8,}", "sk_live_abcd1234XY"));
+        assert!(!full(
+            "sk_(live|test)_[A-Za-z0-9]{8,}",
+            "sk_prod_abcd1234XY"
+        ));
+        assert!(!full("sk_(live|test)_[A-Za-z0-9]{8,}", "sk_live_abc"));
+        assert!(full(r"\d{3}-\d{4}", "555-1234"));
+        assert!(!full(r"\d{3}-\d{4}", "555-12345"));
+        assert!(full(r"^postgres(ql)?://\S+$", "postgresql://u:p@db/app"));
+        assert!(full("a|b", "b"));
+        assert!(!full("a|b", "ab"));
+
+        for bad in ["(ab", "ab)", "[ab", "*a", "a{2,1}", r"\q", "[z-a]"] {
+            assert!(full_match_regex(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_full_match_regex_large_input() {
+        // Both used to overflow the stack or backtrack for minutes
+        let big = "x".repeat(1024 * 1024);
+        assert!(full(".*", &big));
+        assert!(!full("(a|a)*b", &"a".repeat(40)));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("STRIPE_*", "STRIPE_KEY"));
+        assert!(glob_match("*_KEY", "API_KEY"));
+        assert!(glob_match("DB_?", "DB_1"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("A*B*C", "AxxBxxC"));
+        assert!(!glob_match("STRIPE_*", "API_KEY"));
+        assert!(!glob_match("DB_?", "DB_10"));
+        assert!(!glob_match("A*B", "AxxC"));
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        let score = |q, t| fuzzy_match(q, t).map(|(score, _)| score);
+
+        assert_eq!(
+            fuzzy_match("dbpw", "DB_PASSWORD").unwrap().1,
+            vec![0, 1, 3, 7]
+        );
+        assert!(score("dbpw", "DB_PASSWORD") > score("dbpw", "DEBUG_PAYMENT_WEBHOOK"));
+        assert!(score("stripe", "STRIPE_KEY") > score("stripe", "OLD_STRIPE_KEY"));
+        assert!(score("key", "API_KEY") > score("key", "MONKEY_BUSINESS"));
+        assert_eq!(score("", "ANYTHING").map(|_| ()), Some(()));
+        assert!(fuzzy_match("xyz", "DB_PASSWORD").is_none());
+        assert!(fuzzy_match("pd", "DB_PASSWORD").is_some());
+        assert!(fuzzy_match("dp", "PD").is_none());
+    }
+
+    #[test]
+    fn test_key_filter() {
+        let filter = KeyFilter {
+            only: vec!["STRIPE_*".into(), "SENDGRID_*".into()],
+            exclude: vec!["*_TEST".into()],
+        };
+        assert!(filter.matches("STRIPE_KEY"));
+        assert!(filter.matches("SENDGRID_API_KEY"));
+        assert!(!filter.matches("STRIPE_KEY_TEST"));
+        assert!(!filter.matches("DB_PASSWORD"));
+
+        let exclude_only = KeyFilter {
+            only: Vec::new(),
+            exclude: vec!["DB_*".into()],
+        };
+        assert!(exclude_only.matches("API_KEY"));
+        assert!(!exclude_only.matches("DB_PASSWORD"));
+        assert!(KeyFilter::default().matches("ANYTHING"));
+    }
+}
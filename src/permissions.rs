@@ -0,0 +1,301 @@
+//! Owner-only files: the store, its directory, exports and editor temp files
+//!
+//! New stores are created 0700/0600. Opening a store that other users can
+//! read only warns (older versions created stores with the default umask), but
+//! one they can write to is refused unless `--insecure-ok` is given, since
+//! whoever can replace the file can swap in a store they control.
+//!
+//! Editor drafts go in a fresh 0700 directory, on tmpfs when there is one, and
+//! are overwritten before they're deleted.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs::{DirBuilder, File, OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::GlobalConfig;
+use crate::ui;
+
+static INSECURE_OK: AtomicBool = AtomicBool::new(false);
+
+/// `--insecure-ok`: open stores other users can write to (with a warning)
+pub fn set_insecure_ok(allow: bool) {
+    INSECURE_OK.store(allow, Ordering::Relaxed);
+}
+
+/// Who besides the owner can get at a file or directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exposure {
+    Private,
+    /// Group or others can read (or list) it
+    Readable,
+    /// Group or others can write to it (or replace files in it)
+    Writable,
+}
+
+/// Exposure from a Unix mode. A sticky directory (like /tmp) only lets owners
+/// replace their own files, so its write bits don't count.
+pub fn exposure(mode: u32, is_dir: bool) -> Exposure {
+    let sticky = is_dir && mode & 0o1000 != 0;
+    if mode & 0o022 != 0 && !sticky {
+        Exposure::Writable
+    } else if mode & 0o077 != 0 {
+        Exposure::Readable
+    } else {
+        Exposure::Private
+    }
+}
+
+/// The store file and its directory, with their exposure and the mode to fix it
+pub fn store_exposure(path: &Path) -> Vec<(&Path, Exposure, u32)> {
+    [(path, 0o600)]
+        .into_iter()
+        .chain(path.parent().map(|dir| (dir, 0o700)))
+        .filter_map(|(target, wanted)| {
+            let metadata = std::fs::metadata(target).ok()?;
+            let mode = metadata.permissions().mode();
+            Some((target, exposure(mode, metadata.is_dir()), wanted))
+        })
+        .collect()
+}
+
+/// Before unlocking: refuse a store other users can write to (unless
+/// `--insecure-ok`), and warn about one they can read
+pub fn check_store(path: &Path) -> Result<()> {
+    let exposed = store_exposure(path);
+    let writable: Vec<_> = exposed
+        .iter()
+        .filter(|(_, exposure, _)| *exposure == Exposure::Writable)
+        .collect();
+    let fixes = |entries: &[&(&Path, Exposure, u32)]| {
+        entries
+            .iter()
+            .map(|(target, _, wanted)| format!("chmod {:o} {}", wanted, target.display()))
+            .collect::<Vec<_>>()
+            .join(" && ")
+    };
+
+    if !writable.is_empty() {
+        if !INSECURE_OK.load(Ordering::Relaxed) {
+            anyhow::bail!(
+                "Refusing to open {}: other users can write to it and could swap in a store they control.\n\
+                 Fix with `{}`, or pass --insecure-ok if it's shared on purpose.",
+                path.display(),
+                fixes(&writable)
+            );
+        }
+        eprintln!(
+            "{} Store is writable by other users (allowed by --insecure-ok)",
+            ui::warn()
+        );
+        return Ok(());
+    }
+
+    let readable: Vec<_> = exposed
+        .iter()
+        .filter(|(_, exposure, _)| *exposure == Exposure::Readable)
+        .collect();
+    if readable.is_empty() || !GlobalConfig::load()?.warnings.loose_permissions {
+        return Ok(());
+    }
+    eprintln!(
+        "{} Store is readable by other users: {}",
+        ui::warn(),
+        path.display().to_string().cyan()
+    );
+    eprintln!("  Values are encrypted, but the file invites offline guessing.");
+    eprintln!("  Fix with: {}", fixes(&readable).cyan());
+    Ok(())
+}
+
+/// Before reading a passphrase file: refuse one other users can read or
+/// write (unless `--insecure-ok`), since it holds the passphrase in the clear
+pub fn check_passphrase_file(path: &Path) -> Result<()> {
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read passphrase file {}", path.display()))?;
+    if exposure(metadata.permissions().mode(), false) == Exposure::Private {
+        return Ok(());
+    }
+    if !INSECURE_OK.load(Ordering::Relaxed) {
+        anyhow::bail!(
+            "Refusing to read {}: other users can read it.\n\
+             Fix with `chmod 600 {}`, or pass --insecure-ok if that's intended.",
+            path.display(),
+            path.display()
+        );
+    }
+    eprintln!(
+        "{} Passphrase file is readable by other users (allowed by --insecure-ok)",
+        ui::warn()
+    );
+    Ok(())
+}
+
+/// Create a directory (and missing parents) as 0700
+pub fn create_private_dir_all(dir: &Path) -> std::io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+/// Create or truncate a file as 0600. An existing file keeps its mode, so
+/// tighten it too.
+pub fn create_private_file(path: &Path) -> std::io::Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// Create a file as 0600, failing if anything is already at `path`
+pub fn create_new_private_file(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+/// Edit text in $EDITOR through an owner-only temp file, shredded afterwards
+pub fn edit(text: &str) -> std::io::Result<String> {
+    let scratch = ScratchDir::new()?;
+    let path = scratch.path().join("value.txt");
+    create_private_file(&path)?.write_all(text.as_bytes())?;
+    edit::edit_file(&path)?;
+    std::fs::read_to_string(&path)
+}
+
+/// Where editor drafts go: the per-user runtime directory or /dev/shm (both
+/// tmpfs, so drafts never reach a disk), else the system temp directory
+fn scratch_base() -> PathBuf {
+    let runtime = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| {
+            std::fs::metadata(dir).is_ok_and(|m| {
+                m.is_dir() && m.uid() == unsafe { libc::getuid() } && m.mode() & 0o077 == 0
+            })
+        });
+    runtime
+        .or_else(|| Some(PathBuf::from("/dev/shm")).filter(|dir| dir.is_dir()))
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// A private (0700) temp directory whose files are overwritten with zeros
+/// before it's removed. Editors keep swap and backup files next to the draft,
+/// so those are covered too.
+pub struct ScratchDir {
+    dir: tempfile::TempDir,
+}
+
+impl ScratchDir {
+    /// On tmpfs when there is one
+    pub fn new() -> std::io::Result<Self> {
+        Self::new_in(&scratch_base())
+    }
+
+    pub fn new_in(parent: &Path) -> std::io::Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("tinysecrets-")
+            .permissions(Permissions::from_mode(0o700))
+            .tempdir_in(parent)?;
+        Ok(Self { dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let Ok(entries) = std::fs::read_dir(self.dir.path()) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let _ = shred(&entry.path());
+        }
+    }
+}
+
+/// Overwrite a regular file with zeros and flush it to disk
+pub fn shred(path: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_file() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 4096];
+    let mut remaining = metadata.len();
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposure() {
+        assert_eq!(exposure(0o100600, false), Exposure::Private);
+        assert_eq!(exposure(0o100644, false), Exposure::Readable);
+        assert_eq!(exposure(0o100664, false), Exposure::Writable);
+        assert_eq!(exposure(0o40700, true), Exposure::Private);
+        assert_eq!(exposure(0o40755, true), Exposure::Readable);
+        assert_eq!(exposure(0o40777, true), Exposure::Writable);
+        // /tmp
+        assert_eq!(exposure(0o41777, true), Exposure::Readable);
+    }
+
+    #[test]
+    fn test_create_private_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.json");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+
+        create_private_file(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn test_check_passphrase_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "correct horse battery staple").unwrap();
+
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+        assert!(check_passphrase_file(&path).is_err());
+        std::fs::set_permissions(&path, Permissions::from_mode(0o600)).unwrap();
+        assert!(check_passphrase_file(&path).is_ok());
+    }
+
+    #[test]
+    fn test_scratch_dir_shreds() {
+        let scratch = ScratchDir::new().unwrap();
+        let dir = scratch.dir.path().to_path_buf();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        let path = dir.join("value.txt");
+        create_private_file(&path)
+            .unwrap()
+            .write_all(b"hunter2")
+            .unwrap();
+        shred(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), [0u8; 7]);
+
+        drop(scratch);
+        assert!(!dir.exists());
+    }
+}
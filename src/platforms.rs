@@ -0,0 +1,233 @@
+//! Environment variables pulled from hosting platforms through their CLIs
+//!
+//! Each CLI speaks its own format: `vercel env pull` only writes a dotenv file
+//! (into a private scratch directory here, shredded afterwards), while
+//! `netlify env:list` and `railway variables` print JSON. All of them come out
+//! as key/value pairs, with values read the way the platform meant them rather
+//! than however `import-env` would guess.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+use crate::cli::import_env::parse_dotenv;
+use crate::permissions::ScratchDir;
+use crate::sops;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Vercel,
+    Netlify,
+    Railway,
+}
+
+impl Platform {
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Vercel => "Vercel",
+            Platform::Netlify => "Netlify",
+            Platform::Railway => "Railway",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Platform::Vercel => "vercel",
+            Platform::Netlify => "netlify",
+            Platform::Railway => "railway",
+        }
+    }
+
+    /// The platform's name for an environment. Vercel and Netlify have fixed
+    /// ones, so the usual short names are translated; Railway environments
+    /// are named freely and kept as they are.
+    pub fn remote_environment(self, environment: &str) -> String {
+        let translated = match (self, environment) {
+            (Platform::Vercel, "prod") => "production",
+            (Platform::Vercel, "dev" | "local") => "development",
+            (Platform::Vercel, "staging") => "preview",
+            (Platform::Netlify, "prod") => "production",
+            (Platform::Netlify, "development" | "local") => "dev",
+            (Platform::Netlify, "preview" | "staging") => "deploy-preview",
+            _ => environment,
+        };
+        translated.to_string()
+    }
+
+    /// Variables the platform sets on every deployment, which describe the
+    /// deployment rather than configure the app
+    pub fn is_system_var(self, key: &str) -> bool {
+        match self {
+            Platform::Vercel => {
+                key == "VERCEL"
+                    || key == "NX_DAEMON"
+                    || key.starts_with("VERCEL_")
+                    || key.starts_with("TURBO_")
+            }
+            Platform::Netlify => false,
+            Platform::Railway => key.starts_with("RAILWAY_"),
+        }
+    }
+}
+
+/// Pull one environment's variables. `extra_args` (e.g. `--scope`, `--service`)
+/// are passed to the platform CLI as-is.
+pub fn pull(
+    platform: Platform,
+    environment: &str,
+    extra_args: &[String],
+) -> Result<Vec<(String, String)>> {
+    match platform {
+        Platform::Vercel => pull_vercel(environment, extra_args),
+        Platform::Netlify => {
+            let stdout = run(
+                platform,
+                &["env:list", "--json", "--context", environment],
+                extra_args,
+            )?;
+            json_pairs(platform, &stdout)
+        }
+        Platform::Railway => {
+            let stdout = run(
+                platform,
+                &["variables", "--json", "--environment", environment],
+                extra_args,
+            )?;
+            json_pairs(platform, &stdout)
+        }
+    }
+}
+
+/// `vercel env pull` has no stdout mode, so it writes into a scratch directory
+fn pull_vercel(environment: &str, extra_args: &[String]) -> Result<Vec<(String, String)>> {
+    let scratch = ScratchDir::new().context("Failed to create a private temp directory")?;
+    let path = scratch.path().join(".env.pull");
+    let path_arg = path.to_string_lossy();
+    run(
+        Platform::Vercel,
+        &[
+            "env",
+            "pull",
+            &path_arg,
+            "--environment",
+            environment,
+            "--yes",
+        ],
+        extra_args,
+    )?;
+    let contents = Zeroizing::new(
+        std::fs::read_to_string(&path).context("vercel env pull didn't write a file")?,
+    );
+    vercel_pairs(&contents)
+}
+
+/// Run the platform CLI without a terminal, keeping its stdout
+fn run(platform: Platform, args: &[&str], extra_args: &[String]) -> Result<Zeroizing<Vec<u8>>> {
+    let output = Command::new(platform.program())
+        .args(args)
+        .args(extra_args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .with_context(|| format!("Failed to run {} (is it installed?)", platform.program()))?;
+    let stdout = Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} {} failed: {}",
+            platform.program(),
+            args[..args.len().min(2)].join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(stdout)
+}
+
+/// The dotenv file Vercel writes: double-quoted values with `\n` escapes,
+/// under a `# Created by Vercel CLI` comment
+fn vercel_pairs(contents: &str) -> Result<Vec<(String, String)>> {
+    parse_dotenv(contents)
+        .into_iter()
+        .map(|entry| {
+            entry.map_err(|bad| {
+                anyhow::anyhow!(
+                    "Unexpected line {} from vercel env pull: {}",
+                    bad.line,
+                    bad.reason
+                )
+            })
+        })
+        .collect()
+}
+
+/// A `{"KEY": "value"}` object, as Netlify and Railway print it. CLIs may log
+/// a line or two before the JSON, so parsing starts at the first `{`.
+fn json_pairs(platform: Platform, stdout: &[u8]) -> Result<Vec<(String, String)>> {
+    let start = stdout.iter().position(|&b| b == b'{').with_context(|| {
+        format!(
+            "{} printed no variables (is the project linked?)",
+            platform.program()
+        )
+    })?;
+    let document: Value = serde_json::from_slice(&stdout[start..])
+        .with_context(|| format!("Unexpected output from {}", platform.program()))?;
+    sops::flatten(&document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_vercel_pairs() {
+        let contents = "# Created by Vercel CLI\n\
+                        API_KEY=\"sk-1\"\n\
+                        CERT=\"line1\\nline2\"\n\
+                        VERCEL=\"1\"\n\
+                        SECRET=\"\"\n";
+        assert_eq!(
+            vercel_pairs(contents).unwrap(),
+            pairs(&[
+                ("API_KEY", "sk-1"),
+                ("CERT", "line1\nline2"),
+                ("VERCEL", "1"),
+                ("SECRET", ""),
+            ])
+        );
+        assert!(vercel_pairs("not a pair\n").is_err());
+    }
+
+    #[test]
+    fn test_json_pairs() {
+        let stdout =
+            b"Using environment staging\n{\"DATABASE_URL\":\"postgres://db\",\"PORT\":8080}";
+        assert_eq!(
+            json_pairs(Platform::Railway, stdout).unwrap(),
+            pairs(&[("DATABASE_URL", "postgres://db"), ("PORT", "8080")])
+        );
+        assert!(json_pairs(Platform::Netlify, b"No site linked").is_err());
+    }
+
+    #[test]
+    fn test_platform_names() {
+        assert_eq!(Platform::Vercel.remote_environment("prod"), "production");
+        assert_eq!(Platform::Vercel.remote_environment("staging"), "preview");
+        assert_eq!(
+            Platform::Netlify.remote_environment("staging"),
+            "deploy-preview"
+        );
+        assert_eq!(Platform::Railway.remote_environment("prod"), "prod");
+
+        assert!(Platform::Vercel.is_system_var("VERCEL_GIT_COMMIT_SHA"));
+        assert!(Platform::Vercel.is_system_var("VERCEL"));
+        assert!(!Platform::Vercel.is_system_var("VERCELLO_TOKEN"));
+        assert!(Platform::Railway.is_system_var("RAILWAY_PUBLIC_DOMAIN"));
+        assert!(!Platform::Netlify.is_system_var("NETLIFY_AUTH_TOKEN"));
+    }
+}
@@ -0,0 +1,301 @@
+//! Provider plugins: importers and exporters that live outside this crate
+//!
+//! A plugin is any executable on `PATH` named `tinysecrets-plugin-<name>`. It is run
+//! once per request with a single JSON object on stdin and answers with a single JSON
+//! object on stdout; stderr is passed through so plugins can log or prompt on the tty.
+//!
+//! ```text
+//! -> {"protocol":1,"action":"describe"}
+//! <- {"description":"Doppler","capabilities":["pull","push"]}
+//! -> {"protocol":1,"action":"pull","project":"api","environment":"dev","args":["--config","dev"]}
+//! <- {"secrets":[{"key":"API_KEY","value":"...","description":null}]}
+//! -> {"protocol":1,"action":"push","project":"api","environment":"dev","args":[],"secrets":[...]}
+//! <- {}
+//! ```
+//!
+//! Any response may instead be `{"error":"message"}`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Executable name prefix that marks a plugin
+pub const PLUGIN_PREFIX: &str = "tinysecrets-plugin-";
+/// Version of the stdin/stdout protocol spoken to plugins
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A secret crossing the plugin boundary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginSecret {
+    pub key: String,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Where secrets come from (an importer)
+pub trait SecretSource {
+    /// Fetch the secrets for a project/environment; `args` are passed through from the CLI
+    fn pull(&self, project: &str, environment: &str, args: &[String]) -> Result<Vec<PluginSecret>>;
+}
+
+/// Where secrets go (an exporter)
+pub trait SecretSink {
+    /// Send a project/environment's secrets; `args` are passed through from the CLI
+    fn push(
+        &self,
+        project: &str,
+        environment: &str,
+        args: &[String],
+        secrets: &[PluginSecret],
+    ) -> Result<()>;
+}
+
+/// What a plugin says about itself
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PluginInfo {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct PluginRequest<'a> {
+    protocol: u32,
+    action: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    environment: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    args: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secrets: Option<&'a [PluginSecret]>,
+}
+
+#[derive(Deserialize)]
+struct PullResponse {
+    #[serde(default)]
+    secrets: Vec<PluginSecret>,
+}
+
+/// An external `tinysecrets-plugin-*` executable
+#[derive(Debug, Clone)]
+pub struct ExternalPlugin {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl ExternalPlugin {
+    /// Find a plugin by name on `PATH`
+    pub fn find(name: &str) -> Result<Self> {
+        discover()
+            .into_iter()
+            .find(|plugin| plugin.name == name)
+            .with_context(|| {
+                format!(
+                    "No plugin named '{}' (looked for {}{} on PATH)",
+                    name, PLUGIN_PREFIX, name
+                )
+            })
+    }
+
+    pub fn describe(&self) -> Result<PluginInfo> {
+        let response = self.call(&PluginRequest {
+            protocol: PROTOCOL_VERSION,
+            action: "describe",
+            project: None,
+            environment: None,
+            args: &[],
+            secrets: None,
+        })?;
+        parse_response(&self.name, &response)
+    }
+
+    /// Run the plugin with one request and return its raw stdout
+    fn call(&self, request: &PluginRequest) -> Result<Vec<u8>> {
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("Failed to run plugin {}", self.path.display()))?;
+
+        let request = serde_json::to_vec(request)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A plugin that exits without reading its input reports through its status
+            let _ = stdin.write_all(&request);
+        }
+
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("Plugin '{}' failed", self.name))?;
+        if !output.status.success() {
+            // Prefer the plugin's own error message if it managed to send one
+            let error = serde_json::from_slice::<serde_json::Value>(&output.stdout)
+                .ok()
+                .and_then(|v| v.get("error")?.as_str().map(String::from));
+            match error {
+                Some(error) => anyhow::bail!("Plugin '{}': {}", self.name, error),
+                None => anyhow::bail!("Plugin '{}' exited with {}", self.name, output.status),
+            }
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl SecretSource for ExternalPlugin {
+    fn pull(&self, project: &str, environment: &str, args: &[String]) -> Result<Vec<PluginSecret>> {
+        let response = self.call(&PluginRequest {
+            protocol: PROTOCOL_VERSION,
+            action: "pull",
+            project: Some(project),
+            environment: Some(environment),
+            args,
+            secrets: None,
+        })?;
+        Ok(parse_response::<PullResponse>(&self.name, &response)?.secrets)
+    }
+}
+
+impl SecretSink for ExternalPlugin {
+    fn push(
+        &self,
+        project: &str,
+        environment: &str,
+        args: &[String],
+        secrets: &[PluginSecret],
+    ) -> Result<()> {
+        let response = self.call(&PluginRequest {
+            protocol: PROTOCOL_VERSION,
+            action: "push",
+            project: Some(project),
+            environment: Some(environment),
+            args,
+            secrets: Some(secrets),
+        })?;
+        parse_response::<serde_json::Value>(&self.name, &response)?;
+        Ok(())
+    }
+}
+
+/// Decode a plugin response, surfacing `{"error": ...}` as an error
+fn parse_response<T: serde::de::DeserializeOwned>(name: &str, stdout: &[u8]) -> Result<T> {
+    let value: serde_json::Value = serde_json::from_slice(stdout)
+        .with_context(|| format!("Plugin '{}' sent an invalid response (expected JSON)", name))?;
+    if let Some(error) = value.get("error").and_then(|e| e.as_str()) {
+        anyhow::bail!("Plugin '{}': {}", name, error);
+    }
+    serde_json::from_value(value)
+        .with_context(|| format!("Plugin '{}' sent an unexpected response", name))
+}
+
+/// All plugins on `PATH` (the first one wins when a name appears twice)
+pub fn discover() -> Vec<ExternalPlugin> {
+    let dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    discover_in(&dirs)
+}
+
+fn discover_in(dirs: &[PathBuf]) -> Vec<ExternalPlugin> {
+    let mut plugins = BTreeMap::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(plugin_name)
+            else {
+                continue;
+            };
+            if is_executable(&path) {
+                plugins.entry(name.to_string()).or_insert(ExternalPlugin {
+                    name: name.to_string(),
+                    path,
+                });
+            }
+        }
+    }
+    plugins.into_values().collect()
+}
+
+/// Plugin name from an executable's file name (`tinysecrets-plugin-doppler` -> `doppler`)
+fn plugin_name(file_name: &str) -> Option<&str> {
+    file_name
+        .strip_prefix(PLUGIN_PREFIX)
+        .filter(|name| !name.is_empty() && !name.contains('.'))
+}
+
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_name() {
+        assert_eq!(plugin_name("tinysecrets-plugin-doppler"), Some("doppler"));
+        assert_eq!(plugin_name("tinysecrets-plugin-"), None);
+        assert_eq!(plugin_name("tinysecrets-plugin-doppler.md"), None);
+        assert_eq!(plugin_name("tinysecrets"), None);
+    }
+
+    #[test]
+    fn test_discover_in() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let write = |dir: &Path, name: &str, mode: u32| {
+            let path = dir.join(name);
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        write(first.path(), "tinysecrets-plugin-railway", 0o755);
+        write(first.path(), "tinysecrets-plugin-notexec", 0o644);
+        write(second.path(), "tinysecrets-plugin-railway", 0o755);
+        write(second.path(), "tinysecrets-plugin-doppler", 0o755);
+
+        let plugins = discover_in(&[first.path().to_path_buf(), second.path().to_path_buf()]);
+        let found: Vec<(&str, &Path)> = plugins
+            .iter()
+            .map(|p| (p.name.as_str(), p.path.parent().unwrap()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("doppler", second.path()), ("railway", first.path())]
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let pulled: PullResponse =
+            parse_response("x", br#"{"secrets":[{"key":"A","value":"1"}]}"#).unwrap();
+        assert_eq!(
+            pulled.secrets,
+            vec![PluginSecret {
+                key: "A".into(),
+                value: "1".into(),
+                description: None
+            }]
+        );
+
+        let err = parse_response::<PullResponse>("x", br#"{"error":"not logged in"}"#)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Plugin 'x': not logged in");
+        assert!(parse_response::<PullResponse>("x", b"nope").is_err());
+    }
+}
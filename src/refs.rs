@@ -0,0 +1,191 @@
+//! Secret references: values that point at other secrets
+//!
+//! A value may contain `{{ref:project/environment/KEY}}`, which is replaced by that
+//! secret's value whenever values are read for use (`get`, `run`, `shell`, ...).
+//! Shorter forms are relative to the secret being read: `{{ref:environment/KEY}}`
+//! stays in the same project and `{{ref:KEY}}` in the same environment. Referenced
+//! values are resolved in turn; a reference back to a secret already being resolved
+//! is an error rather than a loop. `\{{ref:` is a literal `{{ref:`, for values that
+//! need the text itself.
+
+use anyhow::{Context, Result};
+use std::fmt;
+
+const REF_OPEN: &str = "{{ref:";
+const ESCAPE: char = '\\';
+const REF_CLOSE: &str = "}}";
+
+/// A fully qualified secret: project/environment/key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretPath {
+    pub project: String,
+    pub environment: String,
+    pub key: String,
+}
+
+impl SecretPath {
+    pub fn new(project: &str, environment: &str, key: &str) -> Self {
+        Self {
+            project: project.to_string(),
+            environment: environment.to_string(),
+            key: key.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SecretPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.project, self.environment, self.key)
+    }
+}
+
+/// Whether a value contains any references (or escaped ones)
+pub fn contains_refs(value: &str) -> bool {
+    value.contains(REF_OPEN)
+}
+
+/// Replace every reference in `value`, which belongs to `from`. `lookup` returns a
+/// secret's stored value, or None if it doesn't exist.
+pub fn resolve(
+    value: &str,
+    from: &SecretPath,
+    lookup: &mut dyn FnMut(&SecretPath) -> Result<Option<String>>,
+) -> Result<String> {
+    resolve_chain(value, &mut vec![from.clone()], lookup)
+}
+
+fn resolve_chain(
+    value: &str,
+    chain: &mut Vec<SecretPath>,
+    lookup: &mut dyn FnMut(&SecretPath) -> Result<Option<String>>,
+) -> Result<String> {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find(REF_OPEN) {
+        if rest[..start].ends_with(ESCAPE) {
+            output.push_str(&rest[..start - ESCAPE.len_utf8()]);
+            output.push_str(REF_OPEN);
+            rest = &rest[start + REF_OPEN.len()..];
+            continue;
+        }
+        output.push_str(&rest[..start]);
+        let holder = chain
+            .last()
+            .expect("chain starts with the secret being read");
+        let after = &rest[start + REF_OPEN.len()..];
+        let end = after
+            .find(REF_CLOSE)
+            .with_context(|| format!("Unclosed '{}' in {}", REF_OPEN, holder))?;
+        let target = parse_target(after[..end].trim(), holder)?;
+
+        if chain.contains(&target) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain(std::iter::once(&target))
+                .map(|path| path.to_string())
+                .collect();
+            anyhow::bail!("Reference cycle: {}", cycle.join(" -> "));
+        }
+        let referenced = lookup(&target)?
+            .with_context(|| format!("{} refers to {}, which isn't set", holder, target))?;
+
+        chain.push(target);
+        output.push_str(&resolve_chain(&referenced, chain, lookup)?);
+        chain.pop();
+        rest = &after[end + REF_CLOSE.len()..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Parse `project/environment/KEY`, `environment/KEY` or `KEY` relative to `from`
+fn parse_target(target: &str, from: &SecretPath) -> Result<SecretPath> {
+    let parts: Vec<&str> = target.split('/').collect();
+    if parts.iter().any(|part| part.trim().is_empty()) {
+        anyhow::bail!("Invalid reference '{}' in {}", target, from);
+    }
+    Ok(match parts[..] {
+        [key] => SecretPath::new(&from.project, &from.environment, key),
+        [environment, key] => SecretPath::new(&from.project, environment, key),
+        [project, environment, key] => SecretPath::new(project, environment, key),
+        _ => anyhow::bail!(
+            "Invalid reference '{}' in {} (expected project/environment/KEY)",
+            target,
+            from
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn store(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn resolve_in(secrets: &HashMap<String, String>, path: &str) -> Result<String> {
+        let parts: Vec<&str> = path.split('/').collect();
+        let from = SecretPath::new(parts[0], parts[1], parts[2]);
+        let mut lookup = |p: &SecretPath| Ok(secrets.get(&p.to_string()).cloned());
+        resolve(&secrets[path], &from, &mut lookup)
+    }
+
+    #[test]
+    fn test_resolves_transitively() {
+        let secrets = store(&[
+            ("shared/prod/DB_PASSWORD", "hunter2"),
+            ("api/prod/DB_PASSWORD", "{{ref:shared/prod/DB_PASSWORD}}"),
+            (
+                "api/prod/DB_URL",
+                "postgres://api:{{ref:DB_PASSWORD}}@db/api",
+            ),
+            ("api/dev/DB_URL", "{{ref:prod/DB_URL}}"),
+        ]);
+        assert_eq!(
+            resolve_in(&secrets, "api/prod/DB_URL").unwrap(),
+            "postgres://api:hunter2@db/api"
+        );
+        assert_eq!(
+            resolve_in(&secrets, "api/dev/DB_URL").unwrap(),
+            "postgres://api:hunter2@db/api"
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        let secrets = store(&[
+            ("a/e/TEMPLATE", "prefix \\{{ref:nope}} suffix"),
+            ("a/e/MIXED", "\\{{ref:B}} is {{ref:B}}"),
+            ("a/e/B", "b"),
+        ]);
+        assert_eq!(
+            resolve_in(&secrets, "a/e/TEMPLATE").unwrap(),
+            "prefix {{ref:nope}} suffix"
+        );
+        assert_eq!(resolve_in(&secrets, "a/e/MIXED").unwrap(), "{{ref:B}} is b");
+    }
+
+    #[test]
+    fn test_errors() {
+        let secrets = store(&[
+            ("a/e/A", "{{ref:B}}"),
+            ("a/e/B", "x{{ref:a/e/A}}"),
+            ("a/e/MISSING", "{{ref:NOPE}}"),
+            ("a/e/BAD", "{{ref:a/b/c/d}}"),
+            ("a/e/OPEN", "{{ref:A"),
+        ]);
+        let err = resolve_in(&secrets, "a/e/A").unwrap_err().to_string();
+        assert_eq!(err, "Reference cycle: a/e/A -> a/e/B -> a/e/A");
+        let err = resolve_in(&secrets, "a/e/MISSING").unwrap_err().to_string();
+        assert!(err.contains("a/e/NOPE, which isn't set"), "{}", err);
+        assert!(resolve_in(&secrets, "a/e/BAD").is_err());
+        assert!(resolve_in(&secrets, "a/e/OPEN").is_err());
+    }
+}
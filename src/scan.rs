@@ -0,0 +1,150 @@
+//! Leak scanning: find stored secret values in files without keeping them around
+//!
+//! Values are reduced to a SHA-256 digest plus a rolling hash of the same length.
+//! Text is searched with a Rabin-Karp window per distinct length and every rolling
+//! hash hit is confirmed against the digest, so the scanner never holds plaintext.
+
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// Values shorter than this are too likely to match by accident ("true", "8080")
+pub const MIN_SECRET_LEN: usize = 8;
+
+const BASE: u64 = 257;
+
+/// A stored value, reduced to what's needed to recognise it
+struct Fingerprint {
+    digest: [u8; 32],
+    label: String,
+}
+
+/// A place a stored value appears
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// 1-based line within the scanned text
+    pub line: usize,
+    /// Which secret matched (e.g. "prod/API_KEY"), never the value
+    pub label: String,
+}
+
+/// Searches text for any of a set of secret values
+#[derive(Default)]
+pub struct Scanner {
+    /// value length -> rolling hash -> fingerprints
+    by_len: BTreeMap<usize, HashMap<u64, Vec<Fingerprint>>>,
+}
+
+impl Scanner {
+    /// Add a value to look for; returns false if it's too short to scan for
+    pub fn add(&mut self, label: &str, value: &str) -> bool {
+        let bytes = value.as_bytes();
+        if bytes.len() < MIN_SECRET_LEN {
+            return false;
+        }
+        self.by_len
+            .entry(bytes.len())
+            .or_default()
+            .entry(rolling_hash(bytes))
+            .or_default()
+            .push(Fingerprint {
+                digest: Sha256::digest(bytes).into(),
+                label: label.to_string(),
+            });
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_len.is_empty()
+    }
+
+    /// Find every occurrence of a known value in `text`
+    pub fn scan(&self, text: &str) -> Vec<Finding> {
+        let bytes = text.as_bytes();
+        let mut findings = Vec::new();
+
+        for (&len, hashes) in &self.by_len {
+            if bytes.len() < len {
+                continue;
+            }
+            let high = BASE.wrapping_pow(len as u32 - 1);
+            let mut hash = rolling_hash(&bytes[..len]);
+
+            for start in 0..=bytes.len() - len {
+                if start > 0 {
+                    hash = hash
+                        .wrapping_sub((bytes[start - 1] as u64).wrapping_mul(high))
+                        .wrapping_mul(BASE)
+                        .wrapping_add(bytes[start + len - 1] as u64);
+                }
+                let Some(candidates) = hashes.get(&hash) else {
+                    continue;
+                };
+
+                let window = &bytes[start..start + len];
+                let digest: [u8; 32] = Sha256::digest(window).into();
+                for fingerprint in candidates.iter().filter(|f| f.digest == digest) {
+                    findings.push(Finding {
+                        line: line_of(bytes, start),
+                        label: fingerprint.label.clone(),
+                    });
+                }
+            }
+        }
+
+        findings.sort_by(|a, b| a.line.cmp(&b.line).then_with(|| a.label.cmp(&b.label)));
+        findings.dedup();
+        findings
+    }
+}
+
+fn rolling_hash(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0u64, |h, &b| h.wrapping_mul(BASE).wrapping_add(b as u64))
+}
+
+/// 1-based line number of a byte offset
+fn line_of(bytes: &[u8], offset: usize) -> usize {
+    bytes[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_finds_values() {
+        let mut scanner = Scanner::default();
+        scanner.add("prod/API_KEY", "sk_live_abc123");
+        scanner.add("dev/DB_PASSWORD", "hunter2hunter2");
+
+        let text = "const key = 'sk_live_abc123';\nok\npassword: hunter2hunter2 sk_live_abc123\n";
+        assert_eq!(
+            scanner.scan(text),
+            vec![
+                Finding {
+                    line: 1,
+                    label: "prod/API_KEY".into()
+                },
+                Finding {
+                    line: 3,
+                    label: "dev/DB_PASSWORD".into()
+                },
+                Finding {
+                    line: 3,
+                    label: "prod/API_KEY".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_ignores_short_values_and_near_misses() {
+        let mut scanner = Scanner::default();
+        assert!(!scanner.add("dev/PORT", "8080"));
+        scanner.add("dev/TOKEN", "abcdefgh12");
+
+        assert!(scanner.scan("port 8080, token abcdefgh13").is_empty());
+        assert_eq!(scanner.scan("abcdefgh12").len(), 1);
+    }
+}
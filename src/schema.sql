@@ -18,6 +18,10 @@ CREATE TABLE IF NOT EXISTS secrets (
     created_at TEXT NOT NULL,
     updated_at TEXT NOT NULL,
     version INTEGER NOT NULL DEFAULT 1,
+    source TEXT,
+    changed_by TEXT,
+    reason TEXT,
+    git TEXT,
     UNIQUE(project, environment, key)
 );
 
@@ -30,7 +34,34 @@ CREATE TABLE IF NOT EXISTS secret_history (
     encrypted_value TEXT NOT NULL,
     version INTEGER NOT NULL,
     created_at TEXT NOT NULL,
-    deleted_at TEXT
+    deleted_at TEXT,
+    changed_by TEXT,
+    reason TEXT,
+    git TEXT,
+    description TEXT,
+    source TEXT,
+    prev_hash TEXT,
+    entry_hash TEXT
+);
+
+-- Maintenance that rewrote history (hash-chained like secret_history)
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    at TEXT NOT NULL,
+    action TEXT NOT NULL,
+    detail TEXT,
+    changed_by TEXT,
+    history_head TEXT,
+    prev_hash TEXT,
+    entry_hash TEXT
+);
+
+-- Per-environment settings (environment inheritance)
+CREATE TABLE IF NOT EXISTS environments (
+    project TEXT NOT NULL,
+    name TEXT NOT NULL,
+    parent TEXT,
+    PRIMARY KEY(project, name)
 );
 
 -- Indexes for common queries
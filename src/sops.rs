@@ -0,0 +1,158 @@
+//! SOPS-encrypted files through the `sops` CLI
+//!
+//! Plaintext only ever travels over pipes: `sops` decrypts to our stdout and
+//! encrypts from our stdin, so nothing is written to disk unencrypted.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// Decrypt a SOPS file (YAML, JSON, dotenv or INI) and flatten it to key/value pairs
+pub fn decrypt(path: &Path) -> Result<Vec<(String, String)>> {
+    let output = Command::new("sops")
+        .args(["--decrypt", "--output-type", "json"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .context("Failed to run sops (is it installed?)")?;
+    let stdout = Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        anyhow::bail!(
+            "sops couldn't decrypt {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let document: Value =
+        serde_json::from_slice(&stdout).context("Unexpected output from sops --decrypt")?;
+    flatten(&document)
+}
+
+/// Encrypt `secrets` with SOPS into the format `output`'s extension calls for.
+/// `.sops.yaml` creation rules are matched against `output`; `extra_args`
+/// (e.g. `--age <recipient>`) are passed to sops as-is.
+pub fn encrypt(
+    secrets: &[(String, String)],
+    output: &Path,
+    extra_args: &[String],
+) -> Result<Vec<u8>> {
+    let document: serde_json::Map<String, Value> = secrets
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+    let plaintext = Zeroizing::new(serde_json::to_vec(&document)?);
+
+    let mut child = Command::new("sops")
+        .args(["--encrypt", "--input-type", "json", "--output-type"])
+        .arg(output_type(output))
+        .arg("--filename-override")
+        .arg(output)
+        .args(extra_args)
+        .arg("/dev/stdin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run sops (is it installed?)")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&plaintext)?;
+    }
+    let result = child.wait_with_output()?;
+    if !result.status.success() {
+        anyhow::bail!(
+            "sops couldn't encrypt: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    Ok(result.stdout)
+}
+
+/// The sops output type for a file name, by extension (YAML unless told otherwise)
+fn output_type(path: &Path) -> &'static str {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name.ends_with(".json") {
+        "json"
+    } else if name.ends_with(".env") || name.starts_with(".env") {
+        "dotenv"
+    } else if name.ends_with(".ini") {
+        "ini"
+    } else {
+        "yaml"
+    }
+}
+
+/// Flatten a decrypted (or plain JSON) document: nested keys are joined with `_` (`db: {password}`
+/// becomes `db_password`), scalars become strings and arrays are kept as JSON.
+pub fn flatten(document: &Value) -> Result<Vec<(String, String)>> {
+    let Value::Object(map) = document else {
+        anyhow::bail!("Expected key/value pairs at the top level");
+    };
+    let mut pairs = Vec::new();
+    for (key, value) in map {
+        flatten_into(key, value, &mut pairs);
+    }
+    Ok(pairs)
+}
+
+fn flatten_into(key: &str, value: &Value, pairs: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (child, value) in map {
+                flatten_into(&format!("{}_{}", key, child), value, pairs);
+            }
+        }
+        Value::String(s) => pairs.push((key.to_string(), s.clone())),
+        Value::Null => pairs.push((key.to_string(), String::new())),
+        other => pairs.push((key.to_string(), other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_flatten() {
+        let document = json!({
+            "API_KEY": "sk-1",
+            "database": { "password": "hunter2", "port": 5432 },
+            "hosts": ["a", "b"],
+            "debug": false,
+            "empty": null
+        });
+        let mut pairs = flatten(&document).unwrap();
+        pairs.sort();
+        let expected: Vec<(String, String)> = [
+            ("API_KEY", "sk-1"),
+            ("database_password", "hunter2"),
+            ("database_port", "5432"),
+            ("debug", "false"),
+            ("empty", ""),
+            ("hosts", "[\"a\",\"b\"]"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(pairs, expected);
+        assert!(flatten(&json!(["not", "a", "map"])).is_err());
+    }
+
+    #[test]
+    fn test_output_type() {
+        assert_eq!(output_type(Path::new("secrets.enc.yaml")), "yaml");
+        assert_eq!(output_type(Path::new("deploy/secrets.JSON")), "json");
+        assert_eq!(output_type(Path::new("prod.env")), "dotenv");
+        assert_eq!(output_type(Path::new(".env.production")), "dotenv");
+        assert_eq!(output_type(Path::new("app.ini")), "ini");
+        assert_eq!(output_type(Path::new("secrets")), "yaml");
+    }
+}
@@ -0,0 +1,278 @@
+//! Unlocking with an SSH key held by ssh-agent
+//!
+//! The agent signs a random challenge stored with each registered key, and the
+//! signature is hashed into the key that wraps a copy of the master key. Only
+//! key types with deterministic signatures (Ed25519 and RSA) work, since the
+//! same signature has to come back on every unlock.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+/// Ask for an rsa-sha2-256 signature instead of the legacy SHA-1 one
+const SSH_AGENT_RSA_SHA2_256: u32 = 2;
+
+/// Key types whose signatures are the same every time
+const SUPPORTED: &[&str] = &["ssh-ed25519", "ssh-rsa"];
+
+/// Prefix of the data the agent signs, so the signature is useless elsewhere
+const CHALLENGE_LABEL: &[u8] = b"tinysecrets-ssh-unlock-v1\0";
+
+/// An SSH public key, as in `~/.ssh/id_ed25519.pub`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
+    pub algorithm: String,
+    pub blob: Vec<u8>,
+    pub comment: String,
+}
+
+impl PublicKey {
+    /// Parse an OpenSSH public key line (`ssh-ed25519 AAAA... comment`)
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut fields = line.split_whitespace();
+        let (Some(algorithm), Some(encoded)) = (fields.next(), fields.next()) else {
+            anyhow::bail!("Not an SSH public key (expected e.g. `ssh-ed25519 AAAA... comment`)");
+        };
+        let blob = BASE64
+            .decode(encoded)
+            .context("Not an SSH public key (invalid base64)")?;
+        // The blob starts with its own key type, which has to agree
+        if Reader::new(&blob).string().ok() != Some(algorithm.as_bytes()) {
+            anyhow::bail!("Not an SSH public key (key type doesn't match the key data)");
+        }
+        Ok(Self {
+            algorithm: algorithm.to_string(),
+            blob,
+            comment: fields.collect::<Vec<_>>().join(" "),
+        })
+    }
+
+    /// The key as an OpenSSH public key line
+    pub fn to_line(&self) -> String {
+        let line = format!("{} {}", self.algorithm, BASE64.encode(&self.blob));
+        if self.comment.is_empty() {
+            line
+        } else {
+            format!("{} {}", line, self.comment)
+        }
+    }
+
+    /// `SHA256:...`, as printed by `ssh-keygen -l` and `ssh-add -l`
+    pub fn fingerprint(&self) -> String {
+        use base64::engine::general_purpose::STANDARD_NO_PAD;
+        format!(
+            "SHA256:{}",
+            STANDARD_NO_PAD.encode(Sha256::digest(&self.blob))
+        )
+    }
+
+    /// The comment if there is one, otherwise the fingerprint
+    pub fn label(&self) -> String {
+        if self.comment.is_empty() {
+            self.fingerprint()
+        } else {
+            self.comment.clone()
+        }
+    }
+
+    /// Fail for key types that can't unlock a store
+    pub fn check_supported(&self) -> Result<()> {
+        if SUPPORTED.contains(&self.algorithm.as_str()) {
+            return Ok(());
+        }
+        anyhow::bail!(
+            "{} keys can't unlock a store: their signatures change every time. Use an Ed25519 or RSA key.",
+            self.algorithm
+        )
+    }
+}
+
+/// The bytes the agent signs for a stored challenge
+pub fn challenge_data(challenge: &[u8]) -> Vec<u8> {
+    [CHALLENGE_LABEL, challenge].concat()
+}
+
+/// A connection to the running ssh-agent
+pub struct Agent {
+    #[cfg(unix)]
+    stream: std::os::unix::net::UnixStream,
+}
+
+impl Agent {
+    /// Connect through SSH_AUTH_SOCK; None if no agent is running
+    #[cfg(unix)]
+    pub fn connect() -> Result<Option<Self>> {
+        let Some(socket) = std::env::var_os("SSH_AUTH_SOCK").filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        let stream = std::os::unix::net::UnixStream::connect(&socket)
+            .with_context(|| format!("Failed to connect to ssh-agent at {:?}", socket))?;
+        Ok(Some(Self { stream }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn connect() -> Result<Option<Self>> {
+        Ok(None)
+    }
+
+    /// Blobs of the keys the agent holds
+    pub fn identities(&mut self) -> Result<Vec<Vec<u8>>> {
+        let reply = self.request(SSH_AGENTC_REQUEST_IDENTITIES, &[])?;
+        let mut reader = Reader::new(&reply);
+        if reader.byte()? != SSH_AGENT_IDENTITIES_ANSWER {
+            anyhow::bail!("ssh-agent refused to list keys");
+        }
+        let count = reader.u32()?;
+        let mut keys = Vec::new();
+        for _ in 0..count {
+            keys.push(reader.string()?.to_vec());
+            reader.string()?; // comment
+        }
+        Ok(keys)
+    }
+
+    /// Have the agent sign `data` with `key`; returns the signature blob
+    pub fn sign(&mut self, key: &PublicKey, data: &[u8]) -> Result<Vec<u8>> {
+        let flags = if key.algorithm == "ssh-rsa" {
+            SSH_AGENT_RSA_SHA2_256
+        } else {
+            0
+        };
+        let mut payload = Vec::new();
+        put_string(&mut payload, &key.blob);
+        put_string(&mut payload, data);
+        payload.extend_from_slice(&flags.to_be_bytes());
+
+        let reply = self.request(SSH_AGENTC_SIGN_REQUEST, &payload)?;
+        let mut reader = Reader::new(&reply);
+        if reader.byte()? != SSH_AGENT_SIGN_RESPONSE {
+            anyhow::bail!("ssh-agent refused to sign with {}", key.label());
+        }
+        Ok(reader.string()?.to_vec())
+    }
+
+    #[cfg(unix)]
+    fn request(&mut self, kind: u8, payload: &[u8]) -> Result<Vec<u8>> {
+        use std::io::{Read, Write};
+
+        let mut message = Vec::with_capacity(payload.len() + 5);
+        message.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        message.push(kind);
+        message.extend_from_slice(payload);
+        self.stream
+            .write_all(&message)
+            .context("Failed to talk to ssh-agent")?;
+
+        let mut length = [0u8; 4];
+        self.stream
+            .read_exact(&mut length)
+            .context("Failed to read from ssh-agent")?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length > 256 * 1024 {
+            anyhow::bail!("Unexpectedly large reply from ssh-agent");
+        }
+        let mut reply = vec![0u8; length];
+        self.stream
+            .read_exact(&mut reply)
+            .context("Failed to read from ssh-agent")?;
+        Ok(reply)
+    }
+
+    #[cfg(not(unix))]
+    fn request(&mut self, _kind: u8, _payload: &[u8]) -> Result<Vec<u8>> {
+        anyhow::bail!("ssh-agent unlock is only supported on Unix")
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+/// Reads the SSH wire format (big-endian lengths)
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.data.len() < n {
+            anyhow::bail!("Truncated message from ssh-agent");
+        }
+        let (head, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFRr6qw4jHcfCWcQgJG/0I9/2ZpdTpLt0GRkvfjZZqoi dev@laptop";
+
+    #[test]
+    fn test_parse_public_key() {
+        let key = PublicKey::parse(ED25519).unwrap();
+        assert_eq!(key.algorithm, "ssh-ed25519");
+        assert_eq!(key.comment, "dev@laptop");
+        assert_eq!(key.blob.len(), 51);
+        assert_eq!(key.to_line(), ED25519);
+        // Same as `ssh-keygen -lf`
+        assert_eq!(
+            key.fingerprint(),
+            "SHA256:PjkapYux98DanPZa4CFq6tavphwQkZwII40ayqGoEnA"
+        );
+        assert!(key.check_supported().is_ok());
+
+        assert!(PublicKey::parse(
+            "ssh-rsa AAAAC3NzaC1lZDI1NTE5AAAAIFRr6qw4jHcfCWcQgJG/0I9/2ZpdTpLt0GRkvfjZZqoi"
+        )
+        .is_err());
+        assert!(PublicKey::parse("not a key").is_err());
+        assert!(PublicKey::parse("").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_key_types() {
+        let mut blob = Vec::new();
+        put_string(&mut blob, b"ecdsa-sha2-nistp256");
+        put_string(&mut blob, b"nistp256");
+        let line = format!("ecdsa-sha2-nistp256 {}", BASE64.encode(&blob));
+        assert!(PublicKey::parse(&line).unwrap().check_supported().is_err());
+    }
+
+    #[test]
+    fn test_reader() {
+        let mut data = vec![SSH_AGENT_SIGN_RESPONSE];
+        put_string(&mut data, b"sig");
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.byte().unwrap(), SSH_AGENT_SIGN_RESPONSE);
+        assert_eq!(reader.string().unwrap(), b"sig");
+        assert!(reader.byte().is_err());
+        assert!(Reader::new(&[0, 0, 0, 9, 1]).string().is_err());
+    }
+}
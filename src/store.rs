@@ -2,19 +2,87 @@
 //!
 //! Schema design:
 //! - secrets: current values (project, env, key, encrypted_value, metadata)
-//! - secret_history: all previous versions for audit trail
+//! - secret_history: all previous versions for audit trail (hash-chained)
+//! - audit_log: maintenance that rewrote history (hash-chained)
 //! - metadata: store-level config (passphrase verification, version)
+//! - environments: per-environment settings (parent for inheritance)
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection};
+use log::debug;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-
-use crate::crypto::{self, MasterKey};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+use crate::attempts;
+use crate::backup;
+use crate::chain;
+use crate::compat;
+use crate::config::{self, GlobalConfig};
+use crate::crypto::{self, MasterKey, SecureBytes};
+use crate::lockfile::{LockedKey, Lockfile};
+use crate::logging;
+use crate::migrations::{self, SCHEMA_VERSION};
+use crate::pattern::KeyFilter;
+use crate::permissions;
+use crate::refs::{self, SecretPath};
+use crate::ssh_agent::{self, Agent, PublicKey};
+
+/// Decrypted key/value pairs, wiped from memory when dropped
+pub type SecretValues = Zeroizing<Vec<(String, String)>>;
+
+/// Environment variable overriding the store location
+pub const STORE_ENV_VAR: &str = "TINYSECRETS_STORE";
+
+/// Store location given on the command line (`--store`, `init --path`)
+static PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Point in time reads see instead of the current values (`--as-of`)
+static AS_OF: OnceLock<DateTime<Utc>> = OnceLock::new();
+
+/// Versions `get_all` reads instead of the current values (`run --locked`)
+static LOCKED: OnceLock<Lockfile> = OnceLock::new();
+
+/// Largest value accepted by `set` (binary files included)
+pub const MAX_VALUE_SIZE: usize = 1024 * 1024;
+
+/// How long to wait for another process holding the write lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// First bytes of every plaintext SQLite file; a fully encrypted store starts
+/// with SQLCipher's random salt instead
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Passphrase for opening a fully encrypted store without unlocking it
+/// (`has`, `stats`, `db status`)
+static DATABASE_KEY: OnceLock<SecretString> = OnceLock::new();
+
+/// Store errors callers may want to handle specifically
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("Invalid passphrase")]
+    InvalidPassphrase,
+    #[error("Too many wrong passphrases; try again in {0}")]
+    TooManyAttempts(String),
+    #[error("Store was unlocked with the read-only passphrase; changes need the full passphrase")]
+    ReadOnly,
+    #[error("No store found. Run `ts init` first to create one.")]
+    Missing,
+    #[error("Key names are encrypted in this store, so finding one needs it unlocked")]
+    NamesHidden,
+}
 
-const SCHEMA_VERSION: i32 = 2;
+/// What an unlocked store allows, decided by which passphrase opened it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Full,
+    ReadOnly,
+}
 
 /// Secret entry with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +94,14 @@ pub struct SecretEntry {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub version: i32,
+    /// Where the value was imported from (None if set by hand)
+    pub source: Option<String>,
+    /// Who wrote this version (`user@host`; None for versions written before it was recorded)
+    pub changed_by: Option<String>,
+    /// Why this version was written, if given (`set --reason`)
+    pub reason: Option<String>,
+    /// Git repo, branch and commit it was written from (`[history] record_git`)
+    pub git: Option<String>,
 }
 
 /// Historical secret entry
@@ -37,136 +113,1111 @@ pub struct SecretHistoryEntry {
     pub version: i32,
     pub created_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
+    pub changed_by: Option<String>,
+    pub reason: Option<String>,
+    pub git: Option<String>,
+}
+
+/// A deleted secret that `undelete` can bring back
+#[derive(Debug, Clone)]
+pub struct TrashedSecret {
+    pub project: String,
+    pub environment: String,
+    pub key: String,
+    /// Version it had when deleted
+    pub version: i32,
+    pub deleted_at: DateTime<Utc>,
+    pub description: Option<String>,
+}
+
+/// Schema and crypto state of a store, read without the passphrase
+#[derive(Debug, Clone)]
+pub struct StoreStatus {
+    pub schema_version: i32,
+    pub fast_check: bool,
+    pub has_salt: bool,
+    pub current_values: usize,
+    pub legacy_values: usize,
+    pub written_by_version: Option<String>,
+    pub read_only_passphrase: bool,
+    /// The whole database file is encrypted (SQLCipher)
+    pub full_encryption: bool,
+}
+
+/// Secret and history counts for one environment (`stats`)
+#[derive(Debug, Clone)]
+pub struct EnvironmentStats {
+    pub project: String,
+    pub environment: String,
+    pub secrets: usize,
+    pub history: usize,
+    /// Most recent change to a current secret
+    pub last_updated: Option<DateTime<Utc>>,
+}
+
+/// A project or environment with its own secret count and latest change
+/// (`projects`, `envs`)
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub name: String,
+    pub secrets: usize,
+    pub last_updated: Option<DateTime<Utc>>,
+}
+
+/// Usage figures for `stats`, read without the passphrase
+#[derive(Debug, Clone)]
+pub struct StoreStats {
+    pub environments: Vec<EnvironmentStats>,
+    /// Current secrets least recently changed, oldest first
+    pub oldest: Vec<SecretEntry>,
+    /// Database file plus its WAL, in bytes
+    pub file_size: u64,
+}
+
+/// Outcome of `set_many`
+#[derive(Debug, Default)]
+pub struct BulkWrite {
+    /// Keys that already held the given value, left untouched
+    pub unchanged: Vec<String>,
+    /// Values that couldn't be stored, with why
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+/// Per-section counts from `import`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Imported {
+    /// New versions written
+    pub written: usize,
+    /// Keys that already held the bundle's value
+    pub unchanged: usize,
+}
+
+/// An encrypted value as stored in one environment
+struct StoredValue {
+    encrypted: String,
+    version: i32,
+}
+
+/// Where the active store path came from, highest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSource {
+    Flag,
+    EnvVar,
+    Profile,
+    Config,
+    Default,
+}
+
+impl PathSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            PathSource::Flag => "command-line flag",
+            PathSource::EnvVar => STORE_ENV_VAR,
+            PathSource::Profile => "active profile",
+            PathSource::Config => "global config",
+            PathSource::Default => "default",
+        }
+    }
 }
 
 /// The encrypted secrets store
 pub struct Store {
     conn: Connection,
-    passphrase: SecretString,
+    /// None when unlocked through ssh-agent
+    passphrase: Option<SecretString>,
     master_key: MasterKey,
+    access: Access,
+    migration_backup: Option<PathBuf>,
+    /// Recorded with every write
+    attribution: Attribution,
+    /// Set when the store hides key names (`security hide-names`)
+    names: Option<crypto::NameCipher>,
+}
+
+/// Who is making changes and why, recorded with each version written
+#[derive(Debug, Clone, Default)]
+struct Attribution {
+    changed_by: String,
+    reason: Option<String>,
+    git: Option<String>,
+}
+
+impl Attribution {
+    fn new() -> Self {
+        Self {
+            changed_by: config::changed_by(),
+            ..Default::default()
+        }
+    }
 }
 
 impl Store {
-    /// Get the default store path (~/.tinysecrets/store.db)
+    /// Get the default store path: ~/.tinysecrets/store.db if it exists, otherwise
+    /// $XDG_DATA_HOME/tinysecrets/store.db on Linux when XDG_DATA_HOME is set
     pub fn default_path() -> Result<PathBuf> {
         let home = dirs::home_dir().context("Could not find home directory")?;
-        Ok(home.join(".tinysecrets").join("store.db"))
+        let legacy = home.join(".tinysecrets").join("store.db");
+        if legacy.exists() {
+            return Ok(legacy);
+        }
+
+        match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if cfg!(target_os = "linux") && !dir.is_empty() => {
+                Ok(PathBuf::from(dir).join("tinysecrets").join("store.db"))
+            }
+            _ => Ok(legacy),
+        }
     }
 
-    /// Initialize a new store with the given passphrase
-    pub fn init(passphrase: SecretString) -> Result<Self> {
-        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    /// Use this store path for the rest of the process (from `--store` or `init --path`)
+    pub fn set_path_override(path: PathBuf) -> Result<()> {
+        PATH_OVERRIDE
+            .set(path)
+            .map_err(|_| anyhow::anyhow!("Store path given more than once (--store and --path)"))
+    }
 
-        let path = Self::default_path()?;
+    /// Read values as they were at `at` for the rest of the process (`--as-of`).
+    /// Versions are taken from history, and keys deleted by then are left out.
+    pub fn set_as_of(at: DateTime<Utc>) {
+        let _ = AS_OF.set(at);
+    }
+
+    /// The point in time reads are pinned to, if any
+    pub fn as_of() -> Option<DateTime<Utc>> {
+        AS_OF.get().copied()
+    }
 
+    /// Load the versions in `lock` for the rest of the process (`run --locked`)
+    pub fn set_locked(lock: Lockfile) {
+        let _ = LOCKED.set(lock);
+    }
+
+    /// Resolve the active store path:
+    /// flag > TINYSECRETS_STORE > profile > global config > default
+    pub fn resolve_path() -> Result<(PathBuf, PathSource)> {
+        if let Some(path) = PATH_OVERRIDE.get() {
+            return Ok((path.clone(), PathSource::Flag));
+        }
+        if let Some(path) = std::env::var_os(STORE_ENV_VAR).filter(|p| !p.is_empty()) {
+            return Ok((PathBuf::from(path), PathSource::EnvVar));
+        }
+        if let Some(path) = crate::config::active_profile().and_then(|(_, p)| p.store_path.clone())
+        {
+            return Ok((path, PathSource::Profile));
+        }
+        if let Some(path) = GlobalConfig::load()?.store_path {
+            return Ok((path, PathSource::Config));
+        }
+        Ok((Self::default_path()?, PathSource::Default))
+    }
+
+    /// Get the active store path
+    pub fn path() -> Result<PathBuf> {
+        Ok(Self::resolve_path()?.0)
+    }
+
+    /// Move the store file (and any SQLite sidecar files) to a new location
+    pub fn relocate(from: &Path, to: &Path) -> Result<()> {
+        if !from.exists() {
+            anyhow::bail!("No store found at {}", from.display());
+        }
+        if to.exists() {
+            anyhow::bail!("Destination already exists: {}", to.display());
+        }
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        for suffix in ["", "-wal", "-shm"] {
+            let src = PathBuf::from(format!("{}{}", from.display(), suffix));
+            if !src.exists() {
+                continue;
+            }
+            let dst = PathBuf::from(format!("{}{}", to.display(), suffix));
+            // rename fails across filesystems, so fall back to copy + remove
+            if std::fs::rename(&src, &dst).is_err() {
+                std::fs::copy(&src, &dst)
+                    .with_context(|| format!("Failed to copy {}", src.display()))?;
+                std::fs::remove_file(&src)
+                    .with_context(|| format!("Failed to remove {}", src.display()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Initialize a new store with the given passphrase. With `full_encryption`
+    /// the whole database file is encrypted (SQLCipher), hiding key names,
+    /// projects and timestamps as well as values.
+    pub fn init(passphrase: SecretString, full_encryption: bool) -> Result<Self> {
+        Self::create(&Self::path()?, passphrase, full_encryption)
+    }
+
+    /// Create a store at `path`
+    fn create(path: &Path, passphrase: SecretString, full_encryption: bool) -> Result<Self> {
         if path.exists() {
             anyhow::bail!(
                 "Store already exists at {}. Use `ts` commands to interact with it.",
                 path.display()
             );
         }
+        if full_encryption {
+            require_sqlcipher()?;
+        }
 
-        // Create directory
+        // Create the directory and file owner-only; SQLite gives its -wal and
+        // -shm files the same mode as the database
         if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).context("Failed to create ~/.tinysecrets directory")?;
+            permissions::create_private_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
         }
+        permissions::create_private_file(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
 
-        let conn = Connection::open(&path).context("Failed to create SQLite database")?;
+        let mut conn = Connection::open(path).context("Failed to create SQLite database")?;
+        if full_encryption {
+            apply_key(&conn, &passphrase)?;
+        }
+        logging::trace_sql(&mut conn);
+        configure_connection(&conn)?;
+        debug!("Creating store at {}", path.display());
 
         // Create schema
         conn.execute_batch(include_str!("schema.sql"))
             .context("Failed to initialize database schema")?;
 
-        // Generate salt and derive master key
-        let salt = MasterKey::generate_salt();
+        // Generate and persist the salt, then derive the master key once
+        let salt = load_or_create_salt(&conn)?;
         let master_key = MasterKey::derive(&passphrase, &salt)?;
 
-        // Store passphrase verification (still uses age - only runs once)
-        let verification = crypto::derive_verification(&passphrase)?;
-        conn.execute(
-            "INSERT INTO metadata (key, value) VALUES ('passphrase_verification', ?1)",
-            params![verification],
-        )?;
-        conn.execute(
-            "INSERT INTO metadata (key, value) VALUES ('schema_version', ?1)",
-            params![SCHEMA_VERSION.to_string()],
-        )?;
-        // Store salt for key derivation
-        conn.execute(
-            "INSERT INTO metadata (key, value) VALUES ('encryption_salt', ?1)",
-            params![BASE64.encode(salt)],
+        // Store a fast passphrase check derived from the master key
+        write_metadata(
+            &conn,
+            "passphrase_check",
+            &crypto::derive_check(&master_key),
         )?;
+        write_metadata(&conn, "schema_version", &SCHEMA_VERSION.to_string())?;
+        write_metadata(&conn, "written_by_version", compat::CLI_VERSION)?;
+        write_metadata(&conn, "store_id", &new_store_id())?;
+        write_signing_key(&conn, &master_key, &crypto::generate_signing_key())?;
 
         Ok(Self {
             conn,
-            passphrase,
+            passphrase: Some(passphrase),
             master_key,
+            access: Access::Full,
+            migration_backup: None,
+            attribution: Attribution::new(),
+            names: None,
         })
     }
 
     /// Open an existing store
     pub fn open(passphrase: SecretString) -> Result<Self> {
+        let (path, conn) = Self::connect(Some(&passphrase))?;
+
+        // Refuse to even try while backing off after wrong passphrases, and
+        // count this attempt before the KDF runs
+        let policy = GlobalConfig::load()?.unlock;
+        let attempts = match attempts::reserve(&conn, &policy, Utc::now())? {
+            Ok(attempts) => attempts,
+            Err(wait) => {
+                return Err(StoreError::TooManyAttempts(attempts::describe(wait)).into());
+            }
+        };
+
+        let start = Instant::now();
+        let (master_key, access) = match unlock(&conn, &passphrase) {
+            Err(e) if matches!(e.downcast_ref(), Some(StoreError::InvalidPassphrase)) => {
+                debug!(
+                    "Wrong passphrase ({} failures before this one)",
+                    attempts.failures
+                );
+                return Err(e);
+            }
+            unlocked => unlocked?,
+        };
+        debug!(
+            "Unlocked with passphrase, {:?} access ({})",
+            access,
+            logging::millis(start.elapsed())
+        );
+        attempts::clear(&conn)?;
+
+        Self::unlocked(path, conn, Some(passphrase), master_key, access)
+    }
+
+    /// Open the store by having ssh-agent sign the challenge of a registered SSH
+    /// key. None if no registered key is loaded in the agent (or there's no agent).
+    pub fn open_with_ssh_agent() -> Result<Option<(Self, PublicKey)>> {
         use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 
-        let path = Self::default_path()?;
+        // The file key is the passphrase, so an SSH key can't get past it
+        if Self::fully_encrypted()? {
+            return Ok(None);
+        }
+        let (path, conn) = Self::connect(None)?;
+        let unlocks = read_ssh_unlocks(&conn)?;
+        if unlocks.is_empty() {
+            return Ok(None);
+        }
+        let Some(check) = read_metadata(&conn, "passphrase_check")? else {
+            return Ok(None);
+        };
+        // A stale SSH_AUTH_SOCK just means falling back to the passphrase
+        let Some(mut agent) = Agent::connect().ok().flatten() else {
+            return Ok(None);
+        };
+        let loaded = agent.identities().unwrap_or_default();
 
-        if !path.exists() {
-            anyhow::bail!("No store found. Run `ts init` first to create one.");
+        for (key, unlock) in unlocks {
+            if !loaded.contains(&key.blob) {
+                continue;
+            }
+            let Ok(challenge) = BASE64.decode(&unlock.challenge) else {
+                continue;
+            };
+            // e.g. the user didn't touch their hardware key
+            let Ok(signature) = agent.sign(&key, &ssh_agent::challenge_data(&challenge)) else {
+                continue;
+            };
+            let wrapping_key = MasterKey::from_signature(&signature, &challenge);
+            if let Some(master_key) = MasterKey::unwrap_with(&unlock.wrapped_key, &wrapping_key)
+                .ok()
+                .filter(|master_key| crypto::verify_check(master_key, &check))
+            {
+                debug!("Unlocked with SSH key {}", key.fingerprint());
+                let store = Self::unlocked(path, conn, None, master_key, Access::Full)?;
+                return Ok(Some((store, key)));
+            }
         }
+        debug!("No registered SSH key could unlock the store");
+        Ok(None)
+    }
 
-        let conn = Connection::open(&path).context("Failed to open SQLite database")?;
+    /// Open the database of an existing store, keying it with `passphrase` if
+    /// it's fully encrypted
+    fn connect(passphrase: Option<&SecretString>) -> Result<(PathBuf, Connection)> {
+        let path = Self::path()?;
 
-        // Verify passphrase (this is the slow operation - runs once)
-        let verification: String = conn
-            .query_row(
-                "SELECT value FROM metadata WHERE key = 'passphrase_verification'",
-                [],
-                |row| row.get(0),
-            )
-            .context("Store appears corrupted - no passphrase verification found")?;
+        if !path.exists() {
+            return Err(StoreError::Missing.into());
+        }
 
-        if !crypto::verify_passphrase(&passphrase, &verification) {
-            anyhow::bail!("Invalid passphrase");
+        let mut conn = Connection::open(&path).context("Failed to open SQLite database")?;
+        if is_fully_encrypted(&path)? {
+            let passphrase =
+                passphrase.context("This store is fully encrypted - it needs the passphrase")?;
+            apply_key(&conn, passphrase)?;
         }
+        logging::trace_sql(&mut conn);
+        configure_connection(&conn)?;
+        debug!("Opened store at {}", path.display());
+        Ok((path, conn))
+    }
 
-        // Get or create salt for key derivation
-        let salt: [u8; 32] = match conn.query_row(
-            "SELECT value FROM metadata WHERE key = 'encryption_salt'",
-            [],
-            |row| row.get::<_, String>(0),
-        ) {
-            Ok(salt_b64) => {
-                let salt_vec = BASE64
-                    .decode(&salt_b64)
-                    .context("Failed to decode encryption salt")?;
-                salt_vec
-                    .try_into()
-                    .map_err(|_| anyhow::anyhow!("Invalid salt length"))?
-            }
-            Err(_) => {
-                // Legacy store without salt - create one and migrate
-                let salt = MasterKey::generate_salt();
-                conn.execute(
-                    "INSERT INTO metadata (key, value) VALUES ('encryption_salt', ?1)",
-                    params![BASE64.encode(salt)],
-                )?;
-                salt
-            }
+    fn unlocked(
+        path: PathBuf,
+        conn: Connection,
+        passphrase: Option<SecretString>,
+        master_key: MasterKey,
+        access: Access,
+    ) -> Result<Self> {
+        // Only upgrade the schema once we know the passphrase is right
+        let migration_backup = migrations::run(&conn, &path)?;
+        let names = match hides_key_names(&conn)? {
+            true => Some(crypto::NameCipher::new(&master_key)?),
+            false => None,
         };
 
-        // Derive master key (fast - ~100ms)
-        let master_key = MasterKey::derive(&passphrase, &salt)?;
-
         Ok(Self {
             conn,
             passphrase,
             master_key,
+            access,
+            migration_backup,
+            attribution: Attribution::new(),
+            names,
         })
     }
 
+    /// The passphrase, for what the master key alone can't do
+    fn passphrase(&self) -> Result<&SecretString> {
+        self.passphrase.as_ref().context(
+            "This needs the store passphrase, but the store was unlocked with an SSH key. \
+             Set TINYSECRETS_PASSPHRASE to unlock with the passphrase.",
+        )
+    }
+
+    /// How `key` is stored: encrypted when the store hides key names
+    /// (deterministically, so it can still be looked up)
+    fn stored_key(&self, key: &str) -> Result<String> {
+        match &self.names {
+            Some(names) => names.encrypt(key),
+            None => Ok(key.to_string()),
+        }
+    }
+
+    /// The key name behind a stored `key` column
+    pub fn key_name(&self, stored: &str) -> Result<String> {
+        match &self.names {
+            Some(names) => names.decrypt(stored),
+            None => Ok(stored.to_string()),
+        }
+    }
+
+    /// How a description is stored: encrypted like a value when the store
+    /// hides key names
+    fn stored_description(&self, description: Option<&str>) -> Result<Option<String>> {
+        match (&self.names, description) {
+            (Some(_), Some(description)) => {
+                Ok(Some(crypto::encrypt(description, &self.master_key)?))
+            }
+            (_, description) => Ok(description.map(String::from)),
+        }
+    }
+
+    /// The description behind a stored `description` column
+    fn description(&self, stored: Option<String>) -> Result<Option<String>> {
+        match (&self.names, stored) {
+            (Some(_), Some(stored)) => Ok(Some(self.decrypt_value(&stored)?)),
+            (_, stored) => Ok(stored),
+        }
+    }
+
+    /// Decrypt the key name and description of an entry read from the database
+    fn reveal(&self, mut entry: SecretEntry) -> Result<SecretEntry> {
+        if self.names.is_some() {
+            entry.key = self.key_name(&entry.key)?;
+            entry.description = self.description(entry.description)?;
+        }
+        Ok(entry)
+    }
+
+    /// Encrypt (`hide`) or decrypt every key name and description in place,
+    /// history included. Returns false if the store was already that way.
+    pub fn set_hide_key_names(&mut self, hide: bool) -> Result<bool> {
+        if self.names.is_some() == hide {
+            return Ok(false);
+        }
+        let names = crypto::NameCipher::new(&self.master_key)?;
+        let tx = self.immediate_transaction()?;
+        for table in ["secrets", "secret_history"] {
+            let mut stmt = tx.prepare(&format!("SELECT id, key, description FROM {}", table))?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (id, key, description) in rows {
+                let (key, description) = match hide {
+                    true => (
+                        names.encrypt(&key)?,
+                        description
+                            .map(|d| crypto::encrypt(&d, &self.master_key))
+                            .transpose()?,
+                    ),
+                    false => (
+                        names.decrypt(&key)?,
+                        description.map(|d| self.decrypt_value(&d)).transpose()?,
+                    ),
+                };
+                tx.execute(
+                    &format!(
+                        "UPDATE {} SET key = ?1, description = ?2 WHERE id = ?3",
+                        table
+                    ),
+                    params![key, description, id],
+                )?;
+            }
+        }
+        write_metadata(&tx, "key_names", if hide { "encrypted" } else { "plain" })?;
+        let action = if hide { "hide-names" } else { "show-names" };
+        self.rechain_history(&tx, action, "rewrote every key name and description")?;
+        tx.commit()?;
+        self.names = hide.then_some(names);
+        self.record_writer()?;
+
+        // The old names are still in freed pages and in the WAL until the file
+        // is rebuilt
+        self.conn
+            .execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+            .context("Failed to compact the store after rewriting key names")?;
+        Ok(true)
+    }
+
+    /// Copies of the store kept next to it (before a migration or a restore),
+    /// which still hold whatever they held when they were made
+    pub fn leftover_copies() -> Result<Vec<PathBuf>> {
+        let path = Self::path()?;
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(Vec::new());
+        };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let mut copies: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.starts_with(&prefix) && name.contains(".bak")
+            })
+            .map(|entry| entry.path())
+            .collect();
+        copies.sort();
+        Ok(copies)
+    }
+
+    /// Let an SSH key unlock the store through ssh-agent: a copy of the master key,
+    /// wrapped with a key derived from the agent's signature over a random challenge
+    pub fn add_ssh_unlock(&self, key: &PublicKey) -> Result<()> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        self.require_write()?;
+        if Self::fully_encrypted()? {
+            anyhow::bail!("A fully encrypted store can only be opened with its passphrase");
+        }
+        key.check_supported()?;
+
+        let mut agent = Agent::connect()?.context(
+            "No ssh-agent running (SSH_AUTH_SOCK isn't set). Start one and `ssh-add` the key.",
+        )?;
+        if !agent.identities()?.contains(&key.blob) {
+            anyhow::bail!(
+                "{} isn't loaded in ssh-agent. Add it with `ssh-add` first.",
+                key.label()
+            );
+        }
+
+        let challenge = MasterKey::generate_salt();
+        let data = ssh_agent::challenge_data(&challenge);
+        let signature = agent.sign(key, &data)?;
+        if agent.sign(key, &data)? != signature {
+            anyhow::bail!(
+                "Signatures from {} change every time, so it can't unlock the store",
+                key.label()
+            );
+        }
+
+        let unlock = SshUnlock {
+            public_key: key.to_line(),
+            challenge: BASE64.encode(challenge),
+            wrapped_key: self
+                .master_key
+                .wrap_with(&MasterKey::from_signature(&signature, &challenge))?,
+        };
+        write_metadata(
+            &self.conn,
+            &format!("{}{}", SSH_UNLOCK_PREFIX, key.fingerprint()),
+            &serde_json::to_string(&unlock)?,
+        )
+    }
+
+    /// Stop an SSH key (by fingerprint) from unlocking the store; returns false
+    /// if it wasn't registered
+    pub fn remove_ssh_unlock(&self, fingerprint: &str) -> Result<bool> {
+        self.require_write()?;
+        let removed = self.conn.execute(
+            "DELETE FROM metadata WHERE key = ?1",
+            params![format!("{}{}", SSH_UNLOCK_PREFIX, fingerprint)],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// SSH keys that can unlock the store
+    pub fn ssh_unlocks(&self) -> Result<Vec<PublicKey>> {
+        Ok(read_ssh_unlocks(&self.conn)?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Record `reason` with the writes that follow (`set --reason`)
+    pub fn set_reason(&mut self, reason: Option<&str>) {
+        self.attribution.reason = reason.map(String::from);
+    }
+
+    /// Record the git checkout (`repo (branch @ commit)`) with the writes that follow
+    pub fn set_git_context(&mut self, git: Option<String>) {
+        self.attribution.git = git;
+    }
+
+    /// Whether the store was opened with the full or the read-only passphrase
+    pub fn access(&self) -> Access {
+        self.access
+    }
+
+    /// Fail unless the store was opened with the full passphrase
+    pub fn require_write(&self) -> Result<()> {
+        match self.access {
+            Access::Full => Ok(()),
+            Access::ReadOnly => Err(StoreError::ReadOnly.into()),
+        }
+    }
+
+    /// Add (or replace) a read-only passphrase: a second copy of the master key,
+    /// wrapped with a key derived from `passphrase`
+    pub fn set_read_only_passphrase(&self, passphrase: &SecretString) -> Result<()> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        self.require_write()?;
+        if Self::fully_encrypted()? {
+            anyhow::bail!("A fully encrypted store can only be opened with its full passphrase");
+        }
+        let store_salt = load_or_create_salt(&self.conn)?;
+        if crypto::verify_check(
+            &MasterKey::derive(passphrase, &store_salt)?,
+            &crypto::derive_check(&self.master_key),
+        ) {
+            anyhow::bail!("The read-only passphrase must differ from the full passphrase");
+        }
+
+        let salt = MasterKey::generate_salt();
+        let wrapping_key = MasterKey::derive(passphrase, &salt)?;
+        let wrapped = self.master_key.wrap_with(&wrapping_key)?;
+
+        let tx = self.immediate_transaction()?;
+        write_metadata(&tx, "read_only_salt", &BASE64.encode(salt))?;
+        write_metadata(&tx, "read_only_key", &wrapped)?;
+        tx.commit()?;
+        self.record_writer()
+    }
+
+    /// Remove the read-only passphrase; returns false if there wasn't one
+    pub fn remove_read_only_passphrase(&self) -> Result<bool> {
+        self.require_write()?;
+        let removed = self.conn.execute(
+            "DELETE FROM metadata WHERE key IN ('read_only_salt', 'read_only_key')",
+            [],
+        )?;
+        if removed > 0 {
+            self.record_writer()?;
+        }
+        Ok(removed > 0)
+    }
+
+    /// Rewrite the store with the whole file encrypted (SQLCipher), or back to a
+    /// plain SQLite file where only values are encrypted. The converted copy is
+    /// checked with the passphrase before it replaces the store. Returns false
+    /// if the store was already in that format.
+    pub fn set_full_encryption(self, on: bool) -> Result<bool> {
+        self.require_write()?;
+        let passphrase = self.passphrase()?.clone();
+        let path = Self::path()?;
+        if is_fully_encrypted(&path)? == on {
+            return Ok(false);
+        }
+        require_sqlcipher()?;
+
+        let converted = PathBuf::from(format!("{}.converting", path.display()));
+        if converted.exists() {
+            std::fs::remove_file(&converted)
+                .with_context(|| format!("Failed to remove {}", converted.display()))?;
+        }
+        permissions::create_private_file(&converted)
+            .with_context(|| format!("Failed to create {}", converted.display()))?;
+
+        // An empty key attaches a plaintext database
+        let key = if on {
+            passphrase.expose_secret().as_str()
+        } else {
+            ""
+        };
+        self.conn
+            .execute(
+                "ATTACH DATABASE ?1 AS converted KEY ?2",
+                params![converted.to_string_lossy(), key],
+            )
+            .context("Failed to create the converted store")?;
+        let exported = self
+            .conn
+            .query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(()));
+        self.conn.execute("DETACH DATABASE converted", [])?;
+        exported.context("Failed to copy the store")?;
+        // Closing the last connection checkpoints the WAL into the old file
+        drop(self);
+
+        let check = Connection::open(&converted)?;
+        if on {
+            apply_key(&check, &passphrase)?;
+        }
+        check
+            .query_row("SELECT count(*) FROM secrets", [], |_| Ok(()))
+            .context("Converted store can't be read back; the original is unchanged")?;
+        drop(check);
+
+        for suffix in ["-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        std::fs::rename(&converted, &path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        debug!(
+            "Store at {} is now {}",
+            path.display(),
+            if on {
+                "fully encrypted"
+            } else {
+                "plaintext SQLite"
+            }
+        );
+        Ok(true)
+    }
+
+    /// The whole store (every project, all history, the KDF parameters) as an
+    /// archive encrypted with the passphrase, for `backup export`
+    pub fn backup(&self) -> Result<Vec<u8>> {
+        self.require_write()?;
+        let passphrase = self.passphrase()?;
+
+        // On tmpfs when there is one, and shredded afterwards
+        let scratch =
+            permissions::ScratchDir::new().context("Failed to create a scratch directory")?;
+        let snapshot = scratch.path().join("store.db");
+        self.conn
+            .execute("VACUUM INTO ?1", [snapshot.to_string_lossy()])
+            .context("Failed to snapshot the store")?;
+        let database = std::fs::read(&snapshot).context("Failed to read the snapshot")?;
+        backup::seal(&database, passphrase)
+    }
+
+    /// Make the store in a backup archive the active store. An existing store
+    /// is only replaced with `replace`, and is kept next to it: its new path
+    /// is returned. Open the store afterwards to migrate an older schema.
+    pub fn restore(
+        archive: &[u8],
+        passphrase: &SecretString,
+        replace: bool,
+    ) -> Result<Option<PathBuf>> {
+        let database = backup::open(archive, passphrase)?;
+        let path = Self::path()?;
+        if path.exists() && !replace {
+            anyhow::bail!("A store already exists at {}", path.display());
+        }
+        if let Some(parent) = path.parent() {
+            permissions::create_private_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let restoring = PathBuf::from(format!("{}.restoring", path.display()));
+        {
+            use std::io::Write;
+            permissions::create_private_file(&restoring)
+                .and_then(|mut file| file.write_all(database.expose_secret()))
+                .with_context(|| format!("Failed to write {}", restoring.display()))?;
+        }
+        let readable = Connection::open(&restoring)
+            .map_err(anyhow::Error::from)
+            .and_then(|check| {
+                if is_fully_encrypted(&restoring)? {
+                    apply_key(&check, passphrase)?;
+                }
+                check.query_row("SELECT count(*) FROM secrets", [], |_| Ok(()))?;
+                Ok(())
+            });
+        if let Err(e) = readable {
+            let _ = std::fs::remove_file(&restoring);
+            return Err(e.context("Backup doesn't hold a readable store; nothing was changed"));
+        }
+
+        let previous = match path.exists() {
+            true => {
+                let base = format!("{}.before-restore.bak", path.display());
+                let mut kept = PathBuf::from(&base);
+                let mut n = 1;
+                while kept.exists() {
+                    kept = PathBuf::from(format!("{}.{}", base, n));
+                    n += 1;
+                }
+                Self::relocate(&path, &kept)?;
+                Some(kept)
+            }
+            false => None,
+        };
+        std::fs::rename(&restoring, &path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        debug!("Restored store at {} from a backup", path.display());
+        Ok(previous)
+    }
+
+    /// Backup taken before schema migrations ran on open, if any did
+    pub fn migration_backup(&self) -> Option<&Path> {
+        self.migration_backup.as_deref()
+    }
+
+    /// Inspect the schema and crypto state of the store without unlocking it
+    pub fn status() -> Result<StoreStatus> {
+        let conn = Self::connect_locked()?;
+
+        let mut current_values = 0;
+        let mut legacy_values = 0;
+        for table in ["secrets", "secret_history"] {
+            let mut stmt = conn.prepare(&format!("SELECT encrypted_value FROM {}", table))?;
+            let values = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            for value in values {
+                if crypto::is_current_format(&value?) {
+                    current_values += 1;
+                } else {
+                    legacy_values += 1;
+                }
+            }
+        }
+
+        Ok(StoreStatus {
+            schema_version: migrations::schema_version(&conn)?,
+            fast_check: read_metadata(&conn, "passphrase_check")?.is_some(),
+            has_salt: read_metadata(&conn, "encryption_salt")?.is_some(),
+            current_values,
+            legacy_values,
+            written_by_version: read_metadata(&conn, "written_by_version")?,
+            read_only_passphrase: read_metadata(&conn, "read_only_key")?.is_some(),
+            full_encryption: Self::fully_encrypted()?,
+        })
+    }
+
+    /// Count secrets and history per environment and find the `oldest` least
+    /// recently changed secrets, without unlocking the store
+    pub fn stats(oldest: usize) -> Result<StoreStats> {
+        let conn = Self::connect_locked()?;
+        if migrations::schema_version(&conn)? < migrations::SCHEMA_VERSION {
+            anyhow::bail!(
+                "Store needs upgrading first - run any other tinysecrets command once (e.g. `tinysecrets list`)"
+            );
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT project, environment, SUM(current), SUM(history), MAX(updated_at) FROM (
+                 SELECT project, environment, 1 AS current, 0 AS history, updated_at FROM secrets
+                 UNION ALL
+                 SELECT project, environment, 0, 1, NULL FROM secret_history
+             ) GROUP BY project, environment ORDER BY project, environment",
+        )?;
+        let environments = stmt
+            .query_map([], |row| {
+                let updated: Option<String> = row.get(4)?;
+                Ok(EnvironmentStats {
+                    project: row.get(0)?,
+                    environment: row.get(1)?,
+                    secrets: row.get::<_, i64>(2)? as usize,
+                    history: row.get::<_, i64>(3)? as usize,
+                    last_updated: updated
+                        .and_then(|u| DateTime::parse_from_rfc3339(&u).ok())
+                        .map(|dt| dt.with_timezone(&Utc)),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT project, environment, key, description, created_at, updated_at, version, source, changed_by, reason, git
+             FROM secrets ORDER BY updated_at, project, environment, key LIMIT ?1",
+        )?;
+        let mut oldest = stmt
+            .query_map([oldest as i64], entry_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        // Reading them would need the master key
+        if hides_key_names(&conn)? {
+            for entry in &mut oldest {
+                entry.key = "(hidden)".to_string();
+                entry.description = None;
+            }
+        }
+
+        let path = Self::path()?;
+        let file_size = ["", "-wal"]
+            .iter()
+            .filter_map(|suffix| std::fs::metadata(format!("{}{}", path.display(), suffix)).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        Ok(StoreStats {
+            environments,
+            oldest,
+            file_size,
+        })
+    }
+
+    /// Run SQLite's `PRAGMA integrity_check` without unlocking the store; an empty
+    /// list means the database is fine
+    pub fn integrity_check() -> Result<Vec<String>> {
+        let conn = Self::connect_locked()?;
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(problems.into_iter().filter(|p| p != "ok").collect())
+    }
+
+    /// Open the database read-only, without unlocking it. A fully encrypted
+    /// store is keyed with the passphrase given to `set_database_key`.
+    fn connect_locked() -> Result<Connection> {
+        Self::connect_locked_with(DATABASE_KEY.get())
+    }
+
+    fn connect_locked_with(passphrase: Option<&SecretString>) -> Result<Connection> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Err(StoreError::Missing.into());
+        }
+
+        let mut conn =
+            Connection::open_with_flags(&path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+                .context("Failed to open SQLite database")?;
+        if is_fully_encrypted(&path)? {
+            let passphrase = passphrase.context(
+                "This store is fully encrypted - it can't be read without the passphrase",
+            )?;
+            apply_key(&conn, passphrase)?;
+        }
+        logging::trace_sql(&mut conn);
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        debug!("Opened store at {} (read-only, locked)", path.display());
+        Ok(conn)
+    }
+
+    /// Passphrase for reading a fully encrypted store without unlocking it, for
+    /// the rest of the process
+    pub fn set_database_key(passphrase: SecretString) {
+        let _ = DATABASE_KEY.set(passphrase);
+    }
+
+    /// Whether the active store's whole file is encrypted (SQLCipher)
+    pub fn fully_encrypted() -> Result<bool> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        is_fully_encrypted(&path)
+    }
+
+    /// The active store's random ID, which keys its keychain entry. None if
+    /// there's no store yet, or it predates IDs and hasn't been unlocked since.
+    /// A fully encrypted store is known by its SQLCipher salt, the only part
+    /// of the file readable without the passphrase.
+    pub fn id() -> Result<Option<String>> {
+        if Self::fully_encrypted()? {
+            let header = read_header(&Self::path()?)?
+                .context("Fully encrypted store has no readable header")?;
+            return Ok(Some(header.iter().map(|b| format!("{:02x}", b)).collect()));
+        }
+        match Self::connect_locked() {
+            Ok(conn) => read_metadata(&conn, "store_id"),
+            Err(e) if matches!(e.downcast_ref(), Some(StoreError::Missing)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Which access `passphrase` would unlock (None if it's wrong), checked on a
+    /// read-only connection: nothing is migrated and a wrong passphrase doesn't
+    /// count towards the unlock backoff
+    pub fn check_passphrase(passphrase: &SecretString) -> Result<Option<Access>> {
+        let conn = match Self::connect_locked_with(Some(passphrase)) {
+            Err(e) if matches!(e.downcast_ref(), Some(StoreError::InvalidPassphrase)) => {
+                return Ok(None)
+            }
+            conn => conn?,
+        };
+        if read_metadata(&conn, "passphrase_check")?.is_none() {
+            // Legacy store: unlock() would add the fast check, which needs a write
+            let verification = read_metadata(&conn, "passphrase_verification")?
+                .context("Store appears corrupted - no passphrase verification found")?;
+            return Ok(crypto::verify_passphrase(passphrase, &verification).then_some(Access::Full));
+        }
+        match unlock(&conn, passphrase) {
+            Ok((_, access)) => Ok(Some(access)),
+            Err(e) if matches!(e.downcast_ref(), Some(StoreError::InvalidPassphrase)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether a secret is set (here or in a parent environment), by row lookup alone:
+    /// the store isn't unlocked and nothing is decrypted
+    pub fn has(project: &str, environment: &str, key: &str) -> Result<bool> {
+        let conn = Self::connect_locked()?;
+        if migrations::schema_version(&conn)? < migrations::SCHEMA_VERSION {
+            anyhow::bail!(
+                "Store needs upgrading first - run any other tinysecrets command once (e.g. `tinysecrets list`)"
+            );
+        }
+        if hides_key_names(&conn)? {
+            return Err(StoreError::NamesHidden.into());
+        }
+
+        for env in lineage_of(&conn, project, environment)? {
+            let found = conn
+                .query_row(
+                    "SELECT 1 FROM secrets WHERE project = ?1 AND environment = ?2 AND key = ?3",
+                    params![project, env, key],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if found {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     /// Check if a store exists
     pub fn exists() -> Result<bool> {
-        Ok(Self::default_path()?.exists())
+        Ok(Self::path()?.exists())
+    }
+
+    /// Version of tinysecrets that last wrote to this store (if recorded)
+    pub fn written_by_version(&self) -> Option<String> {
+        read_metadata(&self.conn, "written_by_version")
+            .ok()
+            .flatten()
+    }
+
+    /// Stamp the store with this binary's version after a write
+    pub fn record_writer(&self) -> Result<()> {
+        write_metadata(&self.conn, "written_by_version", compat::CLI_VERSION)
+    }
+
+    /// Add history rows written in this transaction to the hash chain
+    fn seal_history(&self, tx: &Connection) -> Result<()> {
+        chain::seal(tx, &self.master_key, &self.attribution.changed_by)
+    }
+
+    /// Rebuild the history chain after rewriting history on purpose, recording
+    /// `action` in the audit log (see `crate::chain`)
+    pub fn rechain_history(&self, tx: &Connection, action: &str, detail: &str) -> Result<()> {
+        chain::rechain(
+            tx,
+            &self.master_key,
+            action,
+            detail,
+            &self.attribution.changed_by,
+        )
+    }
+
+    /// Check the history and audit log chains, and an `ID:HASH` anchor
+    /// recorded from an earlier check
+    pub fn verify_chain(&self, anchor: Option<&str>) -> Result<chain::ChainReport> {
+        chain::verify(&self.conn, &self.master_key, anchor)
+    }
+
+    /// Decrypt a stored value with the cached master key (legacy age values
+    /// fall back to the passphrase)
+    pub fn decrypt_value(&self, encrypted: &str) -> Result<String> {
+        let decrypted = crypto::decrypt(encrypted, &self.master_key, self.passphrase.as_ref())?;
+        Ok(decrypted.expose_secret().clone())
+    }
+
+    /// Decrypt a stored value to raw bytes (binary secrets aren't UTF-8), wiped when dropped
+    pub fn decrypt_bytes(&self, encrypted: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let decrypted =
+            crypto::decrypt_bytes(encrypted, &self.master_key, self.passphrase.as_ref())?;
+        Ok(Zeroizing::new(decrypted.expose_secret().to_vec()))
+    }
+
+    /// Encrypt a value with the cached master key (current format)
+    pub fn encrypt_value(&self, plaintext: &str) -> Result<String> {
+        self.require_write()?;
+        crypto::encrypt(plaintext, &self.master_key)
     }
 
     /// Get a reference to the underlying connection (for migrations)
@@ -174,93 +1225,329 @@ impl Store {
         &self.conn
     }
 
-    /// Set a secret value
-    pub fn set(
+    /// SQLite's `data_version`: changes whenever another connection commits
+    pub fn data_version(&self) -> Result<i64> {
+        Ok(self
+            .conn
+            .query_row("PRAGMA data_version", [], |row| row.get(0))?)
+    }
+
+    /// Set a secret to arbitrary bytes (e.g. a .p12 keystore), recording where it
+    /// came from (`source`) when imported. Returns false if it already held
+    /// `value`, in which case no new version is written.
+    pub fn set_bytes(
         &self,
         project: &str,
         environment: &str,
         key: &str,
-        value: &str,
+        value: &[u8],
         description: Option<&str>,
-    ) -> Result<()> {
-        let encrypted_value = crypto::encrypt(value, &self.master_key)?;
-        let now = Utc::now();
+        source: Option<&str>,
+    ) -> Result<bool> {
+        let tx = self.immediate_transaction()?;
+        let written =
+            self.write_secret(&tx, project, environment, key, value, description, source)?;
+        tx.commit()?;
+        self.record_writer()?;
+        Ok(written)
+    }
 
-        // Check if secret exists
-        let existing: Option<(i32, String)> = self
-            .conn
+    /// Begin a write transaction that takes the write lock up front, so concurrent
+    /// writers wait on busy_timeout instead of failing mid-transaction
+    pub fn immediate_transaction(&self) -> Result<Transaction<'_>> {
+        self.require_write()?;
+        Ok(Transaction::new_unchecked(
+            &self.conn,
+            TransactionBehavior::Immediate,
+        )?)
+    }
+
+    /// Write a secret (archiving the previous version) inside an open transaction.
+    /// `source` replaces the recorded source, so a hand-set value clears it.
+    /// An unchanged value isn't rewritten (only a new description is applied);
+    /// returns whether a new version was written.
+    #[allow(clippy::too_many_arguments)]
+    fn write_secret(
+        &self,
+        tx: &Connection,
+        project: &str,
+        environment: &str,
+        key: &str,
+        value: &[u8],
+        description: Option<&str>,
+        source: Option<&str>,
+    ) -> Result<bool> {
+        let stored_key = self.stored_key(key)?;
+        let description = self.stored_description(description)?;
+        if self.holds(tx, project, environment, key, value)? {
+            if description.is_some() {
+                tx.execute(
+                    "UPDATE secrets SET description = ?1
+                     WHERE project = ?2 AND environment = ?3 AND key = ?4",
+                    params![description, project, environment, stored_key],
+                )?;
+            }
+            return Ok(false);
+        }
+        let encrypted_value = self.seal(key, value)?;
+        archive_current(tx, project, environment, &[&stored_key])?;
+        self.seal_history(tx)?;
+        upsert_secret(
+            tx,
+            project,
+            environment,
+            &stored_key,
+            &encrypted_value,
+            description.as_deref(),
+            source,
+            &self.attribution,
+        )?;
+        Ok(true)
+    }
+
+    /// Whether `key` is set in exactly this environment (not inherited) to
+    /// `value`. A value that can't be decrypted counts as different.
+    fn holds(
+        &self,
+        conn: &Connection,
+        project: &str,
+        environment: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<bool> {
+        let encrypted: Option<String> = conn
             .query_row(
-                "SELECT version, encrypted_value FROM secrets 
+                "SELECT encrypted_value FROM secrets
                  WHERE project = ?1 AND environment = ?2 AND key = ?3",
-                params![project, environment, key],
-                |row| Ok((row.get(0)?, row.get(1)?)),
+                params![project, environment, self.stored_key(key)?],
+                |row| row.get(0),
             )
-            .ok();
+            .optional()?;
+        Ok(encrypted.is_some_and(|encrypted| {
+            crypto::decrypt_bytes(&encrypted, &self.master_key, self.passphrase.as_ref())
+                .is_ok_and(|current| current.expose_secret()[..] == *value)
+        }))
+    }
 
-        let tx = self.conn.unchecked_transaction()?;
+    /// Write many secrets in one transaction, archiving the previous versions in
+    /// a single pass. Keys that already hold their value are left alone. A value
+    /// that can't be stored is skipped and returned with its error, unless
+    /// `atomic`, in which case nothing is written. `progress` is called with the
+    /// number of values processed so far.
+    pub fn set_many(
+        &self,
+        project: &str,
+        environment: &str,
+        values: &[(String, String)],
+        source: Option<&str>,
+        atomic: bool,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<BulkWrite> {
+        let tx = self.immediate_transaction()?;
+
+        let mut sealed = Vec::with_capacity(values.len());
+        let mut result = BulkWrite::default();
+        for (done, (key, value)) in values.iter().enumerate() {
+            if self.holds(&tx, project, environment, key, value.as_bytes())? {
+                result.unchanged.push(key.clone());
+                progress(done + 1);
+                continue;
+            }
+            match self.seal(key, value.as_bytes()) {
+                Ok(encrypted) => sealed.push((self.stored_key(key)?, encrypted)),
+                // Dropping the transaction rolls it back
+                Err(e) if atomic => return Err(e),
+                Err(e) => result.failed.push((key.clone(), e)),
+            }
+            progress(done + 1);
+        }
 
-        if let Some((version, _old_encrypted)) = existing {
-            // Archive old version
-            tx.execute(
-                "INSERT INTO secret_history (project, environment, key, encrypted_value, version, created_at)
-                 SELECT project, environment, key, encrypted_value, version, updated_at
-                 FROM secrets WHERE project = ?1 AND environment = ?2 AND key = ?3",
-                params![project, environment, key],
+        let keys: Vec<&str> = sealed.iter().map(|(key, _)| key.as_str()).collect();
+        archive_current(&tx, project, environment, &keys)?;
+        self.seal_history(&tx)?;
+        for (key, encrypted_value) in &sealed {
+            upsert_secret(
+                &tx,
+                project,
+                environment,
+                key,
+                encrypted_value,
+                None,
+                source,
+                &self.attribution,
             )?;
+        }
+        tx.commit()?;
+        if !sealed.is_empty() {
+            self.record_writer()?;
+        }
 
-            // Update existing
-            tx.execute(
-                "UPDATE secrets SET encrypted_value = ?1, description = ?2, updated_at = ?3, version = ?4
-                 WHERE project = ?5 AND environment = ?6 AND key = ?7",
-                params![
-                    encrypted_value,
-                    description,
-                    now.to_rfc3339(),
-                    version + 1,
-                    project,
-                    environment,
-                    key
-                ],
-            )?;
-        } else {
-            // Insert new
-            tx.execute(
-                "INSERT INTO secrets (project, environment, key, encrypted_value, description, created_at, updated_at, version)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, 1)",
-                params![
-                    project,
-                    environment,
-                    key,
-                    encrypted_value,
-                    description,
-                    now.to_rfc3339()
-                ],
-            )?;
+        Ok(result)
+    }
+
+    /// Check a value against the size limit and encrypt it for storage
+    fn seal(&self, key: &str, value: &[u8]) -> Result<String> {
+        self.require_write()?;
+        if value.len() > MAX_VALUE_SIZE {
+            anyhow::bail!(
+                "Value for {} is {} bytes; the limit is {} bytes",
+                key,
+                value.len(),
+                MAX_VALUE_SIZE
+            );
+        }
+        crypto::encrypt_bytes(value, &self.master_key)
+    }
+
+    /// Get a secret value, falling back to parent environments
+    /// (raw bytes, which aren't necessarily UTF-8, wiped when dropped)
+    pub fn get(
+        &self,
+        project: &str,
+        environment: &str,
+        key: &str,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        for env in self.lineage(project, environment)? {
+            if let Some(row) = self.rows(project, &env, Some(key))?.remove(key) {
+                return Ok(Some(self.decrypt_bytes(&row.encrypted)?));
+            }
         }
+        Ok(None)
+    }
 
-        tx.commit()?;
-        Ok(())
+    /// Encrypted values set in exactly this environment (not inherited), for
+    /// one key or all of them. Pinned to `--as-of` when it's set.
+    fn rows(
+        &self,
+        project: &str,
+        environment: &str,
+        key: Option<&str>,
+    ) -> Result<BTreeMap<String, StoredValue>> {
+        let key = key.map(|key| self.stored_key(key)).transpose()?;
+        let rows = self.stored_rows(project, environment, key.as_deref())?;
+        match self.names {
+            Some(_) => rows
+                .into_iter()
+                .map(|(key, row)| Ok((self.key_name(&key)?, row)))
+                .collect(),
+            None => Ok(rows),
+        }
     }
 
-    /// Get a secret value
-    pub fn get(&self, project: &str, environment: &str, key: &str) -> Result<Option<String>> {
-        let encrypted: Option<String> = self
+    /// `rows` by stored key names
+    fn stored_rows(
+        &self,
+        project: &str,
+        environment: &str,
+        key: Option<&str>,
+    ) -> Result<BTreeMap<String, StoredValue>> {
+        let Some(at) = Self::as_of() else {
+            let mut stmt = self.conn.prepare_cached(
+                "SELECT key, encrypted_value, version FROM secrets
+                 WHERE project = ?1 AND environment = ?2 AND (?3 IS NULL OR key = ?3)",
+            )?;
+            let rows = stmt
+                .query_map(params![project, environment, key], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        StoredValue {
+                            encrypted: row.get(1)?,
+                            version: row.get(2)?,
+                        },
+                    ))
+                })?
+                .collect::<Result<_, _>>()?;
+            return Ok(rows);
+        };
+
+        // Every version written by then; the latest one wins, unless it had
+        // been deleted by then too
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT key, encrypted_value, version, updated_at, NULL FROM secrets
+             WHERE project = ?1 AND environment = ?2 AND (?3 IS NULL OR key = ?3)
+             UNION ALL
+             SELECT key, encrypted_value, version, created_at, deleted_at FROM secret_history
+             WHERE project = ?1 AND environment = ?2 AND (?3 IS NULL OR key = ?3)",
+        )?;
+        let parse = |text: &str| {
+            DateTime::parse_from_rfc3339(text)
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok()
+        };
+        // key -> (written, deleted, value)
+        type Candidate = (DateTime<Utc>, Option<DateTime<Utc>>, StoredValue);
+        let mut latest: BTreeMap<String, Candidate> = BTreeMap::new();
+        let mut rows = stmt.query(params![project, environment, key])?;
+        while let Some(row) = rows.next()? {
+            let written_text: String = row.get(3)?;
+            let Some(written) = parse(&written_text).filter(|written| *written <= at) else {
+                continue;
+            };
+            let deleted = row
+                .get::<_, Option<String>>(4)?
+                .and_then(|text| parse(&text));
+            let key: String = row.get(0)?;
+            if latest
+                .get(&key)
+                .is_some_and(|(seen, _, _)| *seen >= written)
+            {
+                continue;
+            }
+            let value = StoredValue {
+                encrypted: row.get(1)?,
+                version: row.get(2)?,
+            };
+            latest.insert(key, (written, deleted, value));
+        }
+        Ok(latest
+            .into_iter()
+            .filter(|(_, (_, deleted, _))| deleted.is_none_or(|deleted| deleted > at))
+            .map(|(key, (_, _, value))| (key, value))
+            .collect())
+    }
+
+    /// Get a secret's metadata (without decrypting the value)
+    pub fn entry(
+        &self,
+        project: &str,
+        environment: &str,
+        key: &str,
+    ) -> Result<Option<SecretEntry>> {
+        let entry = self
             .conn
             .query_row(
-                "SELECT encrypted_value FROM secrets 
-                 WHERE project = ?1 AND environment = ?2 AND key = ?3",
-                params![project, environment, key],
-                |row| row.get(0),
+                "SELECT project, environment, key, description, created_at, updated_at, version, source, changed_by, reason, git
+                 FROM secrets WHERE project = ?1 AND environment = ?2 AND key = ?3",
+                params![project, environment, self.stored_key(key)?],
+                entry_from_row,
             )
-            .ok();
+            .optional()?;
+        entry.map(|entry| self.reveal(entry)).transpose()
+    }
 
-        match encrypted {
-            Some(enc) => {
-                let decrypted = crypto::decrypt(&enc, &self.master_key, &self.passphrase)?;
-                Ok(Some(decrypted.expose_secret().clone()))
-            }
-            None => Ok(None),
+    /// Update a secret's description without creating a new version
+    pub fn set_description(
+        &self,
+        project: &str,
+        environment: &str,
+        key: &str,
+        description: Option<&str>,
+    ) -> Result<bool> {
+        self.require_write()?;
+        let updated = self.conn.execute(
+            "UPDATE secrets SET description = ?1
+             WHERE project = ?2 AND environment = ?3 AND key = ?4",
+            params![
+                self.stored_description(description)?,
+                project,
+                environment,
+                self.stored_key(key)?
+            ],
+        )?;
+        if updated > 0 {
+            self.record_writer()?;
         }
+        Ok(updated > 0)
     }
 
     /// List secrets (optionally filtered)
@@ -270,7 +1557,7 @@ impl Store {
         environment: Option<&str>,
     ) -> Result<Vec<SecretEntry>> {
         let mut sql = String::from(
-            "SELECT project, environment, key, description, created_at, updated_at, version FROM secrets WHERE 1=1"
+            "SELECT project, environment, key, description, created_at, updated_at, version, source, changed_by, reason, git FROM secrets WHERE 1=1"
         );
         let mut params_vec: Vec<String> = vec![];
 
@@ -290,67 +1577,531 @@ impl Store {
             .map(|s| s as &dyn rusqlite::ToSql)
             .collect();
 
-        let entries = stmt
-            .query_map(params.as_slice(), |row| {
-                let created_str: String = row.get(4)?;
-                let updated_str: String = row.get(5)?;
-                Ok(SecretEntry {
+        let mut entries = stmt
+            .query_map(params.as_slice(), entry_from_row)?
+            .map(|entry| self.reveal(entry?))
+            .collect::<Result<Vec<_>>>()?;
+        // Encrypted names sort by their ciphertext
+        if self.names.is_some() {
+            entries.sort_by(|a, b| {
+                (&a.project, &a.environment, &a.key).cmp(&(&b.project, &b.environment, &b.key))
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Delete a secret
+    pub fn delete(&self, project: &str, environment: &str, key: &str) -> Result<bool> {
+        Ok(self.delete_keys(project, environment, &[key.to_string()])? > 0)
+    }
+
+    /// Delete several secrets from an environment at once
+    pub fn delete_keys(&self, project: &str, environment: &str, keys: &[String]) -> Result<usize> {
+        let tx = self.immediate_transaction()?;
+        let mut deleted = 0;
+        for key in keys {
+            let key = self.stored_key(key)?;
+            deleted += archive_and_delete(&tx, project, Some(environment), Some(&key))?;
+        }
+        self.seal_history(&tx)?;
+        tx.commit()?;
+        if deleted > 0 {
+            self.record_writer()?;
+        }
+        Ok(deleted)
+    }
+
+    /// Delete every secret in an environment and forget its settings.
+    /// Environments that inherited from it stop inheriting.
+    pub fn delete_environment(&self, project: &str, environment: &str) -> Result<usize> {
+        let tx = self.immediate_transaction()?;
+        let deleted = archive_and_delete(&tx, project, Some(environment), None)?;
+        self.seal_history(&tx)?;
+        tx.execute(
+            "DELETE FROM environments WHERE project = ?1 AND name = ?2",
+            params![project, environment],
+        )?;
+        tx.execute(
+            "UPDATE environments SET parent = NULL WHERE project = ?1 AND parent = ?2",
+            params![project, environment],
+        )?;
+        tx.commit()?;
+        self.record_writer()?;
+        Ok(deleted)
+    }
+
+    /// Delete every secret in every environment of a project
+    pub fn delete_project(&self, project: &str) -> Result<usize> {
+        let tx = self.immediate_transaction()?;
+        let deleted = archive_and_delete(&tx, project, None, None)?;
+        self.seal_history(&tx)?;
+        tx.execute(
+            "DELETE FROM environments WHERE project = ?1",
+            params![project],
+        )?;
+        tx.commit()?;
+        self.record_writer()?;
+        Ok(deleted)
+    }
+
+    /// Deleted secrets that haven't been set again, most recently deleted first.
+    /// Their versions are still in history, so they can be restored.
+    pub fn trash(
+        &self,
+        project: Option<&str>,
+        environment: Option<&str>,
+    ) -> Result<Vec<TrashedSecret>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT h.project, h.environment, h.key, h.version, h.deleted_at, h.description
+             FROM secret_history h
+             WHERE h.deleted_at IS NOT NULL
+               AND (?1 IS NULL OR h.project = ?1) AND (?2 IS NULL OR h.environment = ?2)
+               AND h.id = (SELECT MAX(id) FROM secret_history l
+                           WHERE l.project = h.project AND l.environment = h.environment
+                             AND l.key = h.key AND l.deleted_at IS NOT NULL)
+               AND NOT EXISTS (SELECT 1 FROM secrets s
+                               WHERE s.project = h.project AND s.environment = h.environment
+                                 AND s.key = h.key)
+             ORDER BY h.deleted_at DESC, h.project, h.environment, h.key",
+        )?;
+        let trashed = stmt
+            .query_map(params![project, environment], |row| {
+                let deleted_str: String = row.get(4)?;
+                Ok(TrashedSecret {
                     project: row.get(0)?,
                     environment: row.get(1)?,
                     key: row.get(2)?,
-                    description: row.get(3)?,
-                    created_at: DateTime::parse_from_rfc3339(&created_str)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    updated_at: DateTime::parse_from_rfc3339(&updated_str)
+                    version: row.get(3)?,
+                    deleted_at: DateTime::parse_from_rfc3339(&deleted_str)
                         .map(|dt| dt.with_timezone(&Utc))
                         .unwrap_or_else(|_| Utc::now()),
-                    version: row.get(6)?,
+                    description: row.get(5)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
+        if self.names.is_none() {
+            return Ok(trashed);
+        }
+        trashed
+            .into_iter()
+            .map(|mut t| {
+                t.key = self.key_name(&t.key)?;
+                t.description = self.description(t.description)?;
+                Ok(t)
+            })
+            .collect()
+    }
 
-        Ok(entries)
+    /// Restore a deleted secret from the trash as a new version holding the
+    /// value it was deleted with; earlier versions stay in its history.
+    /// Returns the new version, or None if the key isn't in the trash.
+    pub fn undelete(&self, project: &str, environment: &str, key: &str) -> Result<Option<i32>> {
+        let tx = self.immediate_transaction()?;
+        if self.entry(project, environment, key)?.is_some() {
+            anyhow::bail!("{}/{}/{} already exists", project, environment, key);
+        }
+        let key = self.stored_key(key)?;
+        let deleted: Option<(String, i32, Option<String>, Option<String>)> = tx
+            .query_row(
+                "SELECT encrypted_value, version, description, source FROM secret_history
+                 WHERE project = ?1 AND environment = ?2 AND key = ?3 AND deleted_at IS NOT NULL
+                 ORDER BY id DESC LIMIT 1",
+                params![project, environment, &key],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()?;
+        let Some((encrypted_value, version, description, source)) = deleted else {
+            return Ok(None);
+        };
+        let created_at: String = tx.query_row(
+            "SELECT MIN(created_at) FROM secret_history
+             WHERE project = ?1 AND environment = ?2 AND key = ?3",
+            params![project, environment, &key],
+            |row| row.get(0),
+        )?;
+
+        let reason = self
+            .attribution
+            .reason
+            .as_deref()
+            .unwrap_or("restored from trash");
+        tx.execute(
+            "INSERT INTO secrets (project, environment, key, encrypted_value, description, created_at, updated_at, version, source, changed_by, reason, git)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                project,
+                environment,
+                key,
+                encrypted_value,
+                description,
+                created_at,
+                Utc::now().to_rfc3339(),
+                version + 1,
+                source,
+                self.attribution.changed_by,
+                reason,
+                self.attribution.git,
+            ],
+        )?;
+        tx.commit()?;
+        self.record_writer()?;
+        Ok(Some(version + 1))
+    }
+
+    /// Permanently remove trashed secrets deleted before `cutoff` (all of them
+    /// without one), history included. Returns what was removed.
+    pub fn empty_trash(
+        &self,
+        project: Option<&str>,
+        environment: Option<&str>,
+        cutoff: Option<DateTime<Utc>>,
+    ) -> Result<Vec<TrashedSecret>> {
+        let tx = self.immediate_transaction()?;
+        let purged: Vec<TrashedSecret> = self
+            .trash(project, environment)?
+            .into_iter()
+            .filter(|t| cutoff.is_none_or(|cutoff| t.deleted_at < cutoff))
+            .collect();
+        for trashed in &purged {
+            tx.execute(
+                "DELETE FROM secret_history WHERE project = ?1 AND environment = ?2 AND key = ?3",
+                params![
+                    trashed.project,
+                    trashed.environment,
+                    self.stored_key(&trashed.key)?
+                ],
+            )?;
+        }
+        if !purged.is_empty() {
+            self.rechain_history(
+                &tx,
+                "trash-empty",
+                &format!("removed the history of {} deleted secrets", purged.len()),
+            )?;
+        }
+        tx.commit()?;
+        if !purged.is_empty() {
+            self.record_writer()?;
+        }
+        Ok(purged)
+    }
+
+    /// Environments that inherit directly from `environment`
+    pub fn children(&self, project: &str, environment: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name FROM environments WHERE project = ?1 AND parent = ?2 ORDER BY name",
+        )?;
+        let children = stmt
+            .query_map(params![project, environment], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(children)
+    }
+
+    /// Get all secrets for an environment, merged over its parents (child wins).
+    /// The values are wiped from memory when the returned list is dropped.
+    pub fn get_all(&self, project: &str, environment: &str) -> Result<SecretValues> {
+        if let Some(lock) = LOCKED.get() {
+            return self.get_locked(project, environment, lock);
+        }
+        let start = Instant::now();
+
+        // Walk from the root down so each child overrides its parent
+        let mut merged = BTreeMap::new();
+        for env in self.lineage(project, environment)?.iter().rev() {
+            merged.extend(self.rows(project, env, None)?);
+        }
+
+        // One cipher for every value, decrypted in parallel; references are
+        // resolved afterwards since they go back to the database
+        let decryptor = crypto::Decryptor::new(&self.master_key, self.passphrase.as_ref())?;
+        let ciphertexts: Vec<&str> = merged.values().map(|row| row.encrypted.as_str()).collect();
+        let plaintexts = decryptor.decrypt_many(&ciphertexts);
+
+        let mut decrypted = Zeroizing::new(Vec::with_capacity(merged.len()));
+        for (key, plaintext) in merged.keys().zip(plaintexts) {
+            let context = || format!("Failed to load {}/{}/{}", project, environment, key);
+            let plaintext = plaintext.with_context(context)?;
+            let mut value = std::str::from_utf8(plaintext.expose_secret())
+                .context("Secret holds binary data (not UTF-8) - use `get --to-file` or `--base64`")
+                .with_context(context)?
+                .to_string();
+            let key = key.clone();
+            if refs::contains_refs(&value) {
+                value = self.resolve_refs(&value, &SecretPath::new(project, environment, &key))?;
+            }
+            decrypted.push((key, value));
+        }
+
+        debug!(
+            "Loaded {} secrets for {}/{} ({})",
+            decrypted.len(),
+            project,
+            environment,
+            logging::millis(start.elapsed())
+        );
+        Ok(decrypted)
+    }
+
+    /// Pin the version of every key an environment resolves to (`tinysecrets lock`)
+    pub fn lock(&self, project: &str, environment: &str) -> Result<Lockfile> {
+        let mut merged = BTreeMap::new();
+        for env in self.lineage(project, environment)?.iter().rev() {
+            for (key, row) in self.rows(project, env, None)? {
+                merged.insert(key, (env.clone(), row));
+            }
+        }
+
+        let mut keys = BTreeMap::new();
+        for (key, (env, row)) in merged {
+            let value =
+                crypto::decrypt_bytes(&row.encrypted, &self.master_key, self.passphrase.as_ref())
+                    .with_context(|| format!("Failed to load {}/{}/{}", project, env, key))?;
+            keys.insert(
+                key,
+                LockedKey {
+                    environment: env,
+                    version: row.version,
+                    fingerprint: crypto::fingerprint(&self.master_key, value.expose_secret()),
+                },
+            );
+        }
+        Ok(Lockfile {
+            project: project.to_string(),
+            environment: environment.to_string(),
+            store: read_metadata(&self.conn, "store_id")?,
+            locked_at: Utc::now(),
+            keys,
+        })
+    }
+
+    /// A keyed fingerprint of every value set in `project` (or the whole store),
+    /// so values can be compared without being kept around. Inherited keys
+    /// aren't listed again under each child, and references and values shorter
+    /// than `min_length` characters (ports, flags) are left out.
+    pub fn value_fingerprints(
+        &self,
+        project: Option<&str>,
+        min_length: usize,
+    ) -> Result<Vec<(SecretPath, String)>> {
+        let projects = match project {
+            Some(project) => vec![project.to_string()],
+            None => self.list_projects()?,
+        };
+
+        let mut fingerprints = Vec::new();
+        for project in &projects {
+            for env in self.list_environments(project)? {
+                for (key, row) in self.rows(project, &env, None)? {
+                    let value = crypto::decrypt_bytes(
+                        &row.encrypted,
+                        &self.master_key,
+                        self.passphrase.as_ref(),
+                    )
+                    .with_context(|| format!("Failed to load {}/{}/{}", project, env, key))?;
+                    let skip = match std::str::from_utf8(value.expose_secret()) {
+                        Ok(text) => text.chars().count() < min_length || refs::contains_refs(text),
+                        Err(_) => value.expose_secret().len() < min_length,
+                    };
+                    if skip {
+                        continue;
+                    }
+                    fingerprints.push((
+                        SecretPath::new(project, &env, &key),
+                        crypto::fingerprint(&self.master_key, value.expose_secret()),
+                    ));
+                }
+            }
+        }
+        Ok(fingerprints)
+    }
+
+    /// The values a lockfile pins, current or from history. Fails if any of
+    /// them is gone (e.g. history was purged) rather than run with a different one.
+    fn get_locked(
+        &self,
+        project: &str,
+        environment: &str,
+        lock: &Lockfile,
+    ) -> Result<SecretValues> {
+        if (lock.project.as_str(), lock.environment.as_str()) != (project, environment) {
+            anyhow::bail!(
+                "The lockfile is for {}/{}, not {}/{}",
+                lock.project,
+                lock.environment,
+                project,
+                environment
+            );
+        }
+        let store_id = read_metadata(&self.conn, "store_id")?;
+        if lock.store.is_some() && store_id.is_some() && lock.store != store_id {
+            anyhow::bail!("The lockfile was made from a different store");
+        }
+
+        let mut locked = BTreeMap::new();
+        for (key, pin) in &lock.keys {
+            let value = self.locked_value(project, key, pin)?.with_context(|| {
+                format!(
+                    "{}/{}/{} v{} is no longer in the store or its history; run `tinysecrets lock` again",
+                    project, pin.environment, key, pin.version
+                )
+            })?;
+            locked.insert(key.clone(), Zeroizing::new(value));
+        }
+
+        // References resolve to locked values where the lock has them
+        let mut lookup = |target: &SecretPath| -> Result<Option<String>> {
+            if (target.project.as_str(), target.environment.as_str()) == (project, environment) {
+                if let Some(value) = locked.get(&target.key) {
+                    return Ok(Some(value.to_string()));
+                }
+            }
+            self.ref_value(target)
+        };
+        let mut values = Zeroizing::new(Vec::with_capacity(locked.len()));
+        for (key, value) in &locked {
+            let value = if refs::contains_refs(value) {
+                refs::resolve(
+                    value,
+                    &SecretPath::new(project, environment, key),
+                    &mut lookup,
+                )?
+            } else {
+                value.to_string()
+            };
+            values.push((key.clone(), value));
+        }
+        Ok(values)
     }
 
-    /// Delete a secret
-    pub fn delete(&self, project: &str, environment: &str, key: &str) -> Result<bool> {
-        // First archive to history
-        self.conn.execute(
-            "INSERT INTO secret_history (project, environment, key, encrypted_value, version, created_at, deleted_at)
-             SELECT project, environment, key, encrypted_value, version, updated_at, ?4
-             FROM secrets WHERE project = ?1 AND environment = ?2 AND key = ?3",
-            params![project, environment, key, Utc::now().to_rfc3339()],
+    /// The value of `key` at a locked version, checked against its fingerprint
+    fn locked_value(&self, project: &str, key: &str, pin: &LockedKey) -> Result<Option<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT encrypted_value FROM secrets
+             WHERE project = ?1 AND environment = ?2 AND key = ?3 AND version = ?4
+             UNION ALL
+             SELECT encrypted_value FROM secret_history
+             WHERE project = ?1 AND environment = ?2 AND key = ?3 AND version = ?4",
         )?;
+        let candidates = stmt
+            .query_map(
+                params![project, pin.environment, self.stored_key(key)?, pin.version],
+                |row| row.get::<_, String>(0),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let deleted = self.conn.execute(
-            "DELETE FROM secrets WHERE project = ?1 AND environment = ?2 AND key = ?3",
-            params![project, environment, key],
-        )?;
+        // A key deleted and created again reuses version numbers
+        for encrypted in candidates {
+            let value =
+                crypto::decrypt_bytes(&encrypted, &self.master_key, self.passphrase.as_ref())?;
+            if crypto::fingerprint(&self.master_key, value.expose_secret()) == pin.fingerprint {
+                let text = std::str::from_utf8(value.expose_secret()).context(
+                    "Secret holds binary data (not UTF-8) - use `get --to-file` or `--base64`",
+                )?;
+                return Ok(Some(text.to_string()));
+            }
+        }
+        Ok(None)
+    }
 
-        Ok(deleted > 0)
+    /// Values about to be written to project/environment whose `{{ref:...}}`
+    /// references wouldn't resolve, with the reason. References to other keys
+    /// in `values` see the new values.
+    pub fn unresolvable_refs(
+        &self,
+        project: &str,
+        environment: &str,
+        values: &[(String, String)],
+    ) -> Vec<(String, anyhow::Error)> {
+        let mut lookup = |target: &SecretPath| -> Result<Option<String>> {
+            if (target.project.as_str(), target.environment.as_str()) == (project, environment) {
+                if let Some((_, value)) = values.iter().find(|(key, _)| *key == target.key) {
+                    return Ok(Some(value.clone()));
+                }
+            }
+            self.ref_value(target)
+        };
+        values
+            .iter()
+            .filter(|(_, value)| refs::contains_refs(value))
+            .filter_map(|(key, value)| {
+                let from = SecretPath::new(project, environment, key);
+                refs::resolve(value, &from, &mut lookup)
+                    .err()
+                    .map(|e| (key.clone(), e))
+            })
+            .collect()
     }
 
-    /// Get all secrets for a project/environment (for `ts run`)
-    pub fn get_all(&self, project: &str, environment: &str) -> Result<Vec<(String, String)>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT key, encrypted_value FROM secrets 
-             WHERE project = ?1 AND environment = ?2",
-        )?;
+    /// A referenced secret's value as text
+    fn ref_value(&self, target: &SecretPath) -> Result<Option<String>> {
+        self.get(&target.project, &target.environment, &target.key)?
+            .map(|bytes| {
+                std::str::from_utf8(&bytes)
+                    .map(str::to_string)
+                    .map_err(|_| anyhow::anyhow!("{} holds binary data", target))
+            })
+            .transpose()
+    }
 
-        let secrets = stmt
-            .query_map(params![project, environment], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
+    /// Replace `{{ref:...}}` references in a value read from `from` (see `crate::refs`)
+    pub fn resolve_refs(&self, value: &str, from: &SecretPath) -> Result<String> {
+        let mut lookup = |target: &SecretPath| -> Result<Option<String>> { self.ref_value(target) };
+        refs::resolve(value, from, &mut lookup)
+    }
 
-        let mut decrypted = Vec::new();
-        for (key, encrypted) in secrets {
-            let value = crypto::decrypt(&encrypted, &self.master_key, &self.passphrase)?;
-            decrypted.push((key, value.expose_secret().clone()));
+    /// Metadata for an environment's effective secrets, including inherited ones.
+    /// Inherited entries keep the `environment` they're defined in.
+    pub fn resolved_entries(&self, project: &str, environment: &str) -> Result<Vec<SecretEntry>> {
+        let mut merged = BTreeMap::new();
+        for env in self.lineage(project, environment)?.iter().rev() {
+            for entry in self.list(Some(project), Some(env))? {
+                merged.insert(entry.key.clone(), entry);
+            }
         }
+        Ok(merged.into_values().collect())
+    }
 
-        Ok(decrypted)
+    /// Parent an environment inherits from, if any
+    pub fn parent(&self, project: &str, environment: &str) -> Result<Option<String>> {
+        parent_of(&self.conn, project, environment)
+    }
+
+    /// The environment followed by its ancestors, nearest first
+    pub fn lineage(&self, project: &str, environment: &str) -> Result<Vec<String>> {
+        lineage_of(&self.conn, project, environment)
+    }
+
+    /// Make an environment inherit from `parent` (or stop inheriting with None)
+    pub fn set_parent(&self, project: &str, environment: &str, parent: Option<&str>) -> Result<()> {
+        self.require_write()?;
+        match parent {
+            Some(parent) => {
+                if self
+                    .lineage(project, parent)?
+                    .iter()
+                    .any(|e| e == environment)
+                {
+                    anyhow::bail!(
+                        "{} already inherits from {} - that would create a cycle",
+                        parent,
+                        environment
+                    );
+                }
+                self.conn.execute(
+                    "INSERT INTO environments (project, name, parent) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(project, name) DO UPDATE SET parent = excluded.parent",
+                    params![project, environment, parent],
+                )?;
+            }
+            None => {
+                self.conn.execute(
+                    "UPDATE environments SET parent = NULL WHERE project = ?1 AND name = ?2",
+                    params![project, environment],
+                )?;
+            }
+        }
+        self.record_writer()
     }
 
     /// Get secret history
@@ -362,32 +2113,40 @@ impl Store {
         limit: usize,
     ) -> Result<Vec<SecretHistoryEntry>> {
         let mut stmt = self.conn.prepare(
-            "SELECT project, environment, key, version, created_at, deleted_at 
+            "SELECT project, environment, key, version, created_at, deleted_at, changed_by, reason, git
              FROM secret_history 
              WHERE project = ?1 AND environment = ?2 AND key = ?3
              ORDER BY version DESC
              LIMIT ?4",
         )?;
 
+        let stored_key = self.stored_key(key)?;
         let entries = stmt
-            .query_map(params![project, environment, key, limit as i64], |row| {
-                let created_str: String = row.get(4)?;
-                let deleted_str: Option<String> = row.get(5)?;
-                Ok(SecretHistoryEntry {
-                    project: row.get(0)?,
-                    environment: row.get(1)?,
-                    key: row.get(2)?,
-                    version: row.get(3)?,
-                    created_at: DateTime::parse_from_rfc3339(&created_str)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    deleted_at: deleted_str.and_then(|s| {
-                        DateTime::parse_from_rfc3339(&s)
+            .query_map(
+                params![project, environment, stored_key, limit as i64],
+                |row| {
+                    let created_str: String = row.get(4)?;
+                    let deleted_str: Option<String> = row.get(5)?;
+                    Ok(SecretHistoryEntry {
+                        project: row.get(0)?,
+                        environment: row.get(1)?,
+                        // The stored name may be encrypted
+                        key: key.to_string(),
+                        version: row.get(3)?,
+                        created_at: DateTime::parse_from_rfc3339(&created_str)
                             .map(|dt| dt.with_timezone(&Utc))
-                            .ok()
-                    }),
-                })
-            })?
+                            .unwrap_or_else(|_| Utc::now()),
+                        deleted_at: deleted_str.and_then(|s| {
+                            DateTime::parse_from_rfc3339(&s)
+                                .map(|dt| dt.with_timezone(&Utc))
+                                .ok()
+                        }),
+                        changed_by: row.get(6)?,
+                        reason: row.get(7)?,
+                        git: row.get(8)?,
+                    })
+                },
+            )?
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(entries)
@@ -400,22 +2159,22 @@ impl Store {
         environment: &str,
         key: &str,
         version: i32,
-    ) -> Result<Option<String>> {
+    ) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let key = self.stored_key(key)?;
         // First check if requesting current version
         let current: Option<(i32, String)> = self
             .conn
             .query_row(
                 "SELECT version, encrypted_value FROM secrets 
                  WHERE project = ?1 AND environment = ?2 AND key = ?3",
-                params![project, environment, key],
+                params![project, environment, &key],
                 |row| Ok((row.get(0)?, row.get(1)?)),
             )
-            .ok();
+            .optional()?;
 
         if let Some((current_version, encrypted)) = current {
             if current_version == version {
-                let decrypted = crypto::decrypt(&encrypted, &self.master_key, &self.passphrase)?;
-                return Ok(Some(decrypted.expose_secret().clone()));
+                return Ok(Some(self.decrypt_bytes(&encrypted)?));
             }
         }
 
@@ -423,58 +2182,154 @@ impl Store {
         let encrypted: Option<String> = self
             .conn
             .query_row(
-                "SELECT encrypted_value FROM secret_history 
-                 WHERE project = ?1 AND environment = ?2 AND key = ?3 AND version = ?4",
-                params![project, environment, key, version],
+                "SELECT encrypted_value FROM secret_history
+                 WHERE project = ?1 AND environment = ?2 AND key = ?3 AND version = ?4
+                 ORDER BY id DESC LIMIT 1",
+                params![project, environment, &key, version],
                 |row| row.get(0),
             )
-            .ok();
+            .optional()?;
 
         match encrypted {
-            Some(enc) => {
-                let decrypted = crypto::decrypt(&enc, &self.master_key, &self.passphrase)?;
-                Ok(Some(decrypted.expose_secret().clone()))
-            }
+            Some(enc) => Ok(Some(self.decrypt_bytes(&enc)?)),
             None => Ok(None),
         }
     }
 
     /// List all projects
     pub fn list_projects(&self) -> Result<Vec<String>> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT DISTINCT project FROM secrets ORDER BY project")?;
+        Ok(names(self.project_summaries()?))
+    }
+
+    /// List all environments for a project
+    pub fn list_environments(&self, project: &str) -> Result<Vec<String>> {
+        Ok(names(self.environment_summaries(project)?))
+    }
 
+    /// All projects with their secret counts and latest change
+    pub fn project_summaries(&self) -> Result<Vec<Summary>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT project, COUNT(*), MAX(updated_at) FROM secrets
+             GROUP BY project ORDER BY project",
+        )?;
         let projects = stmt
-            .query_map([], |row| row.get(0))?
+            .query_map([], summary_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
-
         Ok(projects)
     }
 
-    /// List all environments for a project
-    pub fn list_environments(&self, project: &str) -> Result<Vec<String>> {
+    /// A project's environments with their own (not inherited) secret counts
+    /// and latest change
+    pub fn environment_summaries(&self, project: &str) -> Result<Vec<Summary>> {
+        // Environments that only inherit (no own secrets yet) count too
         let mut stmt = self.conn.prepare(
-            "SELECT DISTINCT environment FROM secrets WHERE project = ?1 ORDER BY environment",
+            "SELECT name, SUM(own), MAX(updated_at) FROM (
+                 SELECT environment AS name, 1 AS own, updated_at FROM secrets WHERE project = ?1
+                 UNION ALL
+                 SELECT name, 0, NULL FROM environments WHERE project = ?1
+             ) GROUP BY name ORDER BY name",
         )?;
-
         let envs = stmt
-            .query_map(params![project], |row| row.get(0))?
+            .query_map(params![project], summary_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
-
         Ok(envs)
     }
 
-    /// Export secrets for a project/environment
-    pub fn export(&self, project: &str, environment: &str) -> Result<ExportBundle> {
-        let entries = self.list(Some(project), Some(environment))?;
+    /// Export the secrets for a project/environment that pass `filter`
+    pub fn export(
+        &self,
+        project: &str,
+        environment: &str,
+        filter: &KeyFilter,
+    ) -> Result<ExportBundle> {
+        let mut bundle = self.empty_bundle(compat::SINGLE_BUNDLE_FORMAT_VERSION)?;
+        bundle.project = project.to_string();
+        bundle.environment = environment.to_string();
+        bundle.secrets = self.exported_secrets(project, environment, filter)?;
+        self.tag_bundle(bundle)
+    }
+
+    /// Export several project/environments into one multi-section bundle
+    pub fn export_many(
+        &self,
+        targets: &[(String, String)],
+        filter: &KeyFilter,
+    ) -> Result<ExportBundle> {
+        let mut bundle = self.empty_bundle(compat::BUNDLE_FORMAT_VERSION)?;
+        for (project, environment) in targets {
+            bundle.sections.push(BundleSection {
+                project: project.clone(),
+                environment: environment.clone(),
+                secrets: self.exported_secrets(project, environment, filter)?,
+            });
+        }
+        self.tag_bundle(bundle)
+    }
+
+    /// Add the integrity tag covering everything else in the bundle
+    fn tag_bundle(&self, mut bundle: ExportBundle) -> Result<ExportBundle> {
+        bundle.integrity = Some(crypto::tag_document(
+            &self.master_key,
+            &bundle.canonical_bytes()?,
+        ));
+        Ok(bundle)
+    }
+
+    /// Sign a bundle with the store's signing key, so recipients can check
+    /// who sent it (`import --verify-signer`)
+    pub fn sign_bundle(&self, bundle: &mut ExportBundle) -> Result<()> {
+        let seed = self.signing_key()?;
+        bundle.signature = None;
+        let signature = crypto::sign_document(&seed, &bundle.signed_bytes()?)?;
+        bundle.signature = Some(BundleSignature {
+            signer: crypto::signer_public_key(&seed)?,
+            signature,
+        });
+        Ok(())
+    }
+
+    /// The public half of the store's signing key, `ed25519:<base64>`
+    pub fn signing_public_key(&self) -> Result<String> {
+        crypto::signer_public_key(&self.signing_key()?)
+    }
+
+    /// The store's Ed25519 signing key. Stores created before signed bundles
+    /// get one the first time it's needed.
+    fn signing_key(&self) -> Result<SecureBytes> {
+        if let Some(encrypted) = read_metadata(&self.conn, "signing_key")? {
+            return crypto::decrypt_bytes(&encrypted, &self.master_key, None)
+                .context("Failed to decrypt the store's signing key");
+        }
+        self.require_write()?;
+        let seed = crypto::generate_signing_key();
+        write_signing_key(&self.conn, &self.master_key, &seed)?;
+        Ok(seed)
+    }
+
+    fn exported_secrets(
+        &self,
+        project: &str,
+        environment: &str,
+        filter: &KeyFilter,
+    ) -> Result<Vec<ExportedSecret>> {
+        if Self::as_of().is_some() {
+            let mut secrets = self.exported_secrets_as_of(project, environment)?;
+            secrets.retain(|secret| filter.matches(&secret.key));
+            return Ok(secrets);
+        }
+        let mut entries = self.resolved_entries(project, environment)?;
+        entries.retain(|entry| filter.matches(&entry.key));
         let mut secrets = Vec::new();
 
         for entry in entries {
             let encrypted: String = self.conn.query_row(
                 "SELECT encrypted_value FROM secrets 
                  WHERE project = ?1 AND environment = ?2 AND key = ?3",
-                params![entry.project, entry.environment, entry.key],
+                params![
+                    entry.project,
+                    entry.environment,
+                    self.stored_key(&entry.key)?
+                ],
                 |row| row.get(0),
             )?;
 
@@ -483,70 +2338,832 @@ impl Store {
                 encrypted_value: encrypted,
                 description: entry.description,
                 version: entry.version,
+                source: entry.source,
             });
         }
 
-        // Get passphrase verification for bundle
-        let verification: String = self.conn.query_row(
-            "SELECT value FROM metadata WHERE key = 'passphrase_verification'",
-            [],
-            |row| row.get(0),
-        )?;
+        Ok(secrets)
+    }
+
+    /// Export the versions pinned by `--as-of`. History doesn't keep
+    /// descriptions or sources, so the current description is used if the key
+    /// still exists.
+    fn exported_secrets_as_of(
+        &self,
+        project: &str,
+        environment: &str,
+    ) -> Result<Vec<ExportedSecret>> {
+        let mut merged = BTreeMap::new();
+        for env in self.lineage(project, environment)?.iter().rev() {
+            for (key, row) in self.rows(project, env, None)? {
+                merged.insert(key, (env.clone(), row));
+            }
+        }
+
+        let mut secrets = Vec::with_capacity(merged.len());
+        for (key, (env, row)) in merged {
+            let description = self
+                .entry(project, &env, &key)?
+                .and_then(|entry| entry.description);
+            secrets.push(ExportedSecret {
+                key,
+                encrypted_value: row.encrypted,
+                description,
+                version: row.version,
+                source: None,
+            });
+        }
+        Ok(secrets)
+    }
+
+    fn empty_bundle(&self, version: i32) -> Result<ExportBundle> {
+        // The salt lets the importer derive the key these values were encrypted with
+        let salt = read_metadata(&self.conn, "encryption_salt")?
+            .context("Store appears corrupted - no encryption salt found")?;
 
         Ok(ExportBundle {
-            version: 1,
-            project: project.to_string(),
-            environment: environment.to_string(),
-            passphrase_verification: verification,
+            version,
+            tinysecrets_version: Some(compat::CLI_VERSION.to_string()),
+            project: String::new(),
+            environment: String::new(),
+            passphrase_verification: None,
+            kdf_salt: Some(salt),
+            passphrase_check: Some(crypto::derive_check(&self.master_key)),
             exported_at: Utc::now(),
-            secrets,
+            secrets: Vec::new(),
+            sections: Vec::new(),
+            integrity: None,
+            signature: None,
         })
     }
 
-    /// Import secrets from a bundle
-    pub fn import(&self, bundle: &ExportBundle) -> Result<usize> {
-        // Verify bundle passphrase matches our passphrase
-        if !crypto::verify_passphrase(&self.passphrase, &bundle.passphrase_verification) {
-            anyhow::bail!(
+    /// Derive the key a bundle's values were encrypted with, verifying that our
+    /// passphrase matches the one used to export it
+    fn bundle_key(&self, bundle: &ExportBundle) -> Result<MasterKey> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        let mismatch = || {
+            anyhow::anyhow!(
                 "Bundle was encrypted with a different passphrase. \
                  You need the original passphrase to import these secrets."
-            );
+            )
+        };
+
+        // The version says which scheme protects the values; fields from the
+        // other one are ignored
+        if bundle.version >= 2 {
+            let (Some(salt), Some(check)) = (&bundle.kdf_salt, &bundle.passphrase_check) else {
+                anyhow::bail!(
+                    "Bundle format v{} is missing its KDF salt or passphrase check",
+                    bundle.version
+                );
+            };
+            // Exported from this store: no need to derive the key again
+            if read_metadata(&self.conn, "encryption_salt")?.as_ref() == Some(salt)
+                && crypto::verify_check(&self.master_key, check)
+            {
+                return Ok(self.master_key.clone());
+            }
+            let salt = BASE64
+                .decode(salt)
+                .context("Failed to decode bundle salt")?;
+            let key = MasterKey::derive(self.passphrase()?, &salt)?;
+            if !crypto::verify_check(&key, check) {
+                return Err(mismatch());
+            }
+            return Ok(key);
         }
 
-        let mut imported = 0;
-        for secret in &bundle.secrets {
-            // Decrypt and re-encrypt to verify integrity
-            let decrypted =
-                crypto::decrypt(&secret.encrypted_value, &self.master_key, &self.passphrase)?;
-            self.set(
-                &bundle.project,
-                &bundle.environment,
-                &secret.key,
-                decrypted.expose_secret(),
-                secret.description.as_deref(),
-            )?;
-            imported += 1;
+        // v1 bundles: age verification, values encrypted with this store's key
+        let verification = bundle
+            .passphrase_verification
+            .as_deref()
+            .context("Bundle has no passphrase verification")?;
+        if !crypto::verify_passphrase(self.passphrase()?, verification) {
+            return Err(mismatch());
+        }
+        let salt = load_or_create_salt(&self.conn)?;
+        MasterKey::derive(self.passphrase()?, &salt)
+    }
+
+    /// Which of `keys` already exist in the environment (inherited keys don't count)
+    pub fn existing_keys(
+        &self,
+        project: &str,
+        environment: &str,
+        keys: &[&str],
+    ) -> Result<Vec<String>> {
+        Ok(self
+            .list(Some(project), Some(environment))?
+            .into_iter()
+            .map(|entry| entry.key)
+            .filter(|key| keys.contains(&key.as_str()))
+            .collect())
+    }
+
+    /// Import secrets from a bundle, recording `source` on each. `targets` holds
+    /// one entry per section (in `bundle.sections()` order) saying where it goes;
+    /// existing keys there are skipped unless it overwrites. A bundle without
+    /// an integrity tag is refused, unless it predates them and `allow_untagged`.
+    /// Returns the count per section.
+    pub fn import(
+        &self,
+        bundle: &ExportBundle,
+        source: &str,
+        targets: &[ImportTarget],
+        allow_untagged: bool,
+    ) -> Result<Vec<Imported>> {
+        compat::check_bundle_version(bundle.version)?;
+
+        let bundle_key = self.bundle_key(bundle)?;
+        match &bundle.integrity {
+            Some(tag) if !crypto::verify_document(&bundle_key, &bundle.canonical_bytes()?, tag) => {
+                anyhow::bail!(
+                    "Bundle integrity check failed: it was modified or truncated after export. \
+                     Re-export it from the source store."
+                )
+            }
+            Some(_) => {}
+            // Only tampering removes the tag from these; passing one off as an
+            // older format still takes --allow-untagged
+            None if bundle.version >= compat::TAGGED_BUNDLE_FORMAT_VERSION => anyhow::bail!(
+                "Bundle integrity tag is missing, but format v{} always has one: \
+                 it was modified after export. Re-export it from the source store.",
+                bundle.version
+            ),
+            None if !allow_untagged => anyhow::bail!(
+                "Bundle has no integrity tag (format v{}, from an older tinysecrets), so \
+                 an edited or truncated bundle can't be detected. Re-export it, or pass \
+                 --allow-untagged to import it with only the individual values verified.",
+                bundle.version
+            ),
+            None => {}
+        }
+        let sections = bundle.sections();
+
+        let mut existing = Vec::with_capacity(sections.len());
+        for (section, target) in sections.iter().zip(targets) {
+            existing.push(if target.overwrite {
+                Vec::new()
+            } else {
+                let keys: Vec<&str> = section.secrets.iter().map(|s| s.key.as_str()).collect();
+                self.existing_keys(&target.project, &target.environment, &keys)?
+            });
+        }
+
+        // All sections land in one transaction: a bad value leaves the store untouched
+        let tx = self.immediate_transaction()?;
+        let mut imported = Vec::with_capacity(sections.len());
+        for ((section, target), existing) in sections.iter().zip(targets).zip(&existing) {
+            let mut count = Imported::default();
+            for secret in section
+                .secrets
+                .iter()
+                .filter(|s| !existing.contains(&s.key))
+            {
+                // Decrypt and re-encrypt to verify integrity
+                let decrypted = crypto::decrypt_bytes(
+                    &secret.encrypted_value,
+                    &bundle_key,
+                    self.passphrase.as_ref(),
+                )?;
+                let decrypted = decrypted.expose_secret();
+                let written = self.write_secret(
+                    &tx,
+                    &target.project,
+                    &target.environment,
+                    &secret.key,
+                    decrypted,
+                    secret.description.as_deref(),
+                    Some(secret.source.as_deref().unwrap_or(source)),
+                )?;
+                if written {
+                    count.written += 1;
+                } else {
+                    count.unchanged += 1;
+                }
+            }
+            imported.push(count);
         }
+        tx.commit()?;
+        self.record_writer()?;
 
         Ok(imported)
     }
 }
 
+/// Archive matching secrets to history, then delete them.
+/// `None` for environment or key matches all of them.
+fn archive_and_delete(
+    tx: &Transaction,
+    project: &str,
+    environment: Option<&str>,
+    key: Option<&str>,
+) -> Result<usize> {
+    const MATCHING: &str =
+        "project = ?1 AND (?2 IS NULL OR environment = ?2) AND (?3 IS NULL OR key = ?3)";
+
+    tx.execute(
+        &format!(
+            "INSERT INTO secret_history (project, environment, key, encrypted_value, version, created_at, deleted_at, changed_by, reason, git, description, source)
+             SELECT project, environment, key, encrypted_value, version, updated_at, ?4, changed_by, reason, git, description, source
+             FROM secrets WHERE {}",
+            MATCHING
+        ),
+        params![project, environment, key, Utc::now().to_rfc3339()],
+    )?;
+    Ok(tx.execute(
+        &format!("DELETE FROM secrets WHERE {}", MATCHING),
+        params![project, environment, key],
+    )?)
+}
+
+/// Copy the current versions of `keys` (those that exist) to history
+fn archive_current(tx: &Connection, project: &str, environment: &str, keys: &[&str]) -> Result<()> {
+    tx.execute(
+        "INSERT INTO secret_history (project, environment, key, encrypted_value, version, created_at, changed_by, reason, git)
+         SELECT project, environment, key, encrypted_value, version, updated_at, changed_by, reason, git
+         FROM secrets WHERE project = ?1 AND environment = ?2
+         AND key IN (SELECT value FROM json_each(?3))",
+        params![project, environment, serde_json::to_string(keys)?],
+    )?;
+    Ok(())
+}
+
+/// Insert a secret, or bump the version of an existing one (keeping its
+/// description unless a new one is given). A key set again after a delete
+/// carries on from its history, so a version number names one value.
+/// `attribution` describes this version only.
+#[allow(clippy::too_many_arguments)]
+fn upsert_secret(
+    tx: &Connection,
+    project: &str,
+    environment: &str,
+    key: &str,
+    encrypted_value: &str,
+    description: Option<&str>,
+    source: Option<&str>,
+    attribution: &Attribution,
+) -> Result<()> {
+    tx.execute(
+        "INSERT INTO secrets (project, environment, key, encrypted_value, description, created_at, updated_at, version, source, changed_by, reason, git)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6,
+             (SELECT COALESCE(MAX(version), 0) + 1 FROM secret_history
+              WHERE project = ?1 AND environment = ?2 AND key = ?3),
+             ?7, ?8, ?9, ?10)
+         ON CONFLICT (project, environment, key) DO UPDATE SET
+             encrypted_value = excluded.encrypted_value,
+             description = COALESCE(excluded.description, description),
+             updated_at = excluded.updated_at,
+             version = version + 1,
+             source = excluded.source,
+             changed_by = excluded.changed_by,
+             reason = excluded.reason,
+             git = excluded.git",
+        params![
+            project,
+            environment,
+            key,
+            encrypted_value,
+            description,
+            Utc::now().to_rfc3339(),
+            source,
+            attribution.changed_by,
+            attribution.reason,
+            attribution.git
+        ],
+    )?;
+    Ok(())
+}
+
+/// Parent an environment inherits from, if any
+fn parent_of(conn: &Connection, project: &str, environment: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT parent FROM environments WHERE project = ?1 AND name = ?2",
+            params![project, environment],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten())
+}
+
+/// The environment followed by its ancestors, nearest first
+fn lineage_of(conn: &Connection, project: &str, environment: &str) -> Result<Vec<String>> {
+    let mut chain = vec![environment.to_string()];
+    while let Some(parent) = parent_of(conn, project, chain.last().unwrap())? {
+        if chain.contains(&parent) {
+            anyhow::bail!(
+                "Environment inheritance cycle in {}: {} -> {}",
+                project,
+                chain.join(" -> "),
+                parent
+            );
+        }
+        chain.push(parent);
+    }
+    Ok(chain)
+}
+
+/// Whether the store keeps key names and descriptions encrypted
+fn hides_key_names(conn: &Connection) -> Result<bool> {
+    Ok(read_metadata(conn, "key_names")?.as_deref() == Some("encrypted"))
+}
+
+/// Read a store-level metadata value
+pub(crate) fn read_metadata(conn: &Connection, key: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT value FROM metadata WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Insert or replace a store-level metadata value
+pub(crate) fn write_metadata(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+/// Keep the bundle signing key encrypted under the master key
+fn write_signing_key(conn: &Connection, master_key: &MasterKey, seed: &SecureBytes) -> Result<()> {
+    write_metadata(
+        conn,
+        "signing_key",
+        &crypto::encrypt_bytes(seed.expose_secret(), master_key)?,
+    )
+}
+
+/// Verify the passphrase and derive the master key
+fn unlock(conn: &Connection, passphrase: &SecretString) -> Result<(MasterKey, Access)> {
+    Ok(match read_metadata(conn, "passphrase_check")? {
+        Some(check) => {
+            // Derive master key (~100ms), then verify with a constant-time HMAC compare
+            let salt = load_or_create_salt(conn)?;
+            let master_key = MasterKey::derive(passphrase, &salt)?;
+            if crypto::verify_check(&master_key, &check) {
+                (master_key, Access::Full)
+            } else {
+                let master_key = unlock_read_only(conn, passphrase, &check)?
+                    .ok_or(StoreError::InvalidPassphrase)?;
+                (master_key, Access::ReadOnly)
+            }
+        }
+        None => {
+            // Legacy store: slow age verification, then upgrade to the fast check
+            let verification = read_metadata(conn, "passphrase_verification")?
+                .context("Store appears corrupted - no passphrase verification found")?;
+            if !crypto::verify_passphrase(passphrase, &verification) {
+                return Err(StoreError::InvalidPassphrase.into());
+            }
+
+            // Legacy stores may have no salt yet - one is created here
+            let salt = load_or_create_salt(conn)?;
+            let master_key = MasterKey::derive(passphrase, &salt)?;
+            write_metadata(conn, "passphrase_check", &crypto::derive_check(&master_key))?;
+            (master_key, Access::Full)
+        }
+    })
+}
+
+/// Metadata key prefix for SSH unlocks, followed by the key's fingerprint
+const SSH_UNLOCK_PREFIX: &str = "ssh_unlock:";
+
+/// An SSH key that can unlock the store, with its copy of the master key
+#[derive(Debug, Serialize, Deserialize)]
+struct SshUnlock {
+    public_key: String,
+    /// Random bytes the agent signs (base64)
+    challenge: String,
+    wrapped_key: String,
+}
+
+fn read_ssh_unlocks(conn: &Connection) -> Result<Vec<(PublicKey, SshUnlock)>> {
+    let mut stmt = conn.prepare("SELECT value FROM metadata WHERE key GLOB ?1 ORDER BY key")?;
+    let values = stmt
+        .query_map(params![format!("{}*", SSH_UNLOCK_PREFIX)], |row| {
+            row.get::<_, String>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    values
+        .iter()
+        .map(|value| {
+            let unlock: SshUnlock =
+                serde_json::from_str(value).context("Corrupted SSH unlock entry")?;
+            Ok((PublicKey::parse(&unlock.public_key)?, unlock))
+        })
+        .collect()
+}
+
+/// Unwrap the master key with the read-only passphrase, if the store has one and
+/// `passphrase` is it
+fn unlock_read_only(
+    conn: &Connection,
+    passphrase: &SecretString,
+    check: &str,
+) -> Result<Option<MasterKey>> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+    let (Some(salt), Some(wrapped)) = (
+        read_metadata(conn, "read_only_salt")?,
+        read_metadata(conn, "read_only_key")?,
+    ) else {
+        return Ok(None);
+    };
+    let salt = BASE64
+        .decode(salt)
+        .context("Failed to decode read-only passphrase salt")?;
+    let wrapping_key = MasterKey::derive(passphrase, &salt)?;
+    Ok(MasterKey::unwrap_with(&wrapped, &wrapping_key)
+        .ok()
+        .filter(|key| crypto::verify_check(key, check)))
+}
+
+/// A random ID for a new store (32 hex digits)
+pub(crate) fn new_store_id() -> String {
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Read the key-derivation salt, generating and persisting one if missing
+pub(crate) fn load_or_create_salt(conn: &Connection) -> Result<[u8; crypto::SALT_LEN]> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+    match read_metadata(conn, "encryption_salt")? {
+        Some(salt_b64) => BASE64
+            .decode(&salt_b64)
+            .context("Failed to decode encryption salt")?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid salt length")),
+        None => {
+            let salt = MasterKey::generate_salt();
+            write_metadata(conn, "encryption_salt", &BASE64.encode(salt))?;
+            Ok(salt)
+        }
+    }
+}
+
+/// Whether a store file is fully encrypted: anything but an empty file or a
+/// plaintext SQLite header
+fn is_fully_encrypted(path: &Path) -> Result<bool> {
+    Ok(read_header(path)?.is_some_and(|header| &header != SQLITE_HEADER))
+}
+
+/// The first 16 bytes of a store file, None if it's shorter (just created)
+fn read_header(path: &Path) -> Result<Option<[u8; 16]>> {
+    use std::io::Read;
+
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut header = [0u8; 16];
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(Some(header)),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Fail unless this binary was built with SQLCipher (`--features sqlcipher`)
+fn require_sqlcipher() -> Result<()> {
+    if !cfg!(feature = "sqlcipher") {
+        anyhow::bail!(
+            "Full-database encryption needs a tinysecrets built with SQLCipher \
+             (cargo install tinysecrets --features sqlcipher)"
+        );
+    }
+    Ok(())
+}
+
+/// Key a fully encrypted database: SQLCipher derives the page key from the
+/// passphrase and the salt at the start of the file. Done before SQL tracing
+/// is turned on, since the pragma can't take the key as a bound value.
+fn apply_key(conn: &Connection, passphrase: &SecretString) -> Result<()> {
+    require_sqlcipher()?;
+    conn.pragma_update(None, "key", passphrase.expose_secret())
+        .context("Failed to set the database key")?;
+    // The key is only checked when the first page is read
+    match conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())) {
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if e.code == rusqlite::ErrorCode::NotADatabase =>
+        {
+            Err(StoreError::InvalidPassphrase.into())
+        }
+        checked => checked.context("Failed to open the encrypted database"),
+    }
+}
+
+/// Enable WAL (readers don't block writers) and wait on locks instead of
+/// failing with "database is locked" when several invocations run at once
+fn configure_connection(conn: &Connection) -> Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .context("Failed to set SQLite busy timeout")?;
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .context("Failed to enable SQLite WAL mode")?;
+    Ok(())
+}
+
+/// Map a (name, secret count, latest updated_at) row to a Summary
+fn summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<Summary> {
+    let updated: Option<String> = row.get(2)?;
+    Ok(Summary {
+        name: row.get(0)?,
+        secrets: row.get::<_, i64>(1)? as usize,
+        last_updated: updated
+            .and_then(|u| DateTime::parse_from_rfc3339(&u).ok())
+            .map(|dt| dt.with_timezone(&Utc)),
+    })
+}
+
+fn names(summaries: Vec<Summary>) -> Vec<String> {
+    summaries.into_iter().map(|summary| summary.name).collect()
+}
+
+/// Map a `secrets` row (project, environment, key, description, created_at,
+/// updated_at, version, source, changed_by, reason, git) to a SecretEntry
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<SecretEntry> {
+    let created_str: String = row.get(4)?;
+    let updated_str: String = row.get(5)?;
+    Ok(SecretEntry {
+        project: row.get(0)?,
+        environment: row.get(1)?,
+        key: row.get(2)?,
+        description: row.get(3)?,
+        created_at: DateTime::parse_from_rfc3339(&created_str)
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+        updated_at: DateTime::parse_from_rfc3339(&updated_str)
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+        version: row.get(6)?,
+        source: row.get(7)?,
+        changed_by: row.get(8)?,
+        reason: row.get(9)?,
+        git: row.get(10)?,
+    })
+}
+
+/// Detect whether a path lives inside a cloud-synced folder.
+/// Returns the name of the sync provider if so.
+pub fn synced_folder_provider(path: &Path) -> Option<&'static str> {
+    const PROVIDERS: &[(&str, &str)] = &[
+        ("dropbox", "Dropbox"),
+        ("mobile documents", "iCloud Drive"),
+        ("icloud drive", "iCloud Drive"),
+        ("iclouddrive", "iCloud Drive"),
+        ("onedrive", "OneDrive"),
+        ("google drive", "Google Drive"),
+        ("googledrive", "Google Drive"),
+        ("my drive", "Google Drive"),
+    ];
+
+    path.components().find_map(|component| {
+        let name = component.as_os_str().to_string_lossy().to_lowercase();
+        PROVIDERS
+            .iter()
+            .find(|(pattern, _)| name.starts_with(pattern))
+            .map(|(_, provider)| *provider)
+    })
+}
+
 /// Export bundle format
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExportBundle {
     pub version: i32,
+    /// Version of tinysecrets that produced the bundle (absent in older bundles)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tinysecrets_version: Option<String>,
+    /// Project/environment of a single-environment bundle (empty in multi-section bundles)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub project: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub environment: String,
-    pub passphrase_verification: String,
+    /// Legacy (v1) age-based verification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase_verification: Option<String>,
+    /// Salt for deriving the key the values are encrypted with (v2)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf_salt: Option<String>,
+    /// HMAC passphrase check for the derived key (v2)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase_check: Option<String>,
     pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub secrets: Vec<ExportedSecret>,
+    /// One section per project/environment in multi-section bundles (v3)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<BundleSection>,
+    /// HMAC over the rest of the bundle, keyed like the values (absent in older bundles)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    /// Ed25519 signature by the exporting store (`export --sign`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<BundleSignature>,
+}
+
+/// Who signed a bundle, and their signature over everything else in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSignature {
+    /// The signing store's public key, `ed25519:<base64>`
+    pub signer: String,
+    pub signature: String,
+}
+
+impl ExportBundle {
+    /// The bytes the integrity tag covers: every field but the tag and the
+    /// signature, as JSON with sorted keys and no whitespace
+    pub fn canonical_bytes(&self) -> Result<Vec<u8>> {
+        self.bytes_without(&["integrity", "signature"])
+    }
+
+    /// The bytes the signature covers: everything but the signature itself,
+    /// integrity tag included
+    pub fn signed_bytes(&self) -> Result<Vec<u8>> {
+        self.bytes_without(&["signature"])
+    }
+
+    fn bytes_without(&self, fields: &[&str]) -> Result<Vec<u8>> {
+        let mut value = serde_json::to_value(self)?;
+        if let serde_json::Value::Object(map) = &mut value {
+            for field in fields {
+                map.remove(*field);
+            }
+        }
+        Ok(serde_json::to_vec(&value)?)
+    }
+
+    /// Check the bundle was signed by `signer` (`ed25519:<base64>`) and not
+    /// changed since
+    pub fn verify_signer(&self, signer: &str) -> Result<()> {
+        let key = crypto::parse_signer(signer)?;
+        let Some(signature) = &self.signature else {
+            anyhow::bail!("Bundle isn't signed (the sender can sign it with `export --sign`)");
+        };
+        if crypto::parse_signer(&signature.signer).ok() != Some(key) {
+            anyhow::bail!(
+                "Bundle was signed by {}, not {}",
+                signature.signer,
+                signer.trim()
+            );
+        }
+        if !crypto::verify_signature(&key, &self.signed_bytes()?, &signature.signature) {
+            anyhow::bail!("Bundle signature doesn't match: it was modified after signing");
+        }
+        Ok(())
+    }
+
+    /// The bundle's project/environments, whether it's a single-environment
+    /// bundle or a multi-section one
+    pub fn sections(&self) -> Vec<Section<'_>> {
+        if self.sections.is_empty() {
+            return vec![Section {
+                project: &self.project,
+                environment: &self.environment,
+                secrets: &self.secrets,
+            }];
+        }
+        self.sections
+            .iter()
+            .map(|s| Section {
+                project: &s.project,
+                environment: &s.environment,
+                secrets: &s.secrets,
+            })
+            .collect()
+    }
+}
+
+/// Where one bundle section is imported to, and whether it overwrites existing keys
+#[derive(Debug, Clone)]
+pub struct ImportTarget {
+    pub project: String,
+    pub environment: String,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleSection {
+    pub project: String,
+    pub environment: String,
     pub secrets: Vec<ExportedSecret>,
 }
 
+/// A borrowed view of one project/environment in a bundle
+#[derive(Debug, Clone, Copy)]
+pub struct Section<'a> {
+    pub project: &'a str,
+    pub environment: &'a str,
+    pub secrets: &'a [ExportedSecret],
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExportedSecret {
     pub key: String,
     pub encrypted_value: String,
     pub description: Option<String>,
     pub version: i32,
+    /// Where the secret was originally imported from (absent if set by hand)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import_target(environment: &str) -> Vec<ImportTarget> {
+        vec![ImportTarget {
+            project: "app".to_string(),
+            environment: environment.to_string(),
+            overwrite: true,
+        }]
+    }
+
+    #[test]
+    fn test_import_refuses_untagged_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let passphrase = SecretString::new("correct horse battery staple".to_string());
+        let store = Store::create(&dir.path().join("store.db"), passphrase, false).unwrap();
+        for key in ["A", "B", "C"] {
+            store
+                .set_bytes("app", "dev", key, b"value", None, None)
+                .unwrap();
+        }
+        let mut bundle = store.export("app", "dev", &KeyFilter::default()).unwrap();
+        let imported = store
+            .import(&bundle, "test", &import_target("dev2"), false)
+            .unwrap();
+        assert_eq!(imported[0].written, 3);
+
+        // Stripping the tag is caught even when asked to allow untagged bundles
+        bundle.integrity = None;
+        bundle.secrets.truncate(1);
+        for allow_untagged in [false, true] {
+            let err = store
+                .import(&bundle, "test", &import_target("dev3"), allow_untagged)
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("integrity tag is missing"),
+                "{}",
+                err
+            );
+        }
+        assert!(store.entry("app", "dev3", "A").unwrap().is_none());
+
+        // A legacy bundle needs the explicit opt-in
+        bundle.version = compat::TAGGED_BUNDLE_FORMAT_VERSION - 1;
+        assert!(store
+            .import(&bundle, "test", &import_target("dev3"), false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_synced_folder_detection() {
+        assert_eq!(
+            synced_folder_provider(Path::new("/Users/me/Dropbox/secrets/store.db")),
+            Some("Dropbox")
+        );
+        assert_eq!(
+            synced_folder_provider(Path::new(
+                "/Users/me/Library/Mobile Documents/com~apple~CloudDocs/store.db"
+            )),
+            Some("iCloud Drive")
+        );
+        assert_eq!(
+            synced_folder_provider(Path::new(
+                "/Users/me/Library/CloudStorage/OneDrive-Acme/store.db"
+            )),
+            Some("OneDrive")
+        );
+        assert_eq!(
+            synced_folder_provider(Path::new("/home/me/.tinysecrets/store.db")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_full_encryption_detection() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let plain = dir.path().join("plain.db");
+        let conn = Connection::open(&plain).unwrap();
+        conn.execute_batch("CREATE TABLE t (x)").unwrap();
+        drop(conn);
+        assert!(!is_fully_encrypted(&plain).unwrap());
+
+        let empty = dir.path().join("empty.db");
+        std::fs::write(&empty, b"").unwrap();
+        assert!(!is_fully_encrypted(&empty).unwrap());
+
+        let encrypted = dir.path().join("encrypted.db");
+        std::fs::write(&encrypted, [0x5au8; 64]).unwrap();
+        assert!(is_fully_encrypted(&encrypted).unwrap());
+    }
 }
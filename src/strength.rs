@@ -0,0 +1,441 @@
+//! Offline passphrase strength estimation
+//!
+//! A small take on zxcvbn: find the cheapest way to "spell" the passphrase out of
+//! guessable pieces (common words and passwords, repeats, sequences like `abc`/`123`,
+//! keyboard runs like `qwerty`) and brute-forced characters, and report the total as
+//! bits of entropy. Nothing leaves the machine.
+
+use serde::{Deserialize, Serialize};
+
+/// Most common passwords and passphrase words, most common first
+const COMMON_WORDS: &[&str] = &[
+    "password",
+    "123456",
+    "qwerty",
+    "letmein",
+    "welcome",
+    "admin",
+    "login",
+    "secret",
+    "iloveyou",
+    "monkey",
+    "dragon",
+    "master",
+    "sunshine",
+    "princess",
+    "football",
+    "baseball",
+    "shadow",
+    "superman",
+    "trustno1",
+    "changeme",
+    "default",
+    "passphrase",
+    "passw",
+    "pass",
+    "test",
+    "guest",
+    "root",
+    "user",
+    "hello",
+    "love",
+    "god",
+    "abc",
+    "access",
+    "freedom",
+    "whatever",
+    "starwars",
+    "computer",
+    "michael",
+    "jordan",
+    "charlie",
+    "summer",
+    "winter",
+    "spring",
+    "autumn",
+    "flower",
+    "tinysecrets",
+    "secrets",
+    "token",
+    "key",
+    "safe",
+    "mypass",
+    "temp",
+    "demo",
+    "sample",
+    "office",
+];
+
+/// Keyboard rows for detecting runs like `qwerty` or `asdf`
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// What kind of guessable piece a passphrase contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weakness {
+    CommonWord,
+    Repeat,
+    Sequence,
+    Keyboard,
+    Year,
+}
+
+impl Weakness {
+    pub fn advice(self) -> &'static str {
+        match self {
+            Weakness::CommonWord => "contains a common password or word",
+            Weakness::Repeat => "repeated characters like 'aaa' are easy to guess",
+            Weakness::Sequence => "sequences like 'abc' or '123' are easy to guess",
+            Weakness::Keyboard => "keyboard runs like 'qwerty' are easy to guess",
+            Weakness::Year => "years like '2024' are easy to guess",
+        }
+    }
+}
+
+/// Estimated strength of a passphrase
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strength {
+    /// Estimated entropy in bits
+    pub bits: f64,
+    /// Guessable pieces found, in order of appearance
+    pub weaknesses: Vec<Weakness>,
+}
+
+impl Strength {
+    /// zxcvbn-style score from 0 (very weak) to 4 (strong)
+    pub fn score(&self) -> u8 {
+        match self.bits {
+            b if b < 20.0 => 0,
+            b if b < 30.0 => 1,
+            b if b < 45.0 => 2,
+            b if b < 60.0 => 3,
+            _ => 4,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        ["very weak", "weak", "fair", "good", "strong"][self.score() as usize]
+    }
+}
+
+/// Minimum passphrase strength for new stores (`[passphrase_policy]` in the global config)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassphrasePolicy {
+    /// Minimum length in characters
+    #[serde(default = "default_min_length")]
+    pub min_length: usize,
+    /// Minimum estimated entropy in bits
+    #[serde(default = "default_min_entropy")]
+    pub min_entropy: f64,
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> Self {
+        Self {
+            min_length: default_min_length(),
+            min_entropy: default_min_entropy(),
+        }
+    }
+}
+
+fn default_min_length() -> usize {
+    8
+}
+
+fn default_min_entropy() -> f64 {
+    35.0
+}
+
+impl PassphrasePolicy {
+    /// Estimate the passphrase's strength, failing if it's below the policy
+    pub fn check(&self, passphrase: &str) -> anyhow::Result<Strength> {
+        let length = passphrase.chars().count();
+        if length < self.min_length {
+            anyhow::bail!("Passphrase must be at least {} characters", self.min_length);
+        }
+        let strength = estimate(passphrase);
+        if strength.bits < self.min_entropy {
+            let reason = strength
+                .weaknesses
+                .first()
+                .map(|w| format!(": {}", w.advice()))
+                .unwrap_or_default();
+            anyhow::bail!(
+                "Passphrase is too weak (about {:.0} bits, at least {:.0} needed){}. Try a few unrelated words",
+                strength.bits,
+                self.min_entropy,
+                reason
+            );
+        }
+        Ok(strength)
+    }
+}
+
+/// A guessable piece covering `chars[start..end]`
+struct Piece {
+    start: usize,
+    end: usize,
+    bits: f64,
+    kind: Weakness,
+}
+
+/// Estimate a passphrase's entropy from its cheapest decomposition into pieces
+pub fn estimate(passphrase: &str) -> Strength {
+    let chars: Vec<char> = passphrase.chars().collect();
+    let char_bits = pool_size(&chars).log2();
+
+    let mut pieces = Vec::new();
+    dictionary_pieces(&chars, &mut pieces);
+    repeat_pieces(&chars, char_bits, &mut pieces);
+    sequence_pieces(&chars, &mut pieces);
+    keyboard_pieces(&chars, &mut pieces);
+    year_pieces(&chars, &mut pieces);
+
+    // best[i]: cheapest bits for chars[..i], with the piece that ends the path there
+    let mut best: Vec<(f64, Option<usize>)> = vec![(0.0, None); chars.len() + 1];
+    for end in 1..=chars.len() {
+        best[end] = (best[end - 1].0 + char_bits, None);
+        for (index, piece) in pieces.iter().enumerate().filter(|(_, p)| p.end == end) {
+            let bits = best[piece.start].0 + piece.bits;
+            if bits < best[end].0 {
+                best[end] = (bits, Some(index));
+            }
+        }
+    }
+
+    let mut weaknesses = Vec::new();
+    let mut end = chars.len();
+    while end > 0 {
+        match best[end].1 {
+            Some(index) => {
+                weaknesses.push(pieces[index].kind);
+                end = pieces[index].start;
+            }
+            None => end -= 1,
+        }
+    }
+    weaknesses.reverse();
+    weaknesses.dedup();
+
+    Strength {
+        bits: best[chars.len()].0,
+        weaknesses,
+    }
+}
+
+/// Size of the character set an attacker would brute-force
+fn pool_size(chars: &[char]) -> f64 {
+    let mut pool = 0.0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        pool += 26.0;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        pool += 26.0;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        pool += 10.0;
+    }
+    if chars
+        .iter()
+        .any(|c| c.is_ascii() && !c.is_ascii_alphanumeric())
+    {
+        pool += 33.0;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        pool += 100.0;
+    }
+    f64::max(pool, 2.0)
+}
+
+/// Undo common letter substitutions (`p@ssw0rd` -> `password`)
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        c => c.to_ascii_lowercase(),
+    }
+}
+
+fn dictionary_pieces(chars: &[char], pieces: &mut Vec<Piece>) {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let unleeted: Vec<char> = chars.iter().map(|c| unleet(*c)).collect();
+    for (rank, word) in COMMON_WORDS.iter().enumerate() {
+        let word: Vec<char> = word.chars().collect();
+        for start in 0..chars.len().saturating_sub(word.len() - 1) {
+            let end = start + word.len();
+            let plain = lower[start..end] == word[..];
+            if !plain && unleeted[start..end] != word[..] {
+                continue;
+            }
+            let mut bits = ((rank + 1) as f64).log2().max(1.0);
+            if chars[start..end].iter().any(|c| c.is_ascii_uppercase()) {
+                bits += 1.0;
+            }
+            if !plain {
+                bits += 1.0;
+            }
+            pieces.push(Piece {
+                start,
+                end,
+                bits,
+                kind: Weakness::CommonWord,
+            });
+        }
+    }
+}
+
+fn repeat_pieces(chars: &[char], char_bits: f64, pieces: &mut Vec<Piece>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let end = start
+            + chars[start..]
+                .iter()
+                .take_while(|&&c| c == chars[start])
+                .count();
+        if end - start >= 3 {
+            pieces.push(Piece {
+                start,
+                end,
+                bits: char_bits + ((end - start) as f64).log2(),
+                kind: Weakness::Repeat,
+            });
+        }
+        start = end;
+    }
+}
+
+fn sequence_pieces(chars: &[char], pieces: &mut Vec<Piece>) {
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let step = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 1;
+        if step == 1 || step == -1 {
+            while end < chars.len()
+                && chars[end] as i64 - chars[end - 1] as i64 == step
+                && chars[end].is_ascii_alphanumeric()
+            {
+                end += 1;
+            }
+        }
+        if end - start >= 3 && chars[start].is_ascii_alphanumeric() {
+            let alphabet: f64 = if chars[start].is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            pieces.push(Piece {
+                start,
+                end,
+                bits: alphabet.log2() + ((end - start) as f64).log2() + 1.0,
+                kind: Weakness::Sequence,
+            });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+/// Years from 1900 to 2099, a favourite suffix
+fn year_pieces(chars: &[char], pieces: &mut Vec<Piece>) {
+    for (start, window) in chars.windows(4).enumerate() {
+        let year: String = window.iter().collect();
+        if year.chars().all(|c| c.is_ascii_digit())
+            && (year.starts_with("19") || year.starts_with("20"))
+        {
+            pieces.push(Piece {
+                start,
+                end: start + 4,
+                bits: 200f64.log2(),
+                kind: Weakness::Year,
+            });
+        }
+    }
+}
+
+fn keyboard_pieces(chars: &[char], pieces: &mut Vec<Piece>) {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_ascii_lowercase()).collect();
+    for row in KEYBOARD_ROWS {
+        let forward: Vec<char> = row.chars().collect();
+        let backward: Vec<char> = row.chars().rev().collect();
+        for keys in [&forward, &backward] {
+            for start in 0..lower.len() {
+                let Some(offset) = keys.iter().position(|&k| k == lower[start]) else {
+                    continue;
+                };
+                let length = lower[start..]
+                    .iter()
+                    .zip(&keys[offset..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length >= 4 {
+                    pieces.push(Piece {
+                        start,
+                        end: start + length,
+                        bits: ((KEYBOARD_ROWS.len() * 10 * 2) as f64).log2()
+                            + (length as f64).log2(),
+                        kind: Weakness::Keyboard,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_passphrases() {
+        for weak in [
+            "password",
+            "P@ssw0rd1",
+            "testpass123",
+            "qwertyuiop",
+            "aaaaaaaaaaaa",
+        ] {
+            let strength = estimate(weak);
+            assert!(strength.bits < 35.0, "{} scored {}", weak, strength.bits);
+            assert!(!strength.weaknesses.is_empty());
+        }
+        assert_eq!(estimate("abcdefgh").weaknesses, vec![Weakness::Sequence]);
+        assert_eq!(estimate("zxcvbnm!").weaknesses, vec![Weakness::Keyboard]);
+        assert_eq!(
+            estimate("Summer2024!").weaknesses,
+            vec![Weakness::CommonWord, Weakness::Year]
+        );
+        assert!(estimate("Summer2024!").bits < 35.0);
+    }
+
+    #[test]
+    fn test_strong_passphrases() {
+        for strong in [
+            "correct horse battery staple",
+            "vT9#qL2m!xR4",
+            "plinth-otter-cobalt-ember",
+        ] {
+            let strength = estimate(strong);
+            assert!(strength.bits >= 60.0, "{} scored {}", strong, strength.bits);
+            assert_eq!(strength.score(), 4);
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = PassphrasePolicy::default();
+        assert!(policy.check("short").is_err());
+        let err = policy.check("password123").unwrap_err().to_string();
+        assert!(err.contains("common password"), "{}", err);
+        assert!(policy.check("plinth-otter-cobalt").is_ok());
+
+        let strict = PassphrasePolicy {
+            min_length: 20,
+            min_entropy: 35.0,
+        };
+        assert!(strict.check("plinth-otter-cobalt").is_err());
+    }
+}
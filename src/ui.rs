@@ -0,0 +1,141 @@
+//! Output symbols with an accessible plain-text mode
+//!
+//! With `[ui] accessible = true` in the global config (or TINYSECRETS_ACCESSIBLE=1),
+//! emoji, box-drawing characters, colors, and progress animations are replaced
+//! with descriptive text that reads well in screen readers and braille terminals.
+
+use colored::{ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+const ACCESSIBLE_ENV_VAR: &str = "TINYSECRETS_ACCESSIBLE";
+
+static ACCESSIBLE: OnceLock<bool> = OnceLock::new();
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `[ui]` section of the global config
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UiConfig {
+    /// Plain-text output for screen readers
+    #[serde(default)]
+    pub accessible: bool,
+}
+
+/// Resolve accessible mode once and disable colors if it's on
+pub fn init() {
+    if accessible() {
+        colored::control::set_override(false);
+    }
+}
+
+/// True if accessible (plain-text) output is enabled
+pub fn accessible() -> bool {
+    *ACCESSIBLE.get_or_init(|| {
+        if let Ok(value) = std::env::var(ACCESSIBLE_ENV_VAR) {
+            return matches!(value.as_str(), "1" | "true" | "yes");
+        }
+        if let Some(ui) = crate::config::active_profile().and_then(|(_, p)| p.ui.as_ref()) {
+            return ui.accessible;
+        }
+        crate::config::GlobalConfig::load()
+            .map(|c| c.ui.accessible)
+            .unwrap_or(false)
+    })
+}
+
+/// `-q`: drop status messages and warnings, leaving errors, prompts and values
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// True if status messages on stderr are suppressed (see `eprintln!` in main.rs)
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+fn pick(fancy: &'static str, plain: &'static str) -> &'static str {
+    if accessible() {
+        plain
+    } else {
+        fancy
+    }
+}
+
+/// Success marker
+pub fn ok() -> ColoredString {
+    pick("✓", "OK:").green()
+}
+
+/// Failure marker
+pub fn fail() -> ColoredString {
+    pick("✗", "Error:").red()
+}
+
+/// Warning marker
+pub fn warn() -> ColoredString {
+    pick("⚠", "Warning:").yellow()
+}
+
+/// Neutral "nothing here" marker
+pub fn note() -> ColoredString {
+    pick("○", "Note:").yellow()
+}
+
+/// Informational marker
+pub fn info() -> ColoredString {
+    pick("ℹ", "Info:").blue()
+}
+
+/// Step/next marker
+pub fn arrow() -> ColoredString {
+    pick("→", "-").cyan()
+}
+
+/// List bullet
+pub fn bullet() -> ColoredString {
+    pick("•", "-").dimmed()
+}
+
+/// Tree branch (environment under a project)
+pub fn branch() -> ColoredString {
+    pick("└", "environment").dimmed()
+}
+
+/// Decorative emoji prefix (include the trailing space), or a plain-text label
+pub fn icon(emoji: &'static str, label: &'static str) -> &'static str {
+    pick(emoji, label)
+}
+
+/// Redraw a `done/total` progress bar in place on stderr (nothing if stderr isn't
+/// a terminal). Accessible mode gets a plain line at each tenth instead of redraws.
+pub fn progress(label: &str, done: usize, total: usize) {
+    use std::io::IsTerminal;
+
+    if total == 0 || quiet() || !std::io::stderr().is_terminal() {
+        return;
+    }
+    if accessible() {
+        let step = total.div_ceil(10);
+        if done == total || done.is_multiple_of(step) {
+            eprintln!("{} {} of {}", label, done, total);
+        }
+        return;
+    }
+
+    const WIDTH: usize = 30;
+    let filled = WIDTH * done / total;
+    eprint!(
+        "\r{} [{}{}] {}/{}",
+        label,
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        done,
+        total
+    );
+    if done == total {
+        // Clear the bar so the next line starts clean
+        eprint!("\r{}\r", " ".repeat(label.len() + WIDTH + 24));
+    }
+}
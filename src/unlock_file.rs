@@ -0,0 +1,317 @@
+//! Headless stand-in for the keychain: `~/.tinysecrets/unlock.key`
+//!
+//! Servers without a Secret Service daemon have no keychain, so `keyring`
+//! errors out. With `[keychain] backend = "file"` the keychain entries go in
+//! this file instead, encrypted with a key derived from the machine ID and the
+//! user (a copy taken off the box is useless) or from a second passphrase.
+//! The file is created 0600 and not read if other users can read it.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+use crate::crypto::{self, MasterKey};
+use crate::permissions::{self, Exposure};
+
+/// Second passphrase for a file wrapped with `--wrap passphrase`, for when
+/// there's no terminal to ask on
+pub const PASSPHRASE_ENV_VAR: &str = "TINYSECRETS_UNLOCK_PASSPHRASE";
+
+/// Where the machine ID is kept (systemd, then older D-Bus installs)
+const MACHINE_ID_PATHS: [&str; 2] = ["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+/// What the entries are encrypted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Wrap {
+    /// The machine ID and user ID: unlocks without asking, only on this box
+    #[default]
+    Machine,
+    /// A second passphrase, asked for (or read from TINYSECRETS_UNLOCK_PASSPHRASE)
+    Passphrase,
+}
+
+impl Wrap {
+    pub fn is_machine(&self) -> bool {
+        *self == Wrap::Machine
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Wrap::Machine => "this machine's ID",
+            Wrap::Passphrase => "a separate passphrase",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UnlockFile {
+    wrap: Wrap,
+    /// Base64 scrypt salt for the wrapping key
+    salt: String,
+    /// Keychain account -> encrypted entry
+    #[serde(default)]
+    entries: BTreeMap<String, String>,
+}
+
+/// The wrapping key for a salt, kept so a passphrase is asked for once per run
+static KEY: Mutex<Option<(String, MasterKey)>> = Mutex::new(None);
+
+pub fn path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Could not find home directory")?;
+    Ok(home.join(".tinysecrets").join("unlock.key"))
+}
+
+/// How the file is wrapped, or None if there isn't one
+pub fn wrap() -> Result<Option<Wrap>> {
+    Ok(load()?.map(|file| file.wrap))
+}
+
+/// Decrypt `account`'s entry
+pub fn read(account: &str) -> Result<Option<Zeroizing<String>>> {
+    let Some(file) = load()? else {
+        return Ok(None);
+    };
+    let Some(entry) = file.entries.get(account) else {
+        return Ok(None);
+    };
+    let key = key(file.wrap, &file.salt, false)?;
+    let value = crypto::decrypt(entry, &key, None).with_context(|| {
+        format!(
+            "Failed to decrypt {} ({})",
+            path().map(|p| p.display().to_string()).unwrap_or_default(),
+            match file.wrap {
+                Wrap::Machine => "was it copied from another machine?",
+                Wrap::Passphrase => "wrong passphrase?",
+            }
+        )
+    })?;
+    Ok(Some(Zeroizing::new(value.expose_secret().clone())))
+}
+
+/// Whether `account` has an entry, without decrypting it
+pub fn exists(account: &str) -> Result<bool> {
+    Ok(load()?.is_some_and(|file| file.entries.contains_key(account)))
+}
+
+/// Encrypt and save `account`'s entry, creating the file wrapped with `wrap`.
+/// An existing file keeps its wrapping (see `rewrap`).
+pub fn write(account: &str, value: &str, wrap: Wrap) -> Result<()> {
+    let mut file = match load()? {
+        Some(file) => file,
+        None => UnlockFile {
+            wrap,
+            salt: BASE64.encode(MasterKey::generate_salt()),
+            entries: BTreeMap::new(),
+        },
+    };
+    let key = key(file.wrap, &file.salt, file.entries.is_empty())?;
+    // Don't add an entry under a mistyped passphrase the others can't share
+    if let Some(other) = file.entries.values().next() {
+        crypto::decrypt(other, &key, None).context("Wrong unlock file passphrase")?;
+    }
+    file.entries
+        .insert(account.to_string(), crypto::encrypt(value, &key)?);
+    save(&file)
+}
+
+/// Remove `account`'s entry, and the file once it's empty
+pub fn delete(account: &str) -> Result<bool> {
+    let Some(mut file) = load()? else {
+        return Ok(false);
+    };
+    if file.entries.remove(account).is_none() {
+        return Ok(false);
+    }
+    if file.entries.is_empty() {
+        let path = path()?;
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+        return Ok(true);
+    }
+    save(&file)?;
+    Ok(true)
+}
+
+/// Re-encrypt every entry with a new wrapping. Returns false if there's no
+/// file or it's already wrapped that way.
+pub fn rewrap(wrap: Wrap) -> Result<bool> {
+    let Some(file) = load()? else {
+        return Ok(false);
+    };
+    if file.wrap == wrap {
+        return Ok(false);
+    }
+    let old_key = key(file.wrap, &file.salt, false)?;
+    let salt = BASE64.encode(MasterKey::generate_salt());
+    let new_key = key(wrap, &salt, true)?;
+    let mut entries = BTreeMap::new();
+    for (account, entry) in &file.entries {
+        let value = crypto::decrypt(entry, &old_key, None)?;
+        entries.insert(
+            account.clone(),
+            crypto::encrypt(value.expose_secret(), &new_key)?,
+        );
+    }
+    save(&UnlockFile {
+        wrap,
+        salt,
+        entries,
+    })?;
+    Ok(true)
+}
+
+fn load() -> Result<Option<UnlockFile>> {
+    let path = path()?;
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    use std::os::unix::fs::PermissionsExt;
+    if permissions::exposure(metadata.permissions().mode(), false) != Exposure::Private {
+        anyhow::bail!(
+            "{} is readable by other users; fix with `chmod 600 {}`",
+            path.display(),
+            path.display()
+        );
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let file = toml::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))?;
+    Ok(Some(file))
+}
+
+fn save(file: &UnlockFile) -> Result<()> {
+    let path = path()?;
+    if let Some(dir) = path.parent() {
+        permissions::create_private_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let contents = format!(
+        "# tinysecrets keychain entries, encrypted with {}\n{}",
+        file.wrap.describe(),
+        toml::to_string(file)?
+    );
+    permissions::create_private_file(&path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Derive (or reuse) the key for a wrapping and salt. A `new` passphrase is
+/// asked for twice.
+fn key(wrap: Wrap, salt: &str, new: bool) -> Result<MasterKey> {
+    let mut cached = KEY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_salt, key)) = cached.as_ref() {
+        if cached_salt == salt {
+            return Ok(key.clone());
+        }
+    }
+    let secret = match wrap {
+        Wrap::Machine => machine_secret()?,
+        Wrap::Passphrase => unlock_passphrase(new)?,
+    };
+    let salt_bytes = BASE64.decode(salt).context("Invalid salt in unlock file")?;
+    let key = MasterKey::derive(&secret, &salt_bytes)?;
+    *cached = Some((salt.to_string(), key.clone()));
+    Ok(key)
+}
+
+/// The machine ID and the user ID
+fn machine_secret() -> Result<SecretString> {
+    let id = MACHINE_ID_PATHS
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| {
+            anyhow::anyhow!("No machine ID found (/etc/machine-id); use `--wrap passphrase`")
+        })?;
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    Ok(SecretString::new(format!("{}:{}", id, uid)))
+}
+
+fn unlock_passphrase(new: bool) -> Result<SecretString> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+        return Ok(SecretString::new(passphrase));
+    }
+    if !std::io::stderr().is_terminal() {
+        anyhow::bail!(
+            "The unlock file needs its passphrase: set {} or run in a terminal",
+            PASSPHRASE_ENV_VAR
+        );
+    }
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Unlock file passphrase: ")?);
+    if new {
+        if passphrase.is_empty() {
+            anyhow::bail!("The unlock file passphrase can't be empty");
+        }
+        let confirm = Zeroizing::new(rpassword::prompt_password("Confirm: ")?);
+        if *confirm != *passphrase {
+            anyhow::bail!("Passphrases don't match");
+        }
+    }
+    Ok(SecretString::new(passphrase.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn use_passphrase(passphrase: &str) {
+        std::env::set_var(PASSPHRASE_ENV_VAR, passphrase);
+        *KEY.lock().unwrap() = None;
+    }
+
+    // One test, since HOME and the cached key are process-wide
+    #[test]
+    fn test_unlock_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = tempfile::tempdir().unwrap();
+        std::env::set_var("HOME", home.path());
+        use_passphrase("unlock passphrase");
+
+        write("default", "store passphrase", Wrap::Passphrase).unwrap();
+        write("work", "work passphrase", Wrap::Machine).unwrap();
+        assert_eq!(wrap().unwrap(), Some(Wrap::Passphrase));
+        assert_eq!(
+            read("default").unwrap().unwrap().as_str(),
+            "store passphrase"
+        );
+        assert!(exists("work").unwrap());
+        assert!(read("other").unwrap().is_none());
+        let mode = std::fs::metadata(path().unwrap())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A mistyped passphrase can't add an entry the others don't share
+        use_passphrase("mistyped");
+        let e = write("other", "value", Wrap::Passphrase).unwrap_err();
+        assert!(format!("{:#}", e).contains("Wrong unlock file passphrase"));
+        assert!(!exists("other").unwrap());
+
+        use_passphrase("unlock passphrase");
+        assert!(!rewrap(Wrap::Passphrase).unwrap());
+        if machine_secret().is_ok() {
+            assert!(rewrap(Wrap::Machine).unwrap());
+            std::env::remove_var(PASSPHRASE_ENV_VAR);
+            *KEY.lock().unwrap() = None;
+            assert_eq!(wrap().unwrap(), Some(Wrap::Machine));
+            assert_eq!(read("work").unwrap().unwrap().as_str(), "work passphrase");
+        }
+
+        std::fs::set_permissions(path().unwrap(), std::fs::Permissions::from_mode(0o640)).unwrap();
+        let e = read("default").unwrap_err();
+        assert!(e.to_string().contains("readable by other users"));
+    }
+}
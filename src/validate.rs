@@ -0,0 +1,230 @@
+//! Value rules from `[validate]` in .tinysecrets.toml
+//!
+//! Rules are keyed by secret name or glob (`"*_URL"`), and every matching rule
+//! applies. `set` and `import-env` check new values against them and `check`
+//! checks stored ones, so a credential pasted under the wrong key is caught.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::lint::LintAction;
+use crate::pattern::{full_match_regex, glob_match};
+
+/// Scheme, `://`, then a host
+const URL_PATTERN: &str = r"[A-Za-z][A-Za-z0-9+.-]*://[^\s/?#]+\S*";
+
+/// A `[validate]` entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    /// Regex the whole value must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ValueFormat>,
+    /// Minimum length in characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// Maximum length in characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// The only values allowed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub one_of: Vec<String>,
+    /// `deny` (the default) rejects a value that breaks the rule, `warn` only says so
+    #[serde(default = "default_deny")]
+    pub on_mismatch: LintAction,
+}
+
+fn default_deny() -> LintAction {
+    LintAction::Deny
+}
+
+/// Shapes a value can be required to have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueFormat {
+    /// `scheme://host...`
+    Url,
+    /// Standard or URL-safe alphabet, padded or not
+    Base64,
+    Hex,
+    Json,
+    Integer,
+}
+
+impl ValueFormat {
+    fn name(self) -> &'static str {
+        match self {
+            ValueFormat::Url => "a URL",
+            ValueFormat::Base64 => "base64",
+            ValueFormat::Hex => "hex",
+            ValueFormat::Json => "JSON",
+            ValueFormat::Integer => "an integer",
+        }
+    }
+
+    fn matches(self, value: &str) -> bool {
+        use base64::engine::general_purpose::{
+            STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD,
+        };
+
+        match self {
+            ValueFormat::Url => full_match_regex(URL_PATTERN)
+                .expect("URL pattern compiles")
+                .is_match(value),
+            ValueFormat::Base64 => [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+                .iter()
+                .any(|engine| engine.decode(value.trim()).is_ok()),
+            ValueFormat::Hex => {
+                !value.is_empty()
+                    && value.len().is_multiple_of(2)
+                    && value.chars().all(|c| c.is_ascii_hexdigit())
+            }
+            ValueFormat::Json => serde_json::from_str::<serde_json::Value>(value).is_ok(),
+            ValueFormat::Integer => value
+                .strip_prefix(['-', '+'])
+                .unwrap_or(value)
+                .parse::<u128>()
+                .is_ok(),
+        }
+    }
+}
+
+impl Rule {
+    /// What's wrong with `value`, one message per broken constraint
+    fn problems(&self, value: &str) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        let length = value.chars().count();
+        if let Some(min) = self.min_length.filter(|&min| length < min) {
+            problems.push(format!(
+                "is {} characters, at least {} expected",
+                length, min
+            ));
+        }
+        if let Some(max) = self.max_length.filter(|&max| length > max) {
+            problems.push(format!("is {} characters, at most {} allowed", length, max));
+        }
+        if let Some(format) = self.format.filter(|f| !f.matches(value)) {
+            problems.push(format!("isn't {}", format.name()));
+        }
+        if let Some(pattern) = &self.pattern {
+            let regex = full_match_regex(pattern)
+                .with_context(|| format!("Invalid [validate] pattern '{}'", pattern))?;
+            if !regex.is_match(value) {
+                problems.push(format!("doesn't match /{}/", pattern));
+            }
+        }
+        if !self.one_of.is_empty() && !self.one_of.iter().any(|allowed| allowed == value) {
+            problems.push(format!("isn't one of {}", self.one_of.join(", ")));
+        }
+        Ok(problems)
+    }
+}
+
+/// A rule a value breaks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub action: LintAction,
+    pub message: String,
+}
+
+/// Check `key`'s value against every rule whose name or glob matches it
+pub fn check(rules: &BTreeMap<String, Rule>, key: &str, value: &[u8]) -> Result<Vec<Violation>> {
+    let mut violations = Vec::new();
+    for rule in rules
+        .iter()
+        .filter(|(pattern, rule)| rule.on_mismatch != LintAction::Off && glob_match(pattern, key))
+        .map(|(_, rule)| rule)
+    {
+        let problems = match std::str::from_utf8(value) {
+            Ok(value) => rule.problems(value)?,
+            Err(_) => vec!["isn't text".to_string()],
+        };
+        violations.extend(problems.into_iter().map(|problem| Violation {
+            action: rule.on_mismatch,
+            message: format!("{} {}", key, problem),
+        }));
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> BTreeMap<String, Rule> {
+        #[derive(Deserialize)]
+        struct File {
+            validate: BTreeMap<String, Rule>,
+        }
+        toml::from_str::<File>(toml).unwrap().validate
+    }
+
+    fn messages(rules: &BTreeMap<String, Rule>, key: &str, value: &str) -> Vec<String> {
+        check(rules, key, value.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|v| v.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = rules(
+            r#"
+            [validate]
+            STRIPE_KEY = { pattern = "sk_(live|test)_[A-Za-z0-9]{8,}" }
+            "*_URL" = { format = "url" }
+            JWT_SECRET = { min_length = 32, on_mismatch = "warn" }
+            LOG_LEVEL = { one_of = ["debug", "info", "warn"] }
+            PORT = { format = "integer", max_length = 5 }
+            "#,
+        );
+
+        assert!(messages(&rules, "STRIPE_KEY", "sk_live_abcdefgh12").is_empty());
+        assert_eq!(
+            messages(&rules, "STRIPE_KEY", "pk_live_abcdefgh12"),
+            ["STRIPE_KEY doesn't match /sk_(live|test)_[A-Za-z0-9]{8,}/"]
+        );
+        assert!(messages(&rules, "DATABASE_URL", "postgres://db:5432/app").is_empty());
+        assert_eq!(
+            messages(&rules, "DATABASE_URL", "hunter2"),
+            ["DATABASE_URL isn't a URL"]
+        );
+        let short = check(&rules, "JWT_SECRET", b"short").unwrap();
+        assert_eq!(short.len(), 1);
+        assert_eq!(short[0].action, LintAction::Warn);
+        assert_eq!(
+            messages(&rules, "LOG_LEVEL", "trace"),
+            ["LOG_LEVEL isn't one of debug, info, warn"]
+        );
+        assert_eq!(messages(&rules, "PORT", "123456").len(), 1);
+        assert!(messages(&rules, "UNRULED", "anything").is_empty());
+        assert_eq!(
+            check(&rules, "PORT", &[0xff, 0xfe]).unwrap()[0].message,
+            "PORT isn't text"
+        );
+    }
+
+    #[test]
+    fn test_formats() {
+        assert!(ValueFormat::Base64.matches("aGVsbG8="));
+        assert!(ValueFormat::Base64.matches("aGVsbG8"));
+        assert!(!ValueFormat::Base64.matches("not base64!"));
+        assert!(ValueFormat::Hex.matches("deadBEEF"));
+        assert!(!ValueFormat::Hex.matches("abc"));
+        assert!(ValueFormat::Json.matches(r#"{"type": "service_account"}"#));
+        assert!(!ValueFormat::Json.matches("{oops"));
+        assert!(ValueFormat::Integer.matches("-42"));
+        assert!(!ValueFormat::Integer.matches("4.2"));
+        assert!(!ValueFormat::Url.matches("https://"));
+    }
+
+    #[test]
+    fn test_bad_pattern() {
+        let rules = rules("[validate]\nKEY = { pattern = \"(oops\" }\n");
+        assert!(check(&rules, "KEY", b"value").is_err());
+    }
+}
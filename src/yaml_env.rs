@@ -0,0 +1,424 @@
+//! Key/value pairs from YAML: flat `KEY: value` maps and docker-compose
+//! `environment:` blocks
+//!
+//! Understands the subset these files use: block mappings, `- KEY=value`
+//! lists under `environment:`, plain and quoted scalars, and `|`/`>` block
+//! scalars. Anchors, aliases, tags and `{...}`/`[...]` flow collections are
+//! refused rather than read as text. Nested keys are joined with `_`, as
+//! `import-sops` does.
+
+use anyhow::Result;
+
+struct Line<'a> {
+    /// 1-based line number
+    number: usize,
+    indent: usize,
+    /// The whole line, less trailing whitespace
+    raw: &'a str,
+    /// The line without its indentation
+    text: &'a str,
+}
+
+impl Line<'_> {
+    /// Blank lines, comments and document markers carry no data
+    fn is_content(&self) -> bool {
+        !self.text.is_empty()
+            && !self.text.starts_with('#')
+            && self.text != "---"
+            && self.text != "..."
+    }
+}
+
+/// Parse YAML into key/value pairs. If the document has `environment:` blocks
+/// (docker-compose), only those are read, from every service.
+pub fn parse(input: &str) -> Result<Vec<(String, String)>> {
+    let lines: Vec<Line> = input
+        .lines()
+        .enumerate()
+        .map(|(i, raw)| {
+            let raw = raw.trim_end();
+            let text = raw.trim_start_matches(' ');
+            Line {
+                number: i + 1,
+                indent: raw.len() - text.len(),
+                raw,
+                text,
+            }
+        })
+        .collect();
+    if let Some(line) = lines.iter().find(|l| l.text.starts_with('\t')) {
+        anyhow::bail!("line {}: tabs can't be used for indentation", line.number);
+    }
+
+    let mut pairs = Vec::new();
+    let blocks: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| strip_comment(line.text) == "environment:")
+        .map(|(i, _)| i)
+        .collect();
+    if blocks.is_empty() {
+        let mut pos = 0;
+        parse_map(&lines, &mut pos, 0, "", &mut pairs)?;
+        if let Some(line) = lines.get(pos) {
+            anyhow::bail!("line {}: unexpected indentation", line.number);
+        }
+        return Ok(pairs);
+    }
+
+    for start in blocks {
+        let parent = lines[start].indent;
+        let mut pos = start + 1;
+        skip_blank(&lines, &mut pos);
+        let Some(first) = lines.get(pos).filter(|l| l.indent > parent) else {
+            continue;
+        };
+        if first.text.starts_with("- ") {
+            parse_list(&lines, &mut pos, first.indent, &mut pairs)?;
+        } else {
+            parse_map(&lines, &mut pos, first.indent, "", &mut pairs)?;
+        }
+    }
+    Ok(pairs)
+}
+
+fn skip_blank(lines: &[Line], pos: &mut usize) {
+    while lines.get(*pos).is_some_and(|l| !l.is_content()) {
+        *pos += 1;
+    }
+}
+
+/// `KEY: value` entries at `indent`, recursing into nested maps
+fn parse_map(
+    lines: &[Line],
+    pos: &mut usize,
+    indent: usize,
+    prefix: &str,
+    pairs: &mut Vec<(String, String)>,
+) -> Result<()> {
+    loop {
+        skip_blank(lines, pos);
+        let Some(line) = lines.get(*pos) else {
+            return Ok(());
+        };
+        if line.indent < indent {
+            return Ok(());
+        }
+        if line.indent > indent {
+            anyhow::bail!("line {}: unexpected indentation", line.number);
+        }
+        if line.text.starts_with("- ") {
+            anyhow::bail!(
+                "line {}: lists are only supported under environment:",
+                line.number
+            );
+        }
+        let (key, rest) = split_key(line)?;
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}_{}", prefix, key)
+        };
+        *pos += 1;
+
+        let rest = strip_comment(rest);
+        if rest.starts_with('|') || rest.starts_with('>') {
+            pairs.push((key, block_scalar(lines, pos, indent, rest)));
+            continue;
+        }
+        if !rest.is_empty() {
+            pairs.push((key, scalar(rest, line.number)?));
+            continue;
+        }
+
+        skip_blank(lines, pos);
+        match lines.get(*pos).filter(|l| l.indent > indent) {
+            Some(child) if child.text.starts_with("- ") => anyhow::bail!(
+                "line {}: lists are only supported under environment:",
+                child.number
+            ),
+            Some(child) => parse_map(lines, pos, child.indent, &key, pairs)?,
+            None => pairs.push((key, String::new())),
+        }
+    }
+}
+
+/// docker-compose's `- KEY=value` form. `- KEY` alone passes a variable
+/// through from the host, so there's nothing to import.
+fn parse_list(
+    lines: &[Line],
+    pos: &mut usize,
+    indent: usize,
+    pairs: &mut Vec<(String, String)>,
+) -> Result<()> {
+    loop {
+        skip_blank(lines, pos);
+        let Some(line) = lines.get(*pos).filter(|l| l.indent == indent) else {
+            return Ok(());
+        };
+        let Some(item) = line.text.strip_prefix("- ") else {
+            return Ok(());
+        };
+        *pos += 1;
+        let item = scalar(strip_comment(item.trim_start()), line.number)?;
+        if let Some((key, value)) = item.split_once('=') {
+            pairs.push((key.trim().to_string(), value.to_string()));
+        }
+    }
+}
+
+/// Split `KEY: rest` or `KEY:<tab>rest` (the key may be quoted)
+fn split_key<'a>(line: &Line<'a>) -> Result<(String, &'a str)> {
+    let text = line.text;
+    let (key, rest) = if text.starts_with(['"', '\'']) {
+        let end = closing_quote(text)
+            .ok_or_else(|| anyhow::anyhow!("line {}: unterminated quote", line.number))?;
+        (scalar(&text[..=end], line.number)?, &text[end + 1..])
+    } else {
+        // The first `:` followed by a space, a tab or the end of the line
+        let end = text
+            .match_indices(':')
+            .map(|(i, _)| i)
+            .find(|&i| matches!(text[i + 1..].chars().next(), None | Some(' ' | '\t')))
+            .ok_or_else(|| anyhow::anyhow!("line {}: expected KEY: value", line.number))?;
+        (text[..end].trim_end().to_string(), &text[end..])
+    };
+    match rest.trim_start().strip_prefix(':') {
+        Some(rest) if !key.is_empty() => Ok((key, rest.trim_start())),
+        _ => anyhow::bail!("line {}: expected KEY: value", line.number),
+    }
+}
+
+/// A single-line scalar: quoted, plain, or `null`/`~` (empty)
+fn scalar(text: &str, number: usize) -> Result<String> {
+    match text.chars().next() {
+        Some('"' | '\'') => {
+            let end = closing_quote(text)
+                .ok_or_else(|| anyhow::anyhow!("line {}: unterminated quote", number))?;
+            if !strip_comment(&text[end + 1..]).is_empty() {
+                anyhow::bail!("line {}: unexpected text after the closing quote", number);
+            }
+            let quoted = &text[..=end];
+            if quoted.starts_with('"') {
+                // YAML's double-quote escapes are (nearly) JSON's
+                serde_json::from_str(quoted)
+                    .map_err(|e| anyhow::anyhow!("line {}: bad escape ({})", number, e))
+            } else {
+                Ok(quoted[1..quoted.len() - 1].replace("''", "'"))
+            }
+        }
+        Some('{' | '[') => anyhow::bail!(
+            "line {}: flow collections ({{...}}, [...]) aren't supported",
+            number
+        ),
+        Some(c @ ('&' | '*' | '!')) => anyhow::bail!(
+            "line {}: {} aren't supported",
+            number,
+            match c {
+                '&' => "anchors (&name)",
+                '*' => "aliases (*name)",
+                _ => "tags (!tag)",
+            }
+        ),
+        _ => Ok(match text {
+            "~" | "null" | "Null" | "NULL" => String::new(),
+            plain => plain.to_string(),
+        }),
+    }
+}
+
+/// Byte offset of the quote closing a string that starts with one
+fn closing_quote(text: &str) -> Option<usize> {
+    let quote = text.chars().next()?;
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => {
+                chars.next();
+            }
+            '\'' if quote == '\'' && chars.peek().is_some_and(|&(_, c)| c == '\'') => {
+                chars.next();
+            }
+            c if c == quote => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Drop a trailing ` # comment` and surrounding whitespace. Quoted scalars
+/// are left alone; `scalar` checks what follows their closing quote.
+fn strip_comment(text: &str) -> &str {
+    let text = text.trim();
+    if text.starts_with(['"', '\'']) {
+        return text;
+    }
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| c == '#' && (i == 0 || text[..i].ends_with(char::is_whitespace)))
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    text[..end].trim_end()
+}
+
+/// A `|` (literal) or `>` (folded) block scalar below a key at `indent`,
+/// with `-` (strip) or `+` (keep) chomping of the final newline
+fn block_scalar(lines: &[Line], pos: &mut usize, indent: usize, header: &str) -> String {
+    let mut body: Vec<&str> = Vec::new();
+    let mut block_indent = None;
+    while let Some(line) = lines.get(*pos) {
+        if line.text.is_empty() {
+            body.push("");
+        } else {
+            let wanted = *block_indent.get_or_insert(line.indent);
+            if line.indent <= indent || line.indent < wanted {
+                break;
+            }
+            // Indentation beyond the block's own is part of the value
+            body.push(&line.raw[wanted..]);
+        }
+        *pos += 1;
+    }
+    let mut trailing = 0;
+    while body.last() == Some(&"") {
+        body.pop();
+        trailing += 1;
+    }
+
+    let mut value = String::new();
+    if header.starts_with('>') {
+        for (i, line) in body.iter().enumerate() {
+            if line.is_empty() {
+                value.push('\n');
+            } else {
+                if i > 0 && !body[i - 1].is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(line);
+            }
+        }
+    } else {
+        value = body.join("\n");
+    }
+    if header.contains('+') {
+        value.push_str(&"\n".repeat(trailing + 1));
+    } else if !header.contains('-') && !body.is_empty() {
+        value.push('\n');
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(input: &str) -> Vec<(String, String)> {
+        parse(input).unwrap()
+    }
+
+    fn expected(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_flat_map() {
+        let input = "\
+# exported
+API_KEY: sk-1   # comment
+QUOTED: \"a # b\\n\"
+SINGLE: 'it''s'
+EMPTY: ~
+PORT: 5432
+database:
+  password: hunter2
+";
+        assert_eq!(
+            pairs(input),
+            expected(&[
+                ("API_KEY", "sk-1"),
+                ("QUOTED", "a # b\n"),
+                ("SINGLE", "it's"),
+                ("EMPTY", ""),
+                ("PORT", "5432"),
+                ("database_password", "hunter2"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compose_environment() {
+        let input = "\
+services:
+  web:
+    image: app
+    environment:
+      - DATABASE_URL=postgres://db/app
+      - \"GREETING=hello world\"
+      - FROM_HOST
+  worker:
+    environment:
+      QUEUE: jobs
+      TLS_KEY: |
+        -----BEGIN KEY-----
+        abc
+        -----END KEY-----
+    command: run
+";
+        assert_eq!(
+            pairs(input),
+            expected(&[
+                ("DATABASE_URL", "postgres://db/app"),
+                ("GREETING", "hello world"),
+                ("QUEUE", "jobs"),
+                ("TLS_KEY", "-----BEGIN KEY-----\nabc\n-----END KEY-----\n"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_block_scalars() {
+        let input = "A: |-\n  one\n    two\nB: >\n  folded\n  line\n\n  para\n";
+        assert_eq!(
+            pairs(input),
+            expected(&[("A", "one\n  two"), ("B", "folded line\npara\n")])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("KEY value\n").is_err());
+        assert!(parse("KEY: [a, b]\n").is_err());
+        assert!(parse("KEY: \"open\n").is_err());
+        assert!(parse("A: 1\n  B: 2\n").is_err());
+    }
+
+    #[test]
+    fn test_tab_after_colon() {
+        assert_eq!(
+            pairs("API_KEY:\tsk-1\nURL:\t\thttp://host:80\n"),
+            expected(&[("API_KEY", "sk-1"), ("URL", "http://host:80")])
+        );
+    }
+
+    #[test]
+    fn test_anchors_aliases_and_tags() {
+        for input in [
+            "BASE: &base secret\nCOPY: *base\n",
+            "COPY: *base\n",
+            "RAW: !!binary aGVsbG8=\n",
+            "defaults: &defaults\n  A: 1\n",
+            "services:\n  web:\n    environment:\n      - *shared\n",
+        ] {
+            let err = parse(input).unwrap_err().to_string();
+            assert!(err.contains("aren't supported"), "{}: {}", input, err);
+        }
+        // Only a leading marker means anything; quoted or mid-value it's text
+        assert_eq!(
+            pairs("A: \"*not an alias\"\nB: a&b*c!\n"),
+            expected(&[("A", "*not an alias"), ("B", "a&b*c!")])
+        );
+    }
+}